@@ -115,4 +115,51 @@ openssl pkcs12 -export -legacy \
     -out identity-legacy.p12 \
     -passout pass:mypass
 
-rm -rf rsa
\ No newline at end of file
+# Client certificate for mutual TLS tests, signed by its own CA.
+openssl req -nodes \
+          -newkey rsa:2048 \
+          -keyout rsa/client-ca.key \
+          -out rsa/client-ca.req \
+          -sha256 \
+          -batch \
+          -subj "/CN=salvo client CA"
+
+openssl x509 -req \
+          -in rsa/client-ca.req \
+          -out client-ca.pem \
+          -signkey rsa/client-ca.key \
+          -sha256 \
+          -days 3650 \
+          -extensions v3_client_ca -extfile openssl.cnf
+
+openssl req -nodes \
+          -newkey rsa:2048 \
+          -keyout client-key.pem \
+          -out rsa/client-san.req \
+          -sha256 \
+          -batch \
+          -subj "/O=salvo/CN=client.testserver.com"
+
+openssl x509 -req \
+          -in rsa/client-san.req \
+          -out client-cert.pem \
+          -CA client-ca.pem \
+          -CAkey rsa/client-ca.key \
+          -sha256 \
+          -days 3650 \
+          -set_serial 4660 \
+          -extensions v3_client_san -extfile openssl.cnf
+
+# Self-signed certificate of another host for SNI tests.
+openssl req -nodes \
+          -x509 \
+          -days 3650 \
+          -newkey rsa:2048 \
+          -keyout other-key.pem \
+          -out other-cert.pem \
+          -sha256 \
+          -batch \
+          -subj "/CN=other.testserver.com" \
+          -addext "subjectAltName = DNS:other.testserver.com"
+
+rm -rf rsa
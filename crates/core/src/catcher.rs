@@ -39,7 +39,7 @@ use mime::Mime;
 use once_cell::sync::Lazy;
 
 use crate::handler::{Handler, WhenHoop};
-use crate::http::{guess_accept_mime, header, Request, Response, StatusCode, StatusError};
+use crate::http::{guess_accept_mime, header, Method, Request, Response, StatusCode, StatusError};
 use crate::{Depot, FlowCtrl};
 
 static SUPPORTED_FORMATS: Lazy<Vec<mime::Name>> = Lazy::new(|| vec![mime::JSON, mime::HTML, mime::XML, mime::PLAIN]);
@@ -270,7 +270,12 @@ pub fn write_error_default(req: &Request, res: &mut Response, footer: Option<&st
     };
     res.headers_mut()
         .insert(header::CONTENT_TYPE, format.to_string().parse().unwrap());
-    res.write_body(data).ok();
+    // The response to `HEAD` has the headers of the response to `GET` but no body.
+    if req.method() == Method::HEAD {
+        res.headers_mut().insert(header::CONTENT_LENGTH, data.len().into());
+    } else {
+        res.write_body(data).ok();
+    }
 }
 
 #[cfg(test)]
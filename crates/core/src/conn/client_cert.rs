@@ -0,0 +1,105 @@
+//! Client certificate of mutual TLS connections.
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+/// Client certificate verified during TLS handshake.
+///
+/// The certificate is parsed once per connection and injected into the [`Depot`](crate::Depot) of
+/// every request on it, so handlers can authorize by common name or subject alternative names:
+///
+/// ```
+/// use salvo_core::conn::ClientCert;
+/// use salvo_core::prelude::*;
+///
+/// #[handler]
+/// async fn hello(depot: &mut Depot, res: &mut Response) {
+///     match depot.obtain::<ClientCert>().and_then(|cert| cert.subject_cn.as_deref()) {
+///         Some(cn) => res.render(format!("hello {cn}")),
+///         None => res.set_status_code(StatusCode::FORBIDDEN),
+///     }
+/// }
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ClientCert {
+    /// Common name of the subject.
+    pub subject_cn: Option<String>,
+    /// Distinguished name of the subject, for example `O=salvo, CN=client.testserver.com`.
+    pub subject: String,
+    /// Distinguished name of the issuer.
+    pub issuer: String,
+    /// Subject alternative names, DNS names, emails and URIs are kept as is, IP addresses are formatted.
+    pub sans: Vec<String>,
+    /// Serial number in lower case hex.
+    pub serial: String,
+    /// Start of the validity period.
+    pub not_before: SystemTime,
+    /// End of the validity period.
+    pub not_after: SystemTime,
+    /// DER encoded certificate.
+    pub der: Vec<u8>,
+}
+
+impl ClientCert {
+    /// Parse a DER encoded certificate.
+    pub fn from_der(der: impl Into<Vec<u8>>) -> IoResult<Self> {
+        let der = der.into();
+        // The parsed certificate borrows `der`, so it is parsed in a block before moving `der`.
+        let client_cert = {
+            let (_, cert) = X509Certificate::from_der(&der)
+                .map_err(|e| IoError::new(ErrorKind::InvalidData, format!("invalid client certificate: {e}")))?;
+            let subject_cn = cert
+                .subject()
+                .iter_common_name()
+                .next()
+                .and_then(|cn| cn.as_str().ok())
+                .map(ToOwned::to_owned);
+            let mut sans = Vec::new();
+            if let Ok(Some(san)) = cert.subject_alternative_name() {
+                for name in &san.value.general_names {
+                    match name {
+                        GeneralName::DNSName(name) | GeneralName::RFC822Name(name) | GeneralName::URI(name) => {
+                            sans.push((*name).to_owned())
+                        }
+                        GeneralName::IPAddress(ip) => {
+                            if let Ok(ip) = <[u8; 4]>::try_from(*ip) {
+                                sans.push(std::net::IpAddr::from(ip).to_string());
+                            } else if let Ok(ip) = <[u8; 16]>::try_from(*ip) {
+                                sans.push(std::net::IpAddr::from(ip).to_string());
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            let serial = cert
+                .raw_serial()
+                .iter()
+                .skip_while(|byte| **byte == 0)
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>();
+            ClientCert {
+                subject_cn,
+                subject: cert.subject().to_string(),
+                issuer: cert.issuer().to_string(),
+                sans,
+                serial,
+                not_before: to_system_time(cert.validity().not_before.timestamp()),
+                not_after: to_system_time(cert.validity().not_after.timestamp()),
+                der: vec![],
+            }
+        };
+        Ok(ClientCert { der, ..client_cert })
+    }
+}
+
+#[inline]
+fn to_system_time(timestamp: i64) -> SystemTime {
+    if timestamp >= 0 {
+        UNIX_EPOCH + Duration::from_secs(timestamp as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(timestamp.unsigned_abs())
+    }
+}
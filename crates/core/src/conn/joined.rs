@@ -123,6 +123,18 @@ where
             JoinedStream::B(b) => b.version().await,
         }
     }
+    async fn alpn(&mut self) -> Option<Vec<u8>> {
+        match self {
+            JoinedStream::A(a) => a.alpn().await,
+            JoinedStream::B(b) => b.alpn().await,
+        }
+    }
+    async fn peer_certificate(&mut self) -> Option<Vec<u8>> {
+        match self {
+            JoinedStream::A(a) => a.peer_certificate().await,
+            JoinedStream::B(b) => b.peer_certificate().await,
+        }
+    }
     async fn serve(self, handler: HyperHandler, builders: Arc<HttpBuilders>) -> IoResult<()> {
         match self {
             JoinedStream::A(a) => a.serve(handler, builders).await,
@@ -0,0 +1,246 @@
+//! LimitedListener and it's implements.
+use std::io::Result as IoResult;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::async_trait;
+use crate::conn::{Holding, HttpBuilders};
+use crate::http::{HttpConnection, Version};
+use crate::service::HyperHandler;
+
+use super::{Accepted, Acceptor, Listener};
+
+const SERVICE_UNAVAILABLE: &[u8] =
+    b"HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+
+/// Behavior when the maximum number of connections is reached.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OverflowMode {
+    /// Stop accepting new connections until an alive connection is closed.
+    #[default]
+    Block,
+    /// Accept the new connection, write a minimal `503 Service Unavailable` response and close it.
+    Reject503,
+}
+
+/// A I/O stream for LimitedListener, the connection slot is released when it is dropped.
+pub struct LimitedStream<C> {
+    inner: C,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl<C> AsyncRead for LimitedStream<C>
+where
+    C: AsyncRead + Send + Unpin + 'static,
+{
+    #[inline]
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_read(cx, buf)
+    }
+}
+
+impl<C> AsyncWrite for LimitedStream<C>
+where
+    C: AsyncWrite + Send + Unpin + 'static,
+{
+    #[inline]
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[async_trait]
+impl<C> HttpConnection for LimitedStream<C>
+where
+    C: HttpConnection + Send,
+{
+    async fn version(&mut self) -> Option<Version> {
+        self.inner.version().await
+    }
+    async fn alpn(&mut self) -> Option<Vec<u8>> {
+        self.inner.alpn().await
+    }
+    async fn peer_certificate(&mut self) -> Option<Vec<u8>> {
+        self.inner.peer_certificate().await
+    }
+    async fn serve(self, handler: HyperHandler, builders: Arc<HttpBuilders>) -> IoResult<()> {
+        let LimitedStream { inner, _permit } = self;
+        inner.serve(handler, builders).await
+    }
+}
+
+/// LimitedListener limits the number of alive connections of the inner listener.
+pub struct LimitedListener<T> {
+    inner: T,
+    max_connections: usize,
+    overflow_mode: OverflowMode,
+}
+
+impl<T> LimitedListener<T> {
+    /// Create a new `LimitedListener`.
+    #[inline]
+    pub fn new(inner: T, max_connections: usize) -> Self {
+        LimitedListener {
+            inner,
+            max_connections,
+            overflow_mode: OverflowMode::default(),
+        }
+    }
+
+    /// Sets overflow mode and returns `Self`.
+    #[inline]
+    pub fn with_overflow_mode(mut self, overflow_mode: OverflowMode) -> Self {
+        self.overflow_mode = overflow_mode;
+        self
+    }
+}
+
+#[async_trait]
+impl<T> Listener for LimitedListener<T>
+where
+    T: Listener + Send,
+    T::Acceptor: Send + 'static,
+{
+    type Acceptor = LimitedAcceptor<T::Acceptor>;
+
+    async fn bind(self) -> Self::Acceptor {
+        self.try_bind().await.unwrap()
+    }
+
+    async fn try_bind(self) -> IoResult<Self::Acceptor> {
+        Ok(LimitedAcceptor::new(
+            self.inner.try_bind().await?,
+            self.max_connections,
+            self.overflow_mode,
+        ))
+    }
+}
+
+/// LimitedAcceptor
+pub struct LimitedAcceptor<T> {
+    inner: T,
+    semaphore: Arc<Semaphore>,
+    overflow_mode: OverflowMode,
+}
+
+impl<T> LimitedAcceptor<T> {
+    /// Create a new `LimitedAcceptor`.
+    #[inline]
+    pub fn new(inner: T, max_connections: usize, overflow_mode: OverflowMode) -> Self {
+        LimitedAcceptor {
+            inner,
+            semaphore: Arc::new(Semaphore::new(max_connections)),
+            overflow_mode,
+        }
+    }
+
+    /// Get the number of connections which can still be accepted.
+    #[inline]
+    pub fn available_connections(&self) -> usize {
+        self.semaphore.available_permits()
+    }
+}
+
+#[async_trait]
+impl<T> Acceptor for LimitedAcceptor<T>
+where
+    T: Acceptor + Send + 'static,
+{
+    type Conn = LimitedStream<T::Conn>;
+
+    #[inline]
+    fn holdings(&self) -> &[Holding] {
+        self.inner.holdings()
+    }
+
+    async fn accept(&mut self) -> IoResult<Accepted<Self::Conn>> {
+        match self.overflow_mode {
+            OverflowMode::Block => {
+                let permit = self
+                    .semaphore
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("semaphore should not be closed");
+                let accepted = self.inner.accept().await?;
+                Ok(accepted.map_conn(|inner| LimitedStream { inner, _permit: permit }))
+            }
+            OverflowMode::Reject503 => loop {
+                let accepted = self.inner.accept().await?;
+                match self.semaphore.clone().try_acquire_owned() {
+                    Ok(permit) => {
+                        return Ok(accepted.map_conn(|inner| LimitedStream { inner, _permit: permit }));
+                    }
+                    Err(_) => {
+                        tracing::warn!(remote_addr = %accepted.remote_addr, "too many connections, rejected");
+                        let mut conn = accepted.conn;
+                        tokio::spawn(async move {
+                            if conn.write_all(SERVICE_UNAVAILABLE).await.is_ok() {
+                                conn.shutdown().await.ok();
+                            }
+                        });
+                    }
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    use super::*;
+    use crate::conn::TcpListener;
+
+    #[tokio::test]
+    async fn test_limited_listener_reject() {
+        let mut acceptor = LimitedListener::new(TcpListener::new("127.0.0.1:0"), 1)
+            .with_overflow_mode(OverflowMode::Reject503)
+            .bind()
+            .await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+
+        tokio::spawn(async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream.write_i32(150).await.unwrap();
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+        let Accepted { mut conn, .. } = acceptor.accept().await.unwrap();
+        assert_eq!(conn.read_i32().await.unwrap(), 150);
+        assert_eq!(acceptor.available_connections(), 0);
+
+        let client = tokio::spawn(async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        });
+        let response = tokio::time::timeout(Duration::from_secs(1), async {
+            tokio::select! {
+                _ = acceptor.accept() => panic!("connection over limit should not be accepted"),
+                response = client => response.unwrap(),
+            }
+        })
+        .await
+        .unwrap();
+        assert!(response.starts_with("HTTP/1.1 503"));
+    }
+}
@@ -0,0 +1,243 @@
+//! MockAcceptor and it's implements.
+use std::io::Result as IoResult;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use http::uri::Scheme;
+use tokio::io::{AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::async_trait;
+use crate::conn::{Holding, HttpBuilders, SocketAddr};
+use crate::http::{HttpConnection, Version};
+use crate::service::HyperHandler;
+
+use super::{Accepted, Acceptor};
+
+/// Default buffer size of the in-memory streams of [`MockAcceptor`].
+const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// In-memory connection accepted by [`MockAcceptor`], it is served as plain HTTP.
+pub struct MockStream {
+    inner: DuplexStream,
+}
+
+impl AsyncRead for MockStream {
+    #[inline]
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for MockStream {
+    #[inline]
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[async_trait]
+impl HttpConnection for MockStream {
+    async fn version(&mut self) -> Option<Version> {
+        Some(Version::HTTP_11)
+    }
+    async fn serve(self, handler: HyperHandler, builders: Arc<HttpBuilders>) -> IoResult<()> {
+        builders.serve_plain(self, handler).await
+    }
+}
+
+/// Scripts connections of a [`MockAcceptor`], it can be cloned and used after the acceptor is moved
+/// into a [`Server`](crate::Server).
+#[derive(Clone)]
+pub struct MockConnector {
+    local_addr: SocketAddr,
+    buffer_size: usize,
+    sender: UnboundedSender<Accepted<MockStream>>,
+}
+
+impl MockConnector {
+    /// Script a connection from `remote_addr` and returns the client end of its stream.
+    ///
+    /// The connection is accepted by the next call of [`Acceptor::accept`], connections are accepted
+    /// in the order they are scripted.
+    pub fn connect(&self, remote_addr: impl Into<SocketAddr>) -> DuplexStream {
+        let (client, server) = tokio::io::duplex(self.buffer_size);
+        let accepted = Accepted {
+            conn: MockStream { inner: server },
+            local_addr: self.local_addr.clone(),
+            remote_addr: remote_addr.into(),
+            http_scheme: Scheme::HTTP,
+            http_version: Version::HTTP_11,
+        };
+        // The acceptor is dropped, nobody reads the client end.
+        self.sender.send(accepted).ok();
+        client
+    }
+}
+
+/// MockAcceptor yields scripted in-memory connections, it is used to test acceptor wrappers and
+/// servers without real sockets.
+///
+/// # Example
+///
+/// ```
+/// use salvo_core::conn::{Acceptor, MockAcceptor};
+/// use tokio::io::{AsyncReadExt, AsyncWriteExt};
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let mut acceptor = MockAcceptor::new("127.0.0.1:5800".parse::<std::net::SocketAddr>().unwrap());
+/// let mut client = acceptor.connect("10.0.0.1:40000".parse::<std::net::SocketAddr>().unwrap());
+/// let mut accepted = acceptor.accept().await.unwrap();
+/// assert_eq!(accepted.remote_addr.to_string(), "socket://10.0.0.1:40000");
+///
+/// client.write_all(b"ping").await.unwrap();
+/// let mut buf = [0; 4];
+/// accepted.conn.read_exact(&mut buf).await.unwrap();
+/// assert_eq!(&buf, b"ping");
+/// # }
+/// ```
+pub struct MockAcceptor {
+    holdings: Vec<Holding>,
+    connector: MockConnector,
+    receiver: UnboundedReceiver<Accepted<MockStream>>,
+}
+
+impl MockAcceptor {
+    /// Create a new `MockAcceptor` whose connections are accepted on `local_addr`.
+    #[inline]
+    pub fn new(local_addr: impl Into<SocketAddr>) -> Self {
+        let local_addr = local_addr.into();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        MockAcceptor {
+            holdings: vec![Holding {
+                local_addr: local_addr.clone(),
+                http_version: Version::HTTP_11,
+                http_scheme: Scheme::HTTP,
+            }],
+            connector: MockConnector {
+                local_addr,
+                buffer_size: DEFAULT_BUFFER_SIZE,
+                sender,
+            },
+            receiver,
+        }
+    }
+
+    /// Sets the buffer size of in-memory streams and returns `Self`.
+    ///
+    /// Writes to a stream wait when its buffer is full until the other end reads. The default is 64K.
+    #[inline]
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.connector.buffer_size = buffer_size;
+        self
+    }
+
+    /// Get a [`MockConnector`] to script connections of this acceptor.
+    #[inline]
+    pub fn connector(&self) -> MockConnector {
+        self.connector.clone()
+    }
+
+    /// Script a connection from `remote_addr` and returns the client end of its stream.
+    ///
+    /// See [`MockConnector::connect`].
+    #[inline]
+    pub fn connect(&self, remote_addr: impl Into<SocketAddr>) -> DuplexStream {
+        self.connector.connect(remote_addr)
+    }
+}
+
+#[async_trait]
+impl Acceptor for MockAcceptor {
+    type Conn = MockStream;
+
+    #[inline]
+    fn holdings(&self) -> &[Holding] {
+        &self.holdings
+    }
+
+    /// Accepts the next scripted connection, it waits until one is scripted.
+    #[inline]
+    async fn accept(&mut self) -> IoResult<Accepted<Self::Conn>> {
+        // The acceptor holds a sender, so the channel is never closed.
+        match self.receiver.recv().await {
+            Some(accepted) => Ok(accepted),
+            None => futures_util::future::pending().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr as StdSocketAddr;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::conn::{LimitedAcceptor, OverflowMode};
+    use crate::prelude::*;
+
+    fn addr(addr: &str) -> StdSocketAddr {
+        addr.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_mock_acceptor_serve() {
+        #[handler(internal)]
+        async fn remote(req: &mut Request) -> String {
+            req.remote_addr().to_string()
+        }
+        let acceptor = MockAcceptor::new(addr("127.0.0.1:5800"));
+        let connector = acceptor.connector();
+        tokio::spawn(async move {
+            Server::new(acceptor).serve(Router::new().get(remote)).await;
+        });
+
+        let mut client = connector.connect(addr("10.0.0.1:40000"));
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("socket://10.0.0.1:40000"));
+    }
+
+    #[tokio::test]
+    async fn test_mock_acceptor_limited_reject() {
+        let mock = MockAcceptor::new(addr("127.0.0.1:5800"));
+        let connector = mock.connector();
+        let mut acceptor = LimitedAcceptor::new(mock, 1, OverflowMode::Reject503);
+
+        let _first = connector.connect(addr("10.0.0.1:40000"));
+        let second = connector.connect(addr("10.0.0.2:40000"));
+        let third = connector.connect(addr("10.0.0.3:40000"));
+        let accepted = acceptor.accept().await.unwrap();
+        assert_eq!(accepted.remote_addr.to_string(), "socket://10.0.0.1:40000");
+
+        let next = tokio::spawn(async move { acceptor.accept().await.unwrap().remote_addr.to_string() });
+        for mut rejected in [second, third] {
+            let mut response = String::new();
+            rejected.read_to_string(&mut response).await.unwrap();
+            assert!(response.starts_with("HTTP/1.1 503"));
+        }
+
+        // The slot is released when the first connection is closed.
+        drop(accepted);
+        let _fourth = connector.connect(addr("10.0.0.4:40000"));
+        assert_eq!(next.await.unwrap(), "socket://10.0.0.4:40000");
+    }
+}
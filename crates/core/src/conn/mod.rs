@@ -1,6 +1,7 @@
 //! Listener trait and it's implements.
+use std::error::Error as StdError;
 use std::fmt::{self, Display, Formatter};
-use std::io::Result as IoResult;
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
 
 use http::uri::Scheme;
 use tokio::io::{AsyncRead, AsyncWrite};
@@ -54,8 +55,20 @@ pub use tcp::TcpListener;
 mod joined;
 pub use joined::JoinedListener;
 
+pub mod limited;
+pub use limited::{LimitedListener, OverflowMode};
+
+pub mod parsing;
+pub use parsing::{ParsingListener, ParsingMode};
+
+cfg_feature! {
+    #![feature = "test"]
+    pub mod mock;
+    pub use mock::{MockAcceptor, MockConnector};
+}
+
 mod proto;
-pub use proto::HttpBuilders;
+pub use proto::{HttpBuilders, HttpProtocol};
 
 cfg_feature! {
     #![unix]
@@ -66,12 +79,17 @@ cfg_feature! {
     #![any(feature = "native-tls", feature = "rustls", feature = "openssl", feature = "acme")]
     mod tls_conn_stream;
     pub use tls_conn_stream::TlsConnStream;
+    #[cfg(any(feature = "rustls", feature = "openssl"))]
+    pub(crate) use tls_conn_stream::{handshake_with_timeout, DEFAULT_HANDSHAKE_TIMEOUT};
+
+    mod client_cert;
+    pub use client_cert::ClientCert;
 }
 
 cfg_feature! {
     #![any(feature = "rustls", feature = "acme")]
     mod sealed {
-        use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+        use std::io::Result as IoResult;
         use std::sync::Arc;
 
         use tokio_rustls::server::TlsStream;
@@ -91,19 +109,15 @@ cfg_feature! {
             async fn version(&mut self) -> Option<Version> {
                 self.get_ref().1.alpn_protocol().map(version_from_alpn)
             }
+            async fn alpn(&mut self) -> Option<Vec<u8>> {
+                self.get_ref().1.alpn_protocol().map(|proto| proto.to_vec())
+            }
+            async fn peer_certificate(&mut self) -> Option<Vec<u8>> {
+                self.get_ref().1.peer_certificates().and_then(|certs| certs.first()).map(|cert| cert.0.clone())
+            }
             async fn serve(self, handler: HyperHandler, builders: Arc<HttpBuilders>) -> IoResult<()> {
-                #[cfg(not(feature = "http2"))]
-                {
-                    let _ = handler;
-                    let _ = builders;
-                    panic!("http2 feature is required");
-                }
-                #[cfg(feature = "http2")]
-                builders
-                    .http2
-                    .serve_connection(self, handler)
-                    .await
-                    .map_err(|e| IoError::new(ErrorKind::Other, e.to_string()))
+                let version = self.get_ref().1.alpn_protocol().map(version_from_alpn);
+                builders.serve_negotiated(self, version, handler).await
             }
         }
     }
@@ -119,6 +133,69 @@ pub trait IntoConfigStream<C> {
     fn into_stream(self) -> Self::Stream;
 }
 
+#[cfg(any(feature = "rustls", feature = "native-tls", feature = "openssl"))]
+/// Category of an [`AcceptError`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum AcceptErrorKind {
+    /// A TLS config from the config stream is invalid, the previous config is kept.
+    ConfigLoad,
+    /// The config stream ended before a valid TLS config is loaded, the acceptor is shut down.
+    MissingConfig,
+    /// The TLS handshake of a connection failed or timed out.
+    Handshake,
+}
+
+#[cfg(any(feature = "rustls", feature = "native-tls", feature = "openssl"))]
+/// Failure of a TLS acceptor which is passed to the error observer of the listener, like
+/// `OpensslListener::on_error`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct AcceptError {
+    /// Category of the failure.
+    pub kind: AcceptErrorKind,
+    /// Remote addr of the connection, it is `None` if the failure is not of a connection.
+    pub remote_addr: Option<SocketAddr>,
+    /// The error.
+    pub error: std::io::Error,
+}
+
+#[cfg(any(feature = "rustls", feature = "native-tls", feature = "openssl"))]
+pub(crate) type OnAcceptError = std::sync::Arc<dyn Fn(&AcceptError) + Send + Sync>;
+
+/// Error returned by [`Acceptor::accept`] when the acceptor is shut down, like after the shutdown
+/// signal of [`OpensslListener`](crate::conn::OpensslListener) resolves.
+///
+/// It is wrapped in an [`IoError`], the [`Server`](crate::Server) stops accepting connections when
+/// it gets it and waits for alive connections to close.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct AcceptorShutdown;
+
+impl AcceptorShutdown {
+    /// Check if `error` is returned by a shut down acceptor.
+    #[inline]
+    pub fn is(error: &IoError) -> bool {
+        error.get_ref().map(|e| e.is::<AcceptorShutdown>()).unwrap_or(false)
+    }
+}
+
+impl Display for AcceptorShutdown {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("acceptor is shut down")
+    }
+}
+
+impl StdError for AcceptorShutdown {}
+
+impl From<AcceptorShutdown> for IoError {
+    #[inline]
+    fn from(shutdown: AcceptorShutdown) -> Self {
+        IoError::new(ErrorKind::Other, shutdown)
+    }
+}
+
 /// Acceptor's return type.
 pub struct Accepted<C> {
     /// Incoming stream.
@@ -170,6 +247,19 @@ pub trait Acceptor {
     async fn accept(&mut self) -> IoResult<Accepted<Self::Conn>>;
 }
 
+/// Id of the connection a request is received on, it is unique in a [`Server`](crate::Server).
+///
+/// All requests on a keep-alive connection share the same id, handlers can get it by
+/// `depot.obtain::<ConnectionId>()`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct ConnectionId(pub u64);
+impl Display for ConnectionId {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 /// Holding information.
 #[derive(Clone, Debug)]
 pub struct Holding {
@@ -64,19 +64,19 @@ where
     async fn version(&mut self) -> Option<Version> {
         self.get_ref().negotiated_alpn().ok().flatten().map(version_from_alpn)
     }
+    async fn alpn(&mut self) -> Option<Vec<u8>> {
+        self.get_ref().negotiated_alpn().ok().flatten()
+    }
+    async fn peer_certificate(&mut self) -> Option<Vec<u8>> {
+        self.get_ref()
+            .peer_certificate()
+            .ok()
+            .flatten()
+            .and_then(|cert| cert.to_der().ok())
+    }
     async fn serve(self, handler: HyperHandler, builders: Arc<HttpBuilders>) -> IoResult<()> {
-        #[cfg(not(feature = "http2"))]
-        {
-            let _ = handler;
-            let _ = builders;
-            panic!("http2 feature is required");
-        }
-        #[cfg(feature = "http2")]
-        builders
-            .http2
-            .serve_connection(self, handler)
-            .await
-            .map_err(|e| IoError::new(ErrorKind::Other, e.to_string()))
+        let version = self.get_ref().negotiated_alpn().ok().flatten().map(version_from_alpn);
+        builders.serve_negotiated(self, version, handler).await
     }
 }
 
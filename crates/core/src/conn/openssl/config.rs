@@ -1,14 +1,22 @@
 //! openssl module
+use std::collections::HashMap;
 use std::fmt::{self, Formatter};
-use std::fs::File;
-use std::io::{Error as IoError, Read, Result as IoResult};
-use std::path::Path;
+use std::fs::{File, OpenOptions};
+use std::io::{Error as IoError, Read, Result as IoResult, Write};
+use std::os::raw::c_int;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
 use futures_util::future::{ready, Ready};
 use futures_util::stream::{once, Once, Stream};
+use openssl::asn1::Asn1Time;
 use openssl::pkey::PKey;
-use openssl::ssl::{SslAcceptor, SslAcceptorBuilder, SslMethod, SslRef};
-use openssl::x509::X509;
+use openssl::ssl::{
+    NameType, SniError, SslAcceptor, SslAcceptorBuilder, SslAlert, SslContext, SslMethod, SslRef, SslVerifyMode,
+};
+use openssl::x509::{X509StoreContextRef, X509};
+use parking_lot::Mutex;
 use tokio::io::ErrorKind;
 
 use crate::conn::IntoConfigStream;
@@ -87,10 +95,31 @@ impl Keycert {
 }
 
 type BuilderModifier = Box<dyn FnMut(&mut SslAcceptorBuilder) + Send + 'static>;
+type ClientVerifier = Arc<dyn Fn(bool, &mut X509StoreContextRef) -> bool + Send + Sync + 'static>;
+
+/// `X509_V_ERR_CERT_HAS_EXPIRED` of OpenSSL.
+const X509_V_ERR_CERT_HAS_EXPIRED: c_int = 10;
+
+/// Tls client authentication configuration.
+#[derive(Clone, Debug)]
+enum ClientAuth {
+    /// No client auth.
+    Off,
+    /// Allow any anonymous or authenticated client, trust anchor in PEM format.
+    Optional(Vec<u8>),
+    /// Allow any authenticated client, trust anchor in PEM format.
+    Required(Vec<u8>),
+}
+
 /// Builder to set the configuration for the Tls server.
 pub struct OpensslConfig {
     keycert: Keycert,
+    keycerts: HashMap<String, Keycert>,
     builder_modifier: Option<BuilderModifier>,
+    keylog_file: Option<PathBuf>,
+    alpn_protocols: Vec<Vec<u8>>,
+    client_auth: ClientAuth,
+    client_verifier: Option<ClientVerifier>,
 }
 
 impl fmt::Debug for OpensslConfig {
@@ -101,13 +130,174 @@ impl fmt::Debug for OpensslConfig {
 }
 
 impl OpensslConfig {
-    /// Create new `OpensslConfig`
+    /// Create new `OpensslConfig`, `keycert` is the default certificate.
     #[inline]
     pub fn new(keycert: Keycert) -> Self {
-        OpensslConfig { keycert, builder_modifier: None }
+        OpensslConfig {
+            keycert,
+            keycerts: HashMap::new(),
+            builder_modifier: None,
+            keylog_file: None,
+            alpn_protocols: vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+            client_auth: ClientAuth::Off,
+            client_verifier: None,
+        }
+    }
+
+    /// Sets the certificate presented to clients which request `name` by SNI and returns `Self`.
+    ///
+    /// Names are matched case-insensitively, clients without SNI or with an unknown name get the
+    /// default certificate passed to [`OpensslConfig::new`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use salvo_core::conn::openssl::{Keycert, OpensslConfig};
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let config = OpensslConfig::new(Keycert::new().key_from_path("default.key")?.cert_from_path("default.pem")?)
+    ///     .with_keycert(
+    ///         "api.example.com",
+    ///         Keycert::new().key_from_path("api.key")?.cert_from_path("api.pem")?,
+    ///     );
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn with_keycert(mut self, name: impl Into<String>, keycert: Keycert) -> Self {
+        self.keycerts.insert(name.into().to_ascii_lowercase(), keycert);
+        self
+    }
+
+    /// Sets the certificates presented to clients by SNI name and returns `Self`, see
+    /// [`OpensslConfig::with_keycert`].
+    #[inline]
+    pub fn with_keycerts<K>(mut self, keycerts: impl IntoIterator<Item = (K, Keycert)>) -> Self
+    where
+        K: Into<String>,
+    {
+        for (name, keycert) in keycerts {
+            self.keycerts.insert(name.into().to_ascii_lowercase(), keycert);
+        }
+        self
+    }
+
+    /// Write TLS session secrets to `path` in NSS key log format, which can be used by tools
+    /// like Wireshark to decrypt the traffic.
+    ///
+    /// **Warning**: anyone who can read this file is able to decrypt the traffic, this is only
+    /// for debugging and should never be enabled in production. It is disabled by default.
+    #[inline]
+    pub fn with_keylog_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.keylog_file = Some(path.into());
+        self
+    }
+
+    /// Same as [`OpensslConfig::with_keylog_file`], but uses the path in `SSLKEYLOGFILE` environment
+    /// variable. Nothing will be written if the variable is not set.
+    #[inline]
+    pub fn with_keylog_from_env(mut self) -> Self {
+        self.keylog_file = std::env::var_os("SSLKEYLOGFILE").map(PathBuf::from);
+        self
+    }
+
+    /// Sets the ALPN protocols offered to clients, most preferred first, and returns `Self`.
+    ///
+    /// Use [`HttpProtocol::alpn_protocols`](crate::conn::HttpProtocol::alpn_protocols) to match the
+    /// protocol served by the server.
+    #[inline]
+    pub fn with_alpn_protocols(mut self, alpn_protocols: impl Into<Vec<Vec<u8>>>) -> Self {
+        self.alpn_protocols = alpn_protocols.into();
+        self
+    }
+
+    /// Sets the trust anchor for optional Tls client authentication via file path.
+    ///
+    /// Anonymous and authenticated clients will be accepted, the verified certificate is available
+    /// to handlers as [`ClientCert`](crate::conn::ClientCert). If no trust anchor is provided by any
+    /// of the `with_client_auth_` methods, then client authentication is disabled by default.
+    #[inline]
+    pub fn with_client_auth_optional_path(mut self, path: impl AsRef<Path>) -> IoResult<Self> {
+        let mut data = vec![];
+        File::open(path)?.read_to_end(&mut data)?;
+        self.client_auth = ClientAuth::Optional(data);
+        Ok(self)
+    }
+
+    /// Sets the trust anchor for optional Tls client authentication via bytes slice.
+    ///
+    /// See [`OpensslConfig::with_client_auth_optional_path`].
+    #[inline]
+    pub fn with_client_auth_optional(mut self, trust_anchor: impl Into<Vec<u8>>) -> Self {
+        self.client_auth = ClientAuth::Optional(trust_anchor.into());
+        self
+    }
+
+    /// Sets the trust anchor for required Tls client authentication via file path.
+    ///
+    /// Only authenticated clients will be accepted, handshakes without a client certificate fail.
+    /// If no trust anchor is provided by any of the `with_client_auth_` methods, then client
+    /// authentication is disabled by default.
+    #[inline]
+    pub fn with_client_auth_required_path(mut self, path: impl AsRef<Path>) -> IoResult<Self> {
+        let mut data = vec![];
+        File::open(path)?.read_to_end(&mut data)?;
+        self.client_auth = ClientAuth::Required(data);
+        Ok(self)
+    }
+
+    /// Sets the trust anchor for required Tls client authentication via bytes slice.
+    ///
+    /// See [`OpensslConfig::with_client_auth_required_path`].
+    #[inline]
+    pub fn with_client_auth_required(mut self, trust_anchor: impl Into<Vec<u8>>) -> Self {
+        self.client_auth = ClientAuth::Required(trust_anchor.into());
+        self
+    }
+
+    /// Sets the verifier of client certificates and returns `Self`.
+    ///
+    /// It is called for each certificate of the client chain with the result of the verification
+    /// by OpenSSL, the certificate and the verification error can be inspected in the
+    /// [`X509StoreContextRef`]. The certificate is accepted if it returns `true`. It is only used
+    /// with client authentication.
+    pub fn with_client_verifier<F>(mut self, verifier: F) -> Self
+    where
+        F: Fn(bool, &mut X509StoreContextRef) -> bool + Send + Sync + 'static,
+    {
+        self.client_verifier = Some(Arc::new(verifier));
+        self
+    }
+
+    /// Accepts client certificates which expired within `grace` and returns `Self`.
+    ///
+    /// Only the expiry of the client certificate itself is forgiven, a certificate which is not
+    /// trusted or has any other error is still rejected. A warning is logged for each certificate
+    /// accepted in the grace period. This replaces the verifier set by
+    /// [`OpensslConfig::with_client_verifier`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use salvo_core::conn::openssl::{Keycert, OpensslConfig};
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let config = OpensslConfig::new(Keycert::new().key_from_path("key.pem")?.cert_from_path("cert.pem")?)
+    ///     .with_client_auth_required_path("client-ca.pem")?
+    ///     .with_client_cert_expiry_grace(Duration::from_secs(6 * 3600));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_client_cert_expiry_grace(self, grace: Duration) -> Self {
+        self.with_client_verifier(move |preverified, ctx| preverified || expired_within(ctx, grace))
     }
 
     /// Set builder modifier.
+    ///
+    /// The modifier is called with the builder of the default certificate and, if there are any,
+    /// with the builders of the certificates set by [`OpensslConfig::with_keycert`].
     pub fn with_builder_modifier<F>(mut self, modifier: F) -> Self
     where
         F: FnMut(&mut SslAcceptorBuilder) + Send + 'static,
@@ -117,34 +307,146 @@ impl OpensslConfig {
     }
 
     /// Create [`SslAcceptorBuilder`]
+    ///
+    /// If certificates are set by [`OpensslConfig::with_keycert`], a servername callback is installed
+    /// to switch to the [`SslContext`] of the name requested by the client.
     pub fn create_acceptor_builder(&mut self) -> Result<SslAcceptorBuilder, IoError> {
+        let mut builder = self.new_acceptor_builder()?;
+        set_keycert(&mut builder, &mut self.keycert)?;
+
+        if !self.keycerts.is_empty() {
+            let mut keycerts = std::mem::take(&mut self.keycerts);
+            let contexts = self.create_sni_contexts(&mut keycerts);
+            self.keycerts = keycerts;
+            let contexts = contexts?;
+            builder.set_servername_callback(move |ssl: &mut SslRef, _: &mut SslAlert| {
+                let context = ssl
+                    .servername(NameType::HOST_NAME)
+                    .and_then(|name| contexts.get(&name.to_ascii_lowercase()));
+                if let Some(context) = context {
+                    ssl.set_ssl_context(context).map_err(|e| {
+                        tracing::error!(error = ?e, "openssl: switch ssl context failed.");
+                        SniError::ALERT_FATAL
+                    })?;
+                }
+                Ok(())
+            });
+        }
+
+        if let Some(modifier) = &mut self.builder_modifier {
+            modifier(&mut builder);
+        }
+        Ok(builder)
+    }
+
+    /// Create the [`SslContext`] of each certificate in `keycerts` by SNI name.
+    fn create_sni_contexts(
+        &mut self,
+        keycerts: &mut HashMap<String, Keycert>,
+    ) -> Result<HashMap<String, SslContext>, IoError> {
+        let mut contexts = HashMap::with_capacity(keycerts.len());
+        for (name, keycert) in keycerts {
+            let mut builder = self.new_acceptor_builder()?;
+            set_keycert(&mut builder, keycert)?;
+            if let Some(modifier) = &mut self.builder_modifier {
+                modifier(&mut builder);
+            }
+            contexts.insert(name.clone(), builder.build().into_context());
+        }
+        Ok(contexts)
+    }
+
+    /// Create a [`SslAcceptorBuilder`] with the settings shared by all certificates.
+    fn new_acceptor_builder(&self) -> Result<SslAcceptorBuilder, IoError> {
         let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
 
-        let mut certs = X509::stack_from_pem(self.keycert.cert()?)?;
-        let mut certs = certs.drain(..);
-        builder.set_certificate(
-            certs
-                .next()
-                .ok_or_else(|| IoError::new(ErrorKind::Other, "no leaf certificate"))?
-                .as_ref(),
-        )?;
-        certs.try_for_each(|cert| builder.add_extra_chain_cert(cert))?;
-        builder.set_private_key(PKey::private_key_from_pem(self.keycert.key()?)?.as_ref())?;
-
-        // set ALPN protocols
-        static PROTOS: &[u8] = b"\x02h2\x08http/1.1";
-        builder.set_alpn_protos(PROTOS)?;
+        let (trust_anchor, mode) = match &self.client_auth {
+            ClientAuth::Off => (None, SslVerifyMode::NONE),
+            ClientAuth::Optional(trust_anchor) => (Some(trust_anchor), SslVerifyMode::PEER),
+            ClientAuth::Required(trust_anchor) => (
+                Some(trust_anchor),
+                SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT,
+            ),
+        };
+        if let Some(trust_anchor) = trust_anchor {
+            for cert in X509::stack_from_pem(trust_anchor)? {
+                builder.cert_store_mut().add_cert(cert)?;
+            }
+            match &self.client_verifier {
+                Some(verifier) => {
+                    let verifier = verifier.clone();
+                    builder.set_verify_callback(mode, move |preverified, ctx| verifier(preverified, ctx));
+                }
+                None => builder.set_verify(mode),
+            }
+        }
+
+        // set ALPN protocols, encoded as length prefixed strings
+        let mut protos = Vec::new();
+        for proto in &self.alpn_protocols {
+            let len = u8::try_from(proto.len())
+                .map_err(|_| IoError::new(ErrorKind::Other, "openssl: alpn protocol is too long"))?;
+            protos.push(len);
+            protos.extend_from_slice(proto);
+        }
+        builder.set_alpn_protos(&protos)?;
         // set uo ALPN selection routine - as select_next_proto
         builder.set_alpn_select_callback(move |_: &mut SslRef, list: &[u8]| {
-            openssl::ssl::select_next_proto(PROTOS, list).ok_or(openssl::ssl::AlpnError::NOACK)
+            openssl::ssl::select_next_proto(&protos, list).ok_or(openssl::ssl::AlpnError::NOACK)
         });
-        if let Some(modifier) = &mut self.builder_modifier {
-            modifier(&mut builder);
+        if let Some(path) = &self.keylog_file {
+            tracing::warn!(path = ?path, "openssl: tls key log is enabled, never use this in production.");
+            let file = Mutex::new(OpenOptions::new().create(true).append(true).open(path)?);
+            builder.set_keylog_callback(move |_: &SslRef, line: &str| {
+                if let Err(e) = writeln!(file.lock(), "{line}") {
+                    tracing::error!(error = ?e, "openssl: write tls key log failed.");
+                }
+            });
         }
         Ok(builder)
     }
 }
 
+/// Check the verification error of `ctx` is the expiry of the client certificate, and it expired
+/// within `grace`.
+fn expired_within(ctx: &X509StoreContextRef, grace: Duration) -> bool {
+    if ctx.error().as_raw() != X509_V_ERR_CERT_HAS_EXPIRED || ctx.error_depth() != 0 {
+        return false;
+    }
+    let (cert, now) = match (ctx.current_cert(), Asn1Time::days_from_now(0)) {
+        (Some(cert), Ok(now)) => (cert, now),
+        _ => return false,
+    };
+    let expired_for = match cert.not_after().diff(&now) {
+        Ok(diff) => i64::from(diff.days) * 86400 + i64::from(diff.secs),
+        Err(_) => return false,
+    };
+    if expired_for <= i64::try_from(grace.as_secs()).unwrap_or(i64::MAX) {
+        tracing::warn!(
+            expired_for,
+            "openssl: client certificate is expired, accepted in the grace period."
+        );
+        true
+    } else {
+        false
+    }
+}
+
+/// Set the certificate chain and the private key of `keycert` to `builder`.
+fn set_keycert(builder: &mut SslAcceptorBuilder, keycert: &mut Keycert) -> IoResult<()> {
+    let mut certs = X509::stack_from_pem(keycert.cert()?)?;
+    let mut certs = certs.drain(..);
+    builder.set_certificate(
+        certs
+            .next()
+            .ok_or_else(|| IoError::new(ErrorKind::Other, "no leaf certificate"))?
+            .as_ref(),
+    )?;
+    certs.try_for_each(|cert| builder.add_extra_chain_cert(cert))?;
+    builder.set_private_key(PKey::private_key_from_pem(keycert.key()?)?.as_ref())?;
+    Ok(())
+}
+
 impl IntoConfigStream<OpensslConfig> for OpensslConfig {
     type Stream = Once<Ready<OpensslConfig>>;
 
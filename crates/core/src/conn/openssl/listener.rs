@@ -1,29 +1,89 @@
 //! openssl module
+use std::future::Future;
 use std::io::{Error as IoError, Result as IoResult};
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
+use futures_util::future::{BoxFuture, FutureExt};
 use futures_util::stream::BoxStream;
 use futures_util::task::noop_waker_ref;
 use futures_util::{Stream, StreamExt};
 use http::uri::Scheme;
+use openssl::asn1::Asn1Time;
 use openssl::ssl::{Ssl, SslAcceptor};
+use openssl::x509::X509VerifyResult;
 use tokio::io::ErrorKind;
 use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::Semaphore;
 use tokio_openssl::SslStream;
 
 use super::OpensslConfig;
 
 use crate::async_trait;
-use crate::conn::Holding;
-use crate::conn::{Accepted, Acceptor, HttpBuilders, IntoConfigStream, Listener, TlsConnStream};
+use crate::conn::{
+    handshake_with_timeout, AcceptError, AcceptErrorKind, Accepted, Acceptor, AcceptorShutdown, Holding, HttpBuilders,
+    IntoConfigStream, Listener, OnAcceptError, SocketAddr, TlsConnStream, DEFAULT_HANDSHAKE_TIMEOUT,
+};
 use crate::http::{version_from_alpn, HttpConnection, Version};
 use crate::service::HyperHandler;
 
+type ConfigValidator = Arc<dyn Fn(&SslAcceptor) -> IoResult<()> + Send + Sync>;
+
+/// Validate the certificate of `acceptor` built from a TLS config, it can be used as the validator
+/// of [`OpensslListener::with_config_validator`].
+///
+/// An error is returned if there is no private key, no certificate for the type of the private key,
+/// the private key doesn't match the certificate, the certificate is expired or not valid yet, or a
+/// certificate of the chain is not issued by the next one. Certificates of SNI names set by
+/// [`OpensslConfig::with_keycert`] are not checked.
+pub fn validate_acceptor(acceptor: &SslAcceptor) -> IoResult<()> {
+    let invalid = |msg: String| IoError::new(ErrorKind::InvalidData, format!("openssl: {msg}"));
+    let context = acceptor.context();
+    if context.private_key().is_none() {
+        return Err(invalid("no private key".into()));
+    }
+    // OpenSSL keeps a certificate for each key type, the one of the private key is checked, so a key
+    // of another type than the certificate leaves no certificate here.
+    let cert = context
+        .certificate()
+        .ok_or_else(|| invalid("no certificate for the private key".into()))?;
+    context
+        .check_private_key()
+        .map_err(|e| invalid(format!("private key doesn't match the certificate: {e}")))?;
+    let now = Asn1Time::days_from_now(0)?;
+    if cert.not_after() < &*now {
+        return Err(invalid(format!("certificate expired at {}", cert.not_after())));
+    }
+    if cert.not_before() > &*now {
+        return Err(invalid(format!(
+            "certificate is not valid before {}",
+            cert.not_before()
+        )));
+    }
+    let mut subject = cert;
+    for issuer in context.extra_chain_certs() {
+        let issued = issuer.issued(subject) == X509VerifyResult::OK;
+        if !issued || !subject.verify(&issuer.public_key()?)? {
+            return Err(invalid(format!(
+                "certificate `{:?}` is not issued by the next certificate of the chain",
+                subject.subject_name()
+            )));
+        }
+        subject = issuer;
+    }
+    Ok(())
+}
+
 /// OpensslListener
 pub struct OpensslListener<C, T> {
     config_stream: C,
     inner: T,
+    handshake_timeout: Duration,
+    max_concurrent_handshakes: Option<usize>,
+    on_error: Option<OnAcceptError>,
+    config_validator: Option<ConfigValidator>,
+    shutdown_signal: Option<BoxFuture<'static, ()>>,
 }
 
 impl<C, T> OpensslListener<C, T>
@@ -34,7 +94,111 @@ where
     /// Create new OpensslListener with config stream.
     #[inline]
     pub fn new(config_stream: C, inner: T) -> Self {
-        OpensslListener { config_stream, inner }
+        OpensslListener {
+            config_stream,
+            inner,
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+            max_concurrent_handshakes: None,
+            on_error: None,
+            config_validator: None,
+            shutdown_signal: None,
+        }
+    }
+
+    /// Sets the timeout of TLS handshakes and returns `Self`.
+    ///
+    /// A connection which doesn't complete the handshake in time is dropped, so clients which
+    /// connect and send nothing don't hold resources. `Duration::ZERO` disables the timeout. The
+    /// default is 10 seconds.
+    #[inline]
+    pub fn with_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum number of TLS handshakes in progress at the same time and returns `Self`.
+    ///
+    /// Handshakes are CPU intensive, connections accepted beyond the limit wait for a running
+    /// handshake to finish before they start theirs, so a flood of connections can't take all the
+    /// CPU. The handshake timeout starts when the handshake does. It is separate from the limit of
+    /// connections, see [`LimitedListener`](crate::conn::LimitedListener). There is no limit by
+    /// default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is `0`.
+    #[inline]
+    pub fn with_max_concurrent_handshakes(mut self, max: usize) -> Self {
+        assert!(max > 0, "max concurrent handshakes must be greater than 0");
+        self.max_concurrent_handshakes = Some(max);
+        self
+    }
+
+    /// Sets the observer of accept errors and returns `Self`.
+    ///
+    /// It is called for invalid TLS configs, connections rejected because no valid config is
+    /// loaded, and failed handshakes, the errors are logged by `tracing` as before. Use it to count
+    /// the failures for metrics and alerts.
+    #[inline]
+    pub fn on_error<F>(mut self, on_error: F) -> Self
+    where
+        F: Fn(&AcceptError) + Send + Sync + 'static,
+    {
+        self.on_error = Some(Arc::new(on_error));
+        self
+    }
+
+    /// Sets the validator of TLS configs and returns `Self`.
+    ///
+    /// It is called with the acceptor built from each config of the config stream before the
+    /// acceptor is used. If it returns an error, the config is rejected as an invalid one: the error
+    /// is logged and passed to the observer of [`on_error`](Self::on_error), and the previous
+    /// config is kept, so a bad config never goes live on reload. Use [`validate_acceptor`] to check
+    /// the certificate and private key. There is no validator by default.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use salvo_core::conn::openssl::{validate_acceptor, Keycert, OpensslConfig};
+    /// use salvo_core::conn::{Listener, TcpListener};
+    ///
+    /// # async fn run() {
+    /// let config = OpensslConfig::new(
+    ///     Keycert::new()
+    ///         .key_from_path("certs/key.pem")
+    ///         .unwrap()
+    ///         .cert_from_path("certs/cert.pem")
+    ///         .unwrap(),
+    /// );
+    /// let acceptor = TcpListener::new("0.0.0.0:443")
+    ///     .openssl(config)
+    ///     .with_config_validator(validate_acceptor)
+    ///     .bind()
+    ///     .await;
+    /// # }
+    /// ```
+    #[inline]
+    pub fn with_config_validator<F>(mut self, validator: F) -> Self
+    where
+        F: Fn(&SslAcceptor) -> IoResult<()> + Send + Sync + 'static,
+    {
+        self.config_validator = Some(Arc::new(validator));
+        self
+    }
+
+    /// Sets the shutdown signal and returns `Self`.
+    ///
+    /// Once `signal` resolves, no connection is accepted from the inner listener anymore and
+    /// `accept` returns an [`AcceptorShutdown`] error, the [`Server`](crate::Server) stops accepting
+    /// and waits for alive connections to close. Connections accepted before go on, including
+    /// those still in the TLS handshake.
+    #[inline]
+    pub fn with_shutdown_signal<F>(mut self, signal: F) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.shutdown_signal = Some(signal.boxed());
+        self
     }
 }
 
@@ -52,10 +216,15 @@ where
     }
 
     async fn try_bind(self) -> IoResult<Self::Acceptor> {
-        Ok(OpensslAcceptor::new(
-            self.config_stream.into_stream().boxed(),
-            self.inner.try_bind().await?,
-        ))
+        let mut acceptor = OpensslAcceptor::new(self.config_stream.into_stream().boxed(), self.inner.try_bind().await?)
+            .with_handshake_timeout(self.handshake_timeout);
+        if let Some(max) = self.max_concurrent_handshakes {
+            acceptor = acceptor.with_max_concurrent_handshakes(max);
+        }
+        acceptor.on_error = self.on_error;
+        acceptor.config_validator = self.config_validator;
+        acceptor.shutdown_signal = self.shutdown_signal;
+        Ok(acceptor)
     }
 }
 
@@ -65,6 +234,13 @@ pub struct OpensslAcceptor<C, T> {
     inner: T,
     holdings: Vec<Holding>,
     tls_acceptor: Option<Arc<SslAcceptor>>,
+    handshake_timeout: Option<Duration>,
+    handshake_semaphore: Option<Arc<Semaphore>>,
+    on_error: Option<OnAcceptError>,
+    config_validator: Option<ConfigValidator>,
+    shutdown_signal: Option<BoxFuture<'static, ()>>,
+    shut_down: bool,
+    config_ended: bool,
 }
 impl<C, T> OpensslAcceptor<C, T>
 where
@@ -86,8 +262,145 @@ where
             inner,
             holdings,
             tls_acceptor: None,
+            handshake_timeout: Some(DEFAULT_HANDSHAKE_TIMEOUT),
+            handshake_semaphore: None,
+            on_error: None,
+            config_validator: None,
+            shutdown_signal: None,
+            shut_down: false,
+            config_ended: false,
         }
     }
+
+    /// Sets the timeout of TLS handshakes and returns `Self`, `Duration::ZERO` disables it.
+    ///
+    /// See [`OpensslListener::with_handshake_timeout`].
+    #[inline]
+    pub fn with_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = if timeout.is_zero() { None } else { Some(timeout) };
+        self
+    }
+
+    /// Sets the maximum number of TLS handshakes in progress at the same time and returns `Self`.
+    ///
+    /// See [`OpensslListener::with_max_concurrent_handshakes`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is `0`.
+    #[inline]
+    pub fn with_max_concurrent_handshakes(mut self, max: usize) -> Self {
+        assert!(max > 0, "max concurrent handshakes must be greater than 0");
+        self.handshake_semaphore = Some(Arc::new(Semaphore::new(max)));
+        self
+    }
+
+    /// Sets the observer of accept errors and returns `Self`.
+    ///
+    /// See [`OpensslListener::on_error`].
+    #[inline]
+    pub fn on_error<F>(mut self, on_error: F) -> Self
+    where
+        F: Fn(&AcceptError) + Send + Sync + 'static,
+    {
+        self.on_error = Some(Arc::new(on_error));
+        self
+    }
+
+    /// Sets the validator of TLS configs and returns `Self`.
+    ///
+    /// See [`OpensslListener::with_config_validator`].
+    #[inline]
+    pub fn with_config_validator<F>(mut self, validator: F) -> Self
+    where
+        F: Fn(&SslAcceptor) -> IoResult<()> + Send + Sync + 'static,
+    {
+        self.config_validator = Some(Arc::new(validator));
+        self
+    }
+
+    /// Sets the shutdown signal and returns `Self`.
+    ///
+    /// See [`OpensslListener::with_shutdown_signal`].
+    #[inline]
+    pub fn with_shutdown_signal<F>(mut self, signal: F) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.shutdown_signal = Some(signal.boxed());
+        self
+    }
+
+    fn report_error(&self, kind: AcceptErrorKind, error: IoError) -> IoError {
+        report_error(self.on_error.as_ref(), kind, None, error)
+    }
+
+    fn update_config(&mut self, mut config: OpensslConfig) {
+        match config.create_acceptor_builder() {
+            Ok(builder) => {
+                let tls_acceptor = builder.build();
+                if let Some(validator) = &self.config_validator {
+                    if let Err(e) = validator(&tls_acceptor) {
+                        tracing::error!(error = ?e, "openssl: tls config is rejected by validator.");
+                        self.report_error(AcceptErrorKind::ConfigLoad, e);
+                        return;
+                    }
+                }
+                if self.tls_acceptor.is_some() {
+                    tracing::info!("tls config changed.");
+                } else {
+                    tracing::info!("tls config loaded.");
+                }
+                self.tls_acceptor = Some(Arc::new(tls_acceptor));
+            }
+            Err(e) => {
+                tracing::error!(error = ?e, "openssl: invalid tls config.");
+                self.report_error(AcceptErrorKind::ConfigLoad, e);
+            }
+        }
+    }
+}
+
+/// Pass the error to the observer if it is set and returns the error.
+fn report_error(
+    on_error: Option<&OnAcceptError>,
+    kind: AcceptErrorKind,
+    remote_addr: Option<&SocketAddr>,
+    error: IoError,
+) -> IoError {
+    match on_error {
+        Some(on_error) => {
+            let accept_error = AcceptError {
+                kind,
+                remote_addr: remote_addr.cloned(),
+                error,
+            };
+            on_error(&accept_error);
+            accept_error.error
+        }
+        None => error,
+    }
+}
+
+/// Convert a failed handshake into [`IoError`], keeps the reasons reported by openssl, such as
+/// the alert sent by the peer (`tlsv1 alert unknown ca`, `sslv3 alert bad certificate`...).
+pub(crate) fn handshake_error(err: openssl::ssl::Error) -> IoError {
+    let reasons = err
+        .ssl_error()
+        .map(|stack| {
+            stack
+                .errors()
+                .iter()
+                .filter_map(|e| e.reason())
+                .collect::<Vec<_>>()
+                .join("; ")
+        })
+        .unwrap_or_default();
+    if reasons.is_empty() {
+        IoError::new(ErrorKind::Other, format!("openssl: tls handshake failed: {err}"))
+    } else {
+        IoError::new(ErrorKind::Other, format!("openssl: tls handshake failed: {reasons}"))
+    }
 }
 
 #[async_trait]
@@ -98,19 +411,15 @@ where
     async fn version(&mut self) -> Option<Version> {
         self.ssl().selected_alpn_protocol().map(version_from_alpn)
     }
+    async fn alpn(&mut self) -> Option<Vec<u8>> {
+        self.ssl().selected_alpn_protocol().map(|proto| proto.to_vec())
+    }
+    async fn peer_certificate(&mut self) -> Option<Vec<u8>> {
+        self.ssl().peer_certificate().and_then(|cert| cert.to_der().ok())
+    }
     async fn serve(self, handler: HyperHandler, builders: Arc<HttpBuilders>) -> IoResult<()> {
-        #[cfg(not(feature = "http2"))]
-        {
-            let _ = handler;
-            let _ = builders;
-            panic!("http2 feature is required");
-        }
-        #[cfg(feature = "http2")]
-        builders
-            .http2
-            .serve_connection(self, handler)
-            .await
-            .map_err(|e| IoError::new(ErrorKind::Other, e.to_string()))
+        let version = self.ssl().selected_alpn_protocol().map(version_from_alpn);
+        builders.serve_negotiated(self, version, handler).await
     }
 }
 
@@ -129,45 +438,111 @@ where
 
     #[inline]
     async fn accept(&mut self) -> IoResult<Accepted<Self::Conn>> {
+        if self.shut_down {
+            return Err(AcceptorShutdown.into());
+        }
         let config = {
             let mut config = None;
-            while let Poll::Ready(Some(item)) = self
-                .config_stream
-                .poll_next_unpin(&mut Context::from_waker(noop_waker_ref()))
-            {
-                config = Some(item);
+            // An ended stream must not be polled again.
+            while !self.config_ended {
+                match self
+                    .config_stream
+                    .poll_next_unpin(&mut Context::from_waker(noop_waker_ref()))
+                {
+                    Poll::Ready(Some(item)) => config = Some(item),
+                    Poll::Ready(None) => self.config_ended = true,
+                    Poll::Pending => break,
+                }
             }
             config
         };
-        if let Some(mut config) = config {
-            match config.create_acceptor_builder() {
-                Ok(builder) => {
-                    if self.tls_acceptor.is_some() {
-                        tracing::info!("tls config changed.");
-                    } else {
-                        tracing::info!("tls config loaded.");
-                    }
-                    self.tls_acceptor = Some(Arc::new(builder.build()));
+        if let Some(config) = config {
+            self.update_config(config);
+        }
+        // Connections are not pulled off the inner listener until a valid config is loaded,
+        // otherwise they would be accepted only to fail.
+        while self.tls_acceptor.is_none() {
+            if self.config_ended {
+                // No connection can ever be accepted, so the acceptor is shut down instead of
+                // failing every call.
+                tracing::error!("openssl: tls config stream ended without a valid config.");
+                self.report_error(
+                    AcceptErrorKind::MissingConfig,
+                    IoError::new(ErrorKind::Other, "openssl: invalid tls config."),
+                );
+                self.shut_down = true;
+                return Err(AcceptorShutdown.into());
+            }
+            let next = match &mut self.shutdown_signal {
+                Some(signal) => tokio::select! {
+                    biased;
+                    _ = signal => None,
+                    config = self.config_stream.next() => Some(config),
+                },
+                None => Some(self.config_stream.next().await),
+            };
+            match next {
+                Some(Some(config)) => self.update_config(config),
+                Some(None) => self.config_ended = true,
+                None => {
+                    tracing::info!("openssl: shutdown signal received, stop waiting for tls config.");
+                    self.shutdown_signal = None;
+                    self.shut_down = true;
+                    return Err(AcceptorShutdown.into());
                 }
-                Err(e) => tracing::error!(error = ?e, "openssl: invalid tls config."),
             }
         }
         let tls_acceptor = match &self.tls_acceptor {
             Some(tls_acceptor) => tls_acceptor.clone(),
             None => return Err(IoError::new(ErrorKind::Other, "openssl: invalid tls config.")),
         };
-        let accepted = self.inner.accept().await?.map_conn(|stream| {
-            let fut = async move {
+        let handshake_timeout = self.handshake_timeout;
+        let handshake_semaphore = self.handshake_semaphore.clone();
+        let on_error = self.on_error.clone();
+        let accepted = match &mut self.shutdown_signal {
+            // The signal is checked first, so no connection is accepted once it is resolved.
+            Some(signal) => tokio::select! {
+                biased;
+                _ = signal => None,
+                accepted = self.inner.accept() => Some(accepted),
+            },
+            None => Some(self.inner.accept().await),
+        };
+        let accepted = match accepted {
+            Some(accepted) => accepted?,
+            None => {
+                tracing::info!("openssl: shutdown signal received, stop accepting connections.");
+                self.shutdown_signal = None;
+                self.shut_down = true;
+                return Err(AcceptorShutdown.into());
+            }
+        };
+        let remote_addr = accepted.remote_addr.clone();
+        let accepted = accepted.map_conn(|stream| {
+            let handshake = async move {
+                // Held until the handshake is done, the semaphore is never closed.
+                let _permit = match handshake_semaphore {
+                    Some(semaphore) => semaphore.acquire_owned().await.ok(),
+                    None => None,
+                };
                 let ssl =
                     Ssl::new(tls_acceptor.context()).map_err(|err| IoError::new(ErrorKind::Other, err.to_string()))?;
                 let mut tls_stream =
                     SslStream::new(ssl, stream).map_err(|err| IoError::new(ErrorKind::Other, err.to_string()))?;
                 use std::pin::Pin;
-                Pin::new(&mut tls_stream)
-                    .accept()
-                    .await
-                    .map_err(|err| IoError::new(ErrorKind::Other, err.to_string()))?;
-                Ok(tls_stream)
+                let handshake = Pin::new(&mut tls_stream).accept();
+                handshake_with_timeout(
+                    async move { handshake.await.map_err(handshake_error) },
+                    handshake_timeout,
+                    "openssl",
+                )
+                .await?;
+                Ok::<_, IoError>(tls_stream)
+            };
+            let fut = async move {
+                handshake.await.map_err(|error: IoError| {
+                    report_error(on_error.as_ref(), AcceptErrorKind::Handshake, Some(&remote_addr), error)
+                })
             };
             TlsConnStream::new(fut)
         });
@@ -3,7 +3,7 @@ mod config;
 pub use config::{Keycert, OpensslConfig};
 
 mod listener;
-pub use listener::{OpensslAcceptor, OpensslListener};
+pub use listener::{validate_acceptor, OpensslAcceptor, OpensslListener};
 
 #[cfg(test)]
 mod tests {
@@ -16,6 +16,7 @@ mod tests {
 
     use super::*;
     use crate::conn::{Accepted, Acceptor, Listener, TcpListener};
+    use crate::http::{HttpConnection, Version};
 
     #[tokio::test]
     async fn test_openssl_listener() {
@@ -52,4 +53,728 @@ mod tests {
         let Accepted { mut conn, .. } = acceptor.accept().await.unwrap();
         assert_eq!(conn.read_i32().await.unwrap(), 518);
     }
+
+    #[tokio::test]
+    async fn test_openssl_keylog() {
+        let keylog = tempfile::NamedTempFile::new().unwrap();
+        let mut acceptor = TcpListener::new("127.0.0.1:0")
+            .openssl(
+                OpensslConfig::new(
+                    Keycert::new()
+                        .key_from_path("certs/key.pem")
+                        .unwrap()
+                        .cert_from_path("certs/cert.pem")
+                        .unwrap(),
+                )
+                .with_keylog_file(keylog.path()),
+            )
+            .bind()
+            .await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+
+        tokio::spawn(async move {
+            let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+            connector.set_ca_file("certs/chain.pem").unwrap();
+            let ssl = connector
+                .build()
+                .configure()
+                .unwrap()
+                .into_ssl("testserver.com")
+                .unwrap();
+
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let mut tls_stream = SslStream::new(ssl, stream).unwrap();
+            Pin::new(&mut tls_stream).connect().await.unwrap();
+            tls_stream.write_i32(518).await.unwrap();
+        });
+
+        let Accepted { mut conn, .. } = acceptor.accept().await.unwrap();
+        assert_eq!(conn.read_i32().await.unwrap(), 518);
+
+        let content = std::fs::read_to_string(keylog.path()).unwrap();
+        assert!(!content.is_empty());
+        for line in content.lines() {
+            // NSS key log format: `<label> <client_random in hex> <secret in hex>`
+            let parts = line.split(' ').collect::<Vec<_>>();
+            assert_eq!(parts.len(), 3, "{line}");
+            assert!(parts[0].chars().all(|c| c.is_ascii_uppercase() || c == '_'));
+            assert!(parts[1..].iter().all(|p| p.chars().all(|c| c.is_ascii_hexdigit())));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_openssl_handshake_alert() {
+        let mut acceptor = TcpListener::new("127.0.0.1:0")
+            .openssl(OpensslConfig::new(
+                Keycert::new()
+                    .key_from_path("certs/key.pem")
+                    .unwrap()
+                    .cert_from_path("certs/cert.pem")
+                    .unwrap(),
+            ))
+            .bind()
+            .await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+
+        tokio::spawn(async move {
+            // The client doesn't trust the test CA, so it aborts the handshake with `unknown_ca`.
+            let connector = SslConnector::builder(SslMethod::tls()).unwrap();
+            let ssl = connector
+                .build()
+                .configure()
+                .unwrap()
+                .into_ssl("testserver.com")
+                .unwrap();
+
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let mut tls_stream = SslStream::new(ssl, stream).unwrap();
+            Pin::new(&mut tls_stream).connect().await.ok();
+        });
+
+        let Accepted { mut conn, .. } = acceptor.accept().await.unwrap();
+        let err = conn.read_i32().await.unwrap_err();
+        assert!(err.to_string().contains("unknown ca"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_openssl_alpn() {
+        let mut acceptor = TcpListener::new("127.0.0.1:0")
+            .openssl(
+                OpensslConfig::new(
+                    Keycert::new()
+                        .key_from_path("certs/key.pem")
+                        .unwrap()
+                        .cert_from_path("certs/cert.pem")
+                        .unwrap(),
+                )
+                .with_alpn_protocols(vec![b"h2".to_vec(), b"http/1.1".to_vec()]),
+            )
+            .bind()
+            .await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+
+        let client = tokio::spawn(async move {
+            let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+            connector.set_ca_file("certs/chain.pem").unwrap();
+            // ALPN wire format: length prefixed protocol names.
+            connector.set_alpn_protos(b"\x02h2").unwrap();
+            let ssl = connector
+                .build()
+                .configure()
+                .unwrap()
+                .into_ssl("testserver.com")
+                .unwrap();
+
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let mut tls_stream = SslStream::new(ssl, stream).unwrap();
+            Pin::new(&mut tls_stream).connect().await.unwrap();
+            tls_stream.ssl().selected_alpn_protocol().map(|proto| proto.to_vec())
+        });
+
+        let Accepted { mut conn, .. } = acceptor.accept().await.unwrap();
+        assert_eq!(conn.alpn().await, Some(b"h2".to_vec()));
+        assert_eq!(conn.version().await, Some(Version::HTTP_2));
+        assert_eq!(client.await.unwrap(), Some(b"h2".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_openssl_sni() {
+        use openssl::nid::Nid;
+        use openssl::ssl::SslVerifyMode;
+
+        let mut acceptor = TcpListener::new("127.0.0.1:0")
+            .openssl(
+                OpensslConfig::new(
+                    Keycert::new()
+                        .key_from_path("certs/key.pem")
+                        .unwrap()
+                        .cert_from_path("certs/cert.pem")
+                        .unwrap(),
+                )
+                .with_keycert(
+                    "Other.TestServer.com",
+                    Keycert::new()
+                        .key_from_path("certs/other-key.pem")
+                        .unwrap()
+                        .cert_from_path("certs/other-cert.pem")
+                        .unwrap(),
+                ),
+            )
+            .bind()
+            .await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+
+        for (server_name, subject) in [
+            (Some("testserver.com"), "testserver.com"),
+            (Some("other.testserver.com"), "other.testserver.com"),
+            (Some("unknown.testserver.com"), "testserver.com"),
+            (None, "testserver.com"),
+        ] {
+            let client = tokio::spawn(async move {
+                // Only the presented certificate is checked, so it is not verified.
+                let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+                connector.set_verify(SslVerifyMode::NONE);
+                let mut config = connector.build().configure().unwrap();
+                config.set_verify_hostname(false);
+                config.set_use_server_name_indication(server_name.is_some());
+                let ssl = config.into_ssl(server_name.unwrap_or("testserver.com")).unwrap();
+
+                let stream = TcpStream::connect(addr).await.unwrap();
+                let mut tls_stream = SslStream::new(ssl, stream).unwrap();
+                Pin::new(&mut tls_stream).connect().await.unwrap();
+                tls_stream.write_i32(518).await.unwrap();
+                let cert = tls_stream.ssl().peer_certificate().unwrap();
+                let common_name = cert.subject_name().entries_by_nid(Nid::COMMONNAME).next().unwrap();
+                common_name.data().as_utf8().unwrap().to_string()
+            });
+
+            let Accepted { mut conn, .. } = acceptor.accept().await.unwrap();
+            assert_eq!(conn.read_i32().await.unwrap(), 518);
+            assert_eq!(client.await.unwrap(), subject, "{server_name:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_openssl_handshake_timeout() {
+        let mut acceptor = TcpListener::new("127.0.0.1:0")
+            .openssl(OpensslConfig::new(
+                Keycert::new()
+                    .key_from_path("certs/key.pem")
+                    .unwrap()
+                    .cert_from_path("certs/cert.pem")
+                    .unwrap(),
+            ))
+            .with_handshake_timeout(std::time::Duration::from_millis(200))
+            .bind()
+            .await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+
+        // The client connects but never starts the handshake.
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let Accepted { mut conn, .. } = acceptor.accept().await.unwrap();
+        let started = std::time::Instant::now();
+        let err = conn.read_i32().await.unwrap_err();
+        assert!(started.elapsed() >= std::time::Duration::from_millis(200));
+        assert!(err.to_string().contains("timed out"));
+        drop(conn);
+
+        let mut buf = Vec::new();
+        let closed = tokio::time::timeout(std::time::Duration::from_secs(5), stream.read_to_end(&mut buf)).await;
+        assert!(matches!(closed, Ok(Ok(0)) | Ok(Err(_))));
+    }
+
+    // Handshakes run on several threads, so they can overlap if they are not bounded.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_openssl_max_concurrent_handshakes() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        use openssl::ssl::{SslAlert, SslRef};
+
+        // Counts the handshakes which got the client hello, so they are running, and the maximum number
+        // of them in the servername callback at the same time.
+        let started = Arc::new(AtomicUsize::new(0));
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let mut acceptor = TcpListener::new("127.0.0.1:0")
+            .openssl(
+                OpensslConfig::new(
+                    Keycert::new()
+                        .key_from_path("certs/key.pem")
+                        .unwrap()
+                        .cert_from_path("certs/cert.pem")
+                        .unwrap(),
+                )
+                .with_builder_modifier({
+                    let started = started.clone();
+                    let running = running.clone();
+                    let max_running = max_running.clone();
+                    move |builder| {
+                        let started = started.clone();
+                        let running = running.clone();
+                        let max_running = max_running.clone();
+                        builder.set_servername_callback(move |_: &mut SslRef, _: &mut SslAlert| {
+                            started.fetch_add(1, Ordering::SeqCst);
+                            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                            max_running.fetch_max(now, Ordering::SeqCst);
+                            // Keeps the handshake busy, so other handshakes would overlap with it.
+                            std::thread::sleep(Duration::from_millis(20));
+                            running.fetch_sub(1, Ordering::SeqCst);
+                            Ok(())
+                        });
+                    }
+                }),
+            )
+            .with_max_concurrent_handshakes(2)
+            .bind()
+            .await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+
+        // Two clients which never send the client hello take both handshake slots.
+        let mut stalled = Vec::new();
+        for _ in 0..2 {
+            stalled.push(TcpStream::connect(addr).await.unwrap());
+            let Accepted { mut conn, .. } = acceptor.accept().await.unwrap();
+            tokio::spawn(async move { conn.read_i32().await });
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut clients = Vec::new();
+        let mut servers = Vec::new();
+        for _ in 0..8 {
+            clients.push(tokio::spawn(async move {
+                let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+                connector.set_ca_file("certs/chain.pem").unwrap();
+                let ssl = connector
+                    .build()
+                    .configure()
+                    .unwrap()
+                    .into_ssl("testserver.com")
+                    .unwrap();
+
+                let stream = TcpStream::connect(addr).await.unwrap();
+                let mut tls_stream = SslStream::new(ssl, stream).unwrap();
+                Pin::new(&mut tls_stream).connect().await.unwrap();
+                tls_stream.write_i32(518).await.unwrap();
+            }));
+            let Accepted { mut conn, .. } = acceptor.accept().await.unwrap();
+            servers.push(tokio::spawn(async move { conn.read_i32().await.unwrap() }));
+        }
+
+        // All connections are accepted, but their handshakes wait for a free slot.
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(started.load(Ordering::SeqCst), 0);
+        assert!(servers.iter().all(|server| !server.is_finished()));
+
+        // Closing the stalled clients frees the slots, the queued handshakes run then.
+        drop(stalled);
+        for server in servers {
+            assert_eq!(server.await.unwrap(), 518);
+        }
+        for client in clients {
+            client.await.unwrap();
+        }
+        assert_eq!(started.load(Ordering::SeqCst), 8);
+        let max_running = max_running.load(Ordering::SeqCst);
+        assert!((1..=2).contains(&max_running), "{max_running} handshakes ran at the same time");
+    }
+
+    #[tokio::test]
+    async fn test_openssl_wait_valid_config() {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let config_stream = futures_util::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|c| (c, rx)) });
+        tx.send(OpensslConfig::new(
+            Keycert::new()
+                .with_key(b"invalid".to_vec())
+                .with_cert(b"invalid".to_vec()),
+        ))
+        .unwrap();
+        let mut acceptor = TcpListener::new("127.0.0.1:0").openssl(config_stream).bind().await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+
+        let accept = tokio::spawn(async move {
+            let Accepted { mut conn, .. } = acceptor.accept().await.unwrap();
+            conn.read_i32().await.unwrap()
+        });
+        tokio::spawn(async move {
+            let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+            connector.set_ca_file("certs/chain.pem").unwrap();
+            let ssl = connector
+                .build()
+                .configure()
+                .unwrap()
+                .into_ssl("testserver.com")
+                .unwrap();
+
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let mut tls_stream = SslStream::new(ssl, stream).unwrap();
+            Pin::new(&mut tls_stream).connect().await.unwrap();
+            tls_stream.write_i32(518).await.unwrap();
+        });
+
+        // The connection is not accepted until a valid config is loaded.
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert!(!accept.is_finished());
+
+        tx.send(OpensslConfig::new(
+            Keycert::new()
+                .key_from_path("certs/key.pem")
+                .unwrap()
+                .cert_from_path("certs/cert.pem")
+                .unwrap(),
+        ))
+        .unwrap();
+        assert_eq!(accept.await.unwrap(), 518);
+    }
+
+    #[tokio::test]
+    async fn test_openssl_shutdown_signal() {
+        use crate::conn::AcceptorShutdown;
+
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let mut acceptor = TcpListener::new("127.0.0.1:0")
+            .openssl(OpensslConfig::new(
+                Keycert::new()
+                    .key_from_path("certs/key.pem")
+                    .unwrap()
+                    .cert_from_path("certs/cert.pem")
+                    .unwrap(),
+            ))
+            .with_shutdown_signal(async move {
+                rx.await.ok();
+            })
+            .bind()
+            .await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+
+        // The connection is accepted before the signal, but its handshake is not done yet.
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let Accepted { mut conn, .. } = acceptor.accept().await.unwrap();
+        tx.send(()).unwrap();
+
+        let _late = TcpStream::connect(addr).await.unwrap();
+        for _ in 0..2 {
+            match acceptor.accept().await {
+                Ok(_) => panic!("connection accepted after the shutdown signal"),
+                Err(e) => assert!(AcceptorShutdown::is(&e)),
+            }
+        }
+
+        let client = tokio::spawn(async move {
+            let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+            connector.set_ca_file("certs/chain.pem").unwrap();
+            let ssl = connector
+                .build()
+                .configure()
+                .unwrap()
+                .into_ssl("testserver.com")
+                .unwrap();
+
+            let mut tls_stream = SslStream::new(ssl, stream).unwrap();
+            Pin::new(&mut tls_stream).connect().await.unwrap();
+            tls_stream.write_i32(518).await.unwrap();
+        });
+        assert_eq!(conn.read_i32().await.unwrap(), 518);
+        client.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_openssl_shutdown_signal_without_config() {
+        use crate::conn::AcceptorShutdown;
+
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let mut acceptor = TcpListener::new("127.0.0.1:0")
+            .openssl(futures_util::stream::pending::<OpensslConfig>())
+            .with_shutdown_signal(async move {
+                rx.await.ok();
+            })
+            .bind()
+            .await;
+        let accept = tokio::spawn(async move { acceptor.accept().await.map(|_| ()) });
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert!(!accept.is_finished());
+
+        // The acceptor waits for a valid config, the signal still shuts it down.
+        tx.send(()).unwrap();
+        let result = tokio::time::timeout(std::time::Duration::from_secs(5), accept)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(AcceptorShutdown::is(&result.unwrap_err()));
+    }
+
+    #[tokio::test]
+    async fn test_openssl_on_error() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::{Arc, Mutex};
+
+        use crate::conn::{AcceptError, AcceptErrorKind, AcceptorShutdown};
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let kinds = Arc::new(Mutex::new(Vec::new()));
+        let on_error = {
+            let count = count.clone();
+            let kinds = kinds.clone();
+            move |err: &AcceptError| {
+                count.fetch_add(1, Ordering::SeqCst);
+                kinds.lock().unwrap().push((err.kind, err.remote_addr.is_some()));
+            }
+        };
+
+        // No valid config is ever loaded, the pending connection is rejected.
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let config_stream = futures_util::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|c| (c, rx)) });
+        tx.send(OpensslConfig::new(
+            Keycert::new()
+                .with_key(b"invalid".to_vec())
+                .with_cert(b"invalid".to_vec()),
+        ))
+        .unwrap();
+        drop(tx);
+        let mut acceptor = TcpListener::new("127.0.0.1:0")
+            .openssl(config_stream)
+            .on_error(on_error.clone())
+            .bind()
+            .await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+        let _stream = TcpStream::connect(addr).await.unwrap();
+        // The acceptor is shut down, so the server stops instead of calling `accept` in a busy loop.
+        for _ in 0..2 {
+            match acceptor.accept().await {
+                Ok(_) => panic!("connection accepted without a valid config"),
+                Err(e) => assert!(AcceptorShutdown::is(&e)),
+            }
+        }
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            *kinds.lock().unwrap(),
+            vec![
+                (AcceptErrorKind::ConfigLoad, false),
+                (AcceptErrorKind::MissingConfig, false)
+            ]
+        );
+
+        // A failed handshake is reported with the remote addr.
+        let mut acceptor = TcpListener::new("127.0.0.1:0")
+            .openssl(OpensslConfig::new(
+                Keycert::new()
+                    .key_from_path("certs/key.pem")
+                    .unwrap()
+                    .cert_from_path("certs/cert.pem")
+                    .unwrap(),
+            ))
+            .on_error(on_error)
+            .bind()
+            .await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        let Accepted { mut conn, .. } = acceptor.accept().await.unwrap();
+        assert!(conn.read_i32().await.is_err());
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        assert_eq!(kinds.lock().unwrap()[2], (AcceptErrorKind::Handshake, true));
+    }
+
+    #[tokio::test]
+    async fn test_openssl_config_validator() {
+        use std::sync::{Arc, Mutex};
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        use openssl::asn1::Asn1Time;
+        use openssl::bn::BigNum;
+        use openssl::ec::{EcGroup, EcKey};
+        use openssl::hash::MessageDigest;
+        use openssl::nid::Nid;
+        use openssl::pkey::PKey;
+        use openssl::x509::{X509Name, X509};
+
+        use crate::conn::{AcceptError, AcceptErrorKind};
+
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        // Self-signed, expired a day ago.
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        let mut name = X509Name::builder().unwrap();
+        name.append_entry_by_nid(Nid::COMMONNAME, "testserver.com").unwrap();
+        let name = name.build();
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        builder
+            .set_serial_number(&BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap())
+            .unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::from_unix(now - 30 * 86400).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::from_unix(now - 86400).unwrap())
+            .unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        let expired_cert = builder.build();
+
+        // The EC key is not of the RSA certificate, openssl loads it beside the certificate without error,
+        // and there is no certificate for the key then.
+        let mismatched = OpensslConfig::new(
+            Keycert::new()
+                .with_key(key.private_key_to_pem_pkcs8().unwrap())
+                .cert_from_path("certs/cert.pem")
+                .unwrap(),
+        );
+        let expired = OpensslConfig::new(
+            Keycert::new()
+                .with_key(key.private_key_to_pem_pkcs8().unwrap())
+                .with_cert(expired_cert.to_pem().unwrap()),
+        );
+
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let config_stream = futures_util::stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|c| (c, rx)) });
+        tx.send(OpensslConfig::new(
+            Keycert::new()
+                .key_from_path("certs/key.pem")
+                .unwrap()
+                .cert_from_path("certs/cert.pem")
+                .unwrap(),
+        ))
+        .unwrap();
+        let mut acceptor = TcpListener::new("127.0.0.1:0")
+            .openssl(config_stream)
+            .with_config_validator(validate_acceptor)
+            .on_error({
+                let errors = errors.clone();
+                move |err: &AcceptError| errors.lock().unwrap().push((err.kind, err.error.to_string()))
+            })
+            .bind()
+            .await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+
+        // The previous acceptor keeps serving the verified certificate after each rejected config.
+        for config in [None, Some(mismatched), Some(expired)] {
+            if let Some(config) = config {
+                tx.send(config).unwrap();
+            }
+            let client = tokio::spawn(async move {
+                let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+                connector.set_ca_file("certs/chain.pem").unwrap();
+                let ssl = connector
+                    .build()
+                    .configure()
+                    .unwrap()
+                    .into_ssl("testserver.com")
+                    .unwrap();
+
+                let stream = TcpStream::connect(addr).await.unwrap();
+                let mut tls_stream = SslStream::new(ssl, stream).unwrap();
+                Pin::new(&mut tls_stream).connect().await.unwrap();
+                tls_stream.write_i32(518).await.unwrap();
+            });
+            let Accepted { mut conn, .. } = acceptor.accept().await.unwrap();
+            assert_eq!(conn.read_i32().await.unwrap(), 518);
+            client.await.unwrap();
+        }
+
+        let errors = errors.lock().unwrap();
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert_eq!(errors[0].0, AcceptErrorKind::ConfigLoad);
+        assert!(
+            errors[0].1.contains("no certificate for the private key"),
+            "{}",
+            errors[0].1
+        );
+        assert_eq!(errors[1].0, AcceptErrorKind::ConfigLoad);
+        assert!(errors[1].1.contains("expired"), "{}", errors[1].1);
+    }
+
+    #[tokio::test]
+    async fn test_openssl_client_cert_expiry_grace() {
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+        use openssl::asn1::Asn1Time;
+        use openssl::bn::BigNum;
+        use openssl::ec::{EcGroup, EcKey};
+        use openssl::hash::MessageDigest;
+        use openssl::nid::Nid;
+        use openssl::pkey::{PKey, Private};
+        use openssl::ssl::SslVerifyMode;
+        use openssl::x509::extension::BasicConstraints;
+        use openssl::x509::{X509Name, X509};
+
+        fn new_key() -> PKey<Private> {
+            let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+            PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+        }
+        fn new_cert(name: &str, key: &PKey<Private>, issuer: Option<(&X509, &PKey<Private>)>, not_after: i64) -> X509 {
+            let mut subject = X509Name::builder().unwrap();
+            subject.append_entry_by_nid(Nid::COMMONNAME, name).unwrap();
+            let subject = subject.build();
+            let mut builder = X509::builder().unwrap();
+            builder.set_version(2).unwrap();
+            builder
+                .set_serial_number(&BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap())
+                .unwrap();
+            builder.set_subject_name(&subject).unwrap();
+            builder.set_pubkey(key).unwrap();
+            builder
+                .set_not_before(&Asn1Time::from_unix(not_after - 30 * 86400).unwrap())
+                .unwrap();
+            builder.set_not_after(&Asn1Time::from_unix(not_after).unwrap()).unwrap();
+            match issuer {
+                Some((issuer, issuer_key)) => {
+                    builder.set_issuer_name(issuer.subject_name()).unwrap();
+                    builder.sign(issuer_key, MessageDigest::sha256()).unwrap();
+                }
+                None => {
+                    builder
+                        .append_extension(BasicConstraints::new().critical().ca().build().unwrap())
+                        .unwrap();
+                    builder.set_issuer_name(&subject).unwrap();
+                    builder.sign(key, MessageDigest::sha256()).unwrap();
+                }
+            }
+            builder.build()
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        let ca_key = new_key();
+        let ca = new_cert("salvo test client ca", &ca_key, None, now + 86400);
+        let client_key = new_key();
+        // Expired an hour ago.
+        let client_cert = new_cert("salvo test client", &client_key, Some((&ca, &ca_key)), now - 3600);
+
+        for (grace, accepted) in [
+            (None, false),
+            (Some(Duration::from_secs(2 * 3600)), true),
+            (Some(Duration::from_secs(1800)), false),
+        ] {
+            let mut config = OpensslConfig::new(
+                Keycert::new()
+                    .key_from_path("certs/key.pem")
+                    .unwrap()
+                    .cert_from_path("certs/cert.pem")
+                    .unwrap(),
+            )
+            .with_client_auth_required(ca.to_pem().unwrap());
+            if let Some(grace) = grace {
+                config = config.with_client_cert_expiry_grace(grace);
+            }
+            let mut acceptor = TcpListener::new("127.0.0.1:0").openssl(config).bind().await;
+            let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+
+            let client_key = client_key.clone();
+            let client_cert = client_cert.clone();
+            tokio::spawn(async move {
+                let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+                connector.set_verify(SslVerifyMode::NONE);
+                connector.set_certificate(&client_cert).unwrap();
+                connector.set_private_key(&client_key).unwrap();
+                let ssl = connector
+                    .build()
+                    .configure()
+                    .unwrap()
+                    .into_ssl("testserver.com")
+                    .unwrap();
+
+                let stream = TcpStream::connect(addr).await.unwrap();
+                let mut tls_stream = SslStream::new(ssl, stream).unwrap();
+                if Pin::new(&mut tls_stream).connect().await.is_ok() {
+                    tls_stream.write_i32(518).await.ok();
+                }
+            });
+
+            let Accepted { mut conn, .. } = acceptor.accept().await.unwrap();
+            match conn.read_i32().await {
+                Ok(value) => {
+                    assert!(accepted, "{grace:?}");
+                    assert_eq!(value, 518);
+                }
+                Err(e) => {
+                    assert!(!accepted, "{grace:?}");
+                    assert!(e.to_string().contains("certificate verify failed"), "{e}");
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,736 @@
+//! ParsingListener and it's implements.
+//!
+//! `ParsingListener` checks the head of every HTTP/1 request before it is parsed by hyper. In
+//! [`ParsingMode::Lenient`] mode, some malformed but common requests sent by legacy clients are
+//! normalized, so they can be accepted:
+//!
+//! - Bare `LF` line endings in the request head are replaced with `CRLF`.
+//! - Spaces in the request target are percent-encoded as `%20`, for example
+//!   `GET /a b.txt HTTP/1.1` becomes `GET /a%20b.txt HTTP/1.1`.
+//!
+//! In [`ParsingMode::Strict`] mode, which is the default, requests with bare `LF` line endings are
+//! rejected.
+//!
+//! In both modes, these requests are always rejected:
+//!
+//! - The head contains a bare `CR` or a `NUL` byte.
+//! - A header line starts with whitespace (obsolete line folding).
+//! - Both `Content-Length` and `Transfer-Encoding` headers are present, or `Content-Length`
+//!   headers have different values.
+//! - The head is larger than 64KiB.
+//!
+//! If it is the first request of the connection, `400 Bad Request` is sent and the connection is
+//! closed. Later requests may be pipelined behind a response which is still being written, so they
+//! are handled as if the connection is closed by the peer, the pending response is still sent.
+//!
+//! Bodies with `chunked` transfer coding are checked too, reading the body fails with
+//! [`std::io::ErrorKind::InvalidData`] and the connection is closed when:
+//!
+//! - A chunk-size line or a trailer line is longer than the limit, 4KiB by default, see
+//!   [`ParsingListener::with_max_chunk_line_length`].
+//! - The body has more chunks than the limit, no limit by default, see
+//!   [`ParsingListener::with_max_chunks`].
+//!
+//! After a request with other transfer codings, or an upgrade request, the rest of the connection is
+//! passed through without checks. Only HTTP/1 is supported, so don't use it with listeners which
+//! negotiate HTTP/2.
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::async_trait;
+use crate::conn::{Holding, HttpBuilders};
+use crate::http::{HttpConnection, Version};
+use crate::service::HyperHandler;
+
+use super::{Accepted, Acceptor, Listener};
+
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+const DEFAULT_MAX_CHUNK_LINE_LENGTH: usize = 4 * 1024;
+const BAD_REQUEST: &[u8] = b"HTTP/1.1 400 Bad Request\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+
+/// How strictly request heads are parsed.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ParsingMode {
+    /// Reject requests which are not well formed.
+    #[default]
+    Strict,
+    /// Accept some malformed but common requests, see [module level documentation](self).
+    Lenient,
+}
+
+/// How the bytes after a request head should be handled.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Framing {
+    /// The next bytes are the head of next request.
+    Head,
+    /// The next `n` bytes are body.
+    Body(u64),
+    /// The next bytes are a chunk-size line, `n` chunks are read.
+    ChunkSize(u64),
+    /// The next `n` bytes are chunk data and its line ending, `m` chunks are read.
+    ChunkData(u64, u64),
+    /// The next bytes are trailer lines of a chunked body.
+    Trailers,
+    /// The rest of the connection is not checked.
+    Passthrough,
+}
+
+#[derive(Debug)]
+enum State {
+    Framing(Framing),
+    /// `400 Bad Request` is being written, `n` bytes are written.
+    Rejecting(usize),
+    /// The body is malformed, reading fails with the reason.
+    Failed(&'static str),
+    /// The rest of the connection is dropped, reading returns EOF.
+    Closed,
+}
+
+/// A I/O stream for ParsingListener.
+pub struct ParsingStream<C> {
+    inner: C,
+    mode: ParsingMode,
+    max_chunk_line_length: usize,
+    max_chunks: Option<u64>,
+    state: State,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    output_pos: usize,
+    inner_eof: bool,
+    head_passed: bool,
+}
+
+impl<C> ParsingStream<C> {
+    /// Create a new `ParsingStream`.
+    #[inline]
+    pub fn new(inner: C, mode: ParsingMode) -> Self {
+        ParsingStream {
+            inner,
+            mode,
+            max_chunk_line_length: DEFAULT_MAX_CHUNK_LINE_LENGTH,
+            max_chunks: None,
+            state: State::Framing(Framing::Head),
+            input: Vec::new(),
+            output: Vec::new(),
+            output_pos: 0,
+            inner_eof: false,
+            head_passed: false,
+        }
+    }
+
+    /// Sets the maximum length of chunk-size lines and trailer lines of chunked bodies and returns `Self`.
+    #[inline]
+    pub fn with_max_chunk_line_length(mut self, max_chunk_line_length: usize) -> Self {
+        self.max_chunk_line_length = max_chunk_line_length;
+        self
+    }
+
+    /// Sets the maximum number of chunks of chunked bodies and returns `Self`, `None` means no limit.
+    #[inline]
+    pub fn with_max_chunks(mut self, max_chunks: impl Into<Option<u64>>) -> Self {
+        self.max_chunks = max_chunks.into();
+        self
+    }
+}
+
+impl<C> ParsingStream<C>
+where
+    C: AsyncRead + Unpin,
+{
+    fn poll_fill(&mut self, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        let mut chunk = [0u8; READ_CHUNK_SIZE];
+        let mut buf = ReadBuf::new(&mut chunk);
+        match Pin::new(&mut self.inner).poll_read(cx, &mut buf) {
+            Poll::Ready(Ok(())) => {
+                if buf.filled().is_empty() {
+                    self.inner_eof = true;
+                } else {
+                    self.input.extend_from_slice(buf.filled());
+                }
+                Poll::Ready(Ok(()))
+            }
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+    fn push_output(&mut self, len: usize) {
+        self.output.clear();
+        self.output_pos = 0;
+        self.output.extend(self.input.drain(..len));
+    }
+    /// Find the next line of a chunked body, the length of the line includes its line ending.
+    ///
+    /// Returns `Ok(None)` if more input is needed, or `Err` if the line is too long.
+    fn find_chunk_line(&self) -> Result<Option<usize>, &'static str> {
+        match self.input.iter().position(|b| *b == b'\n') {
+            Some(pos) if pos > self.max_chunk_line_length + 1 => Err("chunk line is too long"),
+            Some(pos) => Ok(Some(pos + 1)),
+            None if self.input.len() > self.max_chunk_line_length + 1 => Err("chunk line is too long"),
+            None => Ok(None),
+        }
+    }
+    /// Reject a malformed request head, see [module level documentation](self).
+    fn reject_head(&mut self, reason: &'static str) {
+        tracing::warn!(reason, "malformed request head, rejected");
+        self.state = if self.head_passed {
+            State::Closed
+        } else {
+            State::Rejecting(0)
+        };
+    }
+}
+
+impl<C> AsyncRead for ParsingStream<C>
+where
+    C: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<IoResult<()>> {
+        let this = self.get_mut();
+        loop {
+            if this.output_pos < this.output.len() {
+                let len = buf.remaining().min(this.output.len() - this.output_pos);
+                buf.put_slice(&this.output[this.output_pos..this.output_pos + len]);
+                this.output_pos += len;
+                return Poll::Ready(Ok(()));
+            }
+            match this.state {
+                State::Rejecting(written) => {
+                    if written < BAD_REQUEST.len() {
+                        match Pin::new(&mut this.inner).poll_write(cx, &BAD_REQUEST[written..]) {
+                            Poll::Ready(Ok(0)) => this.state = State::Rejecting(BAD_REQUEST.len()),
+                            Poll::Ready(Ok(n)) => this.state = State::Rejecting(written + n),
+                            Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                            Poll::Pending => return Poll::Pending,
+                        }
+                        continue;
+                    }
+                    // Pretend the connection is closed by the peer.
+                    return Pin::new(&mut this.inner).poll_flush(cx);
+                }
+                State::Failed(reason) => {
+                    return Poll::Ready(Err(IoError::new(ErrorKind::InvalidData, reason)));
+                }
+                State::Closed => return Poll::Ready(Ok(())),
+                State::Framing(Framing::Passthrough) => {
+                    if this.input.is_empty() {
+                        return Pin::new(&mut this.inner).poll_read(cx, buf);
+                    }
+                    this.push_output(this.input.len());
+                }
+                State::Framing(Framing::Body(remaining)) => {
+                    if this.input.is_empty() {
+                        if this.inner_eof {
+                            return Poll::Ready(Ok(()));
+                        }
+                        ready!(this.poll_fill(cx))?;
+                        continue;
+                    }
+                    let len = (this.input.len() as u64).min(remaining);
+                    this.push_output(len as usize);
+                    this.state = if len == remaining {
+                        State::Framing(Framing::Head)
+                    } else {
+                        State::Framing(Framing::Body(remaining - len))
+                    };
+                }
+                State::Framing(Framing::ChunkData(remaining, chunks)) => {
+                    if this.input.is_empty() {
+                        if this.inner_eof {
+                            return Poll::Ready(Ok(()));
+                        }
+                        ready!(this.poll_fill(cx))?;
+                        continue;
+                    }
+                    let len = (this.input.len() as u64).min(remaining);
+                    this.push_output(len as usize);
+                    this.state = if len == remaining {
+                        State::Framing(Framing::ChunkSize(chunks))
+                    } else {
+                        State::Framing(Framing::ChunkData(remaining - len, chunks))
+                    };
+                }
+                State::Framing(framing @ (Framing::ChunkSize(_) | Framing::Trailers)) => {
+                    let len = match this.find_chunk_line() {
+                        Ok(Some(len)) => len,
+                        Ok(None) if this.inner_eof => {
+                            // Incomplete body, let hyper handles it.
+                            if this.input.is_empty() {
+                                return Poll::Ready(Ok(()));
+                            }
+                            this.push_output(this.input.len());
+                            continue;
+                        }
+                        Ok(None) => {
+                            ready!(this.poll_fill(cx))?;
+                            continue;
+                        }
+                        Err(reason) => {
+                            tracing::warn!(reason, "malformed chunked body, rejected");
+                            this.state = State::Failed(reason);
+                            continue;
+                        }
+                    };
+                    let line = this.input[..len].strip_suffix(b"\n").unwrap_or_default();
+                    let line = line.strip_suffix(b"\r").unwrap_or(line);
+                    let next = match framing {
+                        Framing::ChunkSize(chunks) => {
+                            let size = std::str::from_utf8(line)
+                                .ok()
+                                .and_then(|line| line.split(';').next())
+                                .and_then(|size| u64::from_str_radix(size.trim(), 16).ok());
+                            match size {
+                                Some(0) => Framing::Trailers,
+                                Some(size) => {
+                                    if this.max_chunks.map(|max| chunks >= max).unwrap_or(false) {
+                                        tracing::warn!("too many chunks in chunked body, rejected");
+                                        this.state = State::Failed("too many chunks");
+                                        continue;
+                                    }
+                                    // Chunk data is followed by `CRLF`.
+                                    Framing::ChunkData(size.saturating_add(2), chunks + 1)
+                                }
+                                // Invalid chunk size is rejected by hyper.
+                                None => Framing::Passthrough,
+                            }
+                        }
+                        _ if line.is_empty() => Framing::Head,
+                        _ => Framing::Trailers,
+                    };
+                    this.push_output(len);
+                    this.state = State::Framing(next);
+                }
+                State::Framing(Framing::Head) => {
+                    // Empty lines before request line should be ignored, see RFC 7230 section 3.5.
+                    let skipped = this.input.iter().take_while(|b| **b == b'\r' || **b == b'\n').count();
+                    this.input.drain(..skipped);
+                    if let Some(end) = find_head_end(&this.input) {
+                        let head = this.input.drain(..end).collect::<Vec<_>>();
+                        match process_head(&head, this.mode) {
+                            Ok((head, framing)) => {
+                                this.output = head;
+                                this.output_pos = 0;
+                                this.state = State::Framing(framing);
+                                this.head_passed = true;
+                            }
+                            Err(reason) => this.reject_head(reason),
+                        }
+                    } else if this.input.len() > MAX_HEAD_SIZE {
+                        this.reject_head("request head is too large");
+                    } else if this.inner_eof {
+                        // Incomplete head, let hyper handles it.
+                        if this.input.is_empty() {
+                            return Poll::Ready(Ok(()));
+                        }
+                        this.push_output(this.input.len());
+                    } else {
+                        ready!(this.poll_fill(cx))?;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<C> AsyncWrite for ParsingStream<C>
+where
+    C: AsyncWrite + Unpin,
+{
+    #[inline]
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    #[inline]
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[async_trait]
+impl<C> HttpConnection for ParsingStream<C>
+where
+    C: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    async fn version(&mut self) -> Option<Version> {
+        Some(Version::HTTP_11)
+    }
+    async fn serve(self, handler: HyperHandler, builders: Arc<HttpBuilders>) -> IoResult<()> {
+        #[cfg(not(feature = "http1"))]
+        {
+            let _ = handler;
+            let _ = builders;
+            panic!("http1 feature is required");
+        }
+        #[cfg(feature = "http1")]
+        builders
+            .http1
+            .serve_connection(self, handler)
+            .with_upgrades()
+            .await
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
+    }
+}
+
+/// Find the end of request head, both `CRLF` and bare `LF` line endings are recognized.
+fn find_head_end(input: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    while let Some(pos) = input[i..].iter().position(|b| *b == b'\n') {
+        let pos = i + pos;
+        match input.get(pos + 1) {
+            Some(b'\n') => return Some(pos + 2),
+            Some(b'\r') if input.get(pos + 2) == Some(&b'\n') => return Some(pos + 3),
+            _ => i = pos + 1,
+        }
+    }
+    None
+}
+
+/// Check and normalize a request head, returns the head which should be passed to hyper.
+fn process_head(head: &[u8], mode: ParsingMode) -> Result<(Vec<u8>, Framing), &'static str> {
+    if head.contains(&0) {
+        return Err("head contains NUL");
+    }
+    let mut lines = Vec::new();
+    for line in head.strip_suffix(b"\n").unwrap_or(head).split(|b| *b == b'\n') {
+        let line = match line.strip_suffix(b"\r") {
+            Some(line) => line,
+            None if mode == ParsingMode::Lenient => line,
+            None => return Err("bare LF line ending"),
+        };
+        if line.contains(&b'\r') {
+            return Err("bare CR");
+        }
+        if line.is_empty() {
+            break;
+        }
+        lines.push(line);
+    }
+    let (request_line, headers) = lines.split_first().ok_or("empty head")?;
+
+    let mut normalized = Vec::with_capacity(head.len());
+    let parts = request_line.split(|b| *b == b' ').collect::<Vec<_>>();
+    let is_connect = parts[0].eq_ignore_ascii_case(b"CONNECT");
+    if mode == ParsingMode::Lenient && parts.len() > 3 && parts[parts.len() - 1].starts_with(b"HTTP/") {
+        normalized.extend_from_slice(parts[0]);
+        normalized.push(b' ');
+        normalized.extend_from_slice(&parts[1..parts.len() - 1].join(&b"%20"[..]));
+        normalized.push(b' ');
+        normalized.extend_from_slice(parts[parts.len() - 1]);
+    } else {
+        normalized.extend_from_slice(request_line);
+    }
+    normalized.extend_from_slice(b"\r\n");
+
+    let mut content_length = None;
+    let mut framing = if is_connect {
+        Framing::Passthrough
+    } else {
+        Framing::Head
+    };
+    let mut transfer_encoding = None;
+    for line in headers {
+        if line.starts_with(b" ") || line.starts_with(b"\t") {
+            return Err("obsolete line folding");
+        }
+        if let Some(colon) = line.iter().position(|b| *b == b':') {
+            let name = &line[..colon];
+            let value = std::str::from_utf8(&line[colon + 1..]).unwrap_or_default().trim();
+            if name.eq_ignore_ascii_case(b"content-length") {
+                // Invalid value is rejected by hyper.
+                if let Ok(length) = value.parse::<u64>() {
+                    if content_length.map(|l| l != length).unwrap_or(false) {
+                        return Err("different content-length values");
+                    }
+                    content_length = Some(length);
+                } else {
+                    framing = Framing::Passthrough;
+                }
+            } else if name.eq_ignore_ascii_case(b"transfer-encoding") {
+                // The last transfer coding decides how the body is framed.
+                transfer_encoding = Some(value.rsplit(',').next().unwrap_or_default().trim().to_owned());
+            } else if name.eq_ignore_ascii_case(b"upgrade") {
+                framing = Framing::Passthrough;
+            }
+        }
+        normalized.extend_from_slice(line);
+        normalized.extend_from_slice(b"\r\n");
+    }
+    normalized.extend_from_slice(b"\r\n");
+
+    if let Some(transfer_encoding) = transfer_encoding {
+        if content_length.is_some() {
+            return Err("both content-length and transfer-encoding are present");
+        }
+        framing = if framing == Framing::Head && transfer_encoding.eq_ignore_ascii_case("chunked") {
+            Framing::ChunkSize(0)
+        } else {
+            Framing::Passthrough
+        };
+    }
+    if let (Framing::Head, Some(length)) = (framing, content_length) {
+        if length > 0 {
+            framing = Framing::Body(length);
+        }
+    }
+    Ok((normalized, framing))
+}
+
+/// ParsingListener checks and normalizes request heads of the inner listener's connections.
+pub struct ParsingListener<T> {
+    inner: T,
+    mode: ParsingMode,
+    max_chunk_line_length: usize,
+    max_chunks: Option<u64>,
+}
+
+impl<T> ParsingListener<T> {
+    /// Create a new `ParsingListener`.
+    #[inline]
+    pub fn new(inner: T) -> Self {
+        ParsingListener {
+            inner,
+            mode: ParsingMode::default(),
+            max_chunk_line_length: DEFAULT_MAX_CHUNK_LINE_LENGTH,
+            max_chunks: None,
+        }
+    }
+
+    /// Sets parsing mode and returns `Self`.
+    #[inline]
+    pub fn with_mode(mut self, mode: ParsingMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the maximum length of chunk-size lines and trailer lines of chunked bodies and returns `Self`.
+    ///
+    /// Chunk extensions are included in the length. The default is 4KiB.
+    #[inline]
+    pub fn with_max_chunk_line_length(mut self, max_chunk_line_length: usize) -> Self {
+        self.max_chunk_line_length = max_chunk_line_length;
+        self
+    }
+
+    /// Sets the maximum number of chunks of chunked bodies and returns `Self`, `None` means no limit.
+    ///
+    /// The last zero-length chunk is not counted. The default is no limit.
+    #[inline]
+    pub fn with_max_chunks(mut self, max_chunks: impl Into<Option<u64>>) -> Self {
+        self.max_chunks = max_chunks.into();
+        self
+    }
+}
+
+#[async_trait]
+impl<T> Listener for ParsingListener<T>
+where
+    T: Listener + Send,
+    T::Acceptor: Send + 'static,
+{
+    type Acceptor = ParsingAcceptor<T::Acceptor>;
+
+    async fn bind(self) -> Self::Acceptor {
+        self.try_bind().await.unwrap()
+    }
+
+    async fn try_bind(self) -> IoResult<Self::Acceptor> {
+        Ok(ParsingAcceptor {
+            inner: self.inner.try_bind().await?,
+            mode: self.mode,
+            max_chunk_line_length: self.max_chunk_line_length,
+            max_chunks: self.max_chunks,
+        })
+    }
+}
+
+/// ParsingAcceptor
+pub struct ParsingAcceptor<T> {
+    inner: T,
+    mode: ParsingMode,
+    max_chunk_line_length: usize,
+    max_chunks: Option<u64>,
+}
+
+#[async_trait]
+impl<T> Acceptor for ParsingAcceptor<T>
+where
+    T: Acceptor + Send + 'static,
+{
+    type Conn = ParsingStream<T::Conn>;
+
+    #[inline]
+    fn holdings(&self) -> &[Holding] {
+        self.inner.holdings()
+    }
+
+    #[inline]
+    async fn accept(&mut self) -> IoResult<Accepted<Self::Conn>> {
+        let (mode, max_chunk_line_length, max_chunks) = (self.mode, self.max_chunk_line_length, self.max_chunks);
+        self.inner.accept().await.map(|accepted| {
+            accepted.map_conn(|conn| {
+                ParsingStream::new(conn, mode)
+                    .with_max_chunk_line_length(max_chunk_line_length)
+                    .with_max_chunks(max_chunks)
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    use super::*;
+    use crate::conn::TcpListener;
+    use crate::prelude::*;
+
+    async fn send(mode: ParsingMode, request: &[u8]) -> String {
+        send_to(
+            ParsingListener::new(TcpListener::new("127.0.0.1:0")).with_mode(mode),
+            request,
+        )
+        .await
+    }
+
+    async fn send_to(listener: ParsingListener<TcpListener<&'static str>>, request: &[u8]) -> String {
+        #[handler(internal)]
+        async fn hello(req: &mut Request) -> String {
+            format!("Hello {}", req.uri().path())
+        }
+        #[handler(internal)]
+        async fn echo(req: &mut Request, res: &mut Response) {
+            match req.payload().await {
+                Ok(payload) => res.render(String::from_utf8_lossy(payload).into_owned()),
+                Err(e) => res.set_status_error(StatusError::bad_request().with_summary(e.to_string())),
+            }
+        }
+        let acceptor = listener.bind().await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+        tokio::spawn(async move {
+            let router = Router::new().push(Router::with_path("<**>").get(hello).post(echo));
+            Server::new(acceptor).serve(router).await;
+        });
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_parsing_bare_lf() {
+        let request = b"GET /hello HTTP/1.1\nHost: localhost\nConnection: close\n\n";
+        let response = send(ParsingMode::Lenient, request).await;
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("Hello /hello"));
+
+        let response = send(ParsingMode::Strict, request).await;
+        assert!(response.starts_with("HTTP/1.1 400"));
+    }
+
+    #[tokio::test]
+    async fn test_parsing_space_in_target() {
+        let request = b"GET /a b HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+        let response = send(ParsingMode::Lenient, request).await;
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("Hello /a%20b"));
+    }
+
+    #[tokio::test]
+    async fn test_parsing_chunked() {
+        let request = b"POST /echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+            Transfer-Encoding: chunked\r\n\r\n5;ext=1\r\nhello\r\n6\r\n world\r\n0\r\nA: 1\r\n\r\n";
+        let response = send(ParsingMode::Strict, request).await;
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("hello world"));
+    }
+
+    #[tokio::test]
+    async fn test_parsing_chunk_line_too_long() {
+        let listener = ParsingListener::new(TcpListener::new("127.0.0.1:0")).with_max_chunk_line_length(16);
+        let mut request = b"POST /echo HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n5;".to_vec();
+        request.extend_from_slice(&[b'a'; 64]);
+        request.extend_from_slice(b"\r\nhello\r\n0\r\n\r\n");
+        let response = send_to(listener, &request).await;
+        // The body fails to be read and no raw response is written in the middle of it.
+        assert!(response.starts_with("HTTP/1.1 400"));
+        assert!(!response.contains("hello"));
+        assert_eq!(response.matches("HTTP/1.1").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_parsing_too_many_chunks() {
+        let request = b"POST /echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+            Transfer-Encoding: chunked\r\n\r\n1\r\na\r\n1\r\nb\r\n1\r\nc\r\n0\r\n\r\n";
+        let listener = ParsingListener::new(TcpListener::new("127.0.0.1:0")).with_max_chunks(3);
+        let response = send_to(listener, request).await;
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("abc"));
+
+        let listener = ParsingListener::new(TcpListener::new("127.0.0.1:0")).with_max_chunks(2);
+        let response = send_to(listener, request).await;
+        assert!(response.starts_with("HTTP/1.1 400"));
+        assert!(!response.contains("abc"));
+        assert_eq!(response.matches("HTTP/1.1").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_parsing_pipelined_malformed_head() {
+        let request = b"GET /first HTTP/1.1\r\nHost: localhost\r\n\r\nGET /second HTTP/1.1\nHost: localhost\n\n";
+        let response = send(ParsingMode::Strict, request).await;
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("Hello /first"));
+        assert_eq!(response.matches("HTTP/1.1").count(), 1);
+    }
+
+    #[test]
+    fn test_process_head() {
+        let (head, framing) =
+            process_head(b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\n", ParsingMode::Strict).unwrap();
+        assert_eq!(head, b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\n");
+        assert_eq!(framing, Framing::Body(5));
+
+        let (_, framing) = process_head(
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n",
+            ParsingMode::Strict,
+        )
+        .unwrap();
+        assert_eq!(framing, Framing::ChunkSize(0));
+
+        let (_, framing) = process_head(
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked, gzip\r\n\r\n",
+            ParsingMode::Strict,
+        )
+        .unwrap();
+        assert_eq!(framing, Framing::Passthrough);
+
+        for mode in [ParsingMode::Strict, ParsingMode::Lenient] {
+            assert!(process_head(
+                b"POST / HTTP/1.1\r\nContent-Length: 5\r\nTransfer-Encoding: chunked\r\n\r\n",
+                mode
+            )
+            .is_err());
+            assert!(process_head(b"GET / HTTP/1.1\r\nA: 1\r\n b\r\n\r\n", mode).is_err());
+            assert!(process_head(b"GET / HTTP/1.1\r\nA: 1\rB: 2\r\n\r\n", mode).is_err());
+        }
+    }
+
+    #[test]
+    fn test_find_head_end() {
+        assert_eq!(find_head_end(b"GET / HTTP/1.1\r\n\r\nbody"), Some(18));
+        assert_eq!(find_head_end(b"GET / HTTP/1.1\n\nbody"), Some(16));
+        assert_eq!(find_head_end(b"GET / HTTP/1.1\r\nHost: a\r\n"), None);
+    }
+}
@@ -1,12 +1,65 @@
+use std::io::{Error as IoError, ErrorKind, IoSlice, Result as IoResult};
+use std::pin::Pin;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
 #[cfg(feature = "http2")]
 use crate::runtimes::TokioExecutor;
 #[cfg(feature = "http1")]
 use hyper::server::conn::http1;
 #[cfg(feature = "http2")]
 use hyper::server::conn::http2;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
 
 #[cfg(feature = "quinn")]
 use crate::conn::quinn;
+use crate::http::Version;
+use crate::server::ConnCounters;
+use crate::service::HyperHandler;
+
+/// Connection preface sent by HTTP/2 clients, used to detect h2c prior knowledge connections.
+const H2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// HTTP protocol versions served on connections.
+///
+/// On TLS connections the version is chosen by ALPN, use [`HttpProtocol::alpn_protocols`] to
+/// configure the TLS listener with the same preference. On plain connections `Http2` means h2c
+/// with prior knowledge, `Auto` serves HTTP/1.1 and also accepts h2c prior knowledge if h2c is
+/// enabled with [`Server::h2c`](crate::Server::h2c).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum HttpProtocol {
+    /// HTTP/1.1 only.
+    Http1,
+    /// HTTP/2 only.
+    Http2,
+    /// Both HTTP/1.1 and HTTP/2.
+    #[default]
+    Auto,
+}
+
+impl HttpProtocol {
+    /// ALPN protocols matching this preference, most preferred first.
+    #[inline]
+    pub fn alpn_protocols(&self) -> Vec<Vec<u8>> {
+        match self {
+            HttpProtocol::Http1 => vec![b"http/1.1".to_vec()],
+            HttpProtocol::Http2 => vec![b"h2".to_vec()],
+            HttpProtocol::Auto => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+        }
+    }
+
+    /// Check whether `version` is allowed by this preference.
+    #[inline]
+    pub fn allows(&self, version: Version) -> bool {
+        match self {
+            HttpProtocol::Http1 => version <= Version::HTTP_11,
+            HttpProtocol::Http2 => version == Version::HTTP_2,
+            HttpProtocol::Auto => version <= Version::HTTP_2,
+        }
+    }
+}
 
 #[doc(hidden)]
 pub struct HttpBuilders {
@@ -16,4 +69,340 @@ pub struct HttpBuilders {
     pub(crate) http2: http2::Builder<TokioExecutor>,
     #[cfg(feature = "quinn")]
     pub(crate) quinn: quinn::Builder,
+    pub(crate) protocol: HttpProtocol,
+    pub(crate) h2c: bool,
+    pub(crate) pre_read_timeout: Option<Duration>,
+}
+
+impl HttpBuilders {
+    /// Serve a plain connection, HTTP/2 is served with prior knowledge.
+    pub(crate) async fn serve_plain<S>(&self, stream: S, handler: HyperHandler) -> IoResult<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        match self.protocol {
+            HttpProtocol::Http1 => self.serve_http1(stream, handler).await,
+            HttpProtocol::Http2 => self.serve_http2(stream, handler).await,
+            HttpProtocol::Auto if self.h2c => {
+                let sniff = Rewind::sniff(stream, H2_PREFACE);
+                let (is_h2c, stream) = match self.pre_read_timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, sniff).await.map_err(|_| {
+                        IoError::new(ErrorKind::TimedOut, "pre-read for protocol detection timed out")
+                    })??,
+                    None => sniff.await?,
+                };
+                if is_h2c {
+                    self.serve_http2(stream, handler).await
+                } else {
+                    self.serve_http1(stream, handler).await
+                }
+            }
+            HttpProtocol::Auto => self.serve_http1(stream, handler).await,
+        }
+    }
+
+    /// Serve a TLS connection with the `version` negotiated by ALPN.
+    ///
+    /// If no protocol is negotiated, HTTP/1.1 is served only when HTTP/2 is not allowed.
+    pub(crate) async fn serve_negotiated<S>(
+        &self,
+        stream: S,
+        version: Option<Version>,
+        handler: HyperHandler,
+    ) -> IoResult<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let version = version.unwrap_or(if self.protocol == HttpProtocol::Http1 {
+            Version::HTTP_11
+        } else {
+            Version::HTTP_2
+        });
+        if !self.protocol.allows(version) {
+            return Err(IoError::new(
+                ErrorKind::Other,
+                format!("http version {version:?} is not allowed by {:?}", self.protocol),
+            ));
+        }
+        if version == Version::HTTP_2 {
+            self.serve_http2(stream, handler).await
+        } else {
+            self.serve_http1(stream, handler).await
+        }
+    }
+
+    async fn serve_http1<S>(&self, stream: S, handler: HyperHandler) -> IoResult<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        #[cfg(not(feature = "http1"))]
+        {
+            let _ = stream;
+            let _ = handler;
+            panic!("http1 feature is required");
+        }
+        #[cfg(feature = "http1")]
+        self.http1
+            .serve_connection(Counted::new(stream, &handler), handler)
+            .with_upgrades()
+            .await
+            .map_err(|e| IoError::new(ErrorKind::Other, e.to_string()))
+    }
+
+    async fn serve_http2<S>(&self, stream: S, handler: HyperHandler) -> IoResult<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        #[cfg(not(feature = "http2"))]
+        {
+            let _ = stream;
+            let _ = handler;
+            panic!("http2 feature is required");
+        }
+        #[cfg(feature = "http2")]
+        self.http2
+            .serve_connection(Counted::new(stream, &handler), handler)
+            .await
+            .map_err(|e| IoError::new(ErrorKind::Other, e.to_string()))
+    }
+}
+
+/// A stream which replays the bytes read ahead before reading from the inner stream.
+struct Rewind<S> {
+    prefix: Vec<u8>,
+    pos: usize,
+    inner: S,
+}
+
+impl<S> Rewind<S>
+where
+    S: AsyncRead + Unpin,
+{
+    /// Read from `inner` until the bytes read differ from `expected` or all of `expected` is read,
+    /// returns whether `expected` is matched and the rewound stream.
+    async fn sniff(mut inner: S, expected: &[u8]) -> IoResult<(bool, Self)> {
+        let mut prefix = vec![0; expected.len()];
+        let mut len = 0;
+        while len < expected.len() {
+            let read = inner.read(&mut prefix[len..]).await?;
+            if read == 0 || prefix[len..len + read] != expected[len..len + read] {
+                len += read;
+                prefix.truncate(len);
+                return Ok((false, Rewind { prefix, pos: 0, inner }));
+            }
+            len += read;
+        }
+        Ok((true, Rewind { prefix, pos: 0, inner }))
+    }
+}
+
+impl<S> AsyncRead for Rewind<S>
+where
+    S: AsyncRead + Unpin,
+{
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<IoResult<()>> {
+        let this = &mut *self;
+        if this.pos < this.prefix.len() {
+            let len = buf.remaining().min(this.prefix.len() - this.pos);
+            buf.put_slice(&this.prefix[this.pos..this.pos + len]);
+            this.pos += len;
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S> AsyncWrite for Rewind<S>
+where
+    S: AsyncWrite + Unpin,
+{
+    #[inline]
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    #[inline]
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// A stream which counts the bytes read and written for [`ConnStats`](crate::ConnStats).
+struct Counted<S> {
+    inner: S,
+    counters: Option<Arc<ConnCounters>>,
+}
+
+impl<S> Counted<S> {
+    #[inline]
+    fn new(inner: S, handler: &HyperHandler) -> Self {
+        Counted {
+            inner,
+            counters: handler.conn_counters.clone(),
+        }
+    }
+}
+
+impl<S> AsyncRead for Counted<S>
+where
+    S: AsyncRead + Unpin,
+{
+    #[inline]
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<IoResult<()>> {
+        let this = &mut *self;
+        let filled = buf.filled().len();
+        let result = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Some(counters) = &this.counters {
+            let read = buf.filled().len() - filled;
+            counters.bytes_read.fetch_add(read as u64, Ordering::Relaxed);
+        }
+        result
+    }
+}
+
+impl<S> AsyncWrite for Counted<S>
+where
+    S: AsyncWrite + Unpin,
+{
+    #[inline]
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+        let this = &mut *self;
+        let result = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let (Some(counters), Poll::Ready(Ok(written))) = (&this.counters, &result) {
+            counters.bytes_written.fetch_add(*written as u64, Ordering::Relaxed);
+        }
+        result
+    }
+
+    #[inline]
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<IoResult<usize>> {
+        let this = &mut *self;
+        let result = Pin::new(&mut this.inner).poll_write_vectored(cx, bufs);
+        if let (Some(counters), Poll::Ready(Ok(written))) = (&this.counters, &result) {
+            counters.bytes_written.fetch_add(*written as u64, Ordering::Relaxed);
+        }
+        result
+    }
+
+    #[inline]
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    #[inline]
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::prelude::*;
+
+    #[tokio::test]
+    async fn test_rewind_sniff() {
+        let (mut client, server) = tokio::io::duplex(64);
+        client.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+        let (matched, mut stream) = Rewind::sniff(server, H2_PREFACE).await.unwrap();
+        assert!(!matched);
+        let mut buf = [0; 16];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"GET / HTTP/1.1\r\n");
+
+        let (mut client, server) = tokio::io::duplex(64);
+        client.write_all(H2_PREFACE).await.unwrap();
+        let (matched, _) = Rewind::sniff(server, H2_PREFACE).await.unwrap();
+        assert!(matched);
+    }
+
+    #[test]
+    fn test_http_protocol_allows() {
+        assert!(HttpProtocol::Http1.allows(Version::HTTP_11));
+        assert!(!HttpProtocol::Http1.allows(Version::HTTP_2));
+        assert!(!HttpProtocol::Http2.allows(Version::HTTP_11));
+        assert!(HttpProtocol::Auto.allows(Version::HTTP_2));
+        assert_eq!(HttpProtocol::Http2.alpn_protocols(), vec![b"h2".to_vec()]);
+    }
+
+    #[tokio::test]
+    async fn test_pre_read_timeout() {
+        #[handler(internal)]
+        async fn hello() -> &'static str {
+            "hello"
+        }
+        let acceptor = TcpListener::new("127.0.0.1:0").bind().await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+        tokio::spawn(async move {
+            Server::new(acceptor)
+                .h2c(true)
+                .pre_read_timeout(Some(Duration::from_millis(200)))
+                .serve(Router::new().get(hello))
+                .await;
+        });
+
+        let started = std::time::Instant::now();
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let mut buf = Vec::new();
+        let read = tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut buf))
+            .await
+            .expect("connection is not dropped");
+        assert!(matches!(read, Ok(0)) || read.is_err());
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn test_h2c_prior_knowledge() {
+        #[handler(internal)]
+        async fn hello(req: &mut Request) -> String {
+            format!("{:?}", req.version())
+        }
+        let acceptor = TcpListener::new("127.0.0.1:0").bind().await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+        tokio::spawn(async move {
+            Server::new(acceptor).h2c(true).serve(Router::new().get(hello)).await;
+        });
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let (mut sender, conn) = hyper::client::conn::http2::handshake(TokioExecutor, stream)
+            .await
+            .unwrap();
+        tokio::spawn(async move {
+            let _ = conn.await;
+        });
+        let req = hyper::Request::builder()
+            .uri(format!("http://{addr}/"))
+            .body(http_body_util::Empty::<hyper::body::Bytes>::new())
+            .unwrap();
+        let res = sender.send_request(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.version(), Version::HTTP_2);
+
+        // HTTP/1.1 is still served on the same listener.
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut body = String::new();
+        stream.read_to_string(&mut body).await.unwrap();
+        assert!(body.starts_with("HTTP/1.1 200"));
+        assert!(body.ends_with("HTTP/1.1"));
+    }
 }
@@ -153,6 +153,8 @@ pub struct RustlsConfig {
     keycerts: HashMap<String, Keycert>,
     client_auth: TlsClientAuth,
     alpn_protocols: Vec<Vec<u8>>,
+    reject_unknown_sni: bool,
+    pub(crate) keep_sessions: bool,
 }
 
 impl RustlsConfig {
@@ -164,6 +166,8 @@ impl RustlsConfig {
             keycerts: HashMap::new(),
             client_auth: TlsClientAuth::Off,
             alpn_protocols: vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+            reject_unknown_sni: false,
+            keep_sessions: false,
         }
     }
 
@@ -219,13 +223,47 @@ impl RustlsConfig {
         self
     }
 
-    /// Add a new keycert to be used for the given SNI `name`.
+    /// Sets the ALPN protocols offered to clients, most preferred first.
+    ///
+    /// Use [`HttpProtocol::alpn_protocols`](crate::conn::HttpProtocol::alpn_protocols) to match the
+    /// protocol served by the server.
     #[inline]
     pub fn alpn_protocols(mut self, alpn_protocols: impl Into<Vec<Vec<u8>>>) -> Self {
         self.alpn_protocols = alpn_protocols.into();
         self
     }
 
+    /// Sets the ALPN protocols offered to clients, most preferred first, and returns `Self`.
+    ///
+    /// It is the same as [`RustlsConfig::alpn_protocols`] and named as `OpensslConfig::with_alpn_protocols`,
+    /// so the configs of both listeners are built in the same way.
+    #[inline]
+    pub fn with_alpn_protocols(self, alpn_protocols: impl Into<Vec<Vec<u8>>>) -> Self {
+        self.alpn_protocols(alpn_protocols)
+    }
+
+    /// Sets whether handshakes with an SNI `name` which is not added by [`RustlsConfig::keycert`]
+    /// are aborted with a TLS alert, instead of using the fallback keycert.
+    ///
+    /// Connections without SNI are rejected too. Default is `false`.
+    #[inline]
+    pub fn reject_unknown_sni(mut self, reject_unknown_sni: bool) -> Self {
+        self.reject_unknown_sni = reject_unknown_sni;
+        self
+    }
+
+    /// Sets whether the session cache and session ticket keys of the previous config are kept when
+    /// this config is loaded by a reload, so clients can resume their sessions after a certificate
+    /// rotation instead of making full handshakes.
+    ///
+    /// Resumed sessions are not authenticated again, so keep it disabled if the reload revokes
+    /// clients, like changing the trust anchor of client authentication. Default is `false`.
+    #[inline]
+    pub fn keep_sessions(mut self, keep_sessions: bool) -> Self {
+        self.keep_sessions = keep_sessions;
+        self
+    }
+
     /// ServerConfig
     pub(crate) fn build_server_config(mut self) -> io::Result<ServerConfig> {
         let fallback = self
@@ -254,6 +292,7 @@ impl RustlsConfig {
             .with_cert_resolver(Arc::new(CertResolver {
                 certified_keys,
                 fallback,
+                reject_unknown_sni: self.reject_unknown_sni,
             }));
         config.alpn_protocols = self.alpn_protocols;
         Ok(config)
@@ -263,14 +302,22 @@ impl RustlsConfig {
 pub(crate) struct CertResolver {
     fallback: Option<Arc<CertifiedKey>>,
     certified_keys: HashMap<String, Arc<CertifiedKey>>,
+    reject_unknown_sni: bool,
 }
 
 impl ResolvesServerCert for CertResolver {
     fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
-        client_hello
+        let key = client_hello
             .server_name()
-            .and_then(|name| self.certified_keys.get(name).map(Arc::clone))
-            .or_else(|| self.fallback.clone())
+            .and_then(|name| self.certified_keys.get(name).map(Arc::clone));
+        if self.reject_unknown_sni {
+            if key.is_none() {
+                tracing::warn!(server_name = ?client_hello.server_name(), "rustls: unknown sni, handshake rejected");
+            }
+            key
+        } else {
+            key.or_else(|| self.fallback.clone())
+        }
     }
 }
 
@@ -3,6 +3,7 @@ use std::io::{Error as IoError, ErrorKind, Result as IoResult};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
 use futures_util::stream::BoxStream;
 use futures_util::task::noop_waker_ref;
@@ -12,16 +13,19 @@ use tokio_rustls::server::TlsStream;
 
 use crate::async_trait;
 use crate::conn::Holding;
-use crate::conn::{Accepted, Acceptor, IntoConfigStream, Listener, TlsConnStream};
+use crate::conn::{
+    handshake_with_timeout, Accepted, Acceptor, IntoConfigStream, Listener, TlsConnStream, DEFAULT_HANDSHAKE_TIMEOUT,
+};
 use crate::http::uri::Scheme;
 use crate::http::Version;
 
-use super::RustlsConfig;
+use super::{RustlsConfig, ServerConfig};
 
 /// RustlsListener
 pub struct RustlsListener<C, T> {
     config_stream: C,
     inner: T,
+    handshake_timeout: Duration,
 }
 
 impl<C, T> RustlsListener<C, T>
@@ -32,7 +36,22 @@ where
     /// Create a new `RustlsListener`.
     #[inline]
     pub fn new(config_stream: C, inner: T) -> Self {
-        RustlsListener { config_stream, inner }
+        RustlsListener {
+            config_stream,
+            inner,
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+        }
+    }
+
+    /// Sets the timeout of TLS handshakes and returns `Self`.
+    ///
+    /// A connection which doesn't complete the handshake in time is dropped, so clients which
+    /// connect and send nothing don't hold resources. `Duration::ZERO` disables the timeout. The
+    /// default is 10 seconds.
+    #[inline]
+    pub fn with_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = timeout;
+        self
     }
 }
 
@@ -50,10 +69,10 @@ where
     }
 
     async fn try_bind(self) -> IoResult<Self::Acceptor> {
-        Ok(RustlsAcceptor::new(
-            self.config_stream.into_stream().boxed(),
-            self.inner.try_bind().await?,
-        ))
+        Ok(
+            RustlsAcceptor::new(self.config_stream.into_stream().boxed(), self.inner.try_bind().await?)
+                .with_handshake_timeout(self.handshake_timeout),
+        )
     }
 }
 
@@ -63,6 +82,8 @@ pub struct RustlsAcceptor<C, T> {
     inner: T,
     holdings: Vec<Holding>,
     tls_acceptor: Option<tokio_rustls::TlsAcceptor>,
+    server_config: Option<Arc<ServerConfig>>,
+    handshake_timeout: Option<Duration>,
 }
 impl<C, T> RustlsAcceptor<C, T>
 where
@@ -89,8 +110,19 @@ where
             inner,
             holdings,
             tls_acceptor: None,
+            server_config: None,
+            handshake_timeout: Some(DEFAULT_HANDSHAKE_TIMEOUT),
         }
     }
+
+    /// Sets the timeout of TLS handshakes and returns `Self`, `Duration::ZERO` disables it.
+    ///
+    /// See [`RustlsListener::with_handshake_timeout`].
+    #[inline]
+    pub fn with_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = if timeout.is_zero() { None } else { Some(timeout) };
+        self
+    }
 }
 
 #[async_trait]
@@ -118,24 +150,32 @@ where
             config
         };
         if let Some(config) = config {
-            let tls_acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config.build_server_config()?));
+            let keep_sessions = config.keep_sessions;
+            let mut server_config = config.build_server_config()?;
+            if let (true, Some(previous)) = (keep_sessions, &self.server_config) {
+                server_config.session_storage = previous.session_storage.clone();
+                server_config.ticketer = previous.ticketer.clone();
+            }
+            let server_config = Arc::new(server_config);
+            let tls_acceptor = tokio_rustls::TlsAcceptor::from(server_config.clone());
             if self.tls_acceptor.is_some() {
-                tracing::info!("tls config changed.");
+                tracing::info!(keep_sessions, "tls config changed.");
             } else {
                 tracing::info!("tls config loaded.");
             }
             self.tls_acceptor = Some(tls_acceptor);
+            self.server_config = Some(server_config);
         }
         let tls_acceptor = match &self.tls_acceptor {
-            Some(tls_acceptor) => tls_acceptor,
+            Some(tls_acceptor) => tls_acceptor.clone(),
             None => return Err(IoError::new(ErrorKind::Other, "rustls: invalid tls config.")),
         };
-
-        let accepted = self
-            .inner
-            .accept()
-            .await?
-            .map_conn(|s| TlsConnStream::new(tls_acceptor.accept(s)));
+        let handshake_timeout = self.handshake_timeout;
+        let accepted = self.inner.accept().await?.map_conn(|stream| {
+            TlsConnStream::new(async move {
+                handshake_with_timeout(tls_acceptor.accept(stream), handshake_timeout, "rustls").await
+            })
+        });
 
         Ok(accepted)
     }
@@ -23,15 +23,18 @@ pub(crate) fn read_trust_anchor(mut trust_anchor: &[u8]) -> io::Result<RootCertS
 
 #[cfg(test)]
 mod tests {
+    use std::pin::Pin;
     use std::sync::Arc;
+    use std::task::{Context, Poll};
 
-    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
     use tokio::net::TcpStream;
     use tokio_rustls::rustls::{ClientConfig, ServerName};
     use tokio_rustls::TlsConnector;
 
     use super::*;
     use crate::conn::{Accepted, Acceptor, Listener, TcpListener};
+    use crate::http::{HttpConnection, Version};
 
     #[tokio::test]
     async fn test_rustls_listener() {
@@ -65,4 +68,210 @@ mod tests {
         let Accepted { mut conn, .. } = acceptor.accept().await.unwrap();
         assert_eq!(conn.read_i32().await.unwrap(), 518);
     }
+
+    #[tokio::test]
+    async fn test_rustls_reject_unknown_sni() {
+        async fn connect(config: RustlsConfig) -> Result<(), IoError> {
+            let mut acceptor = TcpListener::new("127.0.0.1:0").rustls(config).bind().await;
+            let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+            tokio::spawn(async move {
+                if let Ok(Accepted { mut conn, .. }) = acceptor.accept().await {
+                    conn.read_i32().await.ok();
+                }
+            });
+
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let trust_anchor = include_bytes!("../../../certs/chain.pem");
+            let client_config = ClientConfig::builder()
+                .with_safe_defaults()
+                .with_root_certificates(read_trust_anchor(trust_anchor.as_slice()).unwrap())
+                .with_no_client_auth();
+            let connector = TlsConnector::from(Arc::new(client_config));
+            let mut tls_stream = connector
+                .connect(ServerName::try_from("testserver.com").unwrap(), stream)
+                .await?;
+            tls_stream.write_i32(518).await
+        }
+        let keycert = || {
+            Keycert::new()
+                .key_from_path("certs/key.pem")
+                .unwrap()
+                .cert_from_path("certs/cert.pem")
+                .unwrap()
+        };
+
+        // `testserver.com` is not a known SNI name, the fallback keycert is used.
+        assert!(connect(RustlsConfig::new(keycert()).keycert("other.com", keycert()))
+            .await
+            .is_ok());
+        let err = connect(
+            RustlsConfig::new(keycert())
+                .keycert("other.com", keycert())
+                .reject_unknown_sni(true),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("alert"), "{err}");
+        assert!(connect(
+            RustlsConfig::new(keycert())
+                .keycert("testserver.com", keycert())
+                .reject_unknown_sni(true)
+        )
+        .await
+        .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rustls_alpn() {
+        let mut acceptor = TcpListener::new("127.0.0.1:0")
+            .rustls(
+                RustlsConfig::new(
+                    Keycert::new()
+                        .key_from_path("certs/key.pem")
+                        .unwrap()
+                        .cert_from_path("certs/cert.pem")
+                        .unwrap(),
+                )
+                .with_alpn_protocols(vec![b"h2".to_vec(), b"http/1.1".to_vec()]),
+            )
+            .bind()
+            .await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+
+        let client = tokio::spawn(async move {
+            let trust_anchor = include_bytes!("../../../certs/chain.pem");
+            let mut client_config = ClientConfig::builder()
+                .with_safe_defaults()
+                .with_root_certificates(read_trust_anchor(trust_anchor.as_slice()).unwrap())
+                .with_no_client_auth();
+            client_config.alpn_protocols = vec![b"h2".to_vec()];
+            let connector = TlsConnector::from(Arc::new(client_config));
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let tls_stream = connector
+                .connect(ServerName::try_from("testserver.com").unwrap(), stream)
+                .await
+                .unwrap();
+            tls_stream.get_ref().1.alpn_protocol().map(|proto| proto.to_vec())
+        });
+
+        let Accepted { mut conn, .. } = acceptor.accept().await.unwrap();
+        assert_eq!(conn.alpn().await, Some(b"h2".to_vec()));
+        assert_eq!(conn.version().await, Some(Version::HTTP_2));
+        assert_eq!(client.await.unwrap(), Some(b"h2".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn test_rustls_handshake_timeout() {
+        let mut acceptor = TcpListener::new("127.0.0.1:0")
+            .rustls(RustlsConfig::new(
+                Keycert::new()
+                    .key_from_path("certs/key.pem")
+                    .unwrap()
+                    .cert_from_path("certs/cert.pem")
+                    .unwrap(),
+            ))
+            .with_handshake_timeout(std::time::Duration::from_millis(200))
+            .bind()
+            .await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+
+        // The client connects but never starts the handshake.
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let Accepted { mut conn, .. } = acceptor.accept().await.unwrap();
+        let started = std::time::Instant::now();
+        let err = conn.read_i32().await.unwrap_err();
+        assert!(started.elapsed() >= std::time::Duration::from_millis(200));
+        assert!(err.to_string().contains("timed out"));
+        drop(conn);
+
+        let mut buf = Vec::new();
+        let closed = tokio::time::timeout(std::time::Duration::from_secs(5), stream.read_to_end(&mut buf)).await;
+        assert!(matches!(closed, Ok(Ok(0)) | Ok(Err(_))));
+    }
+
+    /// A client stream which counts the bytes read from the server.
+    struct CountedStream {
+        inner: TcpStream,
+        read: usize,
+    }
+    impl AsyncRead for CountedStream {
+        fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+            let filled = buf.filled().len();
+            let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+            self.read += buf.filled().len() - filled;
+            poll
+        }
+    }
+    impl AsyncWrite for CountedStream {
+        fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.inner).poll_write(cx, buf)
+        }
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+        fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
+
+    /// Returns the bytes of the handshake sent by the server, the certificate is not sent if the
+    /// session is resumed.
+    async fn handshake(acceptor: &mut impl Acceptor, connector: &TlsConnector, addr: std::net::SocketAddr) -> usize {
+        let connector = connector.clone();
+        let client = tokio::spawn(async move {
+            let stream = CountedStream {
+                inner: TcpStream::connect(addr).await.unwrap(),
+                read: 0,
+            };
+            let mut tls_stream = connector
+                .connect(ServerName::try_from("testserver.com").unwrap(), stream)
+                .await
+                .unwrap();
+            let read = tls_stream.get_ref().0.read;
+            tls_stream.write_i32(518).await.unwrap();
+            // Session tickets are received before the reply.
+            assert_eq!(tls_stream.read_i32().await.unwrap(), 815);
+            read
+        });
+        let Accepted { mut conn, .. } = acceptor.accept().await.unwrap();
+        assert_eq!(conn.read_i32().await.unwrap(), 518);
+        conn.write_i32(815).await.unwrap();
+        client.await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_rustls_keep_sessions() {
+        let config = |keep_sessions: bool| {
+            RustlsConfig::new(
+                Keycert::new()
+                    .key_from_path("certs/key.pem")
+                    .unwrap()
+                    .cert_from_path("certs/cert.pem")
+                    .unwrap(),
+            )
+            .keep_sessions(keep_sessions)
+        };
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let config_stream = futures_util::stream::poll_fn(move |cx| rx.poll_recv(cx));
+        let inner = TcpListener::new("127.0.0.1:0").bind().await;
+        let addr = inner.holdings()[0].local_addr.clone().into_std().unwrap();
+        let mut acceptor = RustlsAcceptor::new(config_stream, inner);
+
+        // The client keeps its sessions, so the handshake is resumed if the server knows the session.
+        let trust_anchor = include_bytes!("../../../certs/chain.pem");
+        let client_config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(read_trust_anchor(trust_anchor.as_slice()).unwrap())
+            .with_no_client_auth();
+        let connector = TlsConnector::from(Arc::new(client_config));
+
+        tx.send(config(true)).unwrap();
+        let full = handshake(&mut acceptor, &connector, addr).await;
+        tx.send(config(true)).unwrap();
+        let resumed = handshake(&mut acceptor, &connector, addr).await;
+        assert!(resumed < full / 2, "full: {full}, resumed: {resumed}");
+        tx.send(config(false)).unwrap();
+        let reloaded = handshake(&mut acceptor, &connector, addr).await;
+        assert!(reloaded > full / 2, "full: {full}, reloaded: {reloaded}");
+    }
 }
@@ -1,9 +1,16 @@
 //! TcpListener and it's implements.
-use std::io::{Error as IoError, Result as IoResult};
+use std::future::Future;
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
 use std::vec;
 
-use tokio::net::{TcpListener as TokioTcpListener, TcpStream, ToSocketAddrs};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener as TokioTcpListener, TcpSocket, TcpStream, ToSocketAddrs};
+use tokio::time::{Instant, Sleep};
 
 use crate::async_trait;
 use crate::conn::Holding;
@@ -32,12 +39,64 @@ use crate::conn::acme::AcmeListener;
 /// TcpListener
 pub struct TcpListener<T> {
     local_addr: T,
+    nodelay: bool,
+    linger: Option<Duration>,
+    graceful_shutdown: Option<Duration>,
+    send_buffer_size: Option<u32>,
 }
 impl<T: ToSocketAddrs + Send> TcpListener<T> {
     /// Bind to socket address.
     #[inline]
     pub fn new(local_addr: T) -> Self {
-        TcpListener { local_addr }
+        TcpListener {
+            local_addr,
+            nodelay: false,
+            linger: None,
+            graceful_shutdown: None,
+            send_buffer_size: None,
+        }
+    }
+
+    /// Sets `TCP_NODELAY` on accepted connections, which disables Nagle's algorithm, so small
+    /// writes like SSE events are sent without delay.
+    #[inline]
+    pub fn nodelay(mut self, nodelay: bool) -> Self {
+        self.nodelay = nodelay;
+        self
+    }
+
+    /// Sets `SO_LINGER` on accepted connections.
+    ///
+    /// `None` keeps the system default, closing the socket returns immediately and pending data is
+    /// sent in background. `Some(Duration::ZERO)` aborts the connection with a RST on close.
+    #[inline]
+    pub fn linger(mut self, linger: Option<Duration>) -> Self {
+        self.linger = linger;
+        self
+    }
+
+    /// Sets graceful shutdown on accepted connections.
+    ///
+    /// When the connection ends, the write side is shut down first, then data sent by the client is
+    /// read and discarded until the client closes its side or `timeout` elapses. Closing a socket which
+    /// still has unread data makes the system send a RST, which may discard the last response before
+    /// the client reads it. `None` disables it, which is the default.
+    #[inline]
+    pub fn graceful_shutdown(mut self, timeout: Option<Duration>) -> Self {
+        self.graceful_shutdown = timeout;
+        self
+    }
+
+    /// Sets `SO_SNDBUF`, the size of the kernel send buffer of accepted connections.
+    ///
+    /// It is set on the listening socket and inherited by accepted connections. A larger buffer lets
+    /// large responses, like static files, be written with fewer wakeups on links with high latency.
+    /// The system may adjust the size, Linux doubles it for bookkeeping. `None` keeps the system
+    /// default, which is the default.
+    #[inline]
+    pub fn send_buffer_size(mut self, size: Option<u32>) -> Self {
+        self.send_buffer_size = size;
+        self
     }
 
     cfg_feature! {
@@ -101,13 +160,45 @@ where
     }
 
     async fn try_bind(self) -> IoResult<Self::Acceptor> {
-        TokioTcpListener::bind(self.local_addr).await?.try_into()
+        let inner = match self.send_buffer_size {
+            Some(size) => bind_with_send_buffer_size(self.local_addr, size).await?,
+            None => TokioTcpListener::bind(self.local_addr).await?,
+        };
+        let mut acceptor: TcpAcceptor = inner.try_into()?;
+        acceptor.nodelay = self.nodelay;
+        acceptor.linger = self.linger;
+        acceptor.graceful_shutdown = self.graceful_shutdown;
+        Ok(acceptor)
+    }
+}
+/// Bind a listener whose accepted connections inherit a send buffer of `size`, the addresses are tried
+/// in order like [`TokioTcpListener::bind`].
+async fn bind_with_send_buffer_size(local_addr: impl ToSocketAddrs, size: u32) -> IoResult<TokioTcpListener> {
+    let mut last_error = None;
+    for addr in tokio::net::lookup_host(local_addr).await? {
+        let socket = if addr.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+        #[cfg(not(windows))]
+        socket.set_reuseaddr(true)?;
+        socket.set_send_buffer_size(size)?;
+        match socket.bind(addr) {
+            Ok(()) => return socket.listen(1024),
+            Err(e) => last_error = Some(e),
+        }
     }
+    Err(last_error.unwrap_or_else(|| IoError::new(ErrorKind::InvalidInput, "could not resolve to any address")))
 }
+
 /// TcpAcceptor
 pub struct TcpAcceptor {
     inner: TokioTcpListener,
     holdings: Vec<Holding>,
+    nodelay: bool,
+    linger: Option<Duration>,
+    graceful_shutdown: Option<Duration>,
 }
 
 impl TryFrom<TokioTcpListener> for TcpAcceptor {
@@ -122,35 +213,130 @@ impl TryFrom<TokioTcpListener> for TcpAcceptor {
         Ok(TcpAcceptor {
             inner,
             holdings: vec![holding],
+            nodelay: false,
+            linger: None,
+            graceful_shutdown: None,
         })
     }
 }
 
+/// Tcp connection accepted by [`TcpAcceptor`].
+///
+/// **Breaking change:** [`TcpAcceptor`] used to accept [`TcpStream`] as its `Conn`, it accepts this
+/// wrapper now so the connection can be shut down gracefully. It dereferences to the [`TcpStream`],
+/// which is returned by [`into_inner`](Self::into_inner) or `From`. `TcpStream` still implements
+/// [`HttpConnection`] for custom acceptors.
+pub struct TcpConnection {
+    inner: TcpStream,
+    graceful_shutdown: Option<Duration>,
+    draining: Option<Pin<Box<Sleep>>>,
+}
+
+impl TcpConnection {
+    /// Consumes this `TcpConnection` and returns the inner [`TcpStream`].
+    #[inline]
+    pub fn into_inner(self) -> TcpStream {
+        self.inner
+    }
+}
+
+impl From<TcpConnection> for TcpStream {
+    #[inline]
+    fn from(conn: TcpConnection) -> Self {
+        conn.inner
+    }
+}
+
+impl Deref for TcpConnection {
+    type Target = TcpStream;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl DerefMut for TcpConnection {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+#[async_trait]
+impl HttpConnection for TcpConnection {
+    async fn version(&mut self) -> Option<Version> {
+        Some(Version::HTTP_11)
+    }
+    async fn serve(self, handler: HyperHandler, builders: Arc<HttpBuilders>) -> IoResult<()> {
+        builders.serve_plain(self, handler).await
+    }
+}
+
 #[async_trait]
 impl HttpConnection for TcpStream {
     async fn version(&mut self) -> Option<Version> {
         Some(Version::HTTP_11)
     }
     async fn serve(self, handler: HyperHandler, builders: Arc<HttpBuilders>) -> IoResult<()> {
-        #[cfg(not(feature = "http1"))]
-        {
-            let _ = handler;
-            let _ = builders;
-            panic!("http1 feature is required");
+        builders.serve_plain(self, handler).await
+    }
+}
+
+impl AsyncRead for TcpConnection {
+    #[inline]
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TcpConnection {
+    #[inline]
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    #[inline]
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        let this = &mut *self;
+        let timeout = match this.graceful_shutdown {
+            Some(timeout) => timeout,
+            None => return Pin::new(&mut this.inner).poll_shutdown(cx),
+        };
+        if this.draining.is_none() {
+            std::task::ready!(Pin::new(&mut this.inner).poll_shutdown(cx))?;
+            this.draining = Some(Box::pin(tokio::time::sleep_until(Instant::now() + timeout)));
+        }
+        // Read and discard until the client closes its side, errors end draining too.
+        let mut buf = [0; 1024];
+        loop {
+            if this
+                .draining
+                .as_mut()
+                .expect("draining should be set")
+                .as_mut()
+                .poll(cx)
+                .is_ready()
+            {
+                return Poll::Ready(Ok(()));
+            }
+            let mut read_buf = ReadBuf::new(&mut buf);
+            match Pin::new(&mut this.inner).poll_read(cx, &mut read_buf) {
+                Poll::Ready(Ok(())) if !read_buf.filled().is_empty() => continue,
+                Poll::Ready(_) => return Poll::Ready(Ok(())),
+                Poll::Pending => return Poll::Pending,
+            }
         }
-        #[cfg(feature = "http1")]
-        builders
-            .http1
-            .serve_connection(self, handler)
-            .with_upgrades()
-            .await
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
     }
 }
 
 #[async_trait]
 impl Acceptor for TcpAcceptor {
-    type Conn = TcpStream;
+    type Conn = TcpConnection;
 
     #[inline]
     fn holdings(&self) -> &[Holding] {
@@ -159,8 +345,19 @@ impl Acceptor for TcpAcceptor {
 
     #[inline]
     async fn accept(&mut self) -> IoResult<Accepted<Self::Conn>> {
-        self.inner.accept().await.map(move |(conn, remote_addr)| Accepted {
-            conn,
+        let (conn, remote_addr) = self.inner.accept().await?;
+        if self.nodelay {
+            conn.set_nodelay(true)?;
+        }
+        if self.linger.is_some() {
+            conn.set_linger(self.linger)?;
+        }
+        Ok(Accepted {
+            conn: TcpConnection {
+                inner: conn,
+                graceful_shutdown: self.graceful_shutdown,
+                draining: None,
+            },
             local_addr: self.holdings[0].local_addr.clone(),
             remote_addr: remote_addr.into(),
             http_version: self.holdings[0].http_version,
@@ -175,6 +372,7 @@ mod tests {
     use tokio::net::TcpStream;
 
     use super::*;
+    use crate::prelude::*;
 
     #[tokio::test]
     async fn test_tcp_listener() {
@@ -189,4 +387,128 @@ mod tests {
         let Accepted { mut conn, .. } = acceptor.accept().await.unwrap();
         assert_eq!(conn.read_i32().await.unwrap(), 150);
     }
+
+    #[tokio::test]
+    async fn test_tcp_listener_nodelay() {
+        let mut acceptor = TcpListener::new("127.0.0.1:0").nodelay(true).bind().await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+        tokio::spawn(async move {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream.write_i32(150).await.unwrap();
+        });
+
+        let Accepted { conn, .. } = acceptor.accept().await.unwrap();
+        assert!(conn.nodelay().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_listener_graceful_shutdown() {
+        #[handler(internal)]
+        async fn hello() -> &'static str {
+            "hello"
+        }
+        let acceptor = TcpListener::new("127.0.0.1:0")
+            .linger(Some(Duration::from_secs(1)))
+            .graceful_shutdown(Some(Duration::from_secs(5)))
+            .bind()
+            .await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+        tokio::spawn(async move {
+            Server::new(acceptor).serve(Router::new().post(hello)).await;
+        });
+
+        // The handler doesn't read the body, so the server closes the connection with unread data,
+        // which would be reset without graceful shutdown.
+        let body = vec![b'a'; 1024 * 1024];
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (mut reader, mut writer) = stream.into_split();
+        tokio::spawn(async move {
+            let head = format!(
+                "POST / HTTP/1.1\r\nhost: localhost\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                body.len()
+            );
+            writer.write_all(head.as_bytes()).await.unwrap();
+            let _ = writer.write_all(&body).await;
+            let _ = writer.shutdown().await;
+        });
+        let mut response = String::new();
+        reader.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("hello"));
+    }
+    #[tokio::test]
+    async fn test_tcp_listener_send_buffer_size() {
+        use std::path::PathBuf;
+
+        use crate::fs::NamedFile;
+
+        struct Download {
+            path: PathBuf,
+            chunk_size: u64,
+        }
+        #[async_trait]
+        impl Handler for Download {
+            async fn handle(&self, req: &mut Request, _depot: &mut Depot, res: &mut Response, _ctrl: &mut FlowCtrl) {
+                let file = NamedFile::builder(&self.path)
+                    .buffer_size(self.chunk_size)
+                    .build()
+                    .await
+                    .unwrap();
+                file.send(req.headers(), res).await;
+            }
+        }
+
+        let path = std::env::temp_dir().join(format!(
+            "salvo_tcp_listener_send_buffer_size_{}.bin",
+            std::process::id()
+        ));
+        let content = (0..4 * 1024 * 1024).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        std::fs::write(&path, &content).unwrap();
+
+        for size in [4 * 1024, 1024 * 1024] {
+            let acceptor = TcpListener::new("127.0.0.1:0")
+                .send_buffer_size(Some(size))
+                .bind()
+                .await;
+            let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+            let _client = TcpStream::connect(addr).await.unwrap();
+            let (stream, _) = acceptor.inner.accept().await.unwrap();
+            let socket = tokio::net::TcpSocket::from_std_stream(stream.into_std().unwrap());
+            // The kernel may round the size up, Linux doubles it for bookkeeping overhead.
+            assert!(socket.send_buffer_size().unwrap() >= size);
+        }
+
+        for send_buffer_size in [None, Some(4 * 1024), Some(1024 * 1024)] {
+            for chunk_size in [1024, 64 * 1024, 1024 * 1024] {
+                let acceptor = TcpListener::new("127.0.0.1:0")
+                    .send_buffer_size(send_buffer_size)
+                    .bind()
+                    .await;
+                let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+                let download = Download {
+                    path: path.clone(),
+                    chunk_size,
+                };
+                let server = tokio::spawn(async move {
+                    Server::new(acceptor).serve(Router::new().get(download)).await;
+                });
+
+                let mut stream = TcpStream::connect(addr).await.unwrap();
+                stream
+                    .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+                    .await
+                    .unwrap();
+                let mut response = Vec::new();
+                stream.read_to_end(&mut response).await.unwrap();
+                let head_len = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+                assert!(response.starts_with(b"HTTP/1.1 200"));
+                assert!(
+                    response[head_len..] == content[..],
+                    "body differs with send buffer size {send_buffer_size:?} and chunk size {chunk_size}"
+                );
+                server.abort();
+            }
+        }
+        std::fs::remove_file(&path).ok();
+    }
 }
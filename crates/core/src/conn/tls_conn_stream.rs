@@ -3,6 +3,7 @@ use std::io::{Error as IoError, ErrorKind, Result as IoResult};
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
 use futures_util::future::{BoxFuture, FutureExt};
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
@@ -12,6 +13,26 @@ use crate::conn::HttpBuilders;
 use crate::http::{HttpConnection, Version};
 use crate::service::HyperHandler;
 
+/// Default timeout of TLS handshakes.
+#[cfg(any(feature = "rustls", feature = "openssl"))]
+pub(crate) const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Run the TLS `handshake`, it fails with `ErrorKind::TimedOut` if it is not done within `timeout`.
+///
+/// `name` is the name of the TLS implementation which prefixes the error message.
+#[cfg(any(feature = "rustls", feature = "openssl"))]
+pub(crate) async fn handshake_with_timeout<F, S>(handshake: F, timeout: Option<Duration>, name: &str) -> IoResult<S>
+where
+    F: Future<Output = IoResult<S>>,
+{
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, handshake)
+            .await
+            .map_err(|_| IoError::new(ErrorKind::TimedOut, format!("{name}: tls handshake timed out")))?,
+        None => handshake.await,
+    }
+}
+
 enum State<S> {
     Handshaking(BoxFuture<'static, IoResult<S>>),
     Ready(S),
@@ -59,6 +80,32 @@ where
             unreachable!()
         }
     }
+    async fn alpn(&mut self) -> Option<Vec<u8>> {
+        if let State::Handshaking(fut) = &mut self.state {
+            match fut.await {
+                Ok(s) => self.state = State::Ready(s),
+                Err(e) => self.state = State::Error(e),
+            }
+        }
+        if let State::Ready(s) = &mut self.state {
+            s.alpn().await
+        } else {
+            None
+        }
+    }
+    async fn peer_certificate(&mut self) -> Option<Vec<u8>> {
+        if let State::Handshaking(fut) = &mut self.state {
+            match fut.await {
+                Ok(s) => self.state = State::Ready(s),
+                Err(e) => self.state = State::Error(e),
+            }
+        }
+        if let State::Ready(s) = &mut self.state {
+            s.peer_certificate().await
+        } else {
+            None
+        }
+    }
     async fn serve(mut self, handler: HyperHandler, builders: Arc<HttpBuilders>) -> IoResult<()> {
         match &mut self.state {
             State::Handshaking(fut) => match fut.await {
@@ -105,6 +152,12 @@ where
     }
 }
 
+/// Writes are passed through to the TLS stream once the handshake is done.
+///
+/// A slow client may make the TLS stream accept only a part of `buf`, the accepted length is returned
+/// as is and the caller writes the rest later, so nothing is assumed to be written in full. `WouldBlock`
+/// of the socket is turned into `Poll::Pending` by the TLS stream after the waker is registered, so it
+/// never surfaces as an error here.
 impl<S> AsyncWrite for TlsConnStream<S>
 where
     S: AsyncWrite + Unpin + Send + 'static,
@@ -162,3 +215,73 @@ where
         }
     }
 }
+
+#[cfg(all(test, feature = "http1"))]
+mod tests {
+    use std::convert::Infallible;
+
+    use hyper::body::Bytes;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    use super::*;
+
+    /// A stream which accepts at most 7 bytes per write and is not ready every other write.
+    struct SlowStream {
+        inner: DuplexStream,
+        ready: bool,
+    }
+    impl AsyncRead for SlowStream {
+        fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<IoResult<()>> {
+            Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+    }
+    impl AsyncWrite for SlowStream {
+        fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+            self.ready = !self.ready;
+            if !self.ready {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let len = buf.len().min(7);
+            Pin::new(&mut self.inner).poll_write(cx, &buf[..len])
+        }
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+        fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+            Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tls_conn_stream_partial_writes() {
+        let (mut client, server) = tokio::io::duplex(64);
+        let stream = TlsConnStream::new(async move {
+            Ok(SlowStream {
+                inner: server,
+                ready: false,
+            })
+        });
+        let body = Bytes::from((0..256 * 1024).map(|i| (i % 251) as u8).collect::<Vec<_>>());
+        let expected = body.clone();
+        tokio::spawn(async move {
+            let service = hyper::service::service_fn(move |_| {
+                let body = body.clone();
+                async move { Ok::<_, Infallible>(hyper::Response::new(http_body_util::Full::new(body))) }
+            });
+            hyper::server::conn::http1::Builder::new()
+                .serve_connection(stream, service)
+                .await
+        });
+
+        client
+            .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = Vec::new();
+        client.read_to_end(&mut response).await.unwrap();
+        let head_len = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        assert!(response.starts_with(b"HTTP/1.1 200"));
+        assert_eq!(&response[head_len..], &expected[..]);
+    }
+}
@@ -87,19 +87,7 @@ impl HttpConnection for UnixStream {
         Some(Version::HTTP_11)
     }
     async fn serve(self, handler: HyperHandler, builders: Arc<HttpBuilders>) -> IoResult<()> {
-        #[cfg(not(feature = "http1"))]
-        {
-            let _ = handler;
-            let _ = builders;
-            panic!("http1 feature is required");
-        }
-        #[cfg(feature = "http1")]
-        builders
-            .http1
-            .serve_connection(self, handler)
-            .with_upgrades()
-            .await
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
+        builders.serve_plain(self, handler).await
     }
 }
 
@@ -6,6 +6,11 @@ use std::io::Error as IoError;
 use crate::http::{ParseError, StatusError};
 use crate::{async_trait, Depot, Request, Response, Writer};
 
+/// Key used to store the unhandled `anyhow::Error` written by a handler in [`Depot`], it is rendered
+/// by the error renderer of the [`Service`](crate::Service).
+#[cfg(feature = "anyhow")]
+pub(crate) const UNHANDLED_ERROR_KEY: &str = "::salvo::unhandled_error";
+
 /// BoxedError
 pub type BoxedError = Box<dyn std::error::Error + Send + Sync>;
 
@@ -143,12 +148,13 @@ cfg_feature! {
     #[async_trait]
     impl Writer for anyhow::Error {
         #[inline]
-        async fn write(self, _req: &mut Request, _depot: &mut Depot, res: &mut Response) {
+        async fn write(self, _req: &mut Request, depot: &mut Depot, res: &mut Response) {
             tracing::error!(error = ?self, "anyhow error occurred");
             #[cfg(debug_assertions)]
             res.set_status_error(StatusError::internal_server_error().with_detail(self.to_string()));
             #[cfg(not(debug_assertions))]
             res.set_status_error(StatusError::internal_server_error());
+            depot.insert(UNHANDLED_ERROR_KEY, self);
         }
     }
 }
@@ -125,4 +125,48 @@ mod test {
             Some(&HeaderValue::from_static("attachment; filename=attach.file"))
         );
     }
+    #[tokio::test]
+    async fn test_named_file_last_modified_seconds() {
+        use crate::prelude::*;
+        use crate::test::TestClient;
+
+        fn path() -> std::path::PathBuf {
+            std::env::temp_dir().join(format!(
+                "salvo_named_file_last_modified_seconds_{}.txt",
+                std::process::id()
+            ))
+        }
+        #[handler(internal)]
+        async fn builder(req: &mut Request, res: &mut Response) {
+            NamedFile::builder(path()).send(req.headers(), res).await;
+        }
+        #[handler(internal)]
+        async fn file(req: &mut Request, res: &mut Response) {
+            NamedFile::open(path()).await.unwrap().send(req.headers(), res).await;
+        }
+
+        std::fs::write(path(), "hello").unwrap();
+        let file_time = NamedFile::open(path()).await.unwrap().last_modified().unwrap();
+        assert_eq!(
+            file_time.duration_since(std::time::UNIX_EPOCH).unwrap().subsec_nanos(),
+            0
+        );
+
+        let service = Service::new(
+            Router::new()
+                .push(Router::with_path("builder").get(builder))
+                .push(Router::with_path("file").get(file)),
+        );
+        for url in ["http://127.0.0.1:5801/builder", "http://127.0.0.1:5801/file"] {
+            let response = TestClient::get(url).send(&service).await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+            let last_modified = response.headers().get("last-modified").unwrap().clone();
+            let response = TestClient::get(url)
+                .add_header("if-modified-since", last_modified, true)
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::NOT_MODIFIED);
+        }
+        std::fs::remove_file(path()).ok();
+    }
 }
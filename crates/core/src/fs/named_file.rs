@@ -3,7 +3,7 @@ use std::cmp;
 use std::fs::Metadata;
 use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[cfg(unix)]
 use std::os::unix::fs::MetadataExt;
@@ -13,7 +13,9 @@ use headers::*;
 use tokio::fs::File;
 
 use super::{ChunkedFile, ChunkedState};
-use crate::http::header::{CONTENT_DISPOSITION, CONTENT_ENCODING, IF_NONE_MATCH, RANGE};
+use crate::http::header::{
+    CONTENT_DISPOSITION, CONTENT_ENCODING, IF_MATCH, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_UNMODIFIED_SINCE, RANGE,
+};
 use crate::http::{HttpRange, Mime, Request, Response, StatusCode, StatusError};
 use crate::{async_trait, Depot, Error, Result, Writer};
 
@@ -52,6 +54,7 @@ pub struct NamedFile {
     content_type: mime::Mime,
     content_disposition: Option<HeaderValue>,
     content_encoding: Option<HeaderValue>,
+    etag: Option<ETag>,
 }
 
 /// Builder for build [`NamedFile`].
@@ -63,6 +66,7 @@ pub struct NamedFileBuilder {
     content_type: Option<mime::Mime>,
     content_encoding: Option<String>,
     buffer_size: Option<u64>,
+    etag: Option<ETag>,
     flags: BitFlags<Flag>,
 }
 impl NamedFileBuilder {
@@ -111,6 +115,15 @@ impl NamedFileBuilder {
         self
     }
 
+    /// Sets ETag value and returns `Self`, it replaces the ETag computed from file metadata.
+    ///
+    /// See [`NamedFile::set_etag`].
+    #[inline]
+    pub fn etag(mut self, etag: ETag) -> Self {
+        self.etag = Some(etag);
+        self
+    }
+
     ///Specifies whether to use ETag or not.
     ///
     /// Default is true.
@@ -138,10 +151,12 @@ impl NamedFileBuilder {
     }
 
     /// Build a new `NamedFile` and send it.
+    ///
+    /// The file is not opened if `304 Not Modified` is sent, see [`NamedFileBuilder::send_not_modified`].
     pub async fn send(self, req_headers: &HeaderMap, res: &mut Response) {
         if !self.path.exists() {
             res.set_status_error(StatusError::not_found());
-        } else {
+        } else if !self.send_not_modified(req_headers, res).await {
             match self.build().await {
                 Ok(file) => file.send(req_headers, res).await,
                 Err(_) => res.set_status_error(StatusError::internal_server_error()),
@@ -149,6 +164,58 @@ impl NamedFileBuilder {
         }
     }
 
+    /// Sends `304 Not Modified` and returns `true` if `If-None-Match` or `If-Modified-Since` in
+    /// `req_headers` match the file, otherwise the response is untouched and `false` is returned.
+    ///
+    /// The ETag and modification time are taken from the file metadata, so the file is neither opened
+    /// nor read. Requests with `If-Match` or `If-Unmodified-Since` are left to [`NamedFile::send`],
+    /// which evaluates them first.
+    pub async fn send_not_modified(&self, req_headers: &HeaderMap, res: &mut Response) -> bool {
+        if req_headers.contains_key(IF_MATCH) || req_headers.contains_key(IF_UNMODIFIED_SINCE) {
+            return false;
+        }
+        if !req_headers.contains_key(IF_NONE_MATCH) && !req_headers.contains_key(IF_MODIFIED_SINCE) {
+            return false;
+        }
+        let metadata = match tokio::fs::metadata(&self.path).await {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => return false,
+        };
+        let modified = metadata.modified().ok();
+        let etag = self.etag.clone().or_else(|| metadata_etag(&metadata, modified));
+        let last_modified = if self.flags.contains(Flag::LastModified) {
+            modified.map(truncate_to_seconds)
+        } else {
+            None
+        };
+        let matched_etag = if self.flags.contains(Flag::Etag) {
+            etag.as_ref()
+        } else {
+            None
+        };
+        if !is_not_modified(matched_etag, last_modified, req_headers) {
+            return false;
+        }
+
+        // Same validators as `NamedFile::send` sets on `304 Not Modified`.
+        if let Some(lm) = last_modified {
+            res.headers_mut().typed_insert(LastModified::from(lm));
+        }
+        if let Some(etag) = matched_etag {
+            res.headers_mut().typed_insert(etag.clone());
+        }
+        res.headers_mut().typed_insert(AcceptRanges::bytes());
+        if let Some(content_encoding) = self
+            .content_encoding
+            .as_deref()
+            .and_then(|content_encoding| content_encoding.parse::<HeaderValue>().ok())
+        {
+            res.headers_mut().insert(CONTENT_ENCODING, content_encoding);
+        }
+        res.set_status_code(StatusCode::NOT_MODIFIED);
+        true
+    }
+
     /// Build a new [`NamedFile`].
     pub async fn build(self) -> Result<NamedFile> {
         let NamedFileBuilder {
@@ -158,23 +225,12 @@ impl NamedFileBuilder {
             buffer_size,
             disposition_type,
             attached_name,
+            etag,
             flags,
         } = self;
 
         let file = File::open(&path).await?;
-        let content_type = content_type.unwrap_or_else(|| {
-            let ct = mime_guess::from_path(&path).first_or_octet_stream();
-            let ftype = ct.type_();
-            let stype = ct.subtype();
-            if (ftype == mime::TEXT || stype == mime::JSON || stype == mime::JAVASCRIPT)
-                && ct.get_param(mime::CHARSET).is_none()
-            {
-                //TODO: auto detect charset
-                format!("{ct}; charset=utf-8").parse::<mime::Mime>().unwrap_or(ct)
-            } else {
-                ct
-            }
-        });
+        let content_type = content_type.unwrap_or_else(|| NamedFile::guess_content_type(&path));
         let metadata = file.metadata().await?;
         let modified = metadata.modified().ok();
         let content_encoding = match content_encoding {
@@ -199,6 +255,7 @@ impl NamedFileBuilder {
             metadata,
             modified,
             content_encoding,
+            etag,
             buffer_size: buffer_size.unwrap_or(CHUNK_SIZE),
             flags,
         })
@@ -241,6 +298,23 @@ fn build_content_disposition(
     Ok(content_disposition)
 }
 impl NamedFile {
+    /// Guess the content type of the file at `path` by its extension, text, JSON and JavaScript are
+    /// UTF-8. It is the content type of a [`NamedFile`] which is not set with
+    /// [`NamedFileBuilder::content_type`].
+    pub fn guess_content_type(path: impl AsRef<Path>) -> Mime {
+        let ct = mime_guess::from_path(path).first_or_octet_stream();
+        let ftype = ct.type_();
+        let stype = ct.subtype();
+        if (ftype == mime::TEXT || stype == mime::JSON || stype == mime::JAVASCRIPT)
+            && ct.get_param(mime::CHARSET).is_none()
+        {
+            //TODO: auto detect charset
+            format!("{ct}; charset=utf-8").parse::<mime::Mime>().unwrap_or(ct)
+        } else {
+            ct
+        }
+    }
+
     /// Create new [`NamedFileBuilder`].
     #[inline]
     pub fn builder(path: impl Into<PathBuf>) -> NamedFileBuilder {
@@ -251,6 +325,7 @@ impl NamedFile {
             content_type: None,
             content_encoding: None,
             buffer_size: None,
+            etag: None,
             flags: BitFlags::default(),
         }
     }
@@ -352,39 +427,21 @@ impl NamedFile {
         self.content_encoding = Some(content_encoding);
     }
 
+    /// Sets ETag value, it replaces the ETag computed from file metadata.
+    ///
+    /// This is useful to serve ETags which don't depend on modification time or inode, for
+    /// example a hash of the file content.
+    #[inline]
+    pub fn set_etag(&mut self, etag: ETag) {
+        self.etag = Some(etag);
+    }
+
     /// Get ETag value.
     pub fn etag(&self) -> Option<ETag> {
-        // This etag format is similar to Apache's.
-        self.modified.as_ref().and_then(|mtime| {
-            let ino = {
-                #[cfg(unix)]
-                {
-                    self.metadata.ino()
-                }
-                #[cfg(not(unix))]
-                {
-                    0
-                }
-            };
-
-            let dur = mtime
-                .duration_since(UNIX_EPOCH)
-                .expect("modification time must be after epoch");
-            let etag_str = format!(
-                "\"{:x}-{:x}-{:x}-{:x}\"",
-                ino,
-                self.metadata.len(),
-                dur.as_secs(),
-                dur.subsec_nanos()
-            );
-            match etag_str.parse::<ETag>() {
-                Ok(etag) => Some(etag),
-                Err(e) => {
-                    tracing::error!(error = ?e, etag = %etag_str, "set file's etag failed");
-                    None
-                }
-            }
-        })
+        if let Some(etag) = &self.etag {
+            return Some(etag.clone());
+        }
+        metadata_etag(&self.metadata, self.modified)
     }
     ///Specifies whether to use ETag or not.
     ///
@@ -398,10 +455,11 @@ impl NamedFile {
         }
     }
 
-    /// GEt last_modified value.
+    /// Get last_modified value, it is truncated to whole seconds like HTTP dates, so it equals the
+    /// time parsed from the `Last-Modified` header sent and `If-Modified-Since` replaying it.
     #[inline]
     pub fn last_modified(&self) -> Option<SystemTime> {
-        self.modified
+        self.modified.map(truncate_to_seconds)
     }
     ///Specifies whether to use Last-Modified or not.
     ///
@@ -415,6 +473,19 @@ impl NamedFile {
         }
     }
     ///Consume self and send content to [`Response`].
+    ///
+    ///Conditional headers are always evaluated. Request cache directives such as `Cache-Control: no-cache`,
+    ///`Cache-Control: no-store` and `Pragma: no-cache` are addressed to caches, not to the origin server, so
+    ///they are ignored here: `no-cache` asks to revalidate, which is what a conditional request does, and a
+    ///`304 Not Modified` is still returned if the etag or modification time matches.
+    ///
+    ///A single byte range in `Range` is served with `206 Partial Content`, `416 Range Not Satisfiable` is
+    ///returned if it starts past the end of the file. Multiple ranges are not supported, the whole file
+    ///is served with `200 OK` instead.
+    ///
+    ///`If-Range` makes the range conditional, the whole file is served with `200 OK` if its ETag doesn't
+    ///strongly match the ETag of the file, its date is older than the modification time of the file, or it
+    ///is malformed.
     pub async fn send(mut self, req_headers: &HeaderMap, res: &mut Response) {
         let etag = if self.flags.contains(Flag::Etag) {
             self.etag()
@@ -427,7 +498,7 @@ impl NamedFile {
             None
         };
 
-        // check preconditions
+        // check preconditions, request cache directives don't bypass them, see `send` doc.
         let precondition_failed = if !any_match(etag.as_ref(), req_headers) {
             true
         } else if let (Some(ref last_modified), Some(since)) =
@@ -439,17 +510,7 @@ impl NamedFile {
         };
 
         // check last modified
-        let not_modified = if !none_match(etag.as_ref(), req_headers) {
-            true
-        } else if req_headers.contains_key(IF_NONE_MATCH) {
-            false
-        } else if let (Some(ref last_modified), Some(since)) =
-            (last_modified, req_headers.typed_get::<IfModifiedSince>())
-        {
-            !since.is_modified(*last_modified)
-        } else {
-            false
-        };
+        let not_modified = is_not_modified(etag.as_ref(), last_modified, req_headers);
 
         if self.flags.contains(Flag::ContentDisposition) {
             if let Some(content_disposition) = self.content_disposition.take() {
@@ -472,8 +533,8 @@ impl NamedFile {
         if let Some(lm) = last_modified {
             res.headers_mut().typed_insert(LastModified::from(lm));
         }
-        if let Some(etag) = self.etag() {
-            res.headers_mut().typed_insert(etag);
+        if let Some(etag) = &etag {
+            res.headers_mut().typed_insert(etag.clone());
         }
         res.headers_mut().typed_insert(AcceptRanges::bytes());
 
@@ -482,18 +543,36 @@ impl NamedFile {
             res.headers_mut().insert(CONTENT_ENCODING, content_encoding.clone());
         }
         let mut offset = 0;
-
-        // check for range header
-        let range = req_headers.get(RANGE);
+        let mut partial = false;
+
+        // a range with `If-Range` is ignored if the file is not the representation the client has.
+        let range_matched = HttpRange::if_range_matched(
+            req_headers,
+            etag.as_ref(),
+            last_modified.map(LastModified::from).as_ref(),
+        );
+        // check for range header, an empty file has no satisfiable range, but an open-ended range
+        // from 0 like `bytes=0-` asks for the whole file, so it is served as is.
+        let range = req_headers
+            .get(RANGE)
+            .filter(|_| range_matched)
+            .filter(|range| length != 0 || !range.to_str().map(HttpRange::is_whole).unwrap_or(false));
         if let Some(range) = range {
             if let Ok(range) = range.to_str() {
-                if let Ok(range) = HttpRange::parse(range, length) {
-                    length = range[0].length;
-                    offset = range[0].start;
-                } else {
-                    res.headers_mut().typed_insert(ContentRange::unsatisfied_bytes(length));
-                    res.set_status_code(StatusCode::RANGE_NOT_SATISFIABLE);
-                    return;
+                match HttpRange::parse(range, length) {
+                    // Multiple ranges are not supported, the range header is ignored and the whole
+                    // file is served.
+                    Ok(range) if range.len() > 1 && range.iter().all(|range| range.length > 0) => {}
+                    Ok(range) if range.first().map(|range| range.length > 0).unwrap_or(false) => {
+                        length = range[0].length;
+                        offset = range[0].start;
+                        partial = true;
+                    }
+                    _ => {
+                        res.headers_mut().typed_insert(ContentRange::unsatisfied_bytes(length));
+                        res.set_status_code(StatusCode::RANGE_NOT_SATISFIABLE);
+                        return;
+                    }
                 };
             } else {
                 res.set_status_code(StatusCode::BAD_REQUEST);
@@ -509,7 +588,7 @@ impl NamedFile {
             return;
         }
 
-        if offset != 0 || length != self.metadata.len() || range.is_some() {
+        if partial {
             res.set_status_code(StatusCode::PARTIAL_CONTENT);
             match ContentRange::bytes(offset..offset + length - 1, self.metadata.len()) {
                 Ok(content_range) => {
@@ -565,6 +644,63 @@ impl DerefMut for NamedFile {
     }
 }
 
+/// Truncate `time` to whole seconds since the epoch, times before the epoch are kept as is.
+fn truncate_to_seconds(time: SystemTime) -> SystemTime {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => UNIX_EPOCH + Duration::from_secs(duration.as_secs()),
+        Err(_) => time,
+    }
+}
+
+/// Get ETag of a file from its metadata, it is computed from inode, size and modification time.
+fn metadata_etag(metadata: &Metadata, modified: Option<SystemTime>) -> Option<ETag> {
+    // This etag format is similar to Apache's.
+    modified.as_ref().and_then(|mtime| {
+        let ino = {
+            #[cfg(unix)]
+            {
+                metadata.ino()
+            }
+            #[cfg(not(unix))]
+            {
+                0
+            }
+        };
+
+        let dur = mtime
+            .duration_since(UNIX_EPOCH)
+            .expect("modification time must be after epoch");
+        let etag_str = format!(
+            "\"{:x}-{:x}-{:x}-{:x}\"",
+            ino,
+            metadata.len(),
+            dur.as_secs(),
+            dur.subsec_nanos()
+        );
+        match etag_str.parse::<ETag>() {
+            Ok(etag) => Some(etag),
+            Err(e) => {
+                tracing::error!(error = ?e, etag = %etag_str, "set file's etag failed");
+                None
+            }
+        }
+    })
+}
+
+/// Returns true if `If-None-Match` in `req_headers` matches `etag`, or if there is no `If-None-Match`
+/// and `If-Modified-Since` is not before `last_modified`.
+fn is_not_modified(etag: Option<&ETag>, last_modified: Option<SystemTime>, req_headers: &HeaderMap) -> bool {
+    if !none_match(etag, req_headers) {
+        true
+    } else if req_headers.contains_key(IF_NONE_MATCH) {
+        false
+    } else if let (Some(ref last_modified), Some(since)) = (last_modified, req_headers.typed_get::<IfModifiedSince>()) {
+        !since.is_modified(*last_modified)
+    } else {
+        false
+    }
+}
+
 /// Returns true if `req_headers` has no `If-Match` header or one which matches `etag`.
 fn any_match(etag: Option<&ETag>, req_headers: &HeaderMap) -> bool {
     match req_headers.typed_get::<IfMatch>() {
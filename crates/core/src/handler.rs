@@ -34,6 +34,8 @@
 //! }
 //! ```
 
+use std::sync::Arc;
+
 use crate::http::StatusCode;
 use crate::{async_trait, Depot, FlowCtrl, Request, Response};
 
@@ -87,6 +89,148 @@ where
     }
 }
 
+/// A named and ordered list of middlewares, which can be added to a [`Router`](crate::Router) with
+/// [`Router::hoop`](crate::Router::hoop) as a single middleware.
+///
+/// Middlewares run in the onion model:
+///
+/// 1. For each middleware in declared order, the code before [`FlowCtrl::call_next`] runs, so the first
+///    declared middleware sees the request first.
+/// 2. Then the rest of the flow runs: middlewares added after the stack, middlewares of child routers
+///    and the matched handler.
+/// 3. The code after `call_next` runs in reverse order, so the first declared middleware sees the
+///    response last.
+///
+/// If a middleware doesn't call `call_next`, the next one runs after it returns, so all of its code is
+/// `pre` code. If a middleware calls [`FlowCtrl::skip_rest`] or writes an error status code, the rest of the
+/// stack is skipped, but the `post` code of the middlewares before it still runs.
+///
+/// ```
+/// use salvo_core::handler::MiddlewareStack;
+/// use salvo_core::prelude::*;
+///
+/// #[handler]
+/// async fn logging(req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+///     println!("request: {}", req.uri());
+///     ctrl.call_next(req, depot, res).await;
+///     println!("response: {:?}", res.status_code());
+/// }
+/// #[handler]
+/// async fn auth() {}
+/// #[handler]
+/// async fn compression() {}
+///
+/// let stack = MiddlewareStack::new()
+///     .push("logging", logging)
+///     .push("compression", compression)
+///     .insert_before("compression", "auth", auth);
+/// assert_eq!(stack.names().collect::<Vec<_>>(), ["logging", "auth", "compression"]);
+/// let router = Router::new().hoop(stack);
+/// ```
+#[derive(Clone, Default)]
+pub struct MiddlewareStack {
+    layers: Vec<(String, Arc<dyn Handler>)>,
+}
+
+impl MiddlewareStack {
+    /// Create a new empty `MiddlewareStack`.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a middleware after all others and returns `Self`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a middleware with the same `name` is already in the stack.
+    #[inline]
+    pub fn push<H: Handler>(mut self, name: impl Into<String>, handler: H) -> Self {
+        let name = self.check_name(name.into());
+        self.layers.push((name, Arc::new(handler)));
+        self
+    }
+
+    /// Add a middleware right before the middleware named `anchor` and returns `Self`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `anchor` is not in the stack, or a middleware with the same `name` is already in the stack.
+    #[inline]
+    pub fn insert_before<H: Handler>(mut self, anchor: &str, name: impl Into<String>, handler: H) -> Self {
+        let name = self.check_name(name.into());
+        let index = self.position(anchor);
+        self.layers.insert(index, (name, Arc::new(handler)));
+        self
+    }
+
+    /// Add a middleware right after the middleware named `anchor` and returns `Self`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `anchor` is not in the stack, or a middleware with the same `name` is already in the stack.
+    #[inline]
+    pub fn insert_after<H: Handler>(mut self, anchor: &str, name: impl Into<String>, handler: H) -> Self {
+        let name = self.check_name(name.into());
+        let index = self.position(anchor) + 1;
+        self.layers.insert(index, (name, Arc::new(handler)));
+        self
+    }
+
+    /// Remove the middleware named `name` if it is in the stack and returns `Self`.
+    #[inline]
+    pub fn remove(mut self, name: &str) -> Self {
+        self.layers.retain(|(layer, _)| layer != name);
+        self
+    }
+
+    /// Check whether a middleware named `name` is in the stack.
+    #[inline]
+    pub fn contains(&self, name: &str) -> bool {
+        self.layers.iter().any(|(layer, _)| layer == name)
+    }
+
+    /// Names of the middlewares in execution order.
+    #[inline]
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.layers.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Get the number of middlewares.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Returns `true` if there is no middleware.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    fn check_name(&self, name: String) -> String {
+        assert!(!self.contains(&name), "middleware `{name}` is already in the stack");
+        name
+    }
+
+    fn position(&self, anchor: &str) -> usize {
+        self.layers
+            .iter()
+            .position(|(name, _)| name == anchor)
+            .unwrap_or_else(|| panic!("middleware `{anchor}` is not in the stack"))
+    }
+}
+
+#[async_trait]
+impl Handler for MiddlewareStack {
+    #[inline]
+    async fn handle(&self, _req: &mut Request, _depot: &mut Depot, _res: &mut Response, ctrl: &mut FlowCtrl) {
+        // The middlewares join the flow right after the stack, so they are executed by `FlowCtrl`
+        // exactly as if they were added one by one.
+        ctrl.insert_next(self.layers.iter().map(|(_, handler)| handler.clone()));
+    }
+}
+
 /// `Skipper` is used in many middlewares.
 pub trait Skipper: Send + Sync + 'static {
     /// Check if the request should be skipped.
@@ -208,3 +352,73 @@ macro_rules! __for_each_tuple {
 
 __for_each_tuple!(handler_tuple_impls);
 __for_each_tuple!(skipper_tuple_impls);
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::prelude::*;
+    use crate::test::{ResponseExt, TestClient};
+
+    struct Recorder {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+    #[async_trait]
+    impl Handler for Recorder {
+        async fn handle(&self, req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+            self.log.lock().unwrap().push(format!("pre {}", self.name));
+            ctrl.call_next(req, depot, res).await;
+            self.log.lock().unwrap().push(format!("post {}", self.name));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_middleware_stack_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let recorder = |name| Recorder { name, log: log.clone() };
+        let stack = MiddlewareStack::new()
+            .push("logging", recorder("logging"))
+            .push("compression", recorder("compression"))
+            .insert_after("logging", "auth", recorder("auth"))
+            .push("timeout", recorder("timeout"))
+            .remove("timeout");
+        assert_eq!(stack.names().collect::<Vec<_>>(), ["logging", "auth", "compression"]);
+
+        struct Hello(Arc<Mutex<Vec<String>>>);
+        #[async_trait]
+        impl Handler for Hello {
+            async fn handle(&self, _req: &mut Request, _depot: &mut Depot, res: &mut Response, _ctrl: &mut FlowCtrl) {
+                self.0.lock().unwrap().push("handler".into());
+                res.render("hello");
+            }
+        }
+        let router = Router::new().hoop(stack).get(Hello(log.clone()));
+        let content = TestClient::get("http://127.0.0.1:5801/")
+            .send(router)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_eq!(content, "hello");
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "pre logging",
+                "pre auth",
+                "pre compression",
+                "handler",
+                "post compression",
+                "post auth",
+                "post logging"
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "middleware `auth` is not in the stack")]
+    fn test_middleware_stack_missing_anchor() {
+        let _ = MiddlewareStack::new().insert_before("auth", "logging", empty());
+    }
+}
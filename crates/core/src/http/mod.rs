@@ -15,7 +15,7 @@ pub use http::method::Method;
 pub use http::{header, method, uri, HeaderMap, HeaderValue, StatusCode};
 pub use mime::{self, Mime};
 pub use range::HttpRange;
-pub use request::Request;
+pub use request::{DuplicateKeys, Request};
 pub mod body;
 pub use body::{Body, ReqBody, ResBody};
 pub use response::Response;
@@ -34,6 +34,15 @@ use crate::service::HyperHandler;
 pub trait HttpConnection {
     /// The http protocol version.
     async fn version(&mut self) -> Option<Version>;
+    /// The protocol negotiated by ALPN, `None` if the connection is not TLS or no protocol is negotiated.
+    async fn alpn(&mut self) -> Option<Vec<u8>> {
+        None
+    }
+    /// The DER encoded end entity certificate of the peer, `None` if the connection is not TLS or
+    /// the client sends no certificate.
+    async fn peer_certificate(&mut self) -> Option<Vec<u8>> {
+        None
+    }
     /// Serve this http connection.
     async fn serve(self, handler: HyperHandler, builders: Arc<HttpBuilders>) -> IoResult<()>;
 }
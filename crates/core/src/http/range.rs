@@ -1,4 +1,7 @@
-use crate::http::ParseError;
+use headers::{ETag, HeaderMapExt, IfRange, LastModified};
+
+use crate::http::header::IF_RANGE;
+use crate::http::{HeaderMap, ParseError};
 
 /// HTTP Range header representation.
 #[derive(Clone, Debug, Copy)]
@@ -96,6 +99,33 @@ impl HttpRange {
 
         Ok(ranges)
     }
+
+    /// Check whether the Range header `header` asks for the whole representation whatever its size,
+    /// that is every range starts at 0 and is open-ended, like `bytes=0-`.
+    ///
+    /// An empty representation has no satisfiable range, but such a header can be answered with it.
+    pub fn is_whole(header: &str) -> bool {
+        let size = i64::MAX as u64;
+        match HttpRange::parse(header, size) {
+            Ok(ranges) => !ranges.is_empty() && ranges.iter().all(|range| range.start == 0 && range.length == size),
+            Err(_) => false,
+        }
+    }
+
+    /// Check whether the `If-Range` of the request `headers` matches the representation with `etag`
+    /// and `last_modified`, a range is only served if it does.
+    ///
+    /// It matches if there is no `If-Range`, and doesn't if it is malformed, so the whole
+    /// representation is served rather than a range of content the client may not have.
+    pub fn if_range_matched(headers: &HeaderMap, etag: Option<&ETag>, last_modified: Option<&LastModified>) -> bool {
+        if !headers.contains_key(IF_RANGE) {
+            return true;
+        }
+        match headers.typed_get::<IfRange>() {
+            Some(if_range) => !if_range.is_modified(etag, last_modified),
+            None => false,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -104,6 +134,38 @@ mod tests {
 
     struct T(&'static str, u64, Vec<HttpRange>);
 
+    #[test]
+    fn test_is_whole() {
+        for header in ["bytes=0-", "bytes= 0- ", "bytes=0-,0-"] {
+            assert!(HttpRange::is_whole(header), "{header}");
+        }
+        for header in [
+            "",
+            "bytes=",
+            "bytes=0-0",
+            "bytes=1-",
+            "bytes=-1",
+            "bytes=0-,1-",
+            "bytes=A-",
+            "items=0-",
+        ] {
+            assert!(!HttpRange::is_whole(header), "{header}");
+        }
+    }
+
+    #[test]
+    fn test_if_range_matched() {
+        let etag = "\"abc\"".parse::<ETag>().unwrap();
+        let mut headers = HeaderMap::new();
+        assert!(HttpRange::if_range_matched(&headers, Some(&etag), None));
+        headers.insert(IF_RANGE, "\"abc\"".parse().unwrap());
+        assert!(HttpRange::if_range_matched(&headers, Some(&etag), None));
+        headers.insert(IF_RANGE, "\"def\"".parse().unwrap());
+        assert!(!HttpRange::if_range_matched(&headers, Some(&etag), None));
+        headers.insert(IF_RANGE, "malformed".parse().unwrap());
+        assert!(!HttpRange::if_range_matched(&headers, Some(&etag), None));
+    }
+
     #[test]
     fn test_parse() {
         let tests = vec![
@@ -22,9 +22,11 @@ use crate::conn::SocketAddr;
 use crate::extract::{Extractible, Metadata};
 use crate::http::body::ReqBody;
 use crate::http::form::{FilePart, FormData};
-use crate::http::{Mime, ParseError, Version};
-use crate::serde::{from_request, from_str_map, from_str_multi_map, from_str_multi_val, from_str_val};
-use crate::Error;
+use crate::http::{Mime, ParseError, Response, Version};
+use crate::serde::{
+    from_request, from_str_map, from_str_multi_map, from_str_multi_map_with, from_str_multi_val, from_str_val,
+};
+use crate::{async_trait, Depot, Error, FlowCtrl, Handler};
 
 static SECURE_MAX_SIZE: RwLock<usize> = RwLock::new(64 * 1024);
 
@@ -39,6 +41,48 @@ pub fn set_secure_max_size(size: usize) {
     *lock = size;
 }
 
+/// Middleware which sets secure max size of requests handled by the router it is added to.
+///
+/// The size set by the innermost `SecureMaxSize` is used, it takes precedence over the global
+/// [`secure_max_size`], so a route can accept larger bodies than the default.
+///
+/// # Example
+///
+/// ```
+/// use salvo_core::http::request::SecureMaxSize;
+/// use salvo_core::prelude::*;
+///
+/// #[handler]
+/// async fn upload(req: &mut Request) -> String {
+///     req.payload().await.map(|p| p.len()).unwrap_or_default().to_string()
+/// }
+///
+/// let router = Router::with_path("upload").hoop(SecureMaxSize(1024 * 1024)).post(upload);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct SecureMaxSize(pub usize);
+#[async_trait]
+impl Handler for SecureMaxSize {
+    #[inline]
+    async fn handle(&self, req: &mut Request, _depot: &mut Depot, _res: &mut Response, _ctrl: &mut FlowCtrl) {
+        req.set_secure_max_size(self.0);
+    }
+}
+
+/// How a single value field is parsed when its key is repeated, for example `?a=1&a=2`.
+///
+/// Sequence fields such as `Vec<T>` always receive all values.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum DuplicateKeys {
+    /// Use the first value.
+    #[default]
+    First,
+    /// Use the last value.
+    Last,
+    /// Return an error.
+    Error,
+}
+
 /// Represents an HTTP request.
 ///
 /// Stores all the properties of the client's request.
@@ -60,11 +104,14 @@ pub struct Request {
     pub(crate) cookies: CookieJar,
 
     pub(crate) params: HashMap<String, String>,
+    pub(crate) raw_params: HashMap<String, String>,
+    pub(crate) route_name: Option<String>,
 
     // accept: Option<Vec<Mime>>,
     pub(crate) queries: OnceCell<MultiMap<String, String>>,
     pub(crate) form_data: tokio::sync::OnceCell<FormData>,
     pub(crate) payload: tokio::sync::OnceCell<Bytes>,
+    pub(crate) secure_max_size: Option<usize>,
 
     /// The version of the HTTP protocol used.
     pub(crate) version: Version,
@@ -109,9 +156,12 @@ impl Request {
             #[cfg(feature = "cookie")]
             cookies: CookieJar::default(),
             params: HashMap::new(),
+            raw_params: HashMap::new(),
+            route_name: None,
             queries: OnceCell::new(),
             form_data: tokio::sync::OnceCell::new(),
             payload: tokio::sync::OnceCell::new(),
+            secure_max_size: None,
             version: Version::default(),
             scheme: Scheme::HTTP,
             local_addr: SocketAddr::Unknown,
@@ -162,8 +212,11 @@ impl Request {
             cookies,
             // accept: None,
             params: HashMap::new(),
+            raw_params: HashMap::new(),
+            route_name: None,
             form_data: tokio::sync::OnceCell::new(),
             payload: tokio::sync::OnceCell::new(),
+            secure_max_size: None,
             // multipart: OnceCell::new(),
             local_addr: SocketAddr::Unknown,
             remote_addr: SocketAddr::Unknown,
@@ -436,10 +489,31 @@ impl Request {
         }
     }
     /// Get params reference.
+    ///
+    /// Params captured by the router are percent-decoded, a `%2F` in the url path is decoded as `/`,
+    /// use [`Request::raw_params`] to get the still encoded values.
     #[inline]
     pub fn params(&self) -> &HashMap<String, String> {
         &self.params
     }
+    /// Get raw params reference, values are still percent-encoded as in the url path.
+    #[inline]
+    pub fn raw_params(&self) -> &HashMap<String, String> {
+        &self.raw_params
+    }
+    /// Get the still percent-encoded value of param `key`.
+    ///
+    /// For example, the param `path` of route `files/<**path>` is `a/b c` for url path
+    /// `/files/a%2Fb%20c`, and the raw param is `a%2Fb%20c`.
+    #[inline]
+    pub fn raw_param(&self, key: &str) -> Option<&str> {
+        self.raw_params.get(key).map(|v| &**v)
+    }
+    /// Get the name of matched route, which is set by [`Router::name`](crate::Router::name).
+    #[inline]
+    pub fn route_name(&self) -> Option<&str> {
+        self.route_name.as_deref()
+    }
     /// Get params mutable reference.
     #[inline]
     pub fn params_mut(&mut self) -> &mut HashMap<String, String> {
@@ -545,13 +619,27 @@ impl Request {
             .unwrap_or_default()
     }
 
+    /// Get secure max size of this request, it is the global [`secure_max_size`] unless it is
+    /// overridden by [`Request::set_secure_max_size`].
+    #[inline]
+    pub fn secure_max_size(&self) -> usize {
+        self.secure_max_size.unwrap_or_else(secure_max_size)
+    }
+
+    /// Set secure max size of this request, it takes precedence over the global [`secure_max_size`],
+    /// so it can be used to raise or lower the limit for some routes, see [`SecureMaxSize`].
+    #[inline]
+    pub fn set_secure_max_size(&mut self, size: usize) {
+        self.secure_max_size = Some(size);
+    }
+
     /// Get request payload with default max size limit.
     ///
     /// https://github.com/hyperium/hyper/issues/3111
     /// *Notice: This method takes body.
     #[inline]
     pub async fn payload(&mut self) -> Result<&Bytes, ParseError> {
-        self.payload_with_max_size(secure_max_size()).await
+        self.payload_with_max_size(self.secure_max_size()).await
     }
 
     /// Get request payload with max size limit.
@@ -621,13 +709,26 @@ impl Request {
     }
 
     /// Parse queries as type `T` from request.
+    ///
+    /// Repeated keys are collected into sequence fields such as `Vec<T>`, a single value field
+    /// receiving repeated keys uses the first value, see [`Request::parse_queries_with`] to change it.
     #[inline]
     pub fn parse_queries<'de, T>(&'de mut self) -> Result<T, ParseError>
+    where
+        T: Deserialize<'de>,
+    {
+        self.parse_queries_with(DuplicateKeys::First)
+    }
+
+    /// Parse queries as type `T` from request, `duplicate_keys` decides how a single value field
+    /// receiving repeated keys is parsed.
+    #[inline]
+    pub fn parse_queries_with<'de, T>(&'de mut self, duplicate_keys: DuplicateKeys) -> Result<T, ParseError>
     where
         T: Deserialize<'de>,
     {
         let queries = self.queries().iter_all();
-        from_str_multi_map(queries).map_err(ParseError::Deserialize)
+        from_str_multi_map_with(queries, duplicate_keys).map_err(ParseError::Deserialize)
     }
 
     /// Parse headers as type `T` from request.
@@ -665,7 +766,7 @@ impl Request {
     where
         T: Deserialize<'de>,
     {
-        self.parse_json_with_max_size(secure_max_size()).await
+        self.parse_json_with_max_size(self.secure_max_size()).await
     }
     /// Parse json body as type `T` from request with max size limit.
     #[inline]
@@ -705,7 +806,7 @@ impl Request {
     where
         T: Deserialize<'de>,
     {
-        self.parse_body_with_max_size(secure_max_size()).await
+        self.parse_body_with_max_size(self.secure_max_size()).await
     }
 
     /// Parse json body or form body as type `T` from request with max size.
@@ -733,7 +834,109 @@ mod tests {
     use serde::{Deserialize, Serialize};
 
     use super::*;
-    use crate::test::TestClient;
+    use crate::prelude::*;
+    use crate::test::{ResponseExt, TestClient};
+
+    #[tokio::test]
+    async fn test_raw_param() {
+        #[handler(internal)]
+        async fn show(req: &mut Request) -> String {
+            format!(
+                "{} {}",
+                req.param::<String>("**path").unwrap(),
+                req.raw_param("**path").unwrap()
+            )
+        }
+        let service = Service::new(Router::with_path("files/<**path>").get(show));
+        let content = TestClient::get("http://127.0.0.1:5801/files/a%2Fb/c")
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_eq!(content, "a/b/c a%2Fb/c");
+    }
+
+    #[tokio::test]
+    async fn test_secure_max_size() {
+        #[handler(internal)]
+        async fn upload(req: &mut Request) -> String {
+            match req.payload().await {
+                Ok(payload) => payload.len().to_string(),
+                Err(_) => "too large".into(),
+            }
+        }
+        let router = Router::new()
+            .push(Router::with_path("default").post(upload))
+            .push(
+                Router::with_path("large")
+                    .hoop(SecureMaxSize(secure_max_size() * 2))
+                    .post(upload),
+            )
+            .push(Router::with_path("small").hoop(SecureMaxSize(8)).post(upload));
+        let service = Service::new(router);
+        let body = "a".repeat(secure_max_size() + 1);
+
+        let content = TestClient::post("http://127.0.0.1:5801/default")
+            .text(body.clone())
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_eq!(content, "too large");
+
+        let content = TestClient::post("http://127.0.0.1:5801/large")
+            .text(body)
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_eq!(content, (secure_max_size() + 1).to_string());
+
+        let content = TestClient::post("http://127.0.0.1:5801/small")
+            .text("0123456789")
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_eq!(content, "too large");
+    }
+
+    #[tokio::test]
+    async fn test_parse_queries_duplicate_keys() {
+        #[derive(Deserialize, Eq, PartialEq, Debug)]
+        struct Single {
+            a: u8,
+        }
+        #[derive(Deserialize, Eq, PartialEq, Debug)]
+        struct Multi {
+            a: Vec<u8>,
+        }
+        let mut req = TestClient::get("http://127.0.0.1:5801/hello?a=1&a=2").build();
+        assert_eq!(req.parse_queries::<Single>().unwrap(), Single { a: 1 });
+        assert_eq!(
+            req.parse_queries_with::<Single>(DuplicateKeys::First).unwrap(),
+            Single { a: 1 }
+        );
+        assert_eq!(
+            req.parse_queries_with::<Single>(DuplicateKeys::Last).unwrap(),
+            Single { a: 2 }
+        );
+        assert!(req.parse_queries_with::<Single>(DuplicateKeys::Error).is_err());
+        assert_eq!(
+            req.parse_queries_with::<Multi>(DuplicateKeys::Error).unwrap(),
+            Multi { a: vec![1, 2] }
+        );
+
+        let mut req = TestClient::get("http://127.0.0.1:5801/hello?a=1").build();
+        assert_eq!(
+            req.parse_queries_with::<Single>(DuplicateKeys::Error).unwrap(),
+            Single { a: 1 }
+        );
+    }
 
     #[tokio::test]
     async fn test_parse_queries() {
@@ -2,18 +2,22 @@
 use std::collections::VecDeque;
 use std::error::Error as StdError;
 use std::fmt::{self, Display, Formatter};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{Context, Poll};
 
 #[cfg(feature = "cookie")]
 use cookie::{Cookie, CookieJar};
-use futures_util::stream::{Stream, TryStreamExt};
-use http::header::{HeaderMap, HeaderValue, IntoHeaderName};
+use futures_util::stream::{BoxStream, Stream, StreamExt, TryStreamExt};
+use http::header::{HeaderMap, HeaderValue, IntoHeaderName, CONTENT_LENGTH};
 pub use http::response::Parts;
 use http::version::Version;
 use mime::Mime;
 
 use super::errors::*;
-use crate::http::StatusCode;
-use crate::{Error, Piece};
+use crate::fs::NamedFile;
+use crate::http::{Request, StatusCode};
+use crate::{BoxedError, Error, Piece};
 use bytes::Bytes;
 
 pub use crate::http::body::ResBody;
@@ -453,6 +457,59 @@ impl Response {
         }
         Ok(())
     }
+    /// Attempts to send a file. If file not exists, not found error will occur.
+    ///
+    /// Content type, `ETag`, `Last-Modified`, range requests and conditional requests are all
+    /// handled according to the headers of `req`, and the status code is set accordingly.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use salvo_core::prelude::*;
+    ///
+    /// #[handler]
+    /// async fn download(req: &mut Request, res: &mut Response) {
+    ///     res.send_file("files/report.pdf", req).await;
+    /// }
+    /// ```
+    #[inline]
+    pub async fn send_file<P>(&mut self, path: P, req: &Request)
+    where
+        P: Into<PathBuf> + Send,
+    {
+        NamedFile::send_file(path, req.headers(), self).await
+    }
+
+    /// Sets `Content-Length` header explicitly, so a streamed body is not sent with chunked encoding.
+    ///
+    /// This should be called after the body is set. If the body is `ResBody::Once` or `ResBody::Chunks`,
+    /// an error is returned when its size does not match `length`. If the body is `ResBody::Stream`,
+    /// the stream is checked while it is written and yields an error when the size does not match.
+    pub fn set_content_length(&mut self, length: u64) -> crate::Result<()> {
+        match self.take_body() {
+            ResBody::Stream(stream) => {
+                self.body = ResBody::Stream(Box::pin(LengthCheckedStream {
+                    inner: stream,
+                    expected: length,
+                    written: 0,
+                    finished: false,
+                }));
+            }
+            body => {
+                let size = body.size();
+                self.body = body;
+                if let Some(size) = size {
+                    if !self.body.is_none() && size != length {
+                        tracing::error!(size, length, "content length does not match body size");
+                        return Err(Error::other("content length does not match body size"));
+                    }
+                }
+            }
+        }
+        self.headers.insert(CONTENT_LENGTH, HeaderValue::from(length));
+        Ok(())
+    }
+
     /// Write streaming data.
     #[inline]
     pub fn streaming<S, O, E>(&mut self, stream: S) -> crate::Result<()>
@@ -477,6 +534,136 @@ impl Response {
         self.body = ResBody::Stream(Box::pin(mapped));
         Ok(())
     }
+
+    /// Write streaming data, each item is flushed to the connection as soon as it is yielded,
+    /// instead of being coalesced with the following items.
+    ///
+    /// This is useful for SSE and live streaming. To also disable Nagle's algorithm on the
+    /// connection, use [`TcpListener::nodelay`](crate::conn::TcpListener::nodelay).
+    #[inline]
+    pub fn streaming_flushed<S, O, E>(&mut self, stream: S) -> crate::Result<()>
+    where
+        S: Stream<Item = Result<O, E>> + Send + 'static,
+        O: Into<Bytes> + 'static,
+        E: Into<Box<dyn StdError + Send + Sync>> + 'static,
+    {
+        self.streaming(FlushEachStream {
+            inner: Box::pin(stream.map_ok(Into::into).map_err(Into::into)),
+            yielded: false,
+        })
+    }
+}
+
+cfg_feature! {
+    #![feature = "decompression"]
+    use async_compression::tokio::bufread::{BrotliDecoder, DeflateDecoder, GzipDecoder};
+    use tokio::io::{AsyncRead, AsyncReadExt, BufReader};
+
+    /// Buffer size used to read decompressed data.
+    const DECOMPRESS_CHUNK_SIZE: usize = 8 * 1024;
+
+    impl Response {
+        /// Stream data read from `reader`, which is compressed with `encoding`, decompressed.
+        ///
+        /// Supported encodings are `gzip`, `deflate` and `br`, an error is returned for others.
+        /// Data is decompressed while it is sent, so the memory used is bounded by the read buffer
+        /// and the decompression window, no matter how large the data is. This is useful when the
+        /// client does not accept `encoding`, the `Content-Encoding` and `Content-Length` headers
+        /// are removed since they describe the compressed data.
+        pub fn stream_decompressed<R>(&mut self, reader: R, encoding: &str) -> crate::Result<()>
+        where
+            R: AsyncRead + Send + Unpin + 'static,
+        {
+            let reader = BufReader::new(reader);
+            let decoder: Pin<Box<dyn AsyncRead + Send>> = match encoding.trim().to_ascii_lowercase().as_str() {
+                "gzip" | "x-gzip" => Box::pin(GzipDecoder::new(reader)),
+                "deflate" => Box::pin(DeflateDecoder::new(reader)),
+                "br" => Box::pin(BrotliDecoder::new(reader)),
+                _ => return Err(Error::other(format!("unsupported content encoding `{encoding}`"))),
+            };
+            self.headers.remove(http::header::CONTENT_ENCODING);
+            self.headers.remove(CONTENT_LENGTH);
+            self.streaming(futures_util::stream::try_unfold(decoder, |mut decoder| async move {
+                let mut buf = vec![0; DECOMPRESS_CHUNK_SIZE];
+                let len = decoder.read(&mut buf).await?;
+                if len == 0 {
+                    Ok::<_, std::io::Error>(None)
+                } else {
+                    buf.truncate(len);
+                    Ok(Some((Bytes::from(buf), decoder)))
+                }
+            }))
+        }
+    }
+}
+
+/// Stream yields `Poll::Pending` once after each item, so the connection flushes its write buffer.
+struct FlushEachStream {
+    inner: BoxStream<'static, Result<Bytes, BoxedError>>,
+    yielded: bool,
+}
+impl Stream for FlushEachStream {
+    type Item = Result<Bytes, BoxedError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.yielded {
+            self.yielded = false;
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        let item = self.inner.poll_next_unpin(cx);
+        if let Poll::Ready(Some(_)) = &item {
+            self.yielded = true;
+        }
+        item
+    }
+}
+
+/// Stream checks the written size against the expected `Content-Length`.
+struct LengthCheckedStream {
+    inner: BoxStream<'static, Result<Bytes, BoxedError>>,
+    expected: u64,
+    written: u64,
+    finished: bool,
+}
+impl Stream for LengthCheckedStream {
+    type Item = Result<Bytes, BoxedError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.finished {
+            return Poll::Ready(None);
+        }
+        match self.inner.poll_next_unpin(cx) {
+            Poll::Ready(Some(Ok(bytes))) => {
+                self.written += bytes.len() as u64;
+                if self.written > self.expected {
+                    self.finished = true;
+                    tracing::error!(
+                        written = self.written,
+                        expected = self.expected,
+                        "streamed body is longer than content length"
+                    );
+                    Poll::Ready(Some(Err("streamed body is longer than content length".into())))
+                } else {
+                    Poll::Ready(Some(Ok(bytes)))
+                }
+            }
+            Poll::Ready(None) => {
+                self.finished = true;
+                if self.written != self.expected {
+                    tracing::error!(
+                        written = self.written,
+                        expected = self.expected,
+                        "streamed body is shorter than content length"
+                    );
+                    Poll::Ready(Some(Err("streamed body is shorter than content length".into())))
+                } else {
+                    Poll::Ready(None)
+                }
+            }
+            other => other,
+        }
+    }
 }
 
 impl fmt::Debug for Response {
@@ -506,6 +693,38 @@ mod test {
 
     use super::*;
 
+    #[cfg(feature = "decompression")]
+    #[tokio::test]
+    async fn test_stream_decompressed() {
+        use async_compression::tokio::bufread::GzipEncoder;
+        use tokio::io::AsyncReadExt;
+
+        let text = "salvo ".repeat(10_000);
+        let mut gzip = Vec::new();
+        GzipEncoder::new(text.as_bytes()).read_to_end(&mut gzip).await.unwrap();
+        assert!(gzip.len() < text.len());
+
+        let mut res = Response::new();
+        res.headers_mut()
+            .insert(http::header::CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+        res.stream_decompressed(std::io::Cursor::new(gzip), "gzip").unwrap();
+        assert!(!res.headers().contains_key(http::header::CONTENT_ENCODING));
+
+        let mut result = BytesMut::new();
+        let mut chunks = 0;
+        let mut body = res.take_body();
+        while let Some(Ok(data)) = body.next().await {
+            assert!(data.len() <= DECOMPRESS_CHUNK_SIZE);
+            result.extend_from_slice(&data);
+            chunks += 1;
+        }
+        assert!(chunks > 1);
+        assert_eq!(result, text.as_bytes());
+
+        let mut res = Response::new();
+        assert!(res.stream_decompressed(std::io::Cursor::new(vec![]), "zstd").is_err());
+    }
+
     #[test]
     fn test_body_empty() {
         let body = ResBody::Once(Bytes::from("hello"));
@@ -540,4 +759,70 @@ mod test {
 
         assert_eq!("Hello World", &result)
     }
+
+    #[tokio::test]
+    async fn test_set_content_length() {
+        use crate::test::ResponseExt;
+
+        let chunks = || {
+            iter(vec![
+                Result::<_, Box<dyn Error + Send + Sync>>::Ok(BytesMut::from("Hello").freeze()),
+                Result::<_, Box<dyn Error + Send + Sync>>::Ok(BytesMut::from(" World").freeze()),
+            ])
+        };
+        let mut res = Response::new();
+        res.streaming(chunks()).unwrap();
+        res.set_content_length(11).unwrap();
+        assert_eq!(res.headers().get(CONTENT_LENGTH).unwrap(), "11");
+        assert!(res.headers().get(http::header::TRANSFER_ENCODING).is_none());
+        assert_eq!(res.take_string().await.unwrap(), "Hello World");
+
+        let mut res = Response::new();
+        res.streaming(chunks()).unwrap();
+        res.set_content_length(5).unwrap();
+        assert!(res.take_bytes().await.is_err());
+
+        let mut res = Response::new();
+        res.write_body("Hello").unwrap();
+        assert!(res.set_content_length(11).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_streaming_flushed() {
+        let chunks = iter(vec![
+            Result::<_, Box<dyn Error + Send + Sync>>::Ok(BytesMut::from("Hello").freeze()),
+            Result::<_, Box<dyn Error + Send + Sync>>::Ok(BytesMut::from(" World").freeze()),
+        ]);
+        let mut res = Response::new();
+        res.streaming_flushed(chunks).unwrap();
+        let mut stream = match res.take_body() {
+            ResBody::Stream(stream) => stream,
+            _ => panic!("body should be stream"),
+        };
+        for expected in ["Hello", " World"] {
+            let item = futures_util::poll!(stream.next());
+            assert!(matches!(item, Poll::Ready(Some(Ok(bytes))) if bytes == expected));
+            assert!(futures_util::poll!(stream.next()).is_pending());
+        }
+        assert!(matches!(futures_util::poll!(stream.next()), Poll::Ready(None)));
+    }
+
+    #[tokio::test]
+    async fn test_send_file_range() {
+        use crate::prelude::*;
+        use crate::test::TestClient;
+
+        #[handler(internal)]
+        async fn download(req: &mut Request, res: &mut Response) {
+            res.send_file("Cargo.toml", req).await;
+        }
+        let router = Router::with_path("download").get(download);
+
+        let res = TestClient::get("http://127.0.0.1:5801/download")
+            .add_header("range", "bytes=0-9", true)
+            .send(router)
+            .await;
+        assert_eq!(res.status_code().unwrap(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(res.headers().get("content-length").unwrap(), "10");
+    }
 }
@@ -42,8 +42,8 @@ pub use self::extract::Extractible;
 pub use self::handler::Handler;
 pub use self::http::{Request, Response};
 pub use self::routing::{FlowCtrl, Router};
-pub use self::server::Server;
-pub use self::service::Service;
+pub use self::server::{ConnStats, Server};
+pub use self::service::{ConnectionClosePolicy, Service};
 pub use self::writer::{Piece, Writer};
 /// Result type which has `salvo::Error` as it's error type.
 pub type Result<T> = std::result::Result<T, Error>;
@@ -164,6 +164,7 @@ where
             return false;
         }
         let picked = picked.unwrap();
+        let start = state.cursor;
         if let Some(max_width) = self.max_width {
             let mut chars = Vec::with_capacity(max_width);
             for ch in picked.chars() {
@@ -172,13 +173,13 @@ where
                 }
                 if chars.len() == max_width {
                     state.forward(max_width);
-                    state.params.insert(self.name.clone(), chars.into_iter().collect());
+                    state.capture(self.name.clone(), chars.into_iter().collect(), start);
                     return true;
                 }
             }
             if chars.len() >= self.min_width {
                 state.forward(chars.len());
-                state.params.insert(self.name.clone(), chars.into_iter().collect());
+                state.capture(self.name.clone(), chars.into_iter().collect(), start);
                 true
             } else {
                 false
@@ -192,7 +193,7 @@ where
             }
             if chars.len() >= self.min_width {
                 state.forward(chars.len());
-                state.params.insert(self.name.clone(), chars.into_iter().collect());
+                state.capture(self.name.clone(), chars.into_iter().collect(), start);
                 true
             } else {
                 false
@@ -225,8 +226,9 @@ impl PathWisp for NamedWisp {
             let rest = state.all_rest().unwrap_or_default();
             if !rest.is_empty() || self.0.starts_with("**") {
                 let rest = rest.to_string();
-                state.params.insert(self.0.clone(), rest);
-                state.cursor.0 = state.parts.len();
+                let start = state.cursor;
+                state.cursor = (state.parts.len(), 0);
+                state.capture(self.0.clone(), rest, start);
                 true
             } else {
                 false
@@ -237,8 +239,9 @@ impl PathWisp for NamedWisp {
                 return false;
             }
             let picked = picked.unwrap().to_owned();
+            let start = state.cursor;
             state.forward(picked.len());
-            state.params.insert(self.0.clone(), picked);
+            state.capture(self.0.clone(), picked, start);
             true
         }
     }
@@ -274,8 +277,9 @@ impl PathWisp for RegexWisp {
                 let cap = self.regex.captures(rest).and_then(|caps| caps.get(0));
                 if let Some(cap) = cap {
                     let cap = cap.as_str().to_owned();
+                    let start = state.cursor;
                     state.forward(cap.len());
-                    state.params.insert(self.name.clone(), cap);
+                    state.capture(self.name.clone(), cap, start);
                     true
                 } else {
                     false
@@ -292,8 +296,9 @@ impl PathWisp for RegexWisp {
             let cap = self.regex.captures(picked).and_then(|caps| caps.get(0));
             if let Some(cap) = cap {
                 let cap = cap.as_str().to_owned();
+                let start = state.cursor;
                 state.forward(cap.len());
-                state.params.insert(self.name.clone(), cap);
+                state.capture(self.name.clone(), cap, start);
                 true
             } else {
                 false
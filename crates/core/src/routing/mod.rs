@@ -19,8 +19,10 @@ pub type PathParams = HashMap<String, String>;
 #[derive(Debug, Eq, PartialEq)]
 pub struct PathState {
     pub(crate) parts: Vec<String>,
+    pub(crate) raw_parts: Vec<String>,
     pub(crate) cursor: (usize, usize),
     pub(crate) params: PathParams,
+    pub(crate) raw_params: PathParams,
     pub(crate) end_slash: bool, // For rest match, we want includs the last slash.
 }
 impl PathState {
@@ -28,26 +30,51 @@ impl PathState {
     #[inline]
     pub fn new(url_path: &str) -> Self {
         let end_slash = url_path.ends_with('/');
-        let parts = url_path
+        let raw_parts = url_path
             .trim_start_matches('/')
             .trim_end_matches('/')
             .split('/')
-            .filter_map(|p| {
-                if !p.is_empty() {
-                    Some(decode_url_path_safely(p))
-                } else {
-                    None
-                }
-            })
+            .filter(|p| !p.is_empty())
+            .map(ToOwned::to_owned)
             .collect::<Vec<_>>();
+        let parts = raw_parts.iter().map(|p| decode_url_path_safely(p)).collect();
         PathState {
             parts,
+            raw_parts,
             cursor: (0, 0),
             params: PathParams::new(),
+            raw_params: PathParams::new(),
             end_slash,
         }
     }
 
+    /// Insert a param whose value is captured from `start` to current cursor, the still encoded
+    /// value is inserted into `raw_params` too.
+    #[inline]
+    pub(crate) fn capture(&mut self, name: String, value: String, start: (usize, usize)) {
+        let end = self.cursor;
+        let mut pieces = Vec::new();
+        for row in start.0..self.raw_parts.len() {
+            if row > end.0 || (row == end.0 && end.1 == 0 && row > start.0) {
+                break;
+            }
+            let raw = &self.raw_parts[row];
+            let from = if row == start.0 { raw_offset(raw, start.1) } else { 0 };
+            let to = if row == end.0 {
+                raw_offset(raw, end.1)
+            } else {
+                raw.len()
+            };
+            pieces.push(&raw[from..to.max(from)]);
+        }
+        let mut raw_value = pieces.join("/");
+        if value.ends_with('/') && !raw_value.ends_with('/') {
+            raw_value.push('/');
+        }
+        self.raw_params.insert(name.clone(), raw_value);
+        self.params.insert(name, value);
+    }
+
     #[inline]
     pub fn pick(&self) -> Option<&str> {
         match self.parts.get(self.cursor.0) {
@@ -105,6 +132,25 @@ impl PathState {
     }
 }
 
+/// Map `offset` in the decoded form of `raw` to the offset in `raw`.
+#[inline]
+fn raw_offset(raw: &str, offset: usize) -> usize {
+    let bytes = raw.as_bytes();
+    let (mut raw_pos, mut decoded_pos) = (0, 0);
+    while raw_pos < bytes.len() && decoded_pos < offset {
+        let is_escape = bytes[raw_pos] == b'%'
+            && bytes.get(raw_pos + 1).map(u8::is_ascii_hexdigit).unwrap_or(false)
+            && bytes.get(raw_pos + 2).map(u8::is_ascii_hexdigit).unwrap_or(false);
+        raw_pos += if is_escape { 3 } else { 1 };
+        decoded_pos += 1;
+    }
+    // Invalid UTF-8 is decoded lossily, so the offset may be out of a char boundary.
+    while !raw.is_char_boundary(raw_pos) {
+        raw_pos += 1;
+    }
+    raw_pos
+}
+
 #[inline]
 fn decode_url_path_safely(path: &str) -> String {
     percent_encoding::percent_decode_str(path)
@@ -185,6 +231,12 @@ impl FlowCtrl {
         }
     }
 
+    /// Insert `handlers` to run right after the current handler.
+    #[inline]
+    pub(crate) fn insert_next(&mut self, handlers: impl IntoIterator<Item = Arc<dyn Handler>>) {
+        self.handlers.splice(self.cursor..self.cursor, handlers);
+    }
+
     /// Skip all reset handlers.
     #[inline]
     pub fn skip_rest(&mut self) {
@@ -88,11 +88,13 @@ pub struct Router {
     pub(crate) filters: Vec<Box<dyn Filter>>,
     pub(crate) hoops: Vec<Arc<dyn Handler>>,
     pub(crate) handler: Option<Arc<dyn Handler>>,
+    pub(crate) name: Option<String>,
 }
 #[doc(hidden)]
 pub struct DetectMatched {
     pub hoops: Vec<Arc<dyn Handler>>,
     pub handler: Arc<dyn Handler>,
+    pub name: Option<String>,
 }
 
 impl Default for Router {
@@ -111,6 +113,7 @@ impl Router {
             filters: Vec::new(),
             hoops: Vec::new(),
             handler: None,
+            name: None,
         }
     }
 
@@ -161,6 +164,7 @@ impl Router {
                     return Some(DetectMatched {
                         hoops: [&self.hoops[..], &dm.hoops[..]].concat(),
                         handler: dm.handler.clone(),
+                        name: dm.name,
                     });
                 } else {
                     path_state.cursor = original_cursor;
@@ -172,6 +176,7 @@ impl Router {
                 return Some(DetectMatched {
                     hoops: self.hoops.clone(),
                     handler,
+                    name: self.name.clone(),
                 });
             }
         }
@@ -286,6 +291,36 @@ impl Router {
         self
     }
 
+    /// Sets a name of the handler, which can be used for diagnostics, it is available by
+    /// [`Request::route_name`] and is recorded in the `route` tracing span of the request.
+    ///
+    /// If current router has no handler, the name is set to the last child router which has a handler,
+    /// so it can be used after [`Router::get`], [`Router::post`] and so on.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use salvo_core::prelude::*;
+    /// # #[handler]
+    /// # async fn show_user(res: &mut Response) {
+    /// # }
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// Router::with_path("users/<id>").get(show_user).name("get_user");
+    /// # }
+    /// ```
+    #[inline]
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        if self.handler.is_none() {
+            if let Some(child) = self.routers.iter_mut().rev().find(|child| child.handler.is_some()) {
+                child.name = Some(name.into());
+                return self;
+            }
+        }
+        self.name = Some(name.into());
+        self
+    }
+
     /// When you want write router chain, this function will be useful,
     /// You can write your custom logic in FnOnce.
     #[inline]
@@ -674,5 +709,28 @@ mod tests {
         let matched = router.detect(&mut req, &mut path_state);
         assert!(matched.is_some());
         assert_eq!(path_state.params["p"], "a/b/c");
+        assert_eq!(path_state.raw_params["p"], "a%2fb%2fc");
+    }
+
+    #[test]
+    fn test_router_detect_raw_params() {
+        let router = Router::new()
+            .push(Router::with_path(r"files/<id:/\d+/>.<ext>/<**rest>").get(fake_handler))
+            .push(Router::with_path("names/<name>").get(fake_handler));
+        let mut req = TestClient::get("http://127.0.0.1:6060/files/12.t%20t/a%2Fb/c%20d/").build();
+        let mut path_state = PathState::new(req.uri().path());
+        assert!(router.detect(&mut req, &mut path_state).is_some());
+        assert_eq!(path_state.params["id"], "12");
+        assert_eq!(path_state.raw_params["id"], "12");
+        assert_eq!(path_state.params["ext"], "t t");
+        assert_eq!(path_state.raw_params["ext"], "t%20t");
+        assert_eq!(path_state.params["**rest"], "a/b/c d/");
+        assert_eq!(path_state.raw_params["**rest"], "a%2Fb/c%20d/");
+
+        let mut req = TestClient::get("http://127.0.0.1:6060/names/%E4%BD%A0%E5%A5%BD").build();
+        let mut path_state = PathState::new(req.uri().path());
+        assert!(router.detect(&mut req, &mut path_state).is_some());
+        assert_eq!(path_state.params["name"], "你好");
+        assert_eq!(path_state.raw_params["name"], "%E4%BD%A0%E5%A5%BD");
     }
 }
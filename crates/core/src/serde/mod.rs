@@ -8,6 +8,8 @@ use serde::de::{
 };
 use serde::forward_to_deserialize_any;
 
+use crate::http::request::DuplicateKeys;
+
 mod request;
 pub(crate) use request::from_request;
 
@@ -32,9 +34,29 @@ where
     C: IntoIterator<Item = V> + 'de,
     V: Into<Cow<'de, str>> + std::cmp::Eq + 'de,
 {
-    let iter = input
-        .into_iter()
-        .map(|(k, v)| (CowValue(k.into()), VecValue(v.into_iter().map(|v| CowValue(v.into())))));
+    from_str_multi_map_with(input, DuplicateKeys::First)
+}
+
+/// Same as [`from_str_multi_map`], `duplicate_keys` decides the value of a single value field which
+/// has more than one values.
+#[inline]
+pub(crate) fn from_str_multi_map_with<'de, I, T, K, C, V>(
+    input: I,
+    duplicate_keys: DuplicateKeys,
+) -> Result<T, ValError>
+where
+    I: IntoIterator<Item = (K, C)> + 'de,
+    T: Deserialize<'de>,
+    K: Into<Cow<'de, str>> + Hash + std::cmp::Eq + 'de,
+    C: IntoIterator<Item = V> + 'de,
+    V: Into<Cow<'de, str>> + std::cmp::Eq + 'de,
+{
+    let iter = input.into_iter().map(|(k, v)| {
+        (
+            CowValue(k.into()),
+            VecValue(v.into_iter().map(|v| CowValue(v.into())), duplicate_keys),
+        )
+    });
     T::deserialize(MapDeserializer::new(iter))
 }
 
@@ -46,7 +68,7 @@ where
     C: Into<Cow<'de, str>> + std::cmp::Eq + 'de,
 {
     let iter = input.into_iter().map(|v| CowValue(v.into()));
-    T::deserialize(VecValue(iter))
+    T::deserialize(VecValue(iter, DuplicateKeys::First))
 }
 
 #[inline]
@@ -79,13 +101,9 @@ macro_rules! forward_vec_parsed_value {
             fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
                 where V: Visitor<'de>
             {
-                if let Some(item) = self.0.into_iter().next() {
-                    match item.0.parse::<$ty>() {
-                        Ok(val) => val.into_deserializer().$method(visitor),
-                        Err(e) => Err(DeError::custom(e))
-                    }
-                } else {
-                    Err(DeError::custom("expected vec not empty"))
+                match self.single()?.0.parse::<$ty>() {
+                    Ok(val) => val.into_deserializer().$method(visitor),
+                    Err(e) => Err(DeError::custom(e))
                 }
             }
         )*
@@ -228,7 +246,31 @@ impl<'de> Deserializer<'de> for CowValue<'de> {
     }
 }
 
-struct VecValue<I>(I);
+struct VecValue<I>(I, DuplicateKeys);
+impl<'de, I> VecValue<I>
+where
+    I: IntoIterator<Item = CowValue<'de>>,
+{
+    /// Take the value for a single value field.
+    fn single(self) -> Result<CowValue<'de>, ValError> {
+        let mut iter = self.0.into_iter();
+        let first = iter.next().ok_or_else(|| DeError::custom("expected vec not empty"))?;
+        match self.1 {
+            DuplicateKeys::First => Ok(first),
+            DuplicateKeys::Last => Ok(iter.last().unwrap_or(first)),
+            DuplicateKeys::Error => {
+                if iter.next().is_some() {
+                    Err(DeError::custom(format!(
+                        "expected a single value, found duplicate values starting with `{}`",
+                        first.0
+                    )))
+                } else {
+                    Ok(first)
+                }
+            }
+        }
+    }
+}
 impl<'de, I> IntoDeserializer<'de> for VecValue<I>
 where
     I: Iterator<Item = CowValue<'de>>,
@@ -252,11 +294,7 @@ where
     where
         V: Visitor<'de>,
     {
-        if let Some(item) = self.0.into_iter().next() {
-            item.deserialize_any(visitor)
-        } else {
-            Err(DeError::custom("expected vec not empty"))
-        }
+        self.single()?.deserialize_any(visitor)
     }
 
     #[inline]
@@ -277,11 +315,7 @@ where
     where
         V: Visitor<'de>,
     {
-        if let Some(item) = self.0.into_iter().next() {
-            visitor.visit_enum(ValueEnumAccess(item.0))
-        } else {
-            Err(DeError::custom("expected vec not empty"))
-        }
+        visitor.visit_enum(ValueEnumAccess(self.single()?.0))
     }
 
     #[inline]
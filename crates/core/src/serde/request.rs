@@ -12,6 +12,7 @@ use crate::extract::metadata::{Field, Source, SourceFormat, SourceFrom};
 use crate::extract::Metadata;
 use crate::http::form::FormData;
 use crate::http::header::HeaderMap;
+use crate::http::request::DuplicateKeys;
 use crate::http::ParseError;
 use crate::Request;
 
@@ -128,7 +129,7 @@ impl<'de> RequestDeserializer<'de> {
         } else if let Some(value) = self.field_str_value.take() {
             seed.deserialize(CowValue(value.into()))
         } else if let Some(value) = self.field_vec_value.take() {
-            seed.deserialize(VecValue(value.into_iter()))
+            seed.deserialize(VecValue(value.into_iter(), DuplicateKeys::First))
         } else {
             Err(ValError::custom("parse value error"))
         }
@@ -1,8 +1,9 @@
 //! Server module
 use std::future::Future;
 use std::io::Result as IoResult;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 
 #[cfg(feature = "http1")]
 use hyper::server::conn::http1;
@@ -13,16 +14,103 @@ use tokio::time::Duration;
 
 #[cfg(feature = "quinn")]
 use crate::conn::quinn;
-use crate::conn::{Accepted, Acceptor, Holding, HttpBuilders};
+use crate::conn::{
+    Accepted, Acceptor, AcceptorShutdown, ConnectionId, Holding, HttpBuilders, HttpProtocol, SocketAddr,
+};
 use crate::http::{HeaderValue, HttpConnection, Version};
 use crate::Service;
 
+/// Default `SETTINGS_MAX_CONCURRENT_STREAMS` of HTTP/2 connections.
+#[cfg(feature = "http2")]
+const DEFAULT_MAX_CONCURRENT_STREAMS: u32 = 200;
+
+/// Default timeout of reading the first bytes of connections to detect the protocol.
+const DEFAULT_PRE_READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Counter of requests which are being handled, used to pause accepting connections.
+pub(crate) struct InFlightRequests {
+    count: AtomicUsize,
+    high_watermark: usize,
+    low_watermark: usize,
+    notify: Notify,
+}
+
+impl InFlightRequests {
+    fn new(high_watermark: usize, low_watermark: usize) -> Self {
+        InFlightRequests {
+            count: AtomicUsize::new(0),
+            high_watermark,
+            low_watermark,
+            notify: Notify::new(),
+        }
+    }
+
+    /// Count a new request, it is uncounted when the returned guard is dropped.
+    pub(crate) fn enter(self: Arc<Self>) -> InFlightGuard {
+        if self.count.fetch_add(1, Ordering::SeqCst) + 1 == self.high_watermark {
+            self.notify.notify_waiters();
+        }
+        InFlightGuard(self)
+    }
+}
+
+/// Guard of a request counted by [`InFlightRequests`].
+pub(crate) struct InFlightGuard(Arc<InFlightRequests>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.0.count.fetch_sub(1, Ordering::SeqCst) - 1 == self.0.low_watermark {
+            self.0.notify.notify_waiters();
+        }
+    }
+}
+
+/// Statistics of a closed connection, passed to the callback set by [`Server::on_disconnect`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct ConnStats {
+    /// Time elapsed since the connection is accepted.
+    pub duration: Duration,
+    /// Number of requests served on the connection.
+    pub requests: u64,
+    /// Number of bytes read from the connection, including HTTP framing.
+    pub bytes_read: u64,
+    /// Number of bytes written to the connection, including HTTP framing.
+    pub bytes_written: u64,
+}
+
+/// Counters of a connection, they are turned into [`ConnStats`] when the connection is closed.
+#[derive(Default)]
+pub(crate) struct ConnCounters {
+    pub(crate) requests: AtomicU64,
+    pub(crate) bytes_read: AtomicU64,
+    pub(crate) bytes_written: AtomicU64,
+}
+
+impl ConnCounters {
+    fn stats(&self, duration: Duration) -> ConnStats {
+        ConnStats {
+            duration,
+            requests: self.requests.load(Ordering::Relaxed),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+        }
+    }
+}
+
+type OnConnect = Arc<dyn Fn(&SocketAddr) + Send + Sync>;
+type OnDisconnect = Arc<dyn Fn(&SocketAddr, ConnStats) + Send + Sync>;
+
 /// HTTP Server
 ///
 /// A `Server` is created to listen on a port, parse HTTP requests, and hand them off to a [`Service`].
 pub struct Server<A> {
     acceptor: A,
     builders: HttpBuilders,
+    backpressure: Option<(usize, usize)>,
+    accept_cooldown: Option<Duration>,
+    on_connect: Option<OnConnect>,
+    on_disconnect: Option<OnDisconnect>,
 }
 
 impl<A: Acceptor + Send> Server<A> {
@@ -41,19 +129,180 @@ impl<A: Acceptor + Send> Server<A> {
     /// ```
     #[inline]
     pub fn new(acceptor: A) -> Self {
+        #[cfg(feature = "http2")]
+        let mut http2 = http2::Builder::new(crate::runtimes::TokioExecutor);
+        #[cfg(feature = "http2")]
+        http2.max_concurrent_streams(DEFAULT_MAX_CONCURRENT_STREAMS);
         Server {
             acceptor,
             builders: HttpBuilders {
                 #[cfg(feature = "http1")]
                 http1: http1::Builder::new(),
                 #[cfg(feature = "http2")]
-                http2: http2::Builder::new(crate::runtimes::TokioExecutor),
+                http2,
                 #[cfg(feature = "quinn")]
                 quinn: crate::conn::quinn::Builder,
+                protocol: HttpProtocol::Auto,
+                h2c: false,
+                pre_read_timeout: Some(DEFAULT_PRE_READ_TIMEOUT),
             },
+            backpressure: None,
+            accept_cooldown: None,
+            on_connect: None,
+            on_disconnect: None,
         }
     }
 
+    /// Sets the [`HttpProtocol`] versions served on connections and returns `Self`.
+    ///
+    /// TLS listeners negotiate the version by ALPN, connections which negotiated a version not
+    /// allowed here are closed, so configure the TLS listener with
+    /// [`HttpProtocol::alpn_protocols`] as well.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use salvo_core::prelude::*;
+    /// use salvo_core::conn::HttpProtocol;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let acceptor = TcpListener::new("127.0.0.1:5800").bind().await;
+    /// Server::new(acceptor).protocol(HttpProtocol::Http1);
+    /// # }
+    /// ```
+    #[inline]
+    pub fn protocol(mut self, protocol: HttpProtocol) -> Self {
+        self.builders.protocol = protocol;
+        self
+    }
+
+    /// Sets whether h2c with prior knowledge is accepted on plain connections and returns `Self`.
+    ///
+    /// It only takes effect when the protocol is [`HttpProtocol::Auto`], HTTP/1.1 is served for
+    /// connections which do not start with the HTTP/2 connection preface.
+    #[inline]
+    pub fn h2c(mut self, h2c: bool) -> Self {
+        self.builders.h2c = h2c;
+        self
+    }
+
+    /// Sets the timeout of reading the first bytes of plain connections to detect the protocol and
+    /// returns `Self`, `None` means no timeout.
+    ///
+    /// Bytes are read ahead only when h2c is enabled with [`Server::h2c`], a connection which sends
+    /// nothing until the timeout is closed. TLS connections negotiate the protocol during the
+    /// handshake, so it does not apply to them. The default is 10 seconds.
+    #[inline]
+    pub fn pre_read_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.builders.pre_read_timeout = timeout;
+        self
+    }
+
+    /// Pauses accepting new connections when `high_watermark` requests are being handled, and resumes
+    /// when they drop to `low_watermark`. Returns `Self`.
+    ///
+    /// New connections wait in the backlog of the listener while accepting is paused, which gives
+    /// natural backpressure to clients when handlers are saturated. Requests on connections which are
+    /// already accepted are still served. It is disabled by default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `low_watermark` is not less than `high_watermark`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use salvo_core::prelude::*;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let acceptor = TcpListener::new("127.0.0.1:5800").bind().await;
+    /// Server::new(acceptor).accept_backpressure(1024, 768);
+    /// # }
+    /// ```
+    #[inline]
+    pub fn accept_backpressure(mut self, high_watermark: usize, low_watermark: usize) -> Self {
+        assert!(
+            low_watermark < high_watermark,
+            "low watermark must be less than high watermark"
+        );
+        self.backpressure = Some((high_watermark, low_watermark));
+        self
+    }
+
+    /// Keeps accepting paused for at least `cooldown` each time [`accept_backpressure`] pauses it and
+    /// returns `Self`.
+    ///
+    /// Without cooldown, accepting resumes as soon as in-flight requests drop to the low watermark, so
+    /// under sustained overload the server flaps between pausing and accepting a few connections. With
+    /// a cooldown, `accept` is not called at all for the window, the backlog of the listener fills and
+    /// the system applies TCP backpressure to clients, connecting clients wait or retry their `SYN`.
+    /// Accepting resumes when the cooldown elapsed and in-flight requests are at the low watermark. It
+    /// has no effect without `accept_backpressure`.
+    ///
+    /// [`accept_backpressure`]: Server::accept_backpressure
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use salvo_core::prelude::*;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let acceptor = TcpListener::new("127.0.0.1:5800").bind().await;
+    /// Server::new(acceptor)
+    ///     .accept_backpressure(1024, 768)
+    ///     .accept_cooldown(Duration::from_secs(1));
+    /// # }
+    /// ```
+    #[inline]
+    pub fn accept_cooldown(mut self, cooldown: Duration) -> Self {
+        self.accept_cooldown = Some(cooldown);
+        self
+    }
+
+    /// Sets a callback called with the remote address when a connection is accepted and returns `Self`.
+    ///
+    /// It is called in the task of the connection before the TLS handshake, so it should not block.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use salvo_core::prelude::*;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let acceptor = TcpListener::new("127.0.0.1:5800").bind().await;
+    /// Server::new(acceptor)
+    ///     .on_connect(|remote_addr| tracing::info!(%remote_addr, "connected"))
+    ///     .on_disconnect(|remote_addr, stats| tracing::info!(%remote_addr, ?stats, "disconnected"));
+    /// # }
+    /// ```
+    #[inline]
+    pub fn on_connect<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&SocketAddr) + Send + Sync + 'static,
+    {
+        self.on_connect = Some(Arc::new(callback));
+        self
+    }
+
+    /// Sets a callback called with the remote address and the [`ConnStats`] when a connection is
+    /// closed and returns `Self`.
+    ///
+    /// Bytes are counted on TCP, Unix and TLS connections, where the bytes of TLS connections are the
+    /// decrypted ones. They are always 0 on HTTP/3 connections.
+    #[inline]
+    pub fn on_disconnect<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&SocketAddr, ConnStats) + Send + Sync + 'static,
+    {
+        self.on_disconnect = Some(Arc::new(callback));
+        self
+    }
+
     /// Get holding information of this server.
     #[inline]
     pub fn holdings(&self) -> &[Holding] {
@@ -76,6 +325,19 @@ impl<A: Acceptor + Send> Server<A> {
         }
     }
 
+    cfg_feature! {
+        #![feature = "http2"]
+        /// Sets the `SETTINGS_MAX_CONCURRENT_STREAMS` advertised on HTTP/2 connections and returns `Self`.
+        ///
+        /// Streams opened by the client beyond this limit are refused with `REFUSED_STREAM`, `None`
+        /// means no limit. The default is 200.
+        #[inline]
+        pub fn max_concurrent_streams(mut self, max: impl Into<Option<u32>>) -> Self {
+            self.builders.http2.max_concurrent_streams(max);
+            self
+        }
+    }
+
     cfg_feature! {
         #![feature = "quinn"]
         /// Use this function to set http3 protocol.
@@ -156,8 +418,20 @@ impl<A: Acceptor + Send> Server<A> {
         S: Into<Service> + Send,
         G: Future<Output = ()> + Send + 'static,
     {
-        let Self { mut acceptor, builders } = self;
+        let Self {
+            mut acceptor,
+            builders,
+            backpressure,
+            accept_cooldown,
+            on_connect,
+            on_disconnect,
+        } = self;
+        let in_flight = backpressure.map(|(high, low)| Arc::new(InFlightRequests::new(high, low)));
+        let mut paused = false;
+        // End of the cooldown of the current pause, accepting is not resumed before it.
+        let mut cooldown_end = None;
         let alive_connections = Arc::new(AtomicUsize::new(0));
+        let next_connection_id = AtomicU64::new(1);
         let notify = Arc::new(Notify::new());
         let timeout_notify = Arc::new(Notify::new());
 
@@ -181,7 +455,34 @@ impl<A: Acceptor + Send> Server<A> {
         let service = Arc::new(service.into());
         let builders = Arc::new(builders);
         loop {
+            // Created before checking the counter, so changes after the check are not missed.
+            let changed = in_flight.as_ref().map(|in_flight| in_flight.notify.notified());
+            if let Some(in_flight) = &in_flight {
+                let count = in_flight.count.load(Ordering::SeqCst);
+                if !paused && count >= in_flight.high_watermark {
+                    tracing::warn!(count, "too many in-flight requests, pause accepting connections");
+                    paused = true;
+                    cooldown_end = accept_cooldown.map(|cooldown| tokio::time::Instant::now() + cooldown);
+                } else if paused && cooldown_end.is_none() && count <= in_flight.low_watermark {
+                    tracing::info!(count, "resume accepting connections");
+                    paused = false;
+                }
+            }
             tokio::select! {
+                _ = async {
+                    match changed {
+                        Some(changed) => changed.await,
+                        None => futures_util::future::pending().await,
+                    }
+                } => {},
+                _ = async {
+                    match cooldown_end {
+                        Some(end) => tokio::time::sleep_until(end).await,
+                        None => futures_util::future::pending().await,
+                    }
+                } => {
+                    cooldown_end = None;
+                },
                 _ = &mut signal => {
                     if let Some(timeout) = timeout {
                         tracing::info!(
@@ -199,36 +500,89 @@ impl<A: Acceptor + Send> Server<A> {
                     }
                     break;
                 },
-                 accepted = acceptor.accept() => {
+                 accepted = acceptor.accept(), if !paused => {
                     match accepted {
-                        Ok(Accepted { conn, local_addr, remote_addr, http_scheme, ..}) => {
+                        Ok(Accepted { mut conn, local_addr, remote_addr, http_scheme, ..}) => {
                             let service = service.clone();
                             let alive_connections = alive_connections.clone();
                             let notify = notify.clone();
                             let timeout_notify = timeout_notify.clone();
-                            let handler = service.hyper_handler(local_addr, remote_addr, http_scheme, alt_svc_h3.clone());
+                            let alt_svc_h3 = alt_svc_h3.clone();
+                            let connection_id = ConnectionId(next_connection_id.fetch_add(1, Ordering::Relaxed));
                             let builders = builders.clone();
+                            let in_flight = in_flight.clone();
+                            let on_connect = on_connect.clone();
+                            let on_disconnect = on_disconnect.clone();
                             tokio::spawn(async move {
                                 alive_connections.fetch_add(1, Ordering::SeqCst);
-                                let conn = conn.serve(handler, builders);
+                                let connected_at = Instant::now();
+                                if let Some(on_connect) = &on_connect {
+                                    on_connect(&remote_addr);
+                                }
+                                let conn_counters = on_disconnect.as_ref().map(|_| Arc::new(ConnCounters::default()));
+                                let callback_addr = on_disconnect.as_ref().map(|_| remote_addr.clone());
+                                let handler_counters = conn_counters.clone();
+                                // Waiting for ALPN and the client certificate finishes the TLS handshake, so it is
+                                // done in the spawned task, and in the same future as serving, so a stalled
+                                // handshake doesn't block the graceful shutdown.
+                                let serve = async move {
+                                    let alpn = if service.alpn_services.is_empty() {
+                                        None
+                                    } else {
+                                        conn.alpn().await
+                                    };
+                                    let mut handler = service
+                                        .alpn_service(alpn.as_deref())
+                                        .hyper_handler(local_addr, remote_addr, http_scheme, alt_svc_h3);
+                                    handler.connection_id = Some(connection_id);
+                                    handler.in_flight = in_flight;
+                                    handler.conn_counters = handler_counters;
+                                    #[cfg(any(
+                                        feature = "native-tls",
+                                        feature = "rustls",
+                                        feature = "openssl",
+                                        feature = "acme"
+                                    ))]
+                                    {
+                                        handler.client_cert = conn.peer_certificate().await.and_then(|der| {
+                                            match crate::conn::ClientCert::from_der(der) {
+                                                Ok(cert) => Some(Arc::new(cert)),
+                                                Err(e) => {
+                                                    tracing::warn!(error = ?e, "parse client certificate failed");
+                                                    None
+                                                }
+                                            }
+                                        });
+                                    }
+                                    conn.serve(handler, builders).await
+                                };
                                 if timeout.is_some() {
                                     tokio::select! {
-                                        result = conn => {
+                                        result = serve => {
                                             if let Err(e) = result {
                                                 tracing::error!(error = ?e, "http serve connection failed");
                                             }
                                         },
                                         _ = timeout_notify.notified() => {}
                                     }
-                                } else if let Err(e) = conn.await {
+                                } else if let Err(e) = serve.await {
                                     tracing::error!(error = ?e, "http serve connection failed");
                                 }
+                                if let (Some(on_disconnect), Some(counters), Some(remote_addr)) =
+                                    (on_disconnect, conn_counters, callback_addr)
+                                {
+                                    on_disconnect(&remote_addr, counters.stats(connected_at.elapsed()));
+                                }
 
                                 if alive_connections.fetch_sub(1, Ordering::SeqCst) == 1 {
                                     notify.notify_one();
                                 }
                             });
                         },
+                        Err(e) if AcceptorShutdown::is(&e) => {
+                            tracing::info!("acceptor is shut down, stop accepting connections");
+                            break;
+                        }
                         Err(e) => {
                             tracing::error!(error = ?e, "accept connection failed");
                         }
@@ -254,6 +608,677 @@ mod tests {
     use crate::prelude::*;
     use crate::test::{ResponseExt, TestClient};
 
+    #[cfg(feature = "http2")]
+    #[tokio::test]
+    async fn test_max_concurrent_streams() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpStream;
+
+        use crate::conn::HttpProtocol;
+
+        fn frame(kind: u8, flags: u8, stream_id: u32, payload: &[u8]) -> Vec<u8> {
+            let mut frame = (payload.len() as u32).to_be_bytes()[1..].to_vec();
+            frame.extend_from_slice(&[kind, flags]);
+            frame.extend_from_slice(&stream_id.to_be_bytes());
+            frame.extend_from_slice(payload);
+            frame
+        }
+        async fn read_frame(stream: &mut TcpStream) -> (u8, u32, Vec<u8>) {
+            let mut head = [0; 9];
+            stream.read_exact(&mut head).await.unwrap();
+            let len = u32::from_be_bytes([0, head[0], head[1], head[2]]) as usize;
+            let stream_id = u32::from_be_bytes([head[5], head[6], head[7], head[8]]) & 0x7fff_ffff;
+            let mut payload = vec![0; len];
+            stream.read_exact(&mut payload).await.unwrap();
+            (head[3], stream_id, payload)
+        }
+
+        #[handler(internal)]
+        async fn slow() -> &'static str {
+            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+            "slow"
+        }
+        let acceptor = TcpListener::new("127.0.0.1:0").bind().await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+        tokio::spawn(async move {
+            Server::new(acceptor)
+                .protocol(HttpProtocol::Http2)
+                .max_concurrent_streams(2)
+                .serve(Router::new().get(slow))
+                .await;
+        });
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n").await.unwrap();
+        stream.write_all(&frame(0x4, 0, 0, &[])).await.unwrap();
+
+        // The first frame sent by server is its SETTINGS.
+        let (kind, _, payload) = read_frame(&mut stream).await;
+        assert_eq!(kind, 0x4);
+        let max_concurrent_streams = payload
+            .chunks(6)
+            .find(|setting| u16::from_be_bytes([setting[0], setting[1]]) == 0x3)
+            .map(|setting| u32::from_be_bytes([setting[2], setting[3], setting[4], setting[5]]));
+        assert_eq!(max_concurrent_streams, Some(2));
+        stream.write_all(&frame(0x4, 0x1, 0, &[])).await.unwrap();
+
+        // `:method: GET`, `:scheme: http` and `:path: /` from the static table, with END_STREAM and END_HEADERS.
+        for stream_id in [1, 3, 5] {
+            stream
+                .write_all(&frame(0x1, 0x5, stream_id, &[0x82, 0x86, 0x84]))
+                .await
+                .unwrap();
+        }
+        let refused = tokio::time::timeout(std::time::Duration::from_secs(3), async {
+            loop {
+                let (kind, stream_id, payload) = read_frame(&mut stream).await;
+                if kind == 0x3 {
+                    break (
+                        stream_id,
+                        u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]),
+                    );
+                }
+            }
+        })
+        .await
+        .unwrap();
+        // The third stream is reset with REFUSED_STREAM.
+        assert_eq!(refused, (5, 0x7));
+    }
+
+    #[tokio::test]
+    async fn test_connection_id() {
+        use http_body_util::{BodyExt, Empty};
+        use hyper::body::Bytes;
+
+        use crate::conn::ConnectionId;
+
+        #[handler(internal)]
+        async fn connection_id(depot: &mut Depot) -> String {
+            depot.obtain::<ConnectionId>().unwrap().to_string()
+        }
+        let acceptor = TcpListener::new("127.0.0.1:0").bind().await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+        tokio::spawn(async move {
+            Server::new(acceptor).serve(Router::new().get(connection_id)).await;
+        });
+
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let (mut sender, conn) = hyper::client::conn::http1::handshake(stream).await.unwrap();
+            tokio::spawn(async move {
+                let _ = conn.await;
+            });
+            let mut bodies = Vec::new();
+            for _ in 0..2 {
+                let req = hyper::Request::builder()
+                    .uri(format!("http://{addr}/"))
+                    .body(Empty::<Bytes>::new())
+                    .unwrap();
+                let res = sender.send_request(req).await.unwrap();
+                let body = res.into_body().collect().await.unwrap().to_bytes();
+                bodies.push(String::from_utf8(body.to_vec()).unwrap());
+            }
+            // Requests on the same keep-alive connection share the connection id.
+            assert_eq!(bodies[0], bodies[1]);
+            ids.push(bodies.remove(0));
+        }
+        assert_ne!(ids[0], ids[1]);
+    }
+
+    #[tokio::test]
+    async fn test_connection_callbacks() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::sync::mpsc;
+
+        use crate::ConnStats;
+
+        #[handler(internal)]
+        async fn hello() -> &'static str {
+            "hello"
+        }
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let connect_tx = tx.clone();
+        let acceptor = TcpListener::new("127.0.0.1:0").bind().await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+        tokio::spawn(async move {
+            Server::new(acceptor)
+                .on_connect(move |remote_addr| {
+                    connect_tx.send((remote_addr.to_string(), None)).unwrap();
+                })
+                .on_disconnect(move |remote_addr, stats| {
+                    tx.send((remote_addr.to_string(), Some(stats))).unwrap();
+                })
+                .serve(Router::new().get(hello))
+                .await;
+        });
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let local_addr = format!("socket://{}", stream.local_addr().unwrap());
+        let requests = concat!(
+            "GET / HTTP/1.1\r\nhost: localhost\r\n\r\n",
+            "GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n"
+        );
+        stream.write_all(requests.as_bytes()).await.unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+        assert_eq!(response.windows(5).filter(|w| w == b"hello").count(), 2);
+
+        let (connected, stats) = rx.recv().await.unwrap();
+        assert_eq!(connected, local_addr);
+        assert!(stats.is_none());
+        let (disconnected, stats) = rx.recv().await.unwrap();
+        assert_eq!(disconnected, local_addr);
+        let ConnStats {
+            duration,
+            requests: served,
+            bytes_read,
+            bytes_written,
+            ..
+        } = stats.unwrap();
+        assert_eq!(served, 2);
+        assert_eq!(bytes_read, requests.len() as u64);
+        assert_eq!(bytes_written, response.len() as u64);
+        assert!(duration > std::time::Duration::ZERO && duration < std::time::Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_connection_close_policy() {
+        use std::io::{Error as IoError, ErrorKind};
+
+        use bytes::Bytes;
+        use futures_util::stream;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        #[handler(internal)]
+        async fn broken_stream(res: &mut Response) {
+            res.set_status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.streaming(stream::iter(vec![
+                Ok(Bytes::from("partial")),
+                Err(IoError::new(ErrorKind::Other, "stream broken")),
+            ]))
+            .unwrap();
+        }
+        #[handler(internal)]
+        async fn plain_error(res: &mut Response) {
+            res.set_status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render("plain");
+        }
+        let acceptor = TcpListener::new("127.0.0.1:0").bind().await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+        tokio::spawn(async move {
+            let router = Router::new()
+                .push(Router::with_path("stream").get(broken_stream))
+                .push(Router::with_path("plain").get(plain_error));
+            Server::new(acceptor).serve(router).await;
+        });
+
+        // The request is kept alive, the response must end by closing the connection.
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /stream HTTP/1.1\r\nhost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = Vec::new();
+        tokio::time::timeout(std::time::Duration::from_secs(5), stream.read_to_end(&mut response))
+            .await
+            .expect("connection should be closed")
+            .unwrap();
+        let response = String::from_utf8_lossy(&response).to_lowercase();
+        assert!(response.starts_with("http/1.1 500"), "{response}");
+        assert!(response.contains("\r\nconnection: close\r\n"), "{response}");
+        assert!(response.contains("partial"), "{response}");
+
+        // Errors with full bodies keep the connection alive, both pipelined requests are answered.
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let requests = concat!(
+            "GET /plain HTTP/1.1\r\nhost: localhost\r\n\r\n",
+            "GET /plain HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n"
+        );
+        stream.write_all(requests.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert_eq!(response.matches("HTTP/1.1 500").count(), 2, "{response}");
+        assert_eq!(response.matches("\r\n\r\nplain").count(), 2, "{response}");
+    }
+
+    #[tokio::test]
+    async fn test_streaming_flushed() {
+        use std::sync::Mutex;
+
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::sync::mpsc;
+
+        struct Events(Mutex<Option<mpsc::UnboundedReceiver<&'static str>>>);
+        #[async_trait]
+        impl Handler for Events {
+            async fn handle(&self, _req: &mut Request, _depot: &mut Depot, res: &mut Response, _ctrl: &mut FlowCtrl) {
+                let rx = self.0.lock().unwrap().take().unwrap();
+                let events = futures_util::stream::unfold(rx, |mut rx| async move {
+                    rx.recv().await.map(|event| (Ok::<_, std::io::Error>(event), rx))
+                });
+                res.streaming_flushed(events).unwrap();
+            }
+        }
+        let (tx, rx) = mpsc::unbounded_channel();
+        let acceptor = TcpListener::new("127.0.0.1:0").nodelay(true).bind().await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+        tokio::spawn(async move {
+            Server::new(acceptor)
+                .serve(Router::new().get(Events(Mutex::new(Some(rx)))))
+                .await;
+        });
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut received = String::new();
+        // Each event must arrive before the next one is produced.
+        for event in ["data: one\n\n", "data: two\n\n"] {
+            tx.send(event).unwrap();
+            tokio::time::timeout(std::time::Duration::from_secs(5), async {
+                let mut buf = [0; 1024];
+                while !received.ends_with(&format!("{event}\r\n")) {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    assert!(n > 0, "connection closed: {received}");
+                    received.push_str(std::str::from_utf8(&buf[..n]).unwrap());
+                }
+            })
+            .await
+            .unwrap_or_else(|_| panic!("event is not flushed: {received}"));
+        }
+        assert!(received.starts_with("HTTP/1.1 200"), "{received}");
+    }
+
+    #[cfg(feature = "rustls")]
+    #[tokio::test]
+    async fn test_alpn_service() {
+        use std::sync::Arc;
+
+        use http_body_util::{BodyExt, Empty};
+        use hyper::body::Bytes;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio_rustls::rustls::{ClientConfig, ServerName};
+        use tokio_rustls::TlsConnector;
+
+        use crate::conn::rustls::{read_trust_anchor, Keycert, RustlsConfig};
+        use crate::runtimes::TokioExecutor;
+
+        #[handler(internal)]
+        async fn rest() -> &'static str {
+            "rest"
+        }
+        #[handler(internal)]
+        async fn grpc() -> &'static str {
+            "grpc"
+        }
+        let config = RustlsConfig::new(
+            Keycert::new()
+                .key_from_path("certs/key.pem")
+                .unwrap()
+                .cert_from_path("certs/cert.pem")
+                .unwrap(),
+        );
+        let acceptor = TcpListener::new("127.0.0.1:0").rustls(config).bind().await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+        let service = Service::new(Router::new().get(rest)).with_alpn_service("h2", Router::new().get(grpc));
+        tokio::spawn(async move {
+            Server::new(acceptor).serve(service).await;
+        });
+
+        let connect = |alpn: &'static [u8]| async move {
+            let mut client_config = ClientConfig::builder()
+                .with_safe_defaults()
+                .with_root_certificates(read_trust_anchor(include_bytes!("../certs/chain.pem").as_slice()).unwrap())
+                .with_no_client_auth();
+            client_config.alpn_protocols = vec![alpn.to_vec()];
+            let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            TlsConnector::from(Arc::new(client_config))
+                .connect(ServerName::try_from("testserver.com").unwrap(), stream)
+                .await
+                .unwrap()
+        };
+
+        let mut stream = connect(b"http/1.1").await;
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: testserver.com\r\nconnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut content = String::new();
+        stream.read_to_string(&mut content).await.ok();
+        assert!(
+            content.starts_with("HTTP/1.1 200") && content.ends_with("rest"),
+            "{content}"
+        );
+
+        let stream = connect(b"h2").await;
+        let (mut sender, conn) = hyper::client::conn::http2::handshake(TokioExecutor, stream)
+            .await
+            .unwrap();
+        tokio::spawn(async move {
+            let _ = conn.await;
+        });
+        let req = hyper::Request::builder()
+            .uri("https://testserver.com/")
+            .body(Empty::<Bytes>::new())
+            .unwrap();
+        let res = sender.send_request(req).await.unwrap();
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"grpc");
+    }
+
+    #[cfg(feature = "rustls")]
+    #[tokio::test]
+    async fn test_graceful_shutdown_stalled_handshake() {
+        use tokio::sync::oneshot;
+
+        use crate::conn::rustls::{Keycert, RustlsConfig};
+
+        #[handler(internal)]
+        async fn hello() -> &'static str {
+            "hello"
+        }
+        let config = RustlsConfig::new(
+            Keycert::new()
+                .key_from_path("certs/key.pem")
+                .unwrap()
+                .cert_from_path("certs/cert.pem")
+                .unwrap(),
+        );
+        let acceptor = TcpListener::new("127.0.0.1:0").rustls(config).bind().await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+        let service = Service::new(Router::new().get(hello)).with_alpn_service("h2", Router::new().get(hello));
+        let (tx, rx) = oneshot::channel();
+        let server = tokio::spawn(async move {
+            Server::new(acceptor)
+                .serve_with_graceful_shutdown(
+                    service,
+                    async {
+                        rx.await.ok();
+                    },
+                    Some(std::time::Duration::from_millis(100)),
+                )
+                .await;
+        });
+
+        // The client never starts the TLS handshake, so ALPN and the client certificate never resolve.
+        let _stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        tx.send(()).unwrap();
+        tokio::time::timeout(std::time::Duration::from_secs(5), server)
+            .await
+            .expect("server should stop after the graceful shutdown timeout")
+            .unwrap();
+    }
+
+    #[cfg(feature = "rustls")]
+    #[tokio::test]
+    async fn test_client_cert() {
+        use std::sync::Arc;
+
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio_rustls::rustls::{Certificate, ClientConfig, PrivateKey, ServerName};
+        use tokio_rustls::TlsConnector;
+
+        use crate::conn::rustls::{read_trust_anchor, Keycert, RustlsConfig};
+        use crate::conn::ClientCert;
+
+        #[handler(internal)]
+        async fn whoami(depot: &mut Depot) -> String {
+            match depot.obtain::<ClientCert>() {
+                Some(cert) => format!(
+                    "{} {} {}",
+                    cert.subject_cn.as_deref().unwrap_or_default(),
+                    cert.sans.join(","),
+                    cert.serial
+                ),
+                None => "anonymous".into(),
+            }
+        }
+        let config = RustlsConfig::new(
+            Keycert::new()
+                .key_from_path("certs/key.pem")
+                .unwrap()
+                .cert_from_path("certs/cert.pem")
+                .unwrap(),
+        )
+        .client_auth_optional_path("certs/client-ca.pem")
+        .unwrap();
+        let acceptor = TcpListener::new("127.0.0.1:0").rustls(config).bind().await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+        tokio::spawn(async move {
+            Server::new(acceptor).serve(Router::new().get(whoami)).await;
+        });
+
+        let request = |client_config: ClientConfig| async move {
+            let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let mut stream = TlsConnector::from(Arc::new(client_config))
+                .connect(ServerName::try_from("testserver.com").unwrap(), stream)
+                .await
+                .unwrap();
+            stream
+                .write_all(b"GET / HTTP/1.1\r\nhost: testserver.com\r\nconnection: close\r\n\r\n")
+                .await
+                .unwrap();
+            let mut content = String::new();
+            stream.read_to_string(&mut content).await.ok();
+            content
+        };
+        let builder = || {
+            ClientConfig::builder()
+                .with_safe_defaults()
+                .with_root_certificates(read_trust_anchor(include_bytes!("../certs/chain.pem").as_slice()).unwrap())
+        };
+
+        let certs = rustls_pemfile::certs(&mut include_bytes!("../certs/client-cert.pem").as_slice()).unwrap();
+        let key = rustls_pemfile::pkcs8_private_keys(&mut include_bytes!("../certs/client-key.pem").as_slice())
+            .unwrap()
+            .remove(0);
+        let client_config = builder()
+            .with_single_cert(certs.into_iter().map(Certificate).collect(), PrivateKey(key))
+            .unwrap();
+        let content = request(client_config).await;
+        assert!(
+            content.ends_with("client.testserver.com client.testserver.com,client@testserver.com 1234"),
+            "{content}"
+        );
+
+        let content = request(builder().with_no_client_auth()).await;
+        assert!(content.ends_with("anonymous"), "{content}");
+    }
+
+    #[cfg(feature = "openssl")]
+    #[tokio::test]
+    async fn test_openssl_client_cert() {
+        use std::pin::Pin;
+
+        use openssl::ssl::{SslConnector, SslFiletype, SslMethod};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio_openssl::SslStream;
+
+        use crate::conn::openssl::{Keycert, OpensslConfig};
+        use crate::conn::ClientCert;
+
+        #[handler(internal)]
+        async fn whoami(depot: &mut Depot) -> String {
+            match depot.obtain::<ClientCert>() {
+                Some(cert) => format!("{} {}", cert.subject_cn.as_deref().unwrap_or_default(), cert.serial),
+                None => "anonymous".into(),
+            }
+        }
+        let config = OpensslConfig::new(
+            Keycert::new()
+                .key_from_path("certs/key.pem")
+                .unwrap()
+                .cert_from_path("certs/cert.pem")
+                .unwrap(),
+        )
+        .with_client_auth_required_path("certs/client-ca.pem")
+        .unwrap();
+        let acceptor = TcpListener::new("127.0.0.1:0").openssl(config).bind().await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+        tokio::spawn(async move {
+            Server::new(acceptor).serve(Router::new().get(whoami)).await;
+        });
+
+        let request = |with_cert: bool| async move {
+            let mut connector = SslConnector::builder(SslMethod::tls()).unwrap();
+            connector.set_ca_file("certs/chain.pem").unwrap();
+            if with_cert {
+                connector
+                    .set_certificate_file("certs/client-cert.pem", SslFiletype::PEM)
+                    .unwrap();
+                connector
+                    .set_private_key_file("certs/client-key.pem", SslFiletype::PEM)
+                    .unwrap();
+            }
+            let ssl = connector
+                .build()
+                .configure()
+                .unwrap()
+                .into_ssl("testserver.com")
+                .unwrap();
+            let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let mut stream = SslStream::new(ssl, stream).unwrap();
+            let mut content = String::new();
+            // With TLS 1.3, the server may reject the client certificate after the client finished
+            // the handshake, so the failure can only be seen when reading.
+            if Pin::new(&mut stream).connect().await.is_ok()
+                && stream
+                    .write_all(b"GET / HTTP/1.1\r\nhost: testserver.com\r\nconnection: close\r\n\r\n")
+                    .await
+                    .is_ok()
+            {
+                stream.read_to_string(&mut content).await.ok();
+            }
+            content
+        };
+
+        let content = request(true).await;
+        assert!(content.ends_with("client.testserver.com 1234"), "{content}");
+
+        let content = request(false).await;
+        assert!(content.is_empty(), "{content}");
+    }
+
+    /// Handler which signals `entered` and waits for a permit before it answers, so tests control how
+    /// long requests are in flight.
+    struct Blocking {
+        entered: tokio::sync::mpsc::UnboundedSender<()>,
+        permits: std::sync::Arc<tokio::sync::Semaphore>,
+    }
+    impl Blocking {
+        fn new() -> (
+            Self,
+            tokio::sync::mpsc::UnboundedReceiver<()>,
+            std::sync::Arc<tokio::sync::Semaphore>,
+        ) {
+            let (entered_tx, entered_rx) = tokio::sync::mpsc::unbounded_channel();
+            let permits = std::sync::Arc::new(tokio::sync::Semaphore::new(0));
+            let handler = Blocking {
+                entered: entered_tx,
+                permits: permits.clone(),
+            };
+            (handler, entered_rx, permits)
+        }
+    }
+    #[async_trait]
+    impl Handler for Blocking {
+        async fn handle(&self, _req: &mut Request, _depot: &mut Depot, res: &mut Response, _ctrl: &mut FlowCtrl) {
+            self.entered.send(()).ok();
+            self.permits.acquire().await.unwrap().forget();
+            res.render("done");
+        }
+    }
+
+    /// Send `GET /` to `addr` on a new connection and read the raw response.
+    async fn get_root(addr: std::net::SocketAddr) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut content = String::new();
+        stream.read_to_string(&mut content).await.unwrap();
+        content
+    }
+
+    #[tokio::test]
+    async fn test_accept_backpressure() {
+        let (handler, mut entered_rx, permits) = Blocking::new();
+        let acceptor = TcpListener::new("127.0.0.1:0").bind().await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+        tokio::spawn(async move {
+            Server::new(acceptor)
+                .accept_backpressure(1, 0)
+                .serve(Router::new().get(handler))
+                .await;
+        });
+
+        let first = tokio::spawn(get_root(addr));
+        entered_rx.recv().await.unwrap();
+
+        // The handler is saturated, the second connection waits in the backlog.
+        let second = tokio::spawn(get_root(addr));
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(200), entered_rx.recv())
+                .await
+                .is_err()
+        );
+
+        permits.add_permits(2);
+        assert!(first.await.unwrap().ends_with("done"));
+        entered_rx.recv().await.unwrap();
+        assert!(second.await.unwrap().ends_with("done"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_accept_cooldown() {
+        use std::time::Duration;
+
+        let (handler, mut entered_rx, permits) = Blocking::new();
+        // The clock is paused and only advanced when the runtime is idle, so the windows below don't depend
+        // on the load of the machine.
+        let cooldown = Duration::from_millis(600);
+        let acceptor = TcpListener::new("127.0.0.1:0").bind().await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+        tokio::spawn(async move {
+            Server::new(acceptor)
+                .accept_backpressure(1, 0)
+                .accept_cooldown(cooldown)
+                .serve(Router::new().get(handler))
+                .await;
+        });
+
+        let first = tokio::spawn(get_root(addr));
+        entered_rx.recv().await.unwrap();
+        let paused_at = tokio::time::Instant::now();
+
+        // The first request is done at once, but accepting stays paused until the cooldown elapsed.
+        let second = tokio::spawn(get_root(addr));
+        permits.add_permits(1);
+        assert!(first.await.unwrap().ends_with("done"));
+        assert!(tokio::time::timeout(Duration::from_millis(200), entered_rx.recv())
+            .await
+            .is_err());
+        entered_rx.recv().await.unwrap();
+        assert!(paused_at.elapsed() >= cooldown);
+        permits.add_permits(1);
+        assert!(second.await.unwrap().ends_with("done"));
+
+        // The server recovers after the cooldown.
+        tokio::time::sleep(cooldown).await;
+        let third = tokio::spawn(get_root(addr));
+        entered_rx.recv().await.unwrap();
+        permits.add_permits(1);
+        assert!(third.await.unwrap().ends_with("done"));
+    }
+
     #[tokio::test]
     async fn test_server() {
         #[handler(internal)]
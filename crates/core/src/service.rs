@@ -1,25 +1,121 @@
 use std::future::Future;
+#[cfg(feature = "anyhow")]
+use std::panic::AssertUnwindSafe;
 use std::pin::Pin;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
+#[cfg(feature = "anyhow")]
+use futures_util::FutureExt;
+
 use headers::HeaderValue;
-use http::header::{ALT_SVC, CONTENT_TYPE};
+use http::header::{ALT_SVC, AUTHORIZATION, CONNECTION, CONTENT_LENGTH, CONTENT_TYPE, COOKIE};
 use http::uri::Scheme;
 use hyper::service::Service as HyperService;
 use hyper::{Method, Request as HyperRequest, Response as HyperResponse};
+use tracing::Instrument;
 
 use crate::catcher::{write_error_default, Catcher};
-use crate::conn::SocketAddr;
+#[cfg(any(feature = "native-tls", feature = "rustls", feature = "openssl", feature = "acme"))]
+use crate::conn::ClientCert;
+use crate::conn::{ConnectionId, SocketAddr};
+#[cfg(feature = "anyhow")]
+use crate::error::UNHANDLED_ERROR_KEY;
 use crate::http::body::{ReqBody, ResBody};
-use crate::http::{Mime, Request, Response, StatusCode};
+use crate::http::{Mime, Request, Response, StatusCode, StatusError, Version};
 use crate::routing::{FlowCtrl, PathState, Router};
+use crate::server::{ConnCounters, InFlightRequests};
 use crate::Depot;
 
+/// Policy used to force `Connection: close` on some responses.
+///
+/// When a handler returns an error after it started to stream the body, the keep-alive connection
+/// may be left in an ambiguous state. By default, every server error (5xx) with a streamed body
+/// closes the connection.
+#[derive(Clone, Debug)]
+pub struct ConnectionClosePolicy {
+    status_codes: Option<Vec<StatusCode>>,
+    streamed_only: bool,
+}
+impl Default for ConnectionClosePolicy {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl ConnectionClosePolicy {
+    /// Create a new `ConnectionClosePolicy` which closes on server errors with streamed bodies.
+    #[inline]
+    pub fn new() -> Self {
+        ConnectionClosePolicy {
+            status_codes: None,
+            streamed_only: true,
+        }
+    }
+
+    /// Create a new `ConnectionClosePolicy` which never forces the connection to close.
+    #[inline]
+    pub fn never() -> Self {
+        ConnectionClosePolicy {
+            status_codes: Some(vec![]),
+            streamed_only: true,
+        }
+    }
+
+    /// Sets status codes which force the connection to close and returns `Self`.
+    ///
+    /// If not set, all server errors (5xx) are used.
+    #[inline]
+    pub fn with_status_codes(mut self, status_codes: impl Into<Vec<StatusCode>>) -> Self {
+        self.status_codes = Some(status_codes.into());
+        self
+    }
+
+    /// Sets whether only responses with streamed body force the connection to close.
+    ///
+    /// Default is `true`.
+    #[inline]
+    pub fn with_streamed_only(mut self, streamed_only: bool) -> Self {
+        self.streamed_only = streamed_only;
+        self
+    }
+
+    /// Check whether the connection should be closed after writing `res`.
+    ///
+    /// It is always `false` for `101 Switching Protocols`, the connection is handed off to the
+    /// upgraded protocol and its `Connection: upgrade` header must be kept.
+    #[inline]
+    pub fn should_close(&self, res: &Response) -> bool {
+        let status = match res.status_code() {
+            Some(StatusCode::SWITCHING_PROTOCOLS) | None => return false,
+            Some(status) => status,
+        };
+        if self.streamed_only && !res.body().is_stream() {
+            return false;
+        }
+        match &self.status_codes {
+            Some(status_codes) => status_codes.contains(&status),
+            None => status.is_server_error(),
+        }
+    }
+}
+
+type ResponseHook = Arc<dyn Fn(&mut Response) + Send + Sync + 'static>;
+#[cfg(feature = "anyhow")]
+type ErrorRenderer = Arc<dyn Fn(&Request, &anyhow::Error) -> Response + Send + Sync + 'static>;
+
 /// Service http request.
 pub struct Service {
     pub(crate) router: Arc<Router>,
     pub(crate) catcher: Option<Arc<Catcher>>,
     pub(crate) allowed_media_types: Arc<Vec<Mime>>,
+    pub(crate) connection_close_policy: Arc<ConnectionClosePolicy>,
+    pub(crate) response_hook: Option<ResponseHook>,
+    #[cfg(feature = "anyhow")]
+    pub(crate) error_renderer: Option<ErrorRenderer>,
+    pub(crate) trace_enabled: bool,
+    pub(crate) max_response_size: Option<u64>,
+    pub(crate) alpn_services: Vec<(Vec<u8>, Service)>,
 }
 
 impl Service {
@@ -33,9 +129,40 @@ impl Service {
             router: router.into(),
             catcher: None,
             allowed_media_types: Arc::new(vec![]),
+            connection_close_policy: Arc::new(ConnectionClosePolicy::default()),
+            response_hook: None,
+            #[cfg(feature = "anyhow")]
+            error_renderer: None,
+            trace_enabled: false,
+            max_response_size: None,
+            alpn_services: vec![],
         }
     }
 
+    /// Serve connections which negotiated `protocol` by ALPN with `service` instead of this one,
+    /// and returns `Self`.
+    ///
+    /// This allows different services on the same TLS port, for example `h2` for a gRPC service
+    /// and `http/1.1` for a REST service. Connections without ALPN or with other protocols are
+    /// served by this service.
+    #[inline]
+    pub fn with_alpn_service(mut self, protocol: impl Into<Vec<u8>>, service: impl Into<Service>) -> Self {
+        self.alpn_services.push((protocol.into(), service.into()));
+        self
+    }
+
+    /// Get the service for connections which negotiated `alpn`.
+    #[inline]
+    pub fn alpn_service(&self, alpn: Option<&[u8]>) -> &Service {
+        alpn.and_then(|alpn| {
+            self.alpn_services
+                .iter()
+                .find(|(protocol, _)| protocol == alpn)
+                .map(|(_, service)| service)
+        })
+        .unwrap_or(self)
+    }
+
     /// Get router in this `Service`.
     #[inline]
     pub fn router(&self) -> Arc<Router> {
@@ -103,6 +230,135 @@ impl Service {
         self.allowed_media_types.clone()
     }
 
+    /// Sets the policy used to force `Connection: close` and returns `Self` for write code chained.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use salvo_core::prelude::*;
+    /// # use salvo_core::ConnectionClosePolicy;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let policy = ConnectionClosePolicy::new().with_status_codes(vec![StatusCode::BAD_GATEWAY]);
+    /// let service = Service::new(Router::new()).with_connection_close_policy(policy);
+    /// # }
+    /// ```
+    #[inline]
+    pub fn with_connection_close_policy(mut self, policy: impl Into<Arc<ConnectionClosePolicy>>) -> Self {
+        self.connection_close_policy = policy.into();
+        self
+    }
+
+    /// Get the policy used to force `Connection: close`.
+    #[inline]
+    pub fn connection_close_policy(&self) -> Arc<ConnectionClosePolicy> {
+        self.connection_close_policy.clone()
+    }
+
+    /// Sets a hook which is called with every response just before it is sent, after all handlers,
+    /// catchers and checks, including error responses like `404`. Returns `Self` for write code chained.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use salvo_core::prelude::*;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let service = Service::new(Router::new()).with_response_hook(|res: &mut Response| {
+    ///     res.headers_mut().remove("x-debug-info");
+    /// });
+    /// # }
+    /// ```
+    #[inline]
+    pub fn with_response_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&mut Response) + Send + Sync + 'static,
+    {
+        self.response_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Sets a renderer of unhandled errors and returns `Self` for write code chained.
+    ///
+    /// The renderer is called with the `anyhow::Error` returned by a handler, or built from a panic
+    /// of a handler, and its response replaces the `500 Internal Server Error` response. Panics of
+    /// handlers are only caught when a renderer is set. If the returned response has no status code,
+    /// `500 Internal Server Error` is used.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use salvo_core::prelude::*;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let service = Service::new(Router::new()).with_error_renderer(|_req: &Request, err: &anyhow::Error| {
+    ///     let mut res = Response::new();
+    ///     res.set_status_code(StatusCode::INTERNAL_SERVER_ERROR);
+    ///     res.render(Text::Plain(format!("oops: {err}")));
+    ///     res
+    /// });
+    /// # }
+    /// ```
+    #[cfg(feature = "anyhow")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "anyhow")))]
+    #[inline]
+    pub fn with_error_renderer<F>(mut self, renderer: F) -> Self
+    where
+        F: Fn(&Request, &anyhow::Error) -> Response + Send + Sync + 'static,
+    {
+        self.error_renderer = Some(Arc::new(renderer));
+        self
+    }
+
+    /// Sets whether `TRACE` requests are supported and returns `Self` for write code chained.
+    ///
+    /// `TRACE` can be abused for cross-site tracing (XST), so it is rejected with `501 Not Implemented`
+    /// by default. When it is enabled, the request line and headers are echoed back as `message/http`,
+    /// except `Authorization` and `Cookie` headers, without routing the request.
+    #[inline]
+    pub fn with_trace_enabled(mut self, trace_enabled: bool) -> Self {
+        self.trace_enabled = trace_enabled;
+        self
+    }
+
+    /// Get whether `TRACE` requests are supported.
+    #[inline]
+    pub fn trace_enabled(&self) -> bool {
+        self.trace_enabled
+    }
+
+    /// Sets the maximum size of buffered response bodies and returns `Self` for write code chained,
+    /// `None` means no limit.
+    ///
+    /// This is a safety net against handlers which accidentally buffer huge bodies. A larger body is
+    /// dropped and logged, and `500 Internal Server Error` is responded instead, it is rendered by the
+    /// catcher. Streamed bodies are not checked, since their size is unknown before they are sent.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use salvo_core::prelude::*;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let service = Service::new(Router::new()).with_max_response_size(64 * 1024 * 1024);
+    /// # }
+    /// ```
+    #[inline]
+    pub fn with_max_response_size(mut self, max_response_size: impl Into<Option<u64>>) -> Self {
+        self.max_response_size = max_response_size.into();
+        self
+    }
+
+    /// Get the maximum size of buffered response bodies.
+    #[inline]
+    pub fn max_response_size(&self) -> Option<u64> {
+        self.max_response_size
+    }
+
     #[doc(hidden)]
     #[inline]
     pub fn hyper_handler(
@@ -119,7 +375,18 @@ impl Service {
             router: self.router.clone(),
             catcher: self.catcher.clone(),
             allowed_media_types: self.allowed_media_types.clone(),
+            connection_close_policy: self.connection_close_policy.clone(),
+            response_hook: self.response_hook.clone(),
+            #[cfg(feature = "anyhow")]
+            error_renderer: self.error_renderer.clone(),
+            trace_enabled: self.trace_enabled,
+            max_response_size: self.max_response_size,
             alt_svc_h3,
+            connection_id: None,
+            in_flight: None,
+            conn_counters: None,
+            #[cfg(any(feature = "native-tls", feature = "rustls", feature = "openssl", feature = "acme"))]
+            client_cert: None,
         }
     }
     /// Handle new request, this function only used for test.
@@ -152,7 +419,18 @@ pub struct HyperHandler {
     pub(crate) router: Arc<Router>,
     pub(crate) catcher: Option<Arc<Catcher>>,
     pub(crate) allowed_media_types: Arc<Vec<Mime>>,
+    pub(crate) connection_close_policy: Arc<ConnectionClosePolicy>,
+    pub(crate) response_hook: Option<ResponseHook>,
+    #[cfg(feature = "anyhow")]
+    pub(crate) error_renderer: Option<ErrorRenderer>,
+    pub(crate) trace_enabled: bool,
+    pub(crate) max_response_size: Option<u64>,
     pub(crate) alt_svc_h3: Option<HeaderValue>,
+    pub(crate) connection_id: Option<ConnectionId>,
+    pub(crate) in_flight: Option<Arc<InFlightRequests>>,
+    pub(crate) conn_counters: Option<Arc<ConnCounters>>,
+    #[cfg(any(feature = "native-tls", feature = "rustls", feature = "openssl", feature = "acme"))]
+    pub(crate) client_cert: Option<Arc<ClientCert>>,
 }
 impl HyperHandler {
     /// Handle [`Request`] and returns [`Response`].
@@ -160,6 +438,10 @@ impl HyperHandler {
     pub fn handle(&self, mut req: Request) -> impl Future<Output = Response> {
         let catcher = self.catcher.clone();
         let allowed_media_types = self.allowed_media_types.clone();
+        let connection_close_policy = self.connection_close_policy.clone();
+        let response_hook = self.response_hook.clone();
+        #[cfg(feature = "anyhow")]
+        let error_renderer = self.error_renderer.clone();
         req.local_addr = self.local_addr.clone();
         req.remote_addr = self.remote_addr.clone();
         #[cfg(not(feature = "cookie"))]
@@ -172,14 +454,53 @@ impl HyperHandler {
             }
         }
         let mut depot = Depot::new();
+        if let Some(connection_id) = self.connection_id {
+            depot.inject(connection_id);
+        }
+        #[cfg(any(feature = "native-tls", feature = "rustls", feature = "openssl", feature = "acme"))]
+        if let Some(client_cert) = &self.client_cert {
+            depot.inject(ClientCert::clone(client_cert));
+        }
         let mut path_state = PathState::new(req.uri().path());
         let router = self.router.clone();
+        let trace_enabled = self.trace_enabled;
+        let max_response_size = self.max_response_size;
 
         async move {
-            if let Some(dm) = router.detect(&mut req, &mut path_state) {
+            if *req.method() == Method::TRACE {
+                if trace_enabled {
+                    echo_trace(&req, &mut res);
+                } else {
+                    res.set_status_code(StatusCode::NOT_IMPLEMENTED);
+                }
+            } else if let Some(dm) = router.detect(&mut req, &mut path_state) {
                 req.params = path_state.params;
+                req.raw_params = path_state.raw_params;
+                req.route_name = dm.name.clone();
                 let mut ctrl = FlowCtrl::new([&dm.hoops[..], &[dm.handler]].concat());
-                ctrl.call_next(&mut req, &mut depot, &mut res).await;
+                let call = async {
+                    match dm.name {
+                        Some(name) => {
+                            ctrl.call_next(&mut req, &mut depot, &mut res)
+                                .instrument(tracing::info_span!("route", name = %name))
+                                .await
+                        }
+                        None => ctrl.call_next(&mut req, &mut depot, &mut res).await,
+                    }
+                };
+                #[cfg(feature = "anyhow")]
+                if error_renderer.is_some() {
+                    if let Err(payload) = AssertUnwindSafe(call).catch_unwind().await {
+                        let e = anyhow::anyhow!("panic occurred: {}", panic_message(&*payload));
+                        tracing::error!(error = ?e, "panic occurred");
+                        res.set_status_code(StatusCode::INTERNAL_SERVER_ERROR);
+                        depot.insert(UNHANDLED_ERROR_KEY, e);
+                    }
+                } else {
+                    call.await;
+                }
+                #[cfg(not(feature = "anyhow"))]
+                call.await;
                 if res.status_code().is_none() {
                     res.set_status_code(StatusCode::OK);
                 }
@@ -187,6 +508,35 @@ impl HyperHandler {
                 res.set_status_code(StatusCode::NOT_FOUND);
             }
 
+            #[cfg(feature = "anyhow")]
+            if let Some(renderer) = &error_renderer {
+                if let Some(e) = depot.remove::<anyhow::Error>(UNHANDLED_ERROR_KEY) {
+                    let mut rendered = renderer(&req, &e);
+                    res.take_body();
+                    res.headers_mut().remove(CONTENT_TYPE);
+                    res.headers_mut().remove(CONTENT_LENGTH);
+                    res.set_status_code(rendered.status_code().unwrap_or(StatusCode::INTERNAL_SERVER_ERROR));
+                    res.headers_mut().extend(std::mem::take(rendered.headers_mut()));
+                    res.set_body(rendered.take_body());
+                }
+            }
+
+            if let Some(max_response_size) = max_response_size {
+                if let Some(size) = res.body.size().filter(|size| *size > max_response_size) {
+                    tracing::error!(
+                        uri = ?req.uri(),
+                        method = req.method().as_str(),
+                        size,
+                        max_response_size,
+                        "response body exceeds the maximum size"
+                    );
+                    res.take_body();
+                    res.headers_mut().remove(CONTENT_TYPE);
+                    res.headers_mut().remove(CONTENT_LENGTH);
+                    res.set_status_error(StatusError::internal_server_error());
+                }
+            }
+
             let status = res.status_code().unwrap();
             let has_error = status.is_client_error() || status.is_server_error();
             if let Some(value) = res.headers().get(CONTENT_TYPE) {
@@ -228,6 +578,13 @@ impl HyperHandler {
                     write_error_default(&req, &mut res, None);
                 }
             }
+            // `Connection` header is only meaningful for http/1.x.
+            if req.version() <= Version::HTTP_11 && connection_close_policy.should_close(&res) {
+                res.headers_mut().insert(CONNECTION, HeaderValue::from_static("close"));
+            }
+            if let Some(hook) = response_hook {
+                hook(&mut res);
+            }
             #[cfg(debug_assertions)]
             if let hyper::Method::HEAD = *req.method() {
                 if !res.body.is_none() {
@@ -239,6 +596,36 @@ impl HyperHandler {
     }
 }
 
+/// Get the message of a panic payload.
+#[cfg(feature = "anyhow")]
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
+
+/// Echo the request line and headers of a `TRACE` request, credentials are not echoed.
+fn echo_trace(req: &Request, res: &mut Response) {
+    let mut message = format!("{} {} {:?}\r\n", req.method(), req.uri(), req.version());
+    for (name, value) in req.headers() {
+        if name == AUTHORIZATION || name == COOKIE {
+            continue;
+        }
+        message.push_str(name.as_str());
+        message.push_str(": ");
+        message.push_str(&String::from_utf8_lossy(value.as_bytes()));
+        message.push_str("\r\n");
+    }
+    res.headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("message/http"));
+    res.set_status_code(StatusCode::OK);
+    res.write_body(message).ok();
+}
+
 impl<B> HyperService<HyperRequest<B>> for HyperHandler
 where
     B: Into<ReqBody>,
@@ -272,8 +659,13 @@ where
             }
         }
         let request = Request::from_hyper(req, scheme);
+        let in_flight = self.in_flight.clone().map(InFlightRequests::enter);
+        if let Some(conn_counters) = &self.conn_counters {
+            conn_counters.requests.fetch_add(1, Ordering::Relaxed);
+        }
         let response = self.handle(request);
         let fut = async move {
+            let _in_flight = in_flight;
             let mut hyper_response = hyper::Response::new(ResBody::None);
             response.await.write_back(&mut hyper_response).await;
             Ok(hyper_response)
@@ -344,4 +736,200 @@ mod tests {
         let content = access(&service, "3").await;
         assert_eq!(content, "before1before2before3");
     }
+
+    #[tokio::test]
+    async fn test_connection_close_on_streamed_error() {
+        use std::io::{Error as IoError, ErrorKind};
+
+        use bytes::Bytes;
+        use futures_util::stream;
+
+        use crate::http::header::CONNECTION;
+        use crate::service::ConnectionClosePolicy;
+
+        #[handler(internal)]
+        async fn broken_stream(res: &mut Response) {
+            res.set_status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.streaming(stream::iter(vec![
+                Ok(Bytes::from("partial")),
+                Err(IoError::new(ErrorKind::Other, "stream broken")),
+            ]))
+            .unwrap();
+        }
+        #[handler(internal)]
+        async fn plain_error(res: &mut Response) {
+            res.set_status_code(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        let router = Router::new()
+            .push(Router::with_path("stream").get(broken_stream))
+            .push(Router::with_path("plain").get(plain_error));
+        let service = Service::new(router);
+
+        let res = TestClient::get("http://127.0.0.1:5801/stream").send(&service).await;
+        assert_eq!(res.headers().get(CONNECTION).unwrap(), "close");
+        let res = TestClient::get("http://127.0.0.1:5801/plain").send(&service).await;
+        assert!(res.headers().get(CONNECTION).is_none());
+
+        let service = Service::new(Router::with_path("stream").get(broken_stream))
+            .with_connection_close_policy(ConnectionClosePolicy::never());
+        let res = TestClient::get("http://127.0.0.1:5801/stream").send(&service).await;
+        assert!(res.headers().get(CONNECTION).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_response_hook() {
+        use crate::http::header::HeaderValue;
+
+        #[handler(internal)]
+        async fn hello(res: &mut Response) {
+            res.headers_mut().insert("x-secret", HeaderValue::from_static("1"));
+            res.render("hello");
+        }
+        let service = Service::new(Router::with_path("hello").get(hello)).with_response_hook(|res: &mut Response| {
+            res.headers_mut().remove("x-secret");
+            res.headers_mut().insert("x-hooked", HeaderValue::from_static("1"));
+        });
+
+        let res = TestClient::get("http://127.0.0.1:5801/hello").send(&service).await;
+        assert_eq!(res.status_code().unwrap(), StatusCode::OK);
+        assert!(res.headers().get("x-secret").is_none());
+        assert_eq!(res.headers().get("x-hooked").unwrap(), "1");
+
+        let res = TestClient::get("http://127.0.0.1:5801/not_exist").send(&service).await;
+        assert_eq!(res.status_code().unwrap(), StatusCode::NOT_FOUND);
+        assert_eq!(res.headers().get("x-hooked").unwrap(), "1");
+    }
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn test_max_response_size() {
+        use bytes::Bytes;
+        use futures_util::stream;
+
+        #[handler(internal)]
+        async fn small() -> &'static str {
+            "small"
+        }
+        #[handler(internal)]
+        async fn huge(res: &mut Response) {
+            res.render("x".repeat(1024));
+        }
+        #[handler(internal)]
+        async fn streamed(res: &mut Response) {
+            let chunks = vec![Ok::<_, std::io::Error>(Bytes::from("x".repeat(1024)))];
+            res.streaming(stream::iter(chunks)).unwrap();
+        }
+        let router = Router::new()
+            .push(Router::with_path("small").get(small))
+            .push(Router::with_path("huge").get(huge))
+            .push(Router::with_path("streamed").get(streamed));
+        let service = Service::new(router).with_max_response_size(512);
+        assert_eq!(service.max_response_size(), Some(512));
+
+        let mut res = TestClient::get("http://127.0.0.1:5801/small").send(&service).await;
+        assert_eq!(res.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(res.take_string().await.unwrap(), "small");
+
+        let mut res = TestClient::get("http://127.0.0.1:5801/huge").send(&service).await;
+        assert_eq!(res.status_code().unwrap(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = res.take_string().await.unwrap();
+        assert!(body.contains("Internal Server Error") && !body.contains("xxxx"));
+        assert!(logs_contain("response body exceeds the maximum size"));
+
+        let mut res = TestClient::get("http://127.0.0.1:5801/streamed").send(&service).await;
+        assert_eq!(res.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(res.take_string().await.unwrap().len(), 1024);
+    }
+
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn test_route_name() {
+        #[handler(internal)]
+        async fn show_user(req: &mut Request) -> String {
+            tracing::info!("show user");
+            req.route_name().unwrap_or_default().to_owned()
+        }
+        let router = Router::with_path("users/<id>").get(show_user).name("get_user");
+
+        let content = TestClient::get("http://127.0.0.1:5801/users/1")
+            .send(router)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_eq!(content, "get_user");
+        assert!(logs_contain("name=get_user"));
+    }
+
+    #[tokio::test]
+    async fn test_trace_method() {
+        #[handler(internal)]
+        async fn hello() -> &'static str {
+            "hello"
+        }
+        let router = Router::with_path("hello").handle(hello);
+
+        let res = TestClient::trace("http://127.0.0.1:5801/hello")
+            .send(&Service::new(router))
+            .await;
+        assert_eq!(res.status_code().unwrap(), StatusCode::NOT_IMPLEMENTED);
+
+        let router = Router::with_path("hello").handle(hello);
+        let mut res = TestClient::trace("http://127.0.0.1:5801/hello")
+            .add_header("x-trace", "1", true)
+            .add_header("cookie", "secret=1", true)
+            .send(&Service::new(router).with_trace_enabled(true))
+            .await;
+        assert_eq!(res.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(res.headers().get("content-type").unwrap(), "message/http");
+        let content = res.take_string().await.unwrap();
+        assert!(content.starts_with("TRACE http://127.0.0.1:5801/hello HTTP/1.1\r\n"));
+        assert!(content.contains("x-trace: 1\r\n"));
+        assert!(!content.contains("secret"));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "anyhow")]
+    async fn test_error_renderer() {
+        #[handler(internal)]
+        async fn failed() -> Result<&'static str, anyhow::Error> {
+            Err(anyhow::anyhow!("database is down"))
+        }
+        #[handler(internal)]
+        async fn panicked() -> &'static str {
+            panic!("unexpected state")
+        }
+        let router = Router::new()
+            .push(Router::with_path("failed").get(failed))
+            .push(Router::with_path("panicked").get(panicked));
+        let service = Service::new(router).with_error_renderer(|req: &Request, err: &anyhow::Error| {
+            let mut res = Response::new();
+            res.set_status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            res.render(Json(serde_json::json!({
+                "error": err.to_string(),
+                "request_id": req.header::<String>("x-request-id"),
+            })));
+            res
+        });
+
+        let mut res = TestClient::get("http://127.0.0.1:5801/failed")
+            .add_header("x-request-id", "req-1", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code().unwrap(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            res.take_string().await.unwrap(),
+            r#"{"error":"database is down","request_id":"req-1"}"#
+        );
+
+        let mut res = TestClient::get("http://127.0.0.1:5801/panicked")
+            .add_header("x-request-id", "req-2", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code().unwrap(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            res.take_string().await.unwrap(),
+            r#"{"error":"panic occurred: unexpected state","request_id":"req-2"}"#
+        );
+    }
 }
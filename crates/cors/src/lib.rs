@@ -74,6 +74,9 @@ impl CorsBuilder {
         }
     }
     /// Sets whether to add the `Access-Control-Allow-Credentials` header.
+    ///
+    /// Browsers reject credentialed responses with `Access-Control-Allow-Origin: *`, so the request
+    /// `Origin` is echoed instead of `*` when any origin is allowed.
     #[inline]
     pub fn allow_credentials(mut self, allow: bool) -> Self {
         self.credentials = allow;
@@ -197,6 +200,8 @@ impl CorsBuilder {
 
     /// Sets that *any* `Origin` header is allowed.
     ///
+    /// `Access-Control-Allow-Origin: *` is sent unless credentials are allowed.
+    ///
     /// # Warning
     ///
     /// This can allow websites you didn't intend to access this resource,
@@ -331,6 +336,10 @@ enum Validated {
 }
 
 /// Cors
+///
+/// Preflight `OPTIONS` requests are answered by `Cors` with `204 No Content` and never reach the
+/// handlers, routes only see actual requests. `Cors` only runs for matched routes, so the router it
+/// is a hoop of must accept `OPTIONS`, like one with [`Router::handle`](salvo_core::Router::handle).
 #[derive(Debug)]
 pub struct Cors {
     credentials: bool,
@@ -416,6 +425,18 @@ impl Cors {
         }
     }
 
+    /// Insert `Access-Control-Allow-Origin`, the origin is echoed with `Vary: Origin` unless any origin
+    /// is allowed without credentials.
+    #[inline]
+    fn insert_allow_origin(&self, origin: HeaderValue, headers: &mut HeaderMap) {
+        if self.origins.is_none() && !self.credentials {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+        } else {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+            headers.append(header::VARY, HeaderValue::from_static("origin"));
+        }
+    }
+
     #[inline]
     fn append_preflight_headers(&self, headers: &mut HeaderMap) {
         self.append_common_headers(headers);
@@ -449,13 +470,15 @@ impl Handler for Cors {
 
         match validated {
             Ok(Validated::Preflight(origin)) => {
+                // Preflight requests are answered here, they never reach the handlers.
                 self.append_preflight_headers(res.headers_mut());
-                res.headers_mut().insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
-                ctrl.call_next(req, depot, res).await;
+                self.insert_allow_origin(origin, res.headers_mut());
+                res.set_status_code(StatusCode::NO_CONTENT);
+                ctrl.skip_rest();
             }
             Ok(Validated::Simple(origin)) => {
                 self.append_common_headers(res.headers_mut());
-                res.headers_mut().insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+                self.insert_allow_origin(origin, res.headers_mut());
                 ctrl.call_next(req, depot, res).await;
             }
             Err(e) => {
@@ -587,4 +610,86 @@ mod tests {
             .unwrap();
         assert!(content.contains("Forbidden"));
     }
+
+    #[tokio::test]
+    async fn test_cors_preflight_short_circuit() {
+        #[handler]
+        async fn asset() -> &'static str {
+            "asset"
+        }
+        let cors_handler = Cors::builder()
+            .allow_origins(["https://salvo.rs", "https://www.salvo.rs"])
+            .allow_methods(vec!["GET", "HEAD"])
+            .allow_headers(vec!["range"])
+            .allow_credentials(true)
+            .max_age(600)
+            .build();
+        let router = Router::new()
+            .hoop(cors_handler)
+            .push(Router::with_path("fonts/<**path>").handle(asset));
+        let service = Service::new(router);
+
+        let mut res = TestClient::options("http://127.0.0.1:5801/fonts/app.woff2")
+            .add_header("origin", "https://www.salvo.rs", true)
+            .add_header("access-control-request-method", "GET", true)
+            .add_header("access-control-request-headers", "range", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code().unwrap(), StatusCode::NO_CONTENT);
+        let headers = res.headers();
+        assert_eq!(
+            headers.get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            "https://www.salvo.rs"
+        );
+        assert_eq!(headers.get(ACCESS_CONTROL_ALLOW_CREDENTIALS).unwrap(), "true");
+        assert_eq!(headers.get(ACCESS_CONTROL_ALLOW_HEADERS).unwrap(), "range");
+        assert_eq!(headers.get(ACCESS_CONTROL_MAX_AGE).unwrap(), "600");
+        assert_eq!(headers.get(VARY).unwrap(), "origin");
+        let methods = headers.get(ACCESS_CONTROL_ALLOW_METHODS).unwrap().to_str().unwrap();
+        assert!(methods.contains("GET") && methods.contains("HEAD"));
+        assert!(res.take_string().await.unwrap().is_empty());
+
+        let mut res = TestClient::get("http://127.0.0.1:5801/fonts/app.woff2")
+            .add_header("origin", "https://salvo.rs", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            "https://salvo.rs"
+        );
+        assert!(res.headers().get(ACCESS_CONTROL_ALLOW_METHODS).is_none());
+        assert_eq!(res.take_string().await.unwrap(), "asset");
+    }
+
+    #[tokio::test]
+    async fn test_cors_any_origin() {
+        #[handler]
+        async fn asset() -> &'static str {
+            "asset"
+        }
+        for (credentials, allow_origin) in [(false, "*"), (true, "https://salvo.rs")] {
+            let cors_handler = Cors::builder()
+                .allow_any_origin()
+                .allow_methods(vec!["GET"])
+                .allow_credentials(credentials)
+                .build();
+            let router = Router::new().hoop(cors_handler).handle(asset);
+            let service = Service::new(router);
+
+            let res = TestClient::options("http://127.0.0.1:5801/")
+                .add_header("origin", "https://salvo.rs", true)
+                .add_header("access-control-request-method", "GET", true)
+                .send(&service)
+                .await;
+            assert_eq!(res.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(), allow_origin);
+
+            let res = TestClient::get("http://127.0.0.1:5801/")
+                .add_header("origin", "https://salvo.rs", true)
+                .send(&service)
+                .await;
+            assert_eq!(res.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(), allow_origin);
+            assert_eq!(res.headers().get(VARY).is_some(), credentials);
+        }
+    }
 }
@@ -0,0 +1,316 @@
+//! Audit body middleware, records request and response bodies to a sink.
+//!
+//! Only textual bodies, such as JSON, XML, plain text and url encoded forms, which are not larger than
+//! the size cap are recorded. A request body is only buffered if its size is known, and a response
+//! body is only recorded if it is already in memory, so streaming bodies are passed through untouched.
+//! Multipart bodies are never recorded, so fields and files in them don't need to be redacted.
+//!
+//! # Example
+//!
+//! ```
+//! use salvo_core::prelude::*;
+//! use salvo_extra::audit_body::{AuditBody, AuditRecord};
+//!
+//! #[handler]
+//! async fn login() -> &'static str {
+//!     "ok"
+//! }
+//!
+//! let audit = AuditBody::new(|record: AuditRecord| println!("{record:?}")).redact(|value| {
+//!     if let Some(password) = value.get_mut("password") {
+//!         *password = "***".into();
+//!     }
+//! });
+//! let router = Router::with_path("login").hoop(audit).post(login);
+//! ```
+use salvo_core::http::uri::Uri;
+use salvo_core::http::{mime, Body, Method, Mime, ReqBody, ResBody, StatusCode};
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler, Request, Response};
+use serde_json::Value;
+
+/// Default size cap of recorded bodies.
+pub const DEFAULT_MAX_SIZE: usize = 64 * 1024;
+
+/// Request and response bodies of a request recorded by [`AuditBody`].
+#[derive(Clone, Debug)]
+pub struct AuditRecord {
+    /// Request method.
+    pub method: Method,
+    /// Request uri.
+    pub uri: Uri,
+    /// Response status code, if it is not set, it is `404 Not Found` for an empty body and `200 OK`
+    /// otherwise.
+    pub status: StatusCode,
+    /// Request body, `None` if it is empty, binary, too large or of unknown size.
+    pub request_body: Option<String>,
+    /// Response body, `None` if it is empty, binary, too large or streamed.
+    pub response_body: Option<String>,
+}
+
+/// Sink which receives [`AuditRecord`]s.
+#[async_trait]
+pub trait AuditSink: Send + Sync + 'static {
+    /// Record a request.
+    async fn record(&self, record: AuditRecord);
+}
+#[async_trait]
+impl<F> AuditSink for F
+where
+    F: Fn(AuditRecord) + Send + Sync + 'static,
+{
+    async fn record(&self, record: AuditRecord) {
+        (self)(record)
+    }
+}
+
+/// Middleware which records request and response bodies, see [module level docs](self).
+pub struct AuditBody<S> {
+    sink: S,
+    redact: Option<Box<dyn Fn(&mut Value) + Send + Sync>>,
+    max_size: usize,
+}
+impl<S: AuditSink> AuditBody<S> {
+    /// Create new `AuditBody` which records to `sink`.
+    #[inline]
+    pub fn new(sink: S) -> Self {
+        AuditBody {
+            sink,
+            redact: None,
+            max_size: DEFAULT_MAX_SIZE,
+        }
+    }
+
+    /// Sets redaction hook and returns `Self`.
+    ///
+    /// The hook is called with each JSON body before it is recorded, it is used to mask sensitive
+    /// fields. A url encoded form is passed to the hook as a JSON object, which maps each field name
+    /// to its value, or to an array of its values if the field is repeated. The bodies handled by
+    /// handlers and sent to clients are not changed.
+    #[inline]
+    pub fn redact(mut self, redact: impl Fn(&mut Value) + Send + Sync + 'static) -> Self {
+        self.redact = Some(Box::new(redact));
+        self
+    }
+
+    /// Sets size cap of recorded bodies and returns `Self`, larger bodies are not recorded.
+    ///
+    /// The default is 64KiB.
+    #[inline]
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    fn render(&self, data: &[u8], content_type: Option<Mime>) -> Option<String> {
+        let content_type = content_type?;
+        if data.is_empty() || data.len() > self.max_size || !is_textual(&content_type) {
+            return None;
+        }
+        if is_json(&content_type) {
+            if let Ok(mut value) = serde_json::from_slice::<Value>(data) {
+                if let Some(redact) = &self.redact {
+                    redact(&mut value);
+                }
+                return Some(value.to_string());
+            }
+        }
+        if content_type.subtype() == mime::WWW_FORM_URLENCODED {
+            if let Some(redact) = &self.redact {
+                let mut value = form_to_value(data);
+                redact(&mut value);
+                return Some(value_to_form(&value));
+            }
+        }
+        std::str::from_utf8(data).ok().map(ToOwned::to_owned)
+    }
+}
+
+#[async_trait]
+impl<S: AuditSink> Handler for AuditBody<S> {
+    async fn handle(&self, req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+        let content_type = req.content_type();
+        let buffered = content_type.as_ref().map(is_textual).unwrap_or(false)
+            && req
+                .body()
+                .size_hint()
+                .upper()
+                .map(|upper| upper <= self.max_size as u64)
+                .unwrap_or(false);
+        let request_body = if buffered {
+            match req.payload_with_max_size(self.max_size).await {
+                Ok(payload) => {
+                    let payload = payload.clone();
+                    // The body is put back, so handlers can read it in any way.
+                    req.replace_body(ReqBody::Once(payload.clone()));
+                    self.render(&payload, content_type)
+                }
+                Err(e) => {
+                    tracing::warn!(error = ?e, "audit body read request body failed");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        ctrl.call_next(req, depot, res).await;
+
+        let response_body = match res.body() {
+            ResBody::Once(bytes) => self.render(bytes, res.content_type()),
+            ResBody::Chunks(chunks) if chunks.iter().map(|chunk| chunk.len()).sum::<usize>() <= self.max_size => {
+                let data = chunks.iter().flat_map(|chunk| chunk.iter().copied()).collect::<Vec<u8>>();
+                self.render(&data, res.content_type())
+            }
+            _ => None,
+        };
+        let status = match res.status_code() {
+            Some(code) => code,
+            None if res.body().is_none() => StatusCode::NOT_FOUND,
+            None => StatusCode::OK,
+        };
+        self.sink
+            .record(AuditRecord {
+                method: req.method().clone(),
+                uri: req.uri().clone(),
+                status,
+                request_body,
+                response_body,
+            })
+            .await;
+    }
+}
+
+fn form_to_value(data: &[u8]) -> Value {
+    let mut fields = serde_json::Map::new();
+    for (name, value) in form_urlencoded::parse(data) {
+        let value = Value::String(value.into_owned());
+        match fields.get_mut(name.as_ref()) {
+            Some(Value::Array(values)) => values.push(value),
+            Some(first) => *first = Value::Array(vec![first.take(), value]),
+            None => {
+                fields.insert(name.into_owned(), value);
+            }
+        }
+    }
+    Value::Object(fields)
+}
+
+fn value_to_form(value: &Value) -> String {
+    let mut serializer = form_urlencoded::Serializer::new(String::new());
+    if let Value::Object(fields) = value {
+        for (name, value) in fields {
+            let values = match value {
+                Value::Array(values) => values.iter().collect(),
+                value => vec![value],
+            };
+            for value in values {
+                match value {
+                    Value::String(value) => serializer.append_pair(name, value),
+                    value => serializer.append_pair(name, &value.to_string()),
+                };
+            }
+        }
+    }
+    serializer.finish()
+}
+
+#[inline]
+fn is_json(mime: &Mime) -> bool {
+    mime.subtype() == mime::JSON || mime.suffix() == Some(mime::JSON)
+}
+
+#[inline]
+fn is_textual(mime: &Mime) -> bool {
+    mime.type_() == mime::TEXT
+        || is_json(mime)
+        || mime.subtype() == mime::XML
+        || mime.suffix() == Some(mime::XML)
+        || mime.subtype() == mime::WWW_FORM_URLENCODED
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_audit_body() {
+        #[handler]
+        async fn login(req: &mut Request, res: &mut Response) {
+            let body = req.parse_json::<Value>().await.unwrap();
+            res.render(Json(json!({"user": body["user"], "token": "abc"})));
+        }
+
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let sink = {
+            let records = records.clone();
+            move |record: AuditRecord| records.lock().unwrap().push(record)
+        };
+        let audit = AuditBody::new(sink).redact(|value| {
+            for field in ["password", "token"] {
+                if let Some(value) = value.get_mut(field) {
+                    *value = "***".into();
+                }
+            }
+        });
+        let router = Router::with_path("login").hoop(audit).post(login);
+
+        let content = TestClient::post("http://127.0.0.1:5801/login")
+            .json(&json!({"user": "alice", "password": "secret"}))
+            .send(router)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_eq!(content, r#"{"token":"abc","user":"alice"}"#);
+
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].method, Method::POST);
+        assert_eq!(records[0].status, StatusCode::OK);
+        assert_eq!(records[0].request_body.as_deref(), Some(r#"{"password":"***","user":"alice"}"#));
+        assert_eq!(records[0].response_body.as_deref(), Some(r#"{"token":"***","user":"alice"}"#));
+    }
+
+    #[tokio::test]
+    async fn test_audit_body_form() {
+        #[handler]
+        async fn login(req: &mut Request) -> String {
+            req.form::<String>("password").await.unwrap()
+        }
+
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let sink = {
+            let records = records.clone();
+            move |record: AuditRecord| records.lock().unwrap().push(record)
+        };
+        let audit = AuditBody::new(sink).redact(|value| {
+            if let Some(value) = value.get_mut("password") {
+                *value = "***".into();
+            }
+        });
+        let router = Router::with_path("login").hoop(audit).post(login);
+
+        let content = TestClient::post("http://127.0.0.1:5801/login")
+            .raw_form("user=alice&password=s%26cret&role=a&role=b")
+            .send(router)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_eq!(content, "s&cret");
+
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(
+            records[0].request_body.as_deref(),
+            Some("password=***&role=a&role=b&user=alice")
+        );
+        assert_eq!(records[0].response_body.as_deref(), Some("s&cret"));
+    }
+}
@@ -1,4 +1,6 @@
 //! basic auth middleware
+use std::future::Future;
+
 use salvo_core::http::header::{HeaderName, PROXY_AUTHORIZATION, AUTHORIZATION};
 use salvo_core::http::{Request, Response, StatusCode};
 use salvo_core::{async_trait, Depot, Error, FlowCtrl, Handler};
@@ -14,6 +16,29 @@ pub trait BasicAuthValidator: Send + Sync {
     #[must_use = "validate future must be used"]
     async fn validate(&self, username: &str, password: &str, depot: &mut Depot) -> bool;
 }
+
+/// Async closures `(username, password) -> bool` are validators, so simple checks don't need a type.
+///
+/// # Example
+///
+/// ```
+/// use salvo_extra::basic_auth::BasicAuth;
+///
+/// let auth = BasicAuth::new(|username: String, password: String| async move {
+///     username == "root" && password == "pwd"
+/// })
+/// .with_realm("static files");
+/// ```
+#[async_trait]
+impl<F, Fut> BasicAuthValidator for F
+where
+    F: Fn(String, String) -> Fut + Send + Sync,
+    Fut: Future<Output = bool> + Send,
+{
+    async fn validate(&self, username: &str, password: &str, _depot: &mut Depot) -> bool {
+        self(username.to_owned(), password.to_owned()).await
+    }
+}
 /// BasicAuthDepotExt
 pub trait BasicAuthDepotExt {
     /// Get basic auth username reference.
@@ -47,6 +72,19 @@ where
         }
     }
 
+    /// Sets the realm sent in `WWW-Authenticate` challenges and returns `Self`, it is `realm` by default.
+    #[inline]
+    pub fn with_realm(mut self, realm: impl Into<String>) -> Self {
+        self.realm = realm.into();
+        self
+    }
+
+    /// Get the realm sent in `WWW-Authenticate` challenges.
+    #[inline]
+    pub fn realm(&self) -> &str {
+        &self.realm
+    }
+
     #[doc(hidden)]
     #[inline]
     pub fn set_header_names(mut self, header_names: impl Into<Vec<HeaderName>>) -> Self {
@@ -177,4 +215,37 @@ mod tests {
             .unwrap();
         assert!(content.contains("Unauthorized"));
     }
+
+    #[tokio::test]
+    async fn test_basic_auth_closure() {
+        #[handler]
+        async fn whoami(depot: &mut Depot) -> String {
+            depot.basic_auth_username().cloned().unwrap_or_default()
+        }
+        let auth_handler =
+            BasicAuth::new(|username: String, password: String| async move { username == "root" && password == "pwd" })
+                .with_realm("static files");
+        let router = Router::with_hoop(auth_handler).handle(whoami);
+        let service = Service::new(router);
+
+        let mut response = TestClient::get("http://127.0.0.1:5800/")
+            .basic_auth("root", Some("pwd"))
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code(), Some(StatusCode::OK));
+        assert_eq!(response.take_string().await.unwrap(), "root");
+
+        let invalid = TestClient::get("http://127.0.0.1:5800/")
+            .basic_auth("root", Some("pwd2"))
+            .send(&service)
+            .await;
+        let missing = TestClient::get("http://127.0.0.1:5800/").send(&service).await;
+        for response in [invalid, missing] {
+            assert_eq!(response.status_code(), Some(StatusCode::UNAUTHORIZED));
+            assert_eq!(
+                response.headers().get("www-authenticate").unwrap(),
+                "Basic realm=\"static files\""
+            );
+        }
+    }
 }
@@ -1,6 +1,8 @@
 //! Compress the body of a response.
+use std::fmt::{self, Formatter};
 use std::io::{Cursor, Error as IoError, ErrorKind};
 use std::str::FromStr;
+use std::sync::Arc;
 
 use async_compression::tokio::bufread::{BrotliEncoder, DeflateEncoder, GzipEncoder};
 use bytes::BytesMut;
@@ -8,9 +10,12 @@ use tokio::io::AsyncReadExt;
 use tokio_stream::{self, StreamExt};
 use tokio_util::io::{ReaderStream, StreamReader};
 
-use salvo_core::http::header::{HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE};
-use salvo_core::http::ResBody;
-use salvo_core::{async_trait, Depot, Handler, Request, Response, FlowCtrl};
+use salvo_core::http::header::{
+    HeaderValue, ACCEPT_ENCODING, ACCEPT_RANGES, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE,
+    USER_AGENT, VARY,
+};
+use salvo_core::http::{ResBody, StatusCode};
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler, Request, Response};
 
 /// CompressionAlgo
 #[derive(Eq, PartialEq, Clone, Copy, Debug)]
@@ -49,12 +54,33 @@ impl From<CompressionAlgo> for HeaderValue {
 }
 
 /// Compression
-#[derive(Clone, Debug)]
+///
+/// Responses whose content type is in the allowlist are compressed with the algorithm preferred by
+/// `Accept-Encoding`, algorithms with `q=0` are never used. They get `Vary: Accept-Encoding` whether
+/// they are compressed or not, because the response depends on it.
+///
+/// Partial responses are never compressed: a range of a body compressed on the fly is meaningless,
+/// so `206 Partial Content` is served as is and `Accept-Ranges: bytes` is advertised. A ranged request
+/// answered with the whole body is compressed as usual.
+#[derive(Clone)]
 pub struct Compression {
     algos: Vec<CompressionAlgo>,
     content_types: Vec<String>,
     min_length: usize,
     force_priority: bool,
+    disable_for: Option<Arc<dyn Fn(&str) -> bool + Send + Sync>>,
+}
+
+impl fmt::Debug for Compression {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Compression")
+            .field("algos", &self.algos)
+            .field("content_types", &self.content_types)
+            .field("min_length", &self.min_length)
+            .field("force_priority", &self.force_priority)
+            .finish()
+    }
 }
 
 impl Default for Compression {
@@ -75,6 +101,7 @@ impl Default for Compression {
             ],
             min_length: 1024,
             force_priority: false,
+            disable_for: None,
         }
     }
 }
@@ -117,6 +144,25 @@ impl Compression {
         self
     }
 
+    /// Disable compression for clients whose `User-Agent` matches `predicate`, like `gzip_disable`
+    /// of nginx. Requests without `User-Agent` are checked with an empty string.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use salvo_extra::compression::Compression;
+    ///
+    /// let compression = Compression::new().disable_for(|user_agent| user_agent.contains("MSIE 6"));
+    /// ```
+    #[inline]
+    pub fn disable_for<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.disable_for = Some(Arc::new(predicate));
+        self
+    }
+
     /// Get content type list reference.
     #[inline]
     pub fn content_types(&self) -> &Vec<String> {
@@ -147,6 +193,18 @@ impl Compression {
     }
 }
 
+fn has_vary_accept_encoding(res: &Response) -> bool {
+    res.headers()
+        .get_all(VARY)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|v| {
+            let v = v.trim();
+            v == "*" || v.eq_ignore_ascii_case("accept-encoding")
+        })
+}
+
 fn parse_accept_encoding(header: &str) -> Vec<(CompressionAlgo, u8)> {
     let mut vec = header
         .split(',')
@@ -161,7 +219,8 @@ fn parse_accept_encoding(header: &str) -> Vec<(CompressionAlgo, u8)> {
                         .and_then(|q| q.parse::<f32>().map(|f| (f * 100.0) as u8).ok())
                 })
                 .unwrap_or(100u8);
-            Some((algo, q))
+            // `q=0` means not acceptable.
+            (q > 0).then_some((algo, q))
         })
         .collect::<Vec<(CompressionAlgo, u8)>>();
 
@@ -207,6 +266,27 @@ impl Handler for Compression {
         {
             return;
         }
+        if !has_vary_accept_encoding(res) {
+            res.headers_mut()
+                .append(VARY, HeaderValue::from_static("accept-encoding"));
+        }
+        if let Some(disable_for) = &self.disable_for {
+            let user_agent = req
+                .headers()
+                .get(USER_AGENT)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default();
+            if disable_for(user_agent) {
+                return;
+            }
+        }
+        if res.status_code() == Some(StatusCode::PARTIAL_CONTENT) || res.headers().contains_key(CONTENT_RANGE) {
+            if !res.headers().contains_key(ACCEPT_RANGES) {
+                res.headers_mut()
+                    .insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+            }
+            return;
+        }
 
         let algo = if let Some(algo) = req
             .headers()
@@ -260,8 +340,35 @@ impl Handler for Compression {
                     }
                 }
             }
-            ResBody::Stream(stream) => {
-                let stream = stream.map(|item| item.map_err(|_| ErrorKind::Other));
+            ResBody::Stream(mut stream) => {
+                // The size of streamed body is unknown, buffer up to `min_length` to decide whether
+                // compression is worthwhile.
+                let mut head = Vec::new();
+                let mut head_len = 0;
+                while head_len < self.min_length {
+                    match stream.next().await {
+                        Some(Ok(chunk)) => {
+                            head_len += chunk.len();
+                            head.push(Ok(chunk));
+                        }
+                        Some(Err(e)) => {
+                            head.push(Err(e));
+                            res.set_body(ResBody::Stream(Box::pin(tokio_stream::iter(head).chain(stream))));
+                            return;
+                        }
+                        None => {
+                            let mut bytes = BytesMut::with_capacity(head_len);
+                            for chunk in head.into_iter().flatten() {
+                                bytes.extend_from_slice(&chunk);
+                            }
+                            res.set_body(ResBody::Once(bytes.freeze()));
+                            return;
+                        }
+                    }
+                }
+                let stream = tokio_stream::iter(head)
+                    .chain(stream)
+                    .map(|item| item.map_err(|_| ErrorKind::Other));
                 let reader = StreamReader::new(stream);
                 match algo {
                     CompressionAlgo::Gzip => {
@@ -293,7 +400,7 @@ impl Handler for Compression {
 
 #[cfg(test)]
 mod tests {
-    use salvo_core::http::header::{ACCEPT_ENCODING, CONTENT_ENCODING};
+    use salvo_core::http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, RANGE};
     use salvo_core::prelude::*;
     use salvo_core::test::{ResponseExt, TestClient};
 
@@ -318,6 +425,155 @@ mod tests {
         assert_eq!(content, "hello");
     }
 
+    #[tokio::test]
+    async fn test_stream_min_length() {
+        #[handler]
+        async fn small(res: &mut Response) {
+            res.headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+            let chunks = vec![Ok::<_, IoError>("hello"), Ok(" world")];
+            res.streaming(tokio_stream::iter(chunks)).unwrap();
+        }
+        #[handler]
+        async fn large(res: &mut Response) {
+            res.headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+            let chunks = (0..100).map(|_| Ok::<_, IoError>("0123456789")).collect::<Vec<_>>();
+            res.streaming(tokio_stream::iter(chunks)).unwrap();
+        }
+        let comp_handler = Compression::new().with_min_length(128);
+        let router = Router::with_hoop(comp_handler)
+            .push(Router::with_path("small").get(small))
+            .push(Router::with_path("large").get(large));
+        let service = Service::new(router);
+
+        let mut res = TestClient::get("http://127.0.0.1:5801/small")
+            .add_header(ACCEPT_ENCODING, "gzip", true)
+            .send(&service)
+            .await;
+        assert!(res.headers().get(CONTENT_ENCODING).is_none());
+        assert_eq!(res.take_string().await.unwrap(), "hello world");
+
+        let mut res = TestClient::get("http://127.0.0.1:5801/large")
+            .add_header(ACCEPT_ENCODING, "gzip", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.headers().get(CONTENT_ENCODING).unwrap(), "gzip");
+        assert_eq!(res.take_string().await.unwrap(), "0123456789".repeat(100));
+    }
+
+    #[tokio::test]
+    async fn test_range_not_compressed() {
+        let path = std::env::temp_dir().join(format!("salvo_compression_range_{}.txt", std::process::id()));
+        std::fs::write(&path, "0123456789".repeat(256)).unwrap();
+        #[handler]
+        async fn file(req: &mut Request, res: &mut Response) {
+            res.send_file(std::env::temp_dir().join(format!("salvo_compression_range_{}.txt", std::process::id())), req)
+                .await;
+        }
+        let comp_handler = Compression::new().with_min_length(1);
+        let router = Router::with_hoop(comp_handler).push(Router::with_path("file").get(file));
+
+        let mut res = TestClient::get("http://127.0.0.1:5801/file")
+            .add_header(ACCEPT_ENCODING, "gzip", true)
+            .add_header(RANGE, "bytes=10-19", true)
+            .send(router)
+            .await;
+        assert_eq!(res.status_code(), Some(StatusCode::PARTIAL_CONTENT));
+        assert!(res.headers().get(CONTENT_ENCODING).is_none());
+        assert_eq!(res.headers().get(ACCEPT_RANGES).unwrap(), "bytes");
+        assert_eq!(res.take_string().await.unwrap(), "0123456789");
+        std::fs::remove_file(path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_range_ignored_compressed() {
+        #[handler]
+        async fn text() -> String {
+            "0123456789".repeat(100)
+        }
+        let comp_handler = Compression::new().with_min_length(1);
+        let router = Router::with_hoop(comp_handler).push(Router::with_path("text").get(text));
+
+        let mut res = TestClient::get("http://127.0.0.1:5801/text")
+            .add_header(ACCEPT_ENCODING, "gzip", true)
+            .add_header(RANGE, "bytes=10-19", true)
+            .send(router)
+            .await;
+        assert_eq!(res.status_code(), Some(StatusCode::OK));
+        assert_eq!(res.headers().get(CONTENT_ENCODING).unwrap(), "gzip");
+        assert!(res.headers().get(ACCEPT_RANGES).is_none());
+        assert_eq!(res.take_string().await.unwrap(), "0123456789".repeat(100));
+    }
+
+    #[tokio::test]
+    async fn test_disable_for() {
+        #[handler]
+        async fn text() -> String {
+            "hello".repeat(100)
+        }
+        let comp_handler = Compression::new()
+            .with_min_length(1)
+            .disable_for(|user_agent| user_agent.contains("MSIE 6"));
+        let router = Router::with_hoop(comp_handler).push(Router::with_path("text").get(text));
+        let service = Service::new(router);
+
+        let mut res = TestClient::get("http://127.0.0.1:5801/text")
+            .add_header(ACCEPT_ENCODING, "gzip", true)
+            .add_header(USER_AGENT, "Mozilla/4.0 (compatible; MSIE 6.0; Windows NT 5.1)", true)
+            .send(&service)
+            .await;
+        assert!(res.headers().get(CONTENT_ENCODING).is_none());
+        assert_eq!(res.take_string().await.unwrap(), "hello".repeat(100));
+
+        let res = TestClient::get("http://127.0.0.1:5801/text")
+            .add_header(ACCEPT_ENCODING, "gzip", true)
+            .add_header(USER_AGENT, "Mozilla/5.0", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.headers().get(CONTENT_ENCODING).unwrap(), "gzip");
+    }
+
+    #[tokio::test]
+    async fn test_json_min_length() {
+        #[handler]
+        async fn large(res: &mut Response) {
+            let items = (0..200).map(|i| format!(r#"{{"id":{i}}}"#)).collect::<Vec<_>>();
+            res.render(Text::Json(format!("[{}]", items.join(","))));
+        }
+        #[handler]
+        async fn tiny(res: &mut Response) {
+            res.render(Text::Json(r#"{"id":1}"#));
+        }
+        let router = Router::with_hoop(Compression::new())
+            .push(Router::with_path("large").get(large))
+            .push(Router::with_path("tiny").get(tiny));
+        let service = Service::new(router);
+
+        let mut res = TestClient::get("http://127.0.0.1:5801/large")
+            .add_header(ACCEPT_ENCODING, "br;q=0, gzip;q=0.8, deflate;q=0.5", true)
+            .send(&service)
+            .await;
+        assert_eq!(res.headers().get(CONTENT_ENCODING).unwrap(), "gzip");
+        assert_eq!(res.headers().get(VARY).unwrap(), "accept-encoding");
+        let content = res.take_string().await.unwrap();
+        assert!(content.starts_with(r#"[{"id":0},{"id":1}"#));
+
+        let mut res = TestClient::get("http://127.0.0.1:5801/tiny")
+            .add_header(ACCEPT_ENCODING, "gzip", true)
+            .send(&service)
+            .await;
+        assert!(res.headers().get(CONTENT_ENCODING).is_none());
+        assert_eq!(res.headers().get(VARY).unwrap(), "accept-encoding");
+        assert_eq!(res.take_string().await.unwrap(), r#"{"id":1}"#);
+
+        let res = TestClient::get("http://127.0.0.1:5801/large")
+            .add_header(ACCEPT_ENCODING, "gzip;q=0", true)
+            .send(&service)
+            .await;
+        assert!(res.headers().get(CONTENT_ENCODING).is_none());
+    }
+
     #[tokio::test]
     async fn test_brotli() {
         let comp_handler = Compression::new().with_min_length(1);
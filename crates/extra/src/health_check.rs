@@ -0,0 +1,147 @@
+//! Health check handler for load balancers and orchestrators.
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use salvo_core::http::header::{HeaderValue, CONTENT_TYPE};
+use salvo_core::http::{Request, Response, StatusCode, StatusError};
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler};
+
+type ReadinessCheck = dyn Fn() -> Pin<Box<dyn Future<Output = bool> + Send>> + Send + Sync;
+
+/// Health check handler, it responds with `200 OK` and a plain text body, `OK` by default, and
+/// skips the rest handlers.
+///
+/// A readiness check is an async closure which is called for each request, `503 Service Unavailable`
+/// is sent if it returns `false`. Without it, the response is written from the configured body as is,
+/// nothing is allocated per request.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::atomic::{AtomicBool, Ordering};
+/// use std::sync::Arc;
+///
+/// use salvo_core::prelude::*;
+/// use salvo_extra::health_check::HealthCheck;
+///
+/// let ready = Arc::new(AtomicBool::new(false));
+/// let router = Router::new()
+///     .push(Router::with_path("livez").get(HealthCheck::new()))
+///     .push(Router::with_path("readyz").get(HealthCheck::readiness(move || {
+///         let ready = ready.clone();
+///         async move { ready.load(Ordering::Relaxed) }
+///     })));
+/// ```
+#[derive(Clone)]
+pub struct HealthCheck {
+    body: Bytes,
+    check: Option<Arc<ReadinessCheck>>,
+}
+impl Default for HealthCheck {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl HealthCheck {
+    /// Create a new `HealthCheck` for liveness, it always responds with `200 OK`.
+    #[inline]
+    pub fn new() -> Self {
+        HealthCheck {
+            body: Bytes::from_static(b"OK"),
+            check: None,
+        }
+    }
+
+    /// Create a new `HealthCheck` for readiness, it responds with `200 OK` if `check` returns `true`,
+    /// otherwise with `503 Service Unavailable`.
+    #[inline]
+    pub fn readiness<F, Fut>(check: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = bool> + Send + 'static,
+    {
+        HealthCheck {
+            body: Bytes::from_static(b"OK"),
+            check: Some(Arc::new(move || Box::pin(check()))),
+        }
+    }
+
+    /// Sets the body of `200 OK` responses and returns `Self`.
+    #[inline]
+    pub fn with_body(mut self, body: impl Into<Bytes>) -> Self {
+        self.body = body.into();
+        self
+    }
+}
+
+#[async_trait]
+impl Handler for HealthCheck {
+    async fn handle(&self, _req: &mut Request, _depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+        ctrl.skip_rest();
+        if let Some(check) = &self.check {
+            if !check().await {
+                res.set_status_error(StatusError::service_unavailable());
+                return;
+            }
+        }
+        res.set_status_code(StatusCode::OK);
+        res.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("text/plain; charset=utf-8"));
+        res.write_body(self.body.clone()).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_health_check_liveness() {
+        #[handler]
+        async fn never() {
+            panic!("other handlers should not be called");
+        }
+
+        let router = Router::new()
+            .push(Router::with_path("livez").hoop(HealthCheck::new()).goal(never))
+            .push(Router::with_path("healthz").get(HealthCheck::new().with_body("alive")));
+        let service = Service::new(router);
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/livez").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.take_string().await.unwrap(), "OK");
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/healthz").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.take_string().await.unwrap(), "alive");
+    }
+
+    #[tokio::test]
+    async fn test_health_check_readiness() {
+        let ready = Arc::new(AtomicBool::new(false));
+        let router = Router::with_path("readyz").get(HealthCheck::readiness({
+            let ready = ready.clone();
+            move || {
+                let ready = ready.clone();
+                async move { ready.load(Ordering::SeqCst) }
+            }
+        }));
+        let service = Service::new(router);
+
+        let response = TestClient::get("http://127.0.0.1:5801/readyz").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::SERVICE_UNAVAILABLE);
+
+        ready.store(true, Ordering::SeqCst);
+        let mut response = TestClient::get("http://127.0.0.1:5801/readyz").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.take_string().await.unwrap(), "OK");
+    }
+}
@@ -20,11 +20,21 @@ cfg_feature! {
     pub mod affix;
 }
 
+cfg_feature! {
+    #![feature = "audit-body"]
+    pub mod audit_body;
+}
+
 cfg_feature! {
     #![feature = "force-https"]
     pub mod force_https;
 }
 
+cfg_feature! {
+    #![feature = "health-check"]
+    pub mod health_check;
+}
+
 cfg_feature! {
     #![feature = "jwt-auth"]
     pub mod jwt_auth;
@@ -3,31 +3,230 @@ use std::time::Instant;
 
 use tracing::{Instrument, Level};
 
+use salvo_core::http::header::{
+    Entry, HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_LENGTH, COOKIE, PROXY_AUTHORIZATION, SET_COOKIE,
+    USER_AGENT,
+};
 use salvo_core::http::{Request, Response, StatusCode};
 use salvo_core::{async_trait, Depot, FlowCtrl, Handler};
 
+/// Detail of the response log written by [`Logger`].
+///
+/// It is also a handler which overrides the verbosity of the routes it is added to as a hoop, so a
+/// single `Logger` can log routes differently.
+///
+/// # Example
+///
+/// ```
+/// use salvo_core::prelude::*;
+/// use salvo_extra::logging::{LogVerbosity, Logger};
+///
+/// #[handler]
+/// async fn health() -> &'static str {
+///     "ok"
+/// }
+/// #[handler]
+/// async fn webhook() -> &'static str {
+///     "received"
+/// }
+///
+/// let router = Router::new()
+///     .hoop(Logger::new())
+///     .push(Router::with_path("health").hoop(LogVerbosity::Skip).get(health))
+///     .push(Router::with_path("webhook").hoop(LogVerbosity::Verbose).post(webhook));
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LogVerbosity {
+    /// Nothing is logged.
+    Skip,
+    /// Only the status code is logged.
+    Minimal,
+    /// The status code and the duration are logged.
+    #[default]
+    Normal,
+    /// The status code, the duration and the headers of the request and the response are logged,
+    /// values of headers set with [`Logger::with_redacted_headers`] are redacted.
+    Verbose,
+}
+
+#[async_trait]
+impl Handler for LogVerbosity {
+    #[inline]
+    async fn handle(&self, _req: &mut Request, depot: &mut Depot, _res: &mut Response, _ctrl: &mut FlowCtrl) {
+        depot.inject(*self);
+    }
+}
+
+/// Call the tracing macro `$mac` at `$level`, the levels of tracing macros must be constant.
+macro_rules! at_level {
+    ($mac:ident, $level:expr, $($arg:tt)+) => {
+        if $level == Level::ERROR {
+            tracing::$mac!(Level::ERROR, $($arg)+)
+        } else if $level == Level::WARN {
+            tracing::$mac!(Level::WARN, $($arg)+)
+        } else if $level == Level::INFO {
+            tracing::$mac!(Level::INFO, $($arg)+)
+        } else if $level == Level::DEBUG {
+            tracing::$mac!(Level::DEBUG, $($arg)+)
+        } else {
+            tracing::$mac!(Level::TRACE, $($arg)+)
+        }
+    };
+}
+
 /// A simple logger middleware.
-#[derive(Default, Debug)]
-pub struct Logger {}
+///
+/// Each request is logged in a `Request` span with the method and the path, and a `Response` event
+/// with the status code, the size of the response body if it is known, and the duration of the
+/// inner handlers. The event is emitted after the response is produced, handlers which set an error
+/// status are logged with it. Events are emitted with the target `salvo_extra::logging`, set the
+/// level with [`Logger::with_level`] and a target of the logger with [`Logger::with_target`] to filter
+/// or route them.
+///
+/// # Example
+///
+/// ```
+/// use salvo_core::prelude::*;
+/// use salvo_extra::logging::Logger;
+/// use tracing::Level;
+///
+/// let logger = Logger::new()
+///     .with_level(Level::DEBUG)
+///     .with_target("access")
+///     .with_user_agent(true)
+///     .with_request_id_header("x-request-id");
+/// let router = Router::new().hoop(logger);
+/// ```
+#[derive(Debug)]
+pub struct Logger {
+    verbosity: LogVerbosity,
+    level: Level,
+    target: Option<String>,
+    remote_addr: bool,
+    user_agent: bool,
+    request_id_header: Option<String>,
+    redacted_headers: Vec<HeaderName>,
+}
+impl Default for Logger {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
 impl Logger {
     /// Create new `Logger` middleware.
     #[inline]
     pub fn new() -> Self {
-        Logger {}
+        Logger {
+            verbosity: LogVerbosity::Normal,
+            level: Level::INFO,
+            target: None,
+            remote_addr: true,
+            user_agent: false,
+            request_id_header: None,
+            redacted_headers: vec![AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE, SET_COOKIE],
+        }
+    }
+
+    /// Sets the verbosity of routes without [`LogVerbosity`] hoop and returns `Self`.
+    #[inline]
+    pub fn with_verbosity(mut self, verbosity: LogVerbosity) -> Self {
+        self.verbosity = verbosity;
+        self
+    }
+
+    /// Sets the level of the span and the event and returns `Self`. Default is `Level::INFO`.
+    #[inline]
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Sets the target of the logger, logged as `log_target` of the `Request` span, and returns `Self`.
+    ///
+    /// The target of tracing events must be constant, so it is a field which subscribers filter on,
+    /// like the `EnvFilter` directive `[Request{log_target=access}]=info`. It is not set by default.
+    #[inline]
+    pub fn with_target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    /// Sets whether the remote address is logged and returns `Self`. Default is `true`.
+    #[inline]
+    pub fn with_remote_addr(mut self, remote_addr: bool) -> Self {
+        self.remote_addr = remote_addr;
+        self
+    }
+
+    /// Sets whether the `User-Agent` of the request is logged and returns `Self`. Default is `false`.
+    #[inline]
+    pub fn with_user_agent(mut self, user_agent: bool) -> Self {
+        self.user_agent = user_agent;
+        self
+    }
+
+    /// Sets the request header whose value is logged as `request_id` and returns `Self`, like
+    /// `x-request-id`. It is not logged by default.
+    #[inline]
+    pub fn with_request_id_header(mut self, name: impl Into<String>) -> Self {
+        self.request_id_header = Some(name.into());
+        self
+    }
+
+    /// Sets the headers whose values are redacted in verbose logs and returns `Self`.
+    ///
+    /// Default is `Authorization`, `Proxy-Authorization`, `Cookie` and `Set-Cookie`.
+    #[inline]
+    pub fn with_redacted_headers(mut self, names: impl IntoIterator<Item = HeaderName>) -> Self {
+        self.redacted_headers = names.into_iter().collect();
+        self
+    }
+
+    fn redact(&self, headers: &HeaderMap) -> HeaderMap {
+        let mut headers = headers.clone();
+        for name in &self.redacted_headers {
+            if let Entry::Occupied(mut entry) = headers.entry(name) {
+                for value in entry.iter_mut() {
+                    *value = HeaderValue::from_static("[redacted]");
+                }
+            }
+        }
+        headers
     }
 }
 
 #[async_trait]
 impl Handler for Logger {
     async fn handle(&self, req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
-        let span = tracing::span!(
-            Level::INFO,
+        let span = at_level!(
+            span,
+            self.level,
             "Request",
-            remote_addr = %req.remote_addr().to_string(),
+            log_target = tracing::field::Empty,
+            remote_addr = tracing::field::Empty,
             version = ?req.version(),
             method = %req.method(),
             path = %req.uri(),
+            user_agent = tracing::field::Empty,
+            request_id = tracing::field::Empty,
         );
+        if let Some(target) = &self.target {
+            span.record("log_target", &target.as_str());
+        }
+        if self.remote_addr {
+            span.record("remote_addr", &tracing::field::display(req.remote_addr()));
+        }
+        if self.user_agent {
+            if let Some(user_agent) = req.headers().get(USER_AGENT).and_then(|v| v.to_str().ok()) {
+                span.record("user_agent", &user_agent);
+            }
+        }
+        if let Some(name) = &self.request_id_header {
+            if let Some(request_id) = req.headers().get(name.as_str()).and_then(|v| v.to_str().ok()) {
+                span.record("request_id", &request_id);
+            }
+        }
 
         async move {
             let now = Instant::now();
@@ -44,11 +243,41 @@ impl Handler for Logger {
                     }
                 }
             };
-            tracing::info!(
-                status = %status,
-                duration = ?duration,
-                "Response"
-            );
+            let size = res
+                .headers()
+                .get(CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<u64>().ok())
+                .or_else(|| res.body().size());
+            let verbosity = depot.obtain::<LogVerbosity>().copied().unwrap_or(self.verbosity);
+            match verbosity {
+                LogVerbosity::Skip => {}
+                LogVerbosity::Minimal => {
+                    at_level!(event, self.level, status = %status, "Response");
+                }
+                LogVerbosity::Normal => {
+                    at_level!(
+                        event,
+                        self.level,
+                        status = %status,
+                        size = size,
+                        duration = ?duration,
+                        "Response"
+                    );
+                }
+                LogVerbosity::Verbose => {
+                    at_level!(
+                        event,
+                        self.level,
+                        status = %status,
+                        size = size,
+                        duration = ?duration,
+                        request_headers = ?self.redact(req.headers()),
+                        response_headers = ?self.redact(res.headers()),
+                        "Response"
+                    );
+                }
+            }
         }
         .instrument(span)
         .await
@@ -83,4 +312,89 @@ mod tests {
             .unwrap();
         assert!(logs_contain("duration"));
     }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_log_verbosity() {
+        #[handler]
+        async fn health() -> &'static str {
+            "ok"
+        }
+        #[handler]
+        async fn webhook() -> &'static str {
+            "received"
+        }
+
+        let router = Router::new()
+            .hoop(Logger::new().with_verbosity(LogVerbosity::Minimal))
+            .push(Router::with_path("health").hoop(LogVerbosity::Skip).get(health))
+            .push(Router::with_path("webhook").hoop(LogVerbosity::Verbose).post(webhook))
+            .push(Router::with_path("other").get(health));
+        let service = Service::new(router);
+
+        TestClient::get("http://127.0.0.1:5801/health").send(&service).await;
+        assert!(!logs_contain("Response"));
+
+        TestClient::get("http://127.0.0.1:5801/other").send(&service).await;
+        assert!(logs_contain("Response"));
+        assert!(!logs_contain("duration"));
+
+        TestClient::post("http://127.0.0.1:5801/webhook")
+            .add_header("x-webhook-id", "42", true)
+            .add_header("authorization", "Bearer secret-token", true)
+            .add_header("cookie", "session=secret-session", true)
+            .send(&service)
+            .await;
+        assert!(logs_contain("duration"));
+        assert!(logs_contain("x-webhook-id"));
+        assert!(logs_contain("[redacted]"));
+        assert!(!logs_contain("secret-token"));
+        assert!(!logs_contain("secret-session"));
+    }
+    #[tokio::test]
+    #[traced_test]
+    async fn test_log_fields() {
+        #[handler]
+        async fn hello() -> &'static str {
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+            "hello"
+        }
+
+        let router = Router::new()
+            .hoop(
+                Logger::new()
+                    .with_level(Level::WARN)
+                    .with_target("access")
+                    .with_remote_addr(false)
+                    .with_user_agent(true)
+                    .with_request_id_header("x-request-id"),
+            )
+            .push(Router::with_path("hello").get(hello));
+
+        TestClient::get("http://127.0.0.1:5801/hello")
+            .add_header("user-agent", "salvo-test", true)
+            .add_header("x-request-id", "req-1", true)
+            .send(router)
+            .await;
+        logs_assert(|lines: &[&str]| {
+            let events = lines
+                .iter()
+                .filter(|line| line.contains("Response"))
+                .collect::<Vec<_>>();
+            match events[..] {
+                [event] => {
+                    assert!(event.contains("WARN"));
+                    assert!(event.contains("log_target=\"access\""));
+                    assert!(event.contains("status=200 OK"));
+                    assert!(event.contains("size=5"));
+                    assert!(event.contains("user_agent=\"salvo-test\""));
+                    assert!(event.contains("request_id=\"req-1\""));
+                    assert!(!event.contains("remote_addr"));
+                    assert!(event.contains("duration=") && !event.contains("duration=0ns"));
+                    Ok(())
+                }
+                _ => Err(format!("expected one response event, got {}", events.len())),
+            }
+        });
+    }
 }
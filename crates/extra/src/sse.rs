@@ -272,7 +272,7 @@ where
             })
             .into_stream()
             .and_then(|event| future::ready(Ok(event.to_string())));
-        res.streaming(body_stream)
+        res.streaming_flushed(body_stream)
     }
 }
 #[inline]
@@ -299,7 +299,7 @@ where
         })
         .into_stream()
         .and_then(|event| future::ready(Ok(event.to_string())));
-    res.streaming(body_stream)
+    res.streaming_flushed(body_stream)
 }
 
 impl<S> Stream for SseKeepAlive<S>
@@ -14,9 +14,9 @@ use futures_util::sink::{Sink, SinkExt};
 use futures_util::stream::{Stream, StreamExt};
 use futures_util::{future, ready, FutureExt, TryFutureExt};
 use hyper::upgrade::OnUpgrade;
-use salvo_core::http::header::{SEC_WEBSOCKET_VERSION, UPGRADE};
+use salvo_core::http::header::{CONTENT_LENGTH, CONTENT_TYPE, SEC_WEBSOCKET_VERSION, UPGRADE};
 use salvo_core::http::headers::{Connection, HeaderMapExt, SecWebsocketAccept, SecWebsocketKey, Upgrade};
-use salvo_core::http::{StatusCode, StatusError};
+use salvo_core::http::{ResBody, StatusCode, StatusError};
 use salvo_core::{Error, Request, Response};
 use tokio_tungstenite::{
     tungstenite::protocol::{self, WebSocketConfig},
@@ -85,6 +85,10 @@ impl WebSocketUpgrade {
     }
 
     /// Upgrade websocket request.
+    ///
+    /// The response is `101 Switching Protocols` with `Connection: upgrade`, `Upgrade: websocket` and
+    /// `Sec-WebSocket-Accept`, any body written before is dropped. After it is sent, the connection is
+    /// no longer served as HTTP and the raw stream is passed to `callback` as [`WebSocket`].
     #[inline]
     pub async fn upgrade<F, Fut>(&self, req: &mut Request, res: &mut Response, callback: F) -> Result<(), StatusError>
     where
@@ -126,7 +130,9 @@ impl WebSocketUpgrade {
         };
 
         res.set_status_code(StatusCode::SWITCHING_PROTOCOLS);
-
+        res.set_body(ResBody::None);
+        res.headers_mut().remove(CONTENT_LENGTH);
+        res.headers_mut().remove(CONTENT_TYPE);
         res.headers_mut().typed_insert(Connection::upgrade());
         res.headers_mut().typed_insert(Upgrade::websocket());
         res.headers_mut().typed_insert(SecWebsocketAccept::from(sec_ws_key));
@@ -384,6 +390,7 @@ mod tests {
     use salvo_core::conn::{Acceptor, Listener};
     use salvo_core::http::header::*;
     use salvo_core::prelude::*;
+    use salvo_core::ConnectionClosePolicy;
 
     use super::*;
 
@@ -439,4 +446,57 @@ mod tests {
 
         assert_eq!(res.status(), StatusCode::SWITCHING_PROTOCOLS);
     }
+
+    #[tokio::test]
+    async fn test_websocket_handshake_headers() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // `Connection: close` is never forced on `101 Switching Protocols`.
+        let service = Service::new(Router::new().handle(connect)).with_connection_close_policy(
+            ConnectionClosePolicy::new()
+                .with_status_codes([StatusCode::SWITCHING_PROTOCOLS])
+                .with_streamed_only(false),
+        );
+        let acceptor = TcpListener::new("127.0.0.1:0").bind().await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+        tokio::spawn(async move {
+            Server::new(acceptor).serve(service).await;
+        });
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(
+                b"GET /chat HTTP/1.1\r\nhost: localhost\r\nupgrade: websocket\r\nconnection: Upgrade\r\n\
+                sec-websocket-key: dGhlIHNhbXBsZSBub25jZQ==\r\nsec-websocket-version: 13\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(stream.read_u8().await.unwrap());
+        }
+        let head = String::from_utf8(head).unwrap().to_lowercase();
+        let mut lines = head.lines();
+        assert_eq!(lines.next().unwrap(), "http/1.1 101 switching protocols");
+        let headers = lines.filter(|line| !line.is_empty()).collect::<Vec<_>>();
+        assert!(headers.contains(&"connection: upgrade"), "{head}");
+        assert!(headers.contains(&"upgrade: websocket"), "{head}");
+        // The accept key of the example in RFC 6455.
+        assert!(
+            headers.contains(&"sec-websocket-accept: s3pplmbitxaq9kygzzhzrbk+xoo="),
+            "{head}"
+        );
+        assert!(!headers.iter().any(|line| line.starts_with("content-length")), "{head}");
+
+        // The raw stream is handed off, a masked text frame is echoed.
+        let mask = [1, 2, 3, 4];
+        let payload = b"hi".iter().zip(mask.iter().cycle()).map(|(b, m)| b ^ m);
+        let mut frame = vec![0x81, 0x82];
+        frame.extend_from_slice(&mask);
+        frame.extend(payload);
+        stream.write_all(&frame).await.unwrap();
+        let mut echo = [0; 4];
+        stream.read_exact(&mut echo).await.unwrap();
+        assert_eq!(echo, [0x81, 0x02, b'h', b'i']);
+    }
 }
@@ -7,6 +7,7 @@
 #![warn(missing_docs)]
 #![warn(clippy::future_not_send)]
 
+use std::collections::HashSet;
 use std::convert::{Infallible, TryFrom};
 
 use hyper::body::Incoming as HyperBody;
@@ -16,10 +17,13 @@ use hyper_util::client::{connect::HttpConnector, legacy::Client};
 use hyper_util::rt::TokioExecutor;
 use once_cell::sync::OnceCell;
 use percent_encoding::{utf8_percent_encode, CONTROLS};
-use salvo_core::http::header::{HeaderMap, HeaderName, HeaderValue, CONNECTION, HOST, UPGRADE};
-use salvo_core::http::uri::{Scheme, Uri};
+use salvo_core::http::header::{
+    HeaderMap, HeaderName, HeaderValue, CONNECTION, CONTENT_LENGTH, CONTENT_TYPE, HOST, LOCATION, TRANSFER_ENCODING,
+    UPGRADE,
+};
+use salvo_core::http::uri::{Parts as UriParts, Scheme, Uri};
 use salvo_core::http::ReqBody;
-use salvo_core::http::StatusCode;
+use salvo_core::http::{Method, StatusCode};
 use salvo_core::{async_trait, BoxedError, Depot, Error, FlowCtrl, Handler, Request, Response};
 use tokio::io::copy_bidirectional;
 
@@ -85,6 +89,7 @@ pub struct Proxy<U> {
     upstreams: U,
     http_client: OnceCell<Client<HttpConnector, ReqBody>>,
     https_client: OnceCell<Client<HttpsConnector<HttpConnector>, ReqBody>>,
+    max_redirects: usize,
 }
 
 impl<U> Proxy<U>
@@ -98,9 +103,28 @@ where
             upstreams,
             http_client: OnceCell::new(),
             https_client: OnceCell::new(),
+            max_redirects: 0,
         }
     }
 
+    /// Follow redirects returned by upstream server up to `max_redirects` hops, instead of passing
+    /// them to the client. The default is 0, which means redirects are passed through.
+    ///
+    /// `303 See Other`, and `301`/`302` responses to `POST` requests are followed with `GET` and
+    /// without body. Other redirects keep the request method, they are passed through if the request
+    /// may have a body, because the body can not be sent again. A redirect loop or exceeding the
+    /// limit is treated as an upstream error.
+    ///
+    /// Only redirects to the same origin (scheme, host and port) as the redirected request are
+    /// followed, the request headers such as `Authorization` and `Cookie` are forwarded to it. Redirects
+    /// to other origins are passed through to the client, so the upstream can't make the proxy send
+    /// credentials or requests to any host it names.
+    #[inline]
+    pub fn with_follow_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
     /// Get upstreams list.
     #[inline]
     pub fn upstreams(&self) -> &U {
@@ -232,6 +256,104 @@ where
         }
         Ok(response)
     }
+
+    /// Follow redirects of `response`, `method`, `uri` and `headers` are from the request which got
+    /// the `response`.
+    async fn follow_redirects(
+        &self,
+        mut response: HyperResponse,
+        mut method: Method,
+        mut uri: Uri,
+        mut headers: HeaderMap,
+    ) -> Result<HyperResponse, Error> {
+        let mut visited = HashSet::new();
+        visited.insert(uri.to_string());
+        loop {
+            let status = response.status();
+            if !status.is_redirection() {
+                return Ok(response);
+            }
+            let location = match response
+                .headers()
+                .get(LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .and_then(|location| resolve_location(&uri, location))
+            {
+                Some(location) if is_same_origin(&uri, &location) => location,
+                _ => return Ok(response),
+            };
+            let to_get = (status == StatusCode::SEE_OTHER && method != Method::HEAD)
+                || ((status == StatusCode::MOVED_PERMANENTLY || status == StatusCode::FOUND) && method == Method::POST);
+            if to_get {
+                method = Method::GET;
+                headers.remove(CONTENT_LENGTH);
+                headers.remove(CONTENT_TYPE);
+                headers.remove(TRANSFER_ENCODING);
+            } else if !matches!(
+                status,
+                StatusCode::MOVED_PERMANENTLY
+                    | StatusCode::FOUND
+                    | StatusCode::TEMPORARY_REDIRECT
+                    | StatusCode::PERMANENT_REDIRECT
+            ) || (method != Method::GET && method != Method::HEAD)
+            {
+                return Ok(response);
+            }
+            if !visited.insert(location.to_string()) {
+                return Err(Error::other(format!("redirect loop detected at {location}")));
+            }
+            if visited.len() > self.max_redirects + 1 {
+                return Err(Error::other("too many redirects"));
+            }
+            if let Some(host) = location.host().and_then(|host| HeaderValue::from_str(host).ok()) {
+                headers.insert(HOST, host);
+            }
+            let mut build = hyper::Request::builder().method(method.clone()).uri(&location);
+            for (key, value) in &headers {
+                build = build.header(key, value);
+            }
+            let request = build.body(ReqBody::None).map_err(Error::other)?;
+            tracing::debug!(status = ?status, location = %location, "follow upstream redirect");
+            response = self.call_proxied_server(request, None).await?;
+            uri = location;
+        }
+    }
+}
+
+/// Check whether `a` and `b` have the same scheme, host and port.
+fn is_same_origin(a: &Uri, b: &Uri) -> bool {
+    let port = |uri: &Uri| {
+        uri.port_u16().or_else(|| match uri.scheme_str() {
+            Some("http") => Some(80),
+            Some("https") => Some(443),
+            _ => None,
+        })
+    };
+    a.scheme().is_some()
+        && a.scheme() == b.scheme()
+        && a.host().map(|host| host.to_ascii_lowercase()) == b.host().map(|host| host.to_ascii_lowercase())
+        && port(a) == port(b)
+}
+
+/// Resolve `location` against the `base` uri.
+fn resolve_location(base: &Uri, location: &str) -> Option<Uri> {
+    if location.contains("://") {
+        return location.parse().ok();
+    }
+    let path_and_query = if location.starts_with('/') {
+        location.to_owned()
+    } else {
+        let base_path = base.path();
+        format!(
+            "{}{location}",
+            &base_path[..base_path.rfind('/').map(|i| i + 1).unwrap_or(0)]
+        )
+    };
+    let mut parts = UriParts::default();
+    parts.scheme = base.scheme().cloned();
+    parts.authority = base.authority().cloned();
+    parts.path_and_query = Some(path_and_query.parse().ok()?);
+    Uri::from_parts(parts).ok()
 }
 
 #[async_trait]
@@ -244,10 +366,26 @@ where
     async fn handle(&self, req: &mut Request, _depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
         match self.build_proxied_request(req) {
             Ok(proxied_request) => {
-                match self
+                let redirected = if self.max_redirects > 0 {
+                    Some((
+                        proxied_request.method().clone(),
+                        proxied_request.uri().clone(),
+                        proxied_request.headers().clone(),
+                    ))
+                } else {
+                    None
+                };
+                let response = match self
                     .call_proxied_server(proxied_request, req.extensions_mut().remove())
                     .await
                 {
+                    Ok(response) => match redirected {
+                        Some((method, uri, headers)) => self.follow_redirects(response, method, uri, headers).await,
+                        None => Ok(response),
+                    },
+                    Err(e) => Err(e),
+                };
+                match response {
                     Ok(response) => {
                         let (
                             salvo_core::http::response::Parts {
@@ -338,6 +476,108 @@ mod tests {
     //         .unwrap();
     //     assert!(content.contains("Install Rust"));
     // }
+    #[test]
+    fn test_resolve_location() {
+        let base: Uri = "http://127.0.0.1:8080/a/b?c=d".parse().unwrap();
+        assert_eq!(
+            resolve_location(&base, "/new?x=1").unwrap().to_string(),
+            "http://127.0.0.1:8080/new?x=1"
+        );
+        assert_eq!(
+            resolve_location(&base, "c").unwrap().to_string(),
+            "http://127.0.0.1:8080/a/c"
+        );
+        assert_eq!(
+            resolve_location(&base, "https://example.com/").unwrap().to_string(),
+            "https://example.com/"
+        );
+    }
+
+    #[test]
+    fn test_is_same_origin() {
+        let base: Uri = "http://127.0.0.1:8080/a".parse().unwrap();
+        assert!(is_same_origin(&base, &"http://127.0.0.1:8080/b".parse().unwrap()));
+        assert!(!is_same_origin(&base, &"http://127.0.0.1:8081/b".parse().unwrap()));
+        assert!(!is_same_origin(&base, &"https://127.0.0.1:8080/b".parse().unwrap()));
+        assert!(!is_same_origin(&base, &"http://localhost:8080/b".parse().unwrap()));
+        assert!(!is_same_origin(&base, &"ftp://127.0.0.1:8080/b".parse().unwrap()));
+        let base: Uri = "https://Example.com/a".parse().unwrap();
+        assert!(is_same_origin(&base, &"https://example.com:443/b".parse().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_proxy_redirects() {
+        use salvo_core::prelude::*;
+        use salvo_core::test::{ResponseExt, TestClient};
+        use salvo_core::writer::Redirect;
+
+        #[handler]
+        async fn old(res: &mut Response) {
+            res.render(Redirect::found("/new"));
+        }
+        #[handler]
+        async fn submit(res: &mut Response) {
+            res.render(Redirect::other("new"));
+        }
+        #[handler]
+        async fn looping(res: &mut Response) {
+            res.render(Redirect::found("/loop"));
+        }
+        #[handler]
+        async fn new() -> &'static str {
+            "new page"
+        }
+        #[handler]
+        async fn external(req: &mut Request, res: &mut Response) {
+            let port = req.local_addr().clone().into_std().unwrap().port();
+            res.render(Redirect::found(format!("http://localhost:{port}/new")));
+        }
+
+        let upstream = Router::new()
+            .push(Router::with_path("old").get(old))
+            .push(Router::with_path("submit").post(submit))
+            .push(Router::with_path("loop").get(looping))
+            .push(Router::with_path("external").get(external))
+            .push(Router::with_path("new").get(new));
+        let acceptor = TcpListener::new("127.0.0.1:0").bind().await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+        tokio::spawn(async move {
+            Server::new(acceptor).serve(upstream).await;
+        });
+        let upstream = format!("http://{addr}");
+
+        let router = Router::with_path("<**rest>").handle(Proxy::new(upstream.clone()));
+        let response = TestClient::get("http://127.0.0.1:5801/old").send(router).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::FOUND);
+        assert_eq!(response.headers().get(LOCATION).unwrap(), "/new");
+
+        let service = Service::new(Router::with_path("<**rest>").handle(Proxy::new(upstream).with_follow_redirects(3)));
+        let mut response = TestClient::get("http://127.0.0.1:5801/old").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.take_string().await.unwrap(), "new page");
+
+        let mut response = TestClient::post("http://127.0.0.1:5801/submit")
+            .text("data")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.take_string().await.unwrap(), "new page");
+
+        let response = TestClient::get("http://127.0.0.1:5801/loop").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        // A redirect to another origin is passed through, the credentials are not sent to it.
+        let response = TestClient::get("http://127.0.0.1:5801/external")
+            .add_header("authorization", "Bearer secret", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::FOUND);
+        assert_eq!(
+            response.headers().get(LOCATION).unwrap(),
+            &format!("http://localhost:{}/new", addr.port())
+        );
+    }
+
     #[test]
     fn test_others() {
         let mut handler = Proxy::new(["https://www.bing.com"]);
@@ -2,7 +2,7 @@ use salvo_core::async_trait;
 use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 
-use super::{BasicQuota, RateGuard};
+use super::{BasicQuota, RateGuard, RateState};
 
 /// Fixed window implement.
 #[derive(Deserialize, Serialize, Clone, Debug)]
@@ -51,4 +51,9 @@ impl RateGuard for FixedGuard {
             false
         }
     }
+
+    fn state(&self, quota: &Self::Quota) -> Option<RateState> {
+        let limit = self.quota.as_ref().map(|quota| quota.limit).unwrap_or(quota.limit);
+        Some(RateState::new(limit, limit.saturating_sub(self.count), self.reset))
+    }
 }
@@ -23,8 +23,10 @@ use std::hash::Hash;
 
 use salvo_core::conn::SocketAddr;
 use salvo_core::handler::{none_skipper, Skipper};
+use salvo_core::http::header::{HeaderName, HeaderValue, RETRY_AFTER};
 use salvo_core::http::{Request, Response, StatusCode, StatusError};
 use salvo_core::{async_trait, Depot, FlowCtrl, Handler};
+use time::OffsetDateTime;
 
 mod quota;
 pub use quota::{BasicQuota, CelledQuota, QuotaGetter};
@@ -86,6 +88,30 @@ impl RateIssuer for RemoteIpIssuer {
     }
 }
 
+/// State of the quota of a key after a request is verified.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct RateState {
+    /// The limit of requests in a window.
+    pub limit: usize,
+    /// The remaining requests in the current window.
+    pub remaining: usize,
+    /// The time more requests are allowed again.
+    pub reset: OffsetDateTime,
+}
+
+impl RateState {
+    /// Create a new `RateState`.
+    #[inline]
+    pub fn new(limit: usize, remaining: usize, reset: OffsetDateTime) -> Self {
+        Self {
+            limit,
+            remaining,
+            reset,
+        }
+    }
+}
+
 /// `RateGuard` is strategy to verify is the request exceeded quota
 #[async_trait]
 pub trait RateGuard: Clone + Send + Sync + 'static {
@@ -93,8 +119,16 @@ pub trait RateGuard: Clone + Send + Sync + 'static {
     type Quota: Clone + Send + Sync + 'static;
     /// Verify is current request exceed the quota.
     async fn verify(&mut self, quota: &Self::Quota) -> bool;
+    /// State of the quota after the last verified request, it is used for the rate limit headers.
+    ///
+    /// Default is `None`, no headers are sent.
+    fn state(&self, _quota: &Self::Quota) -> Option<RateState> {
+        None
+    }
 }
 
+type ExceededHandler = Box<dyn Fn(&Request, Option<&RateState>, &mut Response) + Send + Sync>;
+
 /// `RateStore` is used to store rate limit data.
 #[async_trait]
 pub trait RateStore: Send + Sync + 'static {
@@ -120,6 +154,8 @@ pub struct RateLimiter<G, S, I, Q> {
     issuer: I,
     quota_getter: Q,
     skipper: Box<dyn Skipper>,
+    rate_headers: bool,
+    exceeded_handler: Option<ExceededHandler>,
 }
 
 impl<G: RateGuard, S: RateStore, I: RateIssuer, P: QuotaGetter<I::Key>> RateLimiter<G, S, I, P> {
@@ -132,6 +168,8 @@ impl<G: RateGuard, S: RateStore, I: RateIssuer, P: QuotaGetter<I::Key>> RateLimi
             issuer,
             quota_getter,
             skipper: Box::new(none_skipper),
+            rate_headers: false,
+            exceeded_handler: None,
         }
     }
 
@@ -141,6 +179,69 @@ impl<G: RateGuard, S: RateStore, I: RateIssuer, P: QuotaGetter<I::Key>> RateLimi
         self.skipper = Box::new(skipper);
         self
     }
+
+    /// Sets whether to send `X-RateLimit-Limit`, `X-RateLimit-Remaining` and `X-RateLimit-Reset`
+    /// headers and returns new `RateLimiter`.
+    ///
+    /// Throttled responses also get `Retry-After`. Headers are only sent if the guard reports its
+    /// [`RateState`].
+    #[inline]
+    pub fn with_rate_headers(mut self, rate_headers: bool) -> Self {
+        self.rate_headers = rate_headers;
+        self
+    }
+
+    /// Sets the handler customizing throttled responses and returns new `RateLimiter`.
+    ///
+    /// It is called after the status is set to `429 Too Many Requests` and the rate limit headers
+    /// are added, so it can change the status, the headers and the body.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use salvo_core::prelude::*;
+    /// use salvo_rate_limiter::{BasicQuota, FixedGuard, MemoryStore, RateLimiter, RemoteIpIssuer};
+    ///
+    /// let limiter = RateLimiter::new(
+    ///     FixedGuard::new(),
+    ///     MemoryStore::new(),
+    ///     RemoteIpIssuer,
+    ///     BasicQuota::per_second(10),
+    /// )
+    /// .with_rate_headers(true)
+    /// .with_exceeded_handler(|_req, _state, res| {
+    ///     res.render(Text::Json(r#"{"error":"too_many_requests"}"#));
+    /// });
+    /// ```
+    #[inline]
+    pub fn with_exceeded_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&Request, Option<&RateState>, &mut Response) + Send + Sync + 'static,
+    {
+        self.exceeded_handler = Some(Box::new(handler));
+        self
+    }
+}
+
+fn insert_rate_headers(state: &RateState, verified: bool, res: &mut Response) {
+    let headers = res.headers_mut();
+    headers.insert(
+        HeaderName::from_static("x-ratelimit-limit"),
+        HeaderValue::from(state.limit),
+    );
+    headers.insert(
+        HeaderName::from_static("x-ratelimit-remaining"),
+        HeaderValue::from(state.remaining),
+    );
+    // Seconds since the Unix epoch.
+    headers.insert(
+        HeaderName::from_static("x-ratelimit-reset"),
+        HeaderValue::from(state.reset.unix_timestamp()),
+    );
+    if !verified {
+        let retry_after = (state.reset - OffsetDateTime::now_utc()).whole_seconds().max(0);
+        headers.insert(RETRY_AFTER, HeaderValue::from(retry_after));
+    }
 }
 
 #[async_trait]
@@ -182,8 +283,17 @@ where
             }
         };
         let verified = guard.verify(&quota).await;
+        let state = guard.state(&quota);
+        if self.rate_headers {
+            if let Some(state) = &state {
+                insert_rate_headers(state, verified, res);
+            }
+        }
         if !verified {
             res.set_status_code(StatusCode::TOO_MANY_REQUESTS);
+            if let Some(handler) = &self.exceeded_handler {
+                handler(req, state.as_ref(), res);
+            }
             ctrl.skip_rest();
         }
         if let Err(e) = self.store.save_guard(key, guard).await {
@@ -298,6 +408,73 @@ mod tests {
         assert_eq!(respone.take_string().await.unwrap(), "Limited page");
     }
 
+    #[tokio::test]
+    async fn test_exceeded_handler_and_rate_headers() {
+        let limiter = RateLimiter::new(
+            FixedGuard::default(),
+            MemoryStore::default(),
+            UserIssuer,
+            BasicQuota::set_seconds(2, 60),
+        )
+        .with_rate_headers(true)
+        .with_exceeded_handler(|_req, state, res| {
+            let limit = state.map(|state| state.limit).unwrap_or_default();
+            res.render(Text::Json(format!(r#"{{"code":"rate_limited","limit":{limit}}}"#)));
+        });
+        let router = Router::new().push(Router::with_path("limited").hoop(limiter).get(limited));
+        let service = Service::new(router);
+
+        for remaining in ["1", "0"] {
+            let mut respone = TestClient::get("http://127.0.0.1:5800/limited?user=user1")
+                .send(&service)
+                .await;
+            assert_eq!(respone.status_code(), Some(StatusCode::OK));
+            assert_eq!(respone.headers().get("x-ratelimit-limit").unwrap(), "2");
+            assert_eq!(respone.headers().get("x-ratelimit-remaining").unwrap(), remaining);
+            assert!(respone.headers().get("retry-after").is_none());
+            assert_eq!(respone.take_string().await.unwrap(), "Limited page");
+        }
+
+        let mut respone = TestClient::get("http://127.0.0.1:5800/limited?user=user1")
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code(), Some(StatusCode::TOO_MANY_REQUESTS));
+        let headers = respone.headers();
+        assert_eq!(headers.get("x-ratelimit-limit").unwrap(), "2");
+        assert_eq!(headers.get("x-ratelimit-remaining").unwrap(), "0");
+        let reset: i64 = headers
+            .get("x-ratelimit-reset")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(reset > OffsetDateTime::now_utc().unix_timestamp());
+        let retry_after: i64 = headers.get("retry-after").unwrap().to_str().unwrap().parse().unwrap();
+        assert!((1..=60).contains(&retry_after));
+        assert_eq!(headers.get("content-type").unwrap(), "application/json; charset=utf-8");
+        assert_eq!(
+            respone.take_string().await.unwrap(),
+            r#"{"code":"rate_limited","limit":2}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sliding_guard_state_reset() {
+        let quota = CelledQuota::set_seconds(10, 10, 10);
+        let mut guard = SlidingGuard::default();
+        for _ in 0..3 {
+            assert!(guard.verify(&quota).await);
+        }
+        let state = guard.state(&quota).unwrap();
+        assert_eq!(state.remaining, 7);
+        // The oldest request is two cells before the newest one, so it slides out of the window
+        // after eight more cells, not after the next one.
+        let reset = state.reset - time::OffsetDateTime::now_utc();
+        assert!(reset > time::Duration::seconds(7), "{reset}");
+        assert!(reset <= time::Duration::seconds(8), "{reset}");
+    }
+
     #[tokio::test]
     async fn test_sliding_dynmaic_quota() {
         static USER_QUOTAS: Lazy<HashMap<String, CelledQuota>> = Lazy::new(|| {
@@ -1,7 +1,7 @@
 use salvo_core::async_trait;
 use time::{Duration, OffsetDateTime};
 
-use super::{CelledQuota, RateGuard};
+use super::{CelledQuota, RateGuard, RateState};
 
 /// Sliding window implement.
 #[derive(Clone, Debug)]
@@ -74,4 +74,18 @@ impl RateGuard for SlidingGuard {
         }
         self.counts.iter().cloned().sum::<usize>() <= quota.limit
     }
+
+    /// The reset time is when the oldest cell with requests slides out of the window.
+    fn state(&self, _quota: &Self::Quota) -> Option<RateState> {
+        let quota = self.quota.as_ref()?;
+        let used = self.counts.iter().sum::<usize>();
+        // The cell `age` cells before the head slides out after the head moves on `len - age` cells.
+        let len = self.counts.len();
+        let reset = (0..len)
+            .rev()
+            .find(|age| self.counts[(self.head + len - age) % len] > 0)
+            .map(|age| self.cell_inst + self.cell_span * ((len - age) as u32))
+            .unwrap_or(self.cell_inst);
+        Some(RateState::new(quota.limit, quota.limit.saturating_sub(used), reset))
+    }
 }
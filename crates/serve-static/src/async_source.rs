@@ -0,0 +1,433 @@
+//! serve static assets from an async file source
+
+use std::io::{ErrorKind, Result as IoResult, SeekFrom};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use salvo_core::http::header::CONTENT_LENGTH;
+use salvo_core::http::headers::{HeaderMapExt, LastModified};
+use salvo_core::http::{Method, Request, Response, StatusCode, StatusError};
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler, IntoVecString};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use super::vfs::{prepare_bytes, render_ranges, BytesBody};
+use super::{
+    check_method_allowed, decode_url_path_safely, format_url_path_safely, has_control_chars, redirect_to_dir_url,
+    reject_malformed_path, render_file_options, sanitize_path_parts, strip_head_body,
+};
+
+/// Metadata of a file in an [`AsyncFileSource`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct SourceMetadata {
+    /// Size of the file in bytes.
+    pub len: u64,
+    /// Last modification time, it is sent as `Last-Modified` if set.
+    pub modified: Option<SystemTime>,
+    /// Strong entity tag with quotes, like `"abc"`. A tag which is not a valid strong entity tag is
+    /// hashed into one. If it is not set, a tag is made from `len` and `modified`, or from the SHA-256
+    /// hash of the content if `modified` is not set either.
+    pub etag: Option<String>,
+}
+
+impl SourceMetadata {
+    /// Create a new `SourceMetadata` of a file with `len` bytes.
+    #[inline]
+    pub fn new(len: u64) -> Self {
+        SourceMetadata {
+            len,
+            modified: None,
+            etag: None,
+        }
+    }
+
+    /// Sets the last modification time and returns `Self`.
+    #[inline]
+    pub fn with_modified(mut self, modified: SystemTime) -> Self {
+        self.modified = Some(modified);
+        self
+    }
+
+    /// Sets the strong entity tag and returns `Self`.
+    #[inline]
+    pub fn with_etag(mut self, etag: impl Into<String>) -> Self {
+        self.etag = Some(etag.into());
+        self
+    }
+}
+
+/// Source of the files served by [`AsyncStaticDir`], like a directory, an object store or a
+/// database.
+///
+/// `path` is relative to the root of the source and separated by `/`, it is sanitized by the handler,
+/// so it never contains `.` or `..` segments.
+#[async_trait]
+pub trait AsyncFileSource: Send + Sync + 'static {
+    /// Get the metadata of the file at `path`, `None` if there is no file, directories are not files.
+    async fn metadata(&self, path: &str) -> IoResult<Option<SourceMetadata>>;
+    /// Read the whole content of the file at `path`.
+    async fn read(&self, path: &str) -> IoResult<Bytes>;
+    /// Read the bytes in `range` of the file at `path`, it is used for range requests.
+    ///
+    /// The default implementation reads the whole file and slices it, sources which can read a part of
+    /// a file should override it.
+    async fn read_range(&self, path: &str, range: Range<u64>) -> IoResult<Bytes> {
+        let data = self.read(path).await?;
+        let end = (range.end as usize).min(data.len());
+        let start = (range.start as usize).min(end);
+        Ok(data.slice(start..end))
+    }
+}
+
+/// [`AsyncFileSource`] of a directory on the local filesystem, it is the default source of
+/// [`AsyncStaticDir`].
+///
+/// Like [`StaticDir`](crate::StaticDir), dot files are not served and symlinks which point out of the
+/// root are not followed by default.
+#[derive(Clone, Debug)]
+pub struct FsSource {
+    root: PathBuf,
+    /// Serve paths with a segment starting with `.`, like `.env` or `.git/config`. Default is `false`.
+    pub dot_files: bool,
+    /// Follow symlinks which point out of the root. Default is `false`.
+    pub follow_symlinks: bool,
+}
+
+impl FsSource {
+    /// Create a new `FsSource` serving files in `root`.
+    #[inline]
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FsSource {
+            root: root.into(),
+            dot_files: false,
+            follow_symlinks: false,
+        }
+    }
+
+    /// Sets dot_files and returns `Self`.
+    #[inline]
+    pub fn with_dot_files(mut self, dot_files: bool) -> Self {
+        self.dot_files = dot_files;
+        self
+    }
+
+    /// Sets follow_symlinks and returns `Self`.
+    #[inline]
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Get the path of the file at `path` in the root, `None` if it is not served.
+    async fn resolve(&self, path: &str) -> IoResult<Option<PathBuf>> {
+        // `path` is already decoded by the handler, so it is not decoded again.
+        let parts = match sanitize_path_parts(path, cfg!(windows), true) {
+            Some(parts) => parts,
+            None => return Ok(None),
+        };
+        if !self.dot_files && parts.iter().any(|part| part.starts_with('.')) {
+            return Ok(None);
+        }
+        let mut joined = self.root.clone();
+        joined.extend(parts);
+        if !self.follow_symlinks {
+            let canonical = match tokio::fs::canonicalize(&joined).await {
+                Ok(canonical) => canonical,
+                Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+                Err(e) => return Err(e),
+            };
+            if !canonical.starts_with(tokio::fs::canonicalize(&self.root).await?) {
+                tracing::warn!(path = ?joined, "path resolves out of file source root");
+                return Ok(None);
+            }
+        }
+        Ok(Some(joined))
+    }
+
+    async fn resolve_file(&self, path: &str) -> IoResult<PathBuf> {
+        self.resolve(path).await?.ok_or_else(|| ErrorKind::NotFound.into())
+    }
+}
+
+#[async_trait]
+impl AsyncFileSource for FsSource {
+    async fn metadata(&self, path: &str) -> IoResult<Option<SourceMetadata>> {
+        let path = match self.resolve(path).await? {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        match tokio::fs::metadata(path).await {
+            Ok(metadata) if metadata.is_file() => {
+                let mut source_metadata = SourceMetadata::new(metadata.len());
+                source_metadata.modified = metadata.modified().ok();
+                Ok(Some(source_metadata))
+            }
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+    async fn read(&self, path: &str) -> IoResult<Bytes> {
+        tokio::fs::read(self.resolve_file(path).await?).await.map(Bytes::from)
+    }
+    async fn read_range(&self, path: &str, range: Range<u64>) -> IoResult<Bytes> {
+        let mut file = tokio::fs::File::open(self.resolve_file(path).await?).await?;
+        file.seek(SeekFrom::Start(range.start)).await?;
+        let mut data = Vec::with_capacity(range.end.saturating_sub(range.start) as usize);
+        file.take(range.end.saturating_sub(range.start))
+            .read_to_end(&mut data)
+            .await?;
+        Ok(data.into())
+    }
+}
+
+/// Serve static files from an [`AsyncFileSource`].
+///
+/// Files are served with the same contract as [`StaticVfs`](crate::StaticVfs): the MIME type is
+/// guessed from the path, and conditional and range requests are supported. Only the requested ranges
+/// are read from the source for range requests, and nothing for `HEAD` requests and unmodified files.
+///
+/// ```
+/// use salvo_core::prelude::*;
+/// use salvo_serve_static::{AsyncStaticDir, FsSource};
+///
+/// let dir = AsyncStaticDir::new(FsSource::new("static")).with_defaults("index.html");
+/// let router = Router::with_path("<**path>").get(dir);
+/// ```
+#[derive(Clone, Debug)]
+pub struct AsyncStaticDir<S = FsSource> {
+    source: S,
+    /// Default file names list.
+    pub defaults: Vec<String>,
+    /// Fallback file name. This is used when the requested file is not found.
+    pub fallback: Option<String>,
+    /// Reject request paths with malformed percent-encoding or invalid UTF-8 with `400 Bad Request`,
+    /// instead of decoding them lossily. Default is `false`.
+    pub strict_decode: bool,
+}
+
+impl<S> AsyncStaticDir<S>
+where
+    S: AsyncFileSource,
+{
+    /// Create a new `AsyncStaticDir` serving files of `source`.
+    #[inline]
+    pub fn new(source: S) -> Self {
+        AsyncStaticDir {
+            source,
+            defaults: vec![],
+            fallback: None,
+            strict_decode: false,
+        }
+    }
+
+    /// Get the source.
+    #[inline]
+    pub fn source(&self) -> &S {
+        &self.source
+    }
+
+    /// Sets default file names and returns `Self`.
+    #[inline]
+    pub fn with_defaults(mut self, defaults: impl IntoVecString) -> Self {
+        self.defaults = defaults.into_vec_string();
+        self
+    }
+
+    /// Sets fallback file name and returns `Self`.
+    #[inline]
+    pub fn with_fallback(mut self, fallback: impl Into<String>) -> Self {
+        self.fallback = Some(fallback.into());
+        self
+    }
+
+    /// Sets whether malformed request paths are rejected and returns `Self`.
+    #[inline]
+    pub fn with_strict_decode(mut self, strict_decode: bool) -> Self {
+        self.strict_decode = strict_decode;
+        self
+    }
+
+    /// Read the file at `path`, or the bytes in `range` of it, errors are logged.
+    async fn read(&self, path: &str, range: Option<Range<u64>>) -> Option<Bytes> {
+        let data = match range {
+            Some(range) => self.source.read_range(path, range).await,
+            None => self.source.read(path).await,
+        };
+        match data {
+            Ok(data) => Some(data),
+            Err(e) => {
+                tracing::error!(error = ?e, path = %path, "read file from file source failed");
+                None
+            }
+        }
+    }
+
+    async fn find(&self, path: &str) -> Option<(String, SourceMetadata)> {
+        match self.source.metadata(path).await {
+            Ok(metadata) => metadata.map(|metadata| (path.to_owned(), metadata)),
+            Err(e) => {
+                tracing::error!(error = ?e, path = %path, "get metadata from file source failed");
+                None
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<S> Handler for AsyncStaticDir<S>
+where
+    S: AsyncFileSource,
+{
+    async fn handle(&self, req: &mut Request, _depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+        if !check_method_allowed(req, res) {
+            ctrl.skip_rest();
+            return;
+        }
+        if self.strict_decode && reject_malformed_path(req, res) {
+            return;
+        }
+        let param = req.params().iter().find(|(key, _)| key.starts_with('*'));
+        let req_path = if let Some((_, value)) = param {
+            value.clone()
+        } else {
+            decode_url_path_safely(req.uri().path())
+        };
+        if has_control_chars(&req_path) {
+            res.set_status_error(StatusError::not_found());
+            return;
+        }
+        let req_path = format_url_path_safely(&req_path);
+        let mut found = self.find(&req_path).await;
+        if found.is_none() {
+            for default in &self.defaults {
+                let path = if req_path.is_empty() {
+                    default.clone()
+                } else {
+                    format!("{req_path}/{default}")
+                };
+                found = self.find(&path).await;
+                if found.is_some() {
+                    break;
+                }
+            }
+            if found.is_some() && !req.uri().path().ends_with('/') && !req_path.is_empty() {
+                redirect_to_dir_url(req.uri(), StatusCode::FOUND, res);
+                return;
+            }
+        }
+        if found.is_none() {
+            if let Some(fallback) = &self.fallback {
+                found = self.find(&format_url_path_safely(fallback)).await;
+            }
+        }
+
+        let (path, metadata) = match found {
+            Some(_) if req.method() == Method::OPTIONS => {
+                render_file_options(res);
+                return;
+            }
+            Some(found) => found,
+            None => {
+                res.set_status_error(StatusError::not_found());
+                return;
+            }
+        };
+        if let Some(modified) = metadata.modified {
+            res.headers_mut().typed_insert(LastModified::from(modified));
+        }
+        // The content is only read to hash it if the source knows neither the etag nor the modification
+        // time, it is sent as well then.
+        let mut data = None;
+        let etag = match (&metadata.etag, metadata.modified) {
+            (Some(etag), _) => encode_etag(etag),
+            (None, Some(modified)) => modified_etag(metadata.len, modified),
+            (None, None) => match self.read(&path, None).await {
+                Some(content) => {
+                    let etag = format!("\"{}\"", hex::encode(Sha256::digest(&content)));
+                    data = Some(content);
+                    etag
+                }
+                None => {
+                    res.set_status_error(StatusError::internal_server_error());
+                    return;
+                }
+            },
+        };
+        let mime = mime_guess::from_path(&path).first_or_octet_stream();
+        match prepare_bytes(metadata.len, &etag, req, res, &mime) {
+            BytesBody::Empty => {}
+            BytesBody::Whole if data.is_none() && req.method() == Method::HEAD => {
+                res.headers_mut().insert(CONTENT_LENGTH, metadata.len.into());
+            }
+            BytesBody::Whole => {
+                let data = match data {
+                    Some(data) => data,
+                    None => match self.read(&path, None).await {
+                        Some(data) => data,
+                        None => {
+                            res.set_status_error(StatusError::internal_server_error());
+                            return;
+                        }
+                    },
+                };
+                res.write_body(data).ok();
+                strip_head_body(req, res);
+            }
+            BytesBody::Ranges(ranges) => {
+                let mut parts = Vec::with_capacity(ranges.len());
+                for range in &ranges {
+                    let range = range.start..range.start + range.length;
+                    let part = match &data {
+                        Some(data) if range.end <= data.len() as u64 => {
+                            Some(data.slice(range.start as usize..range.end as usize))
+                        }
+                        Some(_) => None,
+                        None => self.read(&path, Some(range.clone())).await,
+                    };
+                    match part {
+                        // The file is changed, or the source is inconsistent, if it returns fewer
+                        // bytes than its size.
+                        Some(part) if part.len() as u64 == range.end - range.start => parts.push(part),
+                        _ => {
+                            res.set_status_error(StatusError::internal_server_error());
+                            return;
+                        }
+                    }
+                }
+                render_ranges(parts, &ranges, metadata.len, &etag, &mime, res);
+                strip_head_body(req, res);
+            }
+        }
+    }
+}
+
+/// Make a strong entity tag from the size and modification time of a file.
+fn modified_etag(len: u64, modified: SystemTime) -> String {
+    let since_epoch = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!(
+        "\"{:x}-{:x}-{:x}\"",
+        len,
+        since_epoch.as_secs(),
+        since_epoch.subsec_nanos()
+    )
+}
+
+/// Get `etag` if it is a valid strong entity tag, otherwise a strong entity tag made from its hash, so
+/// any tag of a source can be sent as a header.
+fn encode_etag(etag: &str) -> String {
+    let is_valid = etag.len() >= 2
+        && etag.starts_with('"')
+        && etag.ends_with('"')
+        && etag[1..etag.len() - 1]
+            .bytes()
+            .all(|b| b == 0x21 || (0x23..=0x7e).contains(&b));
+    if is_valid {
+        etag.to_owned()
+    } else {
+        format!("\"{}\"", hex::encode(Sha256::digest(etag.as_bytes())))
+    }
+}
@@ -0,0 +1,100 @@
+//! `Cache-Control` of served files.
+
+use std::fmt::{self, Display, Formatter};
+use std::time::Duration;
+
+use salvo_core::http::header::{HeaderValue, CACHE_CONTROL};
+use salvo_core::http::{Response, StatusCode};
+
+/// Who may store a cached response.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CacheVisibility {
+    /// `public`, shared caches such as CDNs may store the response.
+    Public,
+    /// `private`, only the browser may store the response.
+    Private,
+}
+
+/// `Cache-Control` header of successful file responses.
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use salvo_serve_static::{CacheControl, CacheVisibility};
+///
+/// let control = CacheControl::new()
+///     .with_visibility(CacheVisibility::Public)
+///     .with_max_age(Duration::from_secs(31536000))
+///     .with_immutable(true);
+/// assert_eq!(control.to_string(), "public, max-age=31536000, immutable");
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CacheControl {
+    /// `max-age` directive, it is rounded down to seconds.
+    pub max_age: Option<Duration>,
+    /// `public` or `private` directive.
+    pub visibility: Option<CacheVisibility>,
+    /// `immutable` directive, the browser doesn't revalidate the response until it is stale.
+    pub immutable: bool,
+}
+
+impl CacheControl {
+    /// Create a new `CacheControl` without any directive.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets max_age and returns `Self`.
+    #[inline]
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Sets visibility and returns `Self`.
+    #[inline]
+    pub fn with_visibility(mut self, visibility: CacheVisibility) -> Self {
+        self.visibility = Some(visibility);
+        self
+    }
+
+    /// Sets immutable and returns `Self`.
+    #[inline]
+    pub fn with_immutable(mut self, immutable: bool) -> Self {
+        self.immutable = immutable;
+        self
+    }
+
+    /// Insert the header to `res` if it is `200 OK` or `206 Partial Content`.
+    pub(crate) fn apply(&self, res: &mut Response) {
+        if !matches!(res.status_code(), Some(StatusCode::OK | StatusCode::PARTIAL_CONTENT)) {
+            return;
+        }
+        let value = self.to_string();
+        if value.is_empty() {
+            return;
+        }
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            res.headers_mut().insert(CACHE_CONTROL, value);
+        }
+    }
+}
+
+impl Display for CacheControl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut directives = Vec::with_capacity(3);
+        match self.visibility {
+            Some(CacheVisibility::Public) => directives.push("public".to_owned()),
+            Some(CacheVisibility::Private) => directives.push("private".to_owned()),
+            None => {}
+        }
+        if let Some(max_age) = self.max_age {
+            directives.push(format!("max-age={}", max_age.as_secs()));
+        }
+        if self.immutable {
+            directives.push("immutable".to_owned());
+        }
+        f.write_str(&directives.join(", "))
+    }
+}
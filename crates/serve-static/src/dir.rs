@@ -1,21 +1,68 @@
 //! serve static dir
 
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::fmt::Write;
 use std::fs::Metadata;
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
 use std::path::{Path, PathBuf};
-use std::time::SystemTime;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
+use base64::engine::{general_purpose, Engine};
+use bytes::Bytes;
+use rand::Rng;
 use salvo_core::fs::NamedFile;
-use salvo_core::http::{Request, Response, StatusCode, StatusError};
-use salvo_core::writer::Text;
+use salvo_core::http::header::{
+    HeaderValue, ACCEPT_ENCODING, ACCEPT_LANGUAGE, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_LANGUAGE,
+    CONTENT_LENGTH, CONTENT_SECURITY_POLICY, CONTENT_TYPE, ETAG, IF_NONE_MATCH, LINK, RANGE, VARY,
+    X_CONTENT_TYPE_OPTIONS,
+};
+use salvo_core::http::uri::Uri;
+use salvo_core::http::{Method, Mime, Request, Response, StatusCode, StatusError};
+use salvo_core::writer::{Redirect, Text};
 use salvo_core::{async_trait, Depot, FlowCtrl, Handler, IntoVecString};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use sha2::{Digest, Sha256};
 use time::{format_description, OffsetDateTime};
+use tokio::io::AsyncReadExt;
 
-use super::{decode_url_path_safely, encode_url_path, format_url_path_safely, redirect_to_dir_url};
+use super::etag::{self, apply_etag_mode, new_etag_cache, EtagCache};
+use super::file_cache::{FileCache, FILE_CACHE_CAPACITY};
+use super::json_listing::json_listing;
+use super::media::{format_duration, read_duration};
+use super::not_found::render_not_found;
+use super::vfs::render_bytes;
+use super::zip::zip_dir;
+use super::{
+    check_method_allowed, decode_url_path_safely, encode_url_path, format_url_path_safely, has_control_chars,
+    longest_prefix_match, matches_prefix, mime_override, normalize_extension, redirect_to_dir_url,
+    reject_malformed_path, render_file_options, safe_join, CacheControl, Disposition, GlobSet, NotFoundHandler,
+};
+
+/// Resolver of default file names set with [`StaticDir::with_defaults_resolver`].
+type DefaultsResolver = dyn Fn(&Path) -> Vec<String> + Send + Sync;
+
+/// Placeholder of the nonce in HTML files and `Content-Security-Policy`, see [`StaticDir::csp_nonce`].
+const CSP_NONCE_PLACEHOLDER: &str = "{nonce}";
+
+/// Maximum number of request paths remembered by the negative cache of [`StaticDir`].
+const NEGATIVE_CACHE_CAPACITY: usize = 10_000;
+
+/// Number of bytes read to sniff the content type of a file.
+const SNIFF_SIZE: usize = 512;
+
+/// Default maximum size of files rewritten with `substitutions` of [`StaticDir`].
+const DEFAULT_MAX_SUBSTITUTION_SIZE: u64 = 1024 * 1024;
+
+/// Number of files rewritten with `substitutions` whose content is kept by [`StaticDir`].
+const SUBSTITUTION_CACHE_CAPACITY: usize = 128;
+
+/// Header with the duration of media files in seconds.
+const X_CONTENT_DURATION: &str = "x-content-duration";
 
 /// Static roots.
 pub trait StaticRoots {
@@ -66,10 +113,218 @@ where
     }
 }
 
+/// How ETags of files served by [`StaticDir`] and [`StaticFile`](crate::StaticFile) are computed.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum EtagMode {
+    /// Computed from inode, size and modification time, it is cheap but differs between machines
+    /// and deployments even if the content is the same.
+    #[default]
+    Metadata,
+    /// Strong ETag of the SHA-256 hash of the file content, it is identical on all machines serving
+    /// the same content. Hashes are cached by path, size and modification time, so a file is only
+    /// hashed again when it changes.
+    ContentHash,
+    /// No ETag is sent, conditional requests are validated with `Last-Modified` only. It suits
+    /// deployments where ETags computed on different machines would never match, and content hashes
+    /// are too expensive.
+    Disabled,
+}
+
+/// Content type of files served by [`StaticDir`] whose type can not be guessed from the extension.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum UnknownMimePolicy {
+    /// `application/octet-stream`, browsers download the file.
+    #[default]
+    OctetStream,
+    /// `text/plain`, browsers display the file.
+    TextPlain,
+    /// Sniff the first bytes of the file, `text/plain` is used for UTF-8 text without NUL bytes,
+    /// otherwise `application/octet-stream`.
+    Sniff,
+}
+
+/// What [`StaticDir`] does when a directory is requested without a trailing slash.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum RedirectPolicy {
+    /// Redirect to the path with a trailing slash with `302 Found`, the query is kept.
+    #[default]
+    Found,
+    /// Redirect to the path with a trailing slash with `301 Moved Permanently`, the query is kept.
+    MovedPermanently,
+    /// Redirect to the path with a trailing slash with `308 Permanent Redirect`, the query is kept.
+    PermanentRedirect,
+    /// Serve the default file or listing of the directory directly. Links in listings are resolved
+    /// against the directory, relative links in default files are resolved against the parent
+    /// directory by browsers.
+    ServeIndex,
+}
+
+impl RedirectPolicy {
+    fn status_code(self) -> Option<StatusCode> {
+        match self {
+            RedirectPolicy::Found => Some(StatusCode::FOUND),
+            RedirectPolicy::MovedPermanently => Some(StatusCode::MOVED_PERMANENTLY),
+            RedirectPolicy::PermanentRedirect => Some(StatusCode::PERMANENT_REDIRECT),
+            RedirectPolicy::ServeIndex => None,
+        }
+    }
+}
+
+/// What [`StaticDir`] does when a directory is requested.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum DirectoryPolicy {
+    /// Serve the default file of the directory, or render the listing if there is none.
+    Listing,
+    /// Serve the default file of the directory, or respond `404 Not Found` if there is none.
+    #[default]
+    IndexOnly,
+    /// Respond `403 Forbidden`, whether the directory has a default file or not.
+    Forbidden,
+}
+
+/// Order of entries in directory listings of [`StaticDir`], directories always come before files.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ListingSort {
+    /// By name in ascending order.
+    #[default]
+    NameAsc,
+    /// By name in descending order.
+    NameDesc,
+    /// Largest files first, directories are sorted by name.
+    Size,
+    /// Most recently modified first.
+    Modified,
+}
+
+impl ListingSort {
+    fn sort(&self, files: &mut [FileInfo], dirs: &mut [DirInfo]) {
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        dirs.sort_by(|a, b| a.name.cmp(&b.name));
+        match self {
+            ListingSort::NameAsc => {}
+            ListingSort::NameDesc => {
+                files.reverse();
+                dirs.reverse();
+            }
+            ListingSort::Size => files.sort_by(|a, b| b.size.cmp(&a.size)),
+            ListingSort::Modified => {
+                files.sort_by(|a, b| b.modified.cmp(&a.modified));
+                dirs.sort_by(|a, b| b.modified.cmp(&a.modified));
+            }
+        }
+    }
+}
+
+/// Compression of pre-compressed sidecar files served by [`StaticDir`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PrecompressedEncoding {
+    /// Brotli, the sidecar of `app.js` is `app.js.br`.
+    Brotli,
+    /// Gzip, the sidecar of `app.js` is `app.js.gz`.
+    Gzip,
+}
+impl PrecompressedEncoding {
+    /// Get the extension of sidecar files without leading dot.
+    #[inline]
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PrecompressedEncoding::Brotli => "br",
+            PrecompressedEncoding::Gzip => "gz",
+        }
+    }
+
+    /// Get the value of `Content-Encoding` and `Accept-Encoding`.
+    #[inline]
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            PrecompressedEncoding::Brotli => "br",
+            PrecompressedEncoding::Gzip => "gzip",
+        }
+    }
+}
+
+/// Serving metrics of a [`StaticDir`].
+///
+/// Counters are updated with atomics in the serve path, so reading them is cheap.
+#[derive(Default, Debug)]
+pub struct StaticDirMetrics {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    not_modified: AtomicU64,
+    bytes_served: AtomicU64,
+    negative_cache_hits: AtomicU64,
+    #[cfg(test)]
+    pub(crate) root_lookups: AtomicU64,
+}
+impl StaticDirMetrics {
+    /// Number of files or listings served successfully.
+    #[inline]
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+    /// Number of requests which returned `404 Not Found`.
+    #[inline]
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+    /// Number of requests which returned `304 Not Modified`.
+    #[inline]
+    pub fn not_modified(&self) -> u64 {
+        self.not_modified.load(Ordering::Relaxed)
+    }
+    /// Total body bytes served.
+    #[inline]
+    pub fn bytes_served(&self) -> u64 {
+        self.bytes_served.load(Ordering::Relaxed)
+    }
+    /// Number of misses answered from the negative cache without looking up the filesystem, they
+    /// are counted in `misses` as well.
+    #[inline]
+    pub fn negative_cache_hits(&self) -> u64 {
+        self.negative_cache_hits.load(Ordering::Relaxed)
+    }
+    /// Ratio of `304 Not Modified` responses among all found requests (hits and not modified).
+    #[inline]
+    pub fn cache_hit_ratio(&self) -> f64 {
+        let not_modified = self.not_modified();
+        let total = self.hits() + not_modified;
+        if total == 0 {
+            0.0
+        } else {
+            not_modified as f64 / total as f64
+        }
+    }
+
+    fn record(&self, res: &Response) {
+        match res.status_code() {
+            Some(StatusCode::NOT_FOUND) => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+            }
+            Some(StatusCode::NOT_MODIFIED) => {
+                self.not_modified.fetch_add(1, Ordering::Relaxed);
+            }
+            Some(code) if code.is_success() => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                let bytes = res
+                    .headers()
+                    .get(CONTENT_LENGTH)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.parse::<u64>().ok())
+                    .or_else(|| res.body().size())
+                    .unwrap_or(0);
+                self.bytes_served.fetch_add(bytes, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+    }
+}
+
 /// StaticDir
 #[derive(Clone)]
 pub struct StaticDir {
-    /// Static roots.
+    /// Static roots, they are searched in order. A file is served from the first root containing it,
+    /// a directory listing merges the entries of the directory in all roots, the entry of the earliest
+    /// root wins if a name exists in several of them.
     pub roots: Vec<PathBuf>,
     /// During the file chunk read, the maximum read size at one time will affect the
     /// access experience and the demand for server memory.
@@ -80,54 +335,1038 @@ pub struct StaticDir {
     pub chunk_size: Option<u64>,
     /// List dot files.
     pub dot_files: bool,
-    /// Listing dir
+    /// Paths matched by these patterns are not served, listed or included in zip archives.
+    pub excludes: GlobSet,
+    /// What is done when a directory is requested. Default is [`DirectoryPolicy::IndexOnly`].
+    ///
+    /// The format of listings is negotiated by `Accept`, and the `format` query, which is one of
+    /// `html`, `json`, `xml` or `text`, takes precedence over it, e.g. `/files/?format=json`.
+    pub directory_policy: DirectoryPolicy,
+    /// Listing dir, directories are listed if it is `true` and `directory_policy` is
+    /// [`DirectoryPolicy::IndexOnly`].
+    #[deprecated(note = "use `directory_policy` with `DirectoryPolicy::Listing` instead")]
     pub listing: bool,
     /// Default file names list.
+    ///
+    /// When a directory contains more than one of these files, the first one in this list wins.
     pub defaults: Vec<String>,
+    /// Default file names lists by directory prefix, the list of the longest prefix matching the
+    /// requested directory is used instead of `defaults`.
+    pub prefix_defaults: HashMap<String, Vec<String>>,
     /// Fallback file name. This is used when the requested file is not found.
     pub fallback: Option<String>,
+    /// Response when the requested file is not found, the default is the `404 Not Found` error page.
+    /// It is not used if `fallback` is found or the request falls through.
+    pub not_found_handler: Option<NotFoundHandler>,
+    /// Redirect requests with mismatched case to the on-disk casing of the file with `301 Moved Permanently`.
+    ///
+    /// This is useful on case-insensitive filesystems to avoid duplicated content.
+    pub canonical_case_redirect: bool,
+    /// What to do when a directory is requested without a trailing slash, the default redirects with
+    /// `302 Found`.
+    pub redirect_policy: RedirectPolicy,
+    /// Negotiate language variants by `Accept-Language`, a request to `page` is served by
+    /// `page.<lang>.html` if `page` does not exist.
+    pub language_negotiation: bool,
+    /// Language used when no variant matches `Accept-Language`.
+    pub default_language: Option<String>,
+    /// Download a directory as zip archive when it is requested with `?download=zip`.
+    ///
+    /// The archive is streamed on the fly, dot files are included only if `dot_files` is `true`,
+    /// and paths matched by `excludes` are not included.
+    pub zip_download: bool,
+    /// HTML served instead of the listing when a listed directory is empty, `{path}` is replaced with
+    /// the requested path.
+    pub empty_listing: Option<String>,
+    /// Maximum number of entries rendered in a listing, directories are kept before files. Listings
+    /// with more entries are marked as truncated, see [`CurrentInfo::truncated`].
+    pub max_listing_entries: Option<usize>,
+    /// Order of entries in listings.
+    pub listing_sort: ListingSort,
+    /// Omit file sizes and modification times from listings, only names are listed. This keeps the
+    /// sizes of private files, like encrypted blobs, from being exposed. Entries are sorted by name
+    /// then, as sorting by size or modification time would expose them as well.
+    pub listing_hide_metadata: bool,
+    /// Send a weak ETag with listings and answer `If-None-Match` with `304 Not Modified`. The ETag is
+    /// computed from the names, sizes and modification times of the entries and the listing format.
+    pub listing_etag: bool,
+    /// Name of the query parameter overriding the listing format negotiated from `Accept`, its value is
+    /// one of `html`, `json`, `xml` or `text`, unknown values are ignored. `None` disables the override.
+    /// Default is `Some("format")`.
+    pub listing_format_query: Option<String>,
+    /// Stream JSON listings while the directory is read, instead of building the whole listing in
+    /// memory, this suits very large directories. Default is `false`.
+    ///
+    /// Streamed listings have the fields of the JSON listing which is not streamed, see
+    /// [`CurrentInfo`], with `dirs` written before `files`. Entries are listed in the order they are
+    /// read, `listing_sort`, `listing_etag`, `empty_listing` and listing renderers don't apply to
+    /// streamed listings, `max_listing_entries` and `listing_hide_metadata` do.
+    pub streaming_json_listing: bool,
+    /// How ETags of served files are computed.
+    pub etag_mode: EtagMode,
+    /// Content type of files whose type can not be guessed from the extension.
+    pub unknown_mime: UnknownMimePolicy,
+    /// Content types of files by lower case extension without leading dot, they take precedence
+    /// over the guessed content type and `unknown_mime`.
+    pub mime_overrides: HashMap<String, Mime>,
+    /// `Cache-Control` of successful file responses, directory listings and errors don't have it.
+    pub cache_control: Option<CacheControl>,
+    /// `Cache-Control` of files by lower case extension without leading dot, they override
+    /// `cache_control`.
+    pub extension_cache_controls: HashMap<String, CacheControl>,
+    /// `Content-Disposition` of files by lower case extension without leading dot. Files of other
+    /// extensions are sent inline if their content type is text, image, audio or video, and as
+    /// attachment otherwise. They don't apply to files sent as attachment by `untrusted_prefixes`.
+    pub extension_dispositions: HashMap<String, Disposition>,
+    /// Fall through to the next handlers when a directory without default file is requested and
+    /// `directory_policy` is [`DirectoryPolicy::IndexOnly`], instead of responding `404 Not Found`.
+    ///
+    /// `StaticDir` should be added as a hoop, so the next handlers of the router are called, for
+    /// example `Router::with_path("<**path>").hoop(static_dir).get(search)`. Other handlers after it
+    /// are skipped if the request is served.
+    pub fall_through: bool,
+    /// Add `X-Content-Duration` header with the duration in seconds to responses of media files of
+    /// known formats, which are WAV and FLAC.
+    ///
+    /// The duration is read from the head of the file once and cached until the file is modified.
+    pub media_duration: bool,
+    /// Serve files and directories reached through symlinks which point outside of the roots.
+    ///
+    /// When it is `false`, the resolved path must be under one of the roots, `404 Not Found` is
+    /// returned otherwise. Symlinks which point inside of the roots are always followed. Default is
+    /// `false`.
+    pub follow_symlinks: bool,
+    /// How long a request path which is not found is remembered, repeated requests for it within
+    /// this time get `404 Not Found` without looking up the files.
+    ///
+    /// The cached miss is dropped when the parent directory of the path in any root is modified, so
+    /// a file created there is served at once. With `language_negotiation`, misses are remembered by
+    /// the languages accepted by the request as well. It is disabled by default.
+    pub negative_cache_ttl: Option<Duration>,
+    /// Pre-compressed sidecar files served instead of the requested file if `Accept-Encoding` allows
+    /// it, they are looked up in this order.
+    ///
+    /// The sidecar is served with `Content-Encoding` and the content type of the requested file, and
+    /// its own ETag. The requested file is served if no sidecar is accepted or exists.
+    ///
+    /// Range requests are always served from the requested file, so a range never applies to a
+    /// compressed body and an `If-Range` ETag of the requested file keeps matching.
+    pub precompressed: Vec<PrecompressedEncoding>,
+    /// `Content-Security-Policy` of successful responses of HTML files.
+    pub content_security_policy: Option<String>,
+    /// Generate a nonce for each response of HTML files, `{nonce}` placeholders in the HTML file and in
+    /// `content_security_policy` are replaced with it.
+    ///
+    /// HTML files are read into memory to be rewritten, they are sent with `Cache-Control: no-store`
+    /// and without ETag or byte ranges, because the nonce must not be reused. Files larger than
+    /// `max_substitution_size` are sent as is, the placeholders in them and in the policy are left,
+    /// so inline scripts are blocked.
+    pub csp_nonce: bool,
+    /// `Link` header values sent with default HTML files of directories, like `index.html`, to preload
+    /// critical assets, like `</app.css>; rel=preload; as=style`.
+    ///
+    /// They are sent as headers of the final response, not as `103 Early Hints`, because the HTTP
+    /// server can't send informational responses. Proxies and CDNs supporting early hints send them
+    /// to clients from the `Link` headers of previous responses.
+    pub preload_links: Vec<HeaderValue>,
+    /// Values of `{{NAME}}` placeholders replaced in files whose content type is in
+    /// `substitution_types`, like the base URL of an API or the build version. Placeholders without a
+    /// value are kept as is.
+    ///
+    /// Rewritten files are read into memory and sent with a strong ETag of the rewritten content, so
+    /// conditional and range requests are supported, but pre-compressed sidecars are not used. Files
+    /// larger than `max_substitution_size` or not valid UTF-8 are sent as is.
+    pub substitutions: HashMap<String, String>,
+    /// Content types, without parameters, of files rewritten with `substitutions`. Default is
+    /// `text/html`, `text/javascript` and `application/javascript`.
+    pub substitution_types: Vec<String>,
+    /// Maximum size in bytes of files rewritten with `substitutions` or `csp_nonce`. Default is 1 MiB.
+    pub max_substitution_size: u64,
+    /// Directory prefixes, relative to the roots, of untrusted content like user uploads. Files under
+    /// them are sent with `X-Content-Type-Options: nosniff`, files of unknown types as
+    /// `application/octet-stream`, and files of types other than plain text, images except SVG, audio
+    /// and video with `Content-Disposition: attachment`, so browsers download them instead of rendering
+    /// them. `substitutions` and `csp_nonce` are not applied to them.
+    pub untrusted_prefixes: Vec<String>,
+    /// Reject request paths with malformed percent-encoding or invalid UTF-8 with `400 Bad Request`,
+    /// instead of decoding them lossily. Default is `false`.
+    pub strict_decode: bool,
+    metrics: Arc<StaticDirMetrics>,
+    etag_cache: EtagCache,
+    duration_cache: Arc<FileCache<Option<f64>>>,
+    /// Substituted content and ETags of files, it is replaced when `substitutions` are set, so
+    /// clones with other substitutions don't share it.
+    substitution_cache: Arc<FileCache<Option<(Bytes, String)>>>,
+    negative_cache: Arc<Mutex<HashMap<String, (Instant, Vec<Option<SystemTime>>)>>>,
+    listing_renderers: Vec<(Mime, Arc<dyn DirListRenderer>)>,
+    listing_renderer: Arc<dyn DirListRenderer>,
+    defaults_resolver: Option<Arc<DefaultsResolver>>,
+    /// Roots canonicalized when `StaticDir` is created, paired with the roots they come from.
+    canonical_roots: Vec<(PathBuf, Option<PathBuf>)>,
 }
 impl StaticDir {
     /// Create new `StaticDir`.
     #[inline]
+    #[allow(deprecated)]
     pub fn new<T: StaticRoots + Sized>(roots: T) -> Self {
+        let roots = roots.collect();
+        let canonical_roots = canonicalize_roots(&roots);
         StaticDir {
-            roots: roots.collect(),
+            roots,
             chunk_size: None,
             dot_files: false,
+            excludes: GlobSet::new(),
+            directory_policy: DirectoryPolicy::default(),
             listing: false,
             defaults: vec![],
+            prefix_defaults: HashMap::new(),
             fallback: None,
+            not_found_handler: None,
+            canonical_case_redirect: false,
+            redirect_policy: RedirectPolicy::default(),
+            language_negotiation: false,
+            default_language: None,
+            zip_download: false,
+            empty_listing: None,
+            max_listing_entries: None,
+            listing_sort: ListingSort::default(),
+            listing_hide_metadata: false,
+            listing_etag: false,
+            listing_format_query: Some("format".into()),
+            streaming_json_listing: false,
+            etag_mode: EtagMode::default(),
+            unknown_mime: UnknownMimePolicy::default(),
+            mime_overrides: HashMap::new(),
+            cache_control: None,
+            extension_cache_controls: HashMap::new(),
+            extension_dispositions: HashMap::new(),
+            fall_through: false,
+            media_duration: false,
+            follow_symlinks: false,
+            negative_cache_ttl: None,
+            precompressed: vec![],
+            content_security_policy: None,
+            csp_nonce: false,
+            preload_links: vec![],
+            substitutions: HashMap::new(),
+            substitution_types: vec![
+                "text/html".into(),
+                "text/javascript".into(),
+                "application/javascript".into(),
+            ],
+            max_substitution_size: DEFAULT_MAX_SUBSTITUTION_SIZE,
+            untrusted_prefixes: vec![],
+            strict_decode: false,
+            metrics: Arc::new(StaticDirMetrics::default()),
+            etag_cache: new_etag_cache(),
+            duration_cache: Arc::new(FileCache::new(FILE_CACHE_CAPACITY)),
+            substitution_cache: Arc::new(FileCache::new(SUBSTITUTION_CACHE_CAPACITY)),
+            negative_cache: Arc::new(Mutex::new(HashMap::new())),
+            listing_renderers: vec![],
+            listing_renderer: Arc::new(DefaultDirListRenderer),
+            defaults_resolver: None,
+            canonical_roots,
+        }
+    }
+
+    /// Validate the configuration, this is useful to fail fast at startup instead of on the
+    /// first request.
+    ///
+    /// Returns an error if there is no root, a root does not exist, is not a directory or can not
+    /// be read, a default file name is not a plain file name, or an exclude pattern is invalid.
+    /// Overlapping roots are allowed, but a warning is logged.
+    pub fn validate(&self) -> IoResult<()> {
+        if self.roots.is_empty() {
+            return Err(IoError::new(ErrorKind::InvalidInput, "static dir has no root"));
+        }
+        let mut roots = Vec::with_capacity(self.roots.len());
+        for root in &self.roots {
+            let metadata = std::fs::metadata(root).map_err(|e| {
+                IoError::new(
+                    e.kind(),
+                    format!("static root `{}` is not accessible: {e}", root.display()),
+                )
+            })?;
+            if !metadata.is_dir() {
+                return Err(IoError::new(
+                    ErrorKind::InvalidInput,
+                    format!("static root `{}` is not a directory", root.display()),
+                ));
+            }
+            std::fs::read_dir(root).map_err(|e| {
+                IoError::new(
+                    e.kind(),
+                    format!("static root `{}` is not readable: {e}", root.display()),
+                )
+            })?;
+            roots.push(root.canonicalize().unwrap_or_else(|_| root.clone()));
+        }
+        for (i, root) in roots.iter().enumerate() {
+            for other in &roots[i + 1..] {
+                if root.starts_with(other) || other.starts_with(root) {
+                    tracing::warn!(root = ?root, other = ?other, "static roots overlap");
+                }
+            }
+        }
+        if let Some(name) = self
+            .defaults
+            .iter()
+            .chain(self.prefix_defaults.values().flatten())
+            .find(|name| !is_default_name(name))
+        {
+            return Err(IoError::new(
+                ErrorKind::InvalidInput,
+                format!("default file name `{name}` is not a file name"),
+            ));
+        }
+        self.excludes.check().map_err(|pattern| {
+            IoError::new(
+                ErrorKind::InvalidInput,
+                format!("exclude pattern `{pattern}` is invalid"),
+            )
+        })
+    }
+
+    /// Get the serving metrics handle, it is shared by all clones of this `StaticDir`.
+    #[inline]
+    pub fn metrics(&self) -> Arc<StaticDirMetrics> {
+        self.metrics.clone()
+    }
+
+    /// Sets dot_files and returns a new `StaticDirOptions`.
+    #[inline]
+    pub fn with_dot_files(mut self, dot_files: bool) -> Self {
+        self.dot_files = dot_files;
+        self
+    }
+
+    /// Sets excludes and returns a new `StaticDirOptions`.
+    #[inline]
+    pub fn with_excludes(mut self, excludes: GlobSet) -> Self {
+        self.excludes = excludes;
+        self
+    }
+
+    /// Sets directory_policy to [`DirectoryPolicy::Listing`] if `listing` is `true`, or to
+    /// [`DirectoryPolicy::IndexOnly`] otherwise, and returns a new `StaticDirOptions`.
+    #[inline]
+    #[allow(deprecated)]
+    pub fn with_listing(mut self, listing: bool) -> Self {
+        self.directory_policy = if listing {
+            DirectoryPolicy::Listing
+        } else {
+            DirectoryPolicy::IndexOnly
+        };
+        self.listing = false;
+        self
+    }
+
+    /// Sets directory_policy and returns a new `StaticDirOptions`.
+    #[inline]
+    #[allow(deprecated)]
+    pub fn with_directory_policy(mut self, directory_policy: DirectoryPolicy) -> Self {
+        self.directory_policy = directory_policy;
+        self.listing = false;
+        self
+    }
+
+    /// Get the policy for directories, the deprecated `listing` turns
+    /// [`DirectoryPolicy::IndexOnly`] into [`DirectoryPolicy::Listing`].
+    #[allow(deprecated)]
+    fn effective_directory_policy(&self) -> DirectoryPolicy {
+        if self.listing && self.directory_policy == DirectoryPolicy::IndexOnly {
+            DirectoryPolicy::Listing
+        } else {
+            self.directory_policy
+        }
+    }
+
+    /// Sets defaults and returns a new `StaticDirOptions`.
+    ///
+    /// The order of `defaults` is authoritative, the first existing file is served.
+    #[inline]
+    pub fn with_defaults(mut self, defaults: impl IntoVecString) -> Self {
+        self.defaults = defaults.into_vec_string();
+        self
+    }
+
+    /// Sets prefix_defaults and returns a new `StaticDirOptions`.
+    ///
+    /// The key is a directory prefix and the value is the default file names list of directories
+    /// under it, the longest matching prefix wins. Directories which match no prefix use `defaults`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    ///
+    /// use salvo_serve_static::StaticDir;
+    ///
+    /// let static_dir = StaticDir::new("static")
+    ///     .with_defaults("index.html")
+    ///     .with_prefix_defaults(HashMap::from([("docs".to_owned(), vec!["readme.html".to_owned()])]));
+    /// ```
+    #[inline]
+    pub fn with_prefix_defaults(mut self, prefix_defaults: HashMap<String, Vec<String>>) -> Self {
+        self.prefix_defaults = prefix_defaults;
+        self
+    }
+
+    /// Sets the resolver of default file names by directory and returns a new `StaticDirOptions`.
+    ///
+    /// The resolver is called with the path of the requested directory relative to the roots, it is
+    /// empty for the roots themselves, and returns the ordered default file names of the directory.
+    /// `prefix_defaults` and `defaults` are used if it returns an empty list. Names which are not
+    /// plain file names are ignored.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use salvo_serve_static::StaticDir;
+    ///
+    /// // The home page is `home.html`, other directories have `index.html`.
+    /// let static_dir = StaticDir::new("static").with_defaults("index.html").with_defaults_resolver(|dir| {
+    ///     if dir.as_os_str().is_empty() {
+    ///         vec!["home.html".to_owned()]
+    ///     } else {
+    ///         vec![]
+    ///     }
+    /// });
+    /// ```
+    #[inline]
+    pub fn with_defaults_resolver<F>(mut self, resolver: F) -> Self
+    where
+        F: Fn(&Path) -> Vec<String> + Send + Sync + 'static,
+    {
+        self.defaults_resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Get the default file names of the directory at `rel_path`.
+    fn defaults_of(&self, rel_path: &str) -> Cow<'_, [String]> {
+        if let Some(resolver) = &self.defaults_resolver {
+            let defaults = resolver(Path::new(rel_path))
+                .into_iter()
+                .filter(|name| is_default_name(name))
+                .collect::<Vec<_>>();
+            if !defaults.is_empty() {
+                return Cow::Owned(defaults);
+            }
+        }
+        Cow::Borrowed(longest_prefix_match(&self.prefix_defaults, rel_path).unwrap_or(&self.defaults))
+    }
+
+    /// Sets fallback and returns a new `StaticDirOptions`.
+    pub fn with_fallback(mut self, fallback: impl Into<String>) -> Self {
+        self.fallback = Some(fallback.into());
+        self
+    }
+
+    /// Sets not_found_handler and returns a new `StaticDirOptions`.
+    ///
+    /// A file path is relative to the roots, the file is served with `404 Not Found`.
+    #[inline]
+    pub fn with_not_found_handler(mut self, handler: impl Into<NotFoundHandler>) -> Self {
+        self.not_found_handler = Some(handler.into());
+        self
+    }
+
+    /// Sets canonical_case_redirect and returns a new `StaticDirOptions`.
+    #[inline]
+    pub fn with_canonical_case_redirect(mut self, canonical_case_redirect: bool) -> Self {
+        self.canonical_case_redirect = canonical_case_redirect;
+        self
+    }
+
+    /// Sets redirect_policy and returns a new `StaticDirOptions`.
+    #[inline]
+    pub fn with_redirect_policy(mut self, redirect_policy: RedirectPolicy) -> Self {
+        self.redirect_policy = redirect_policy;
+        self
+    }
+
+    /// Sets language_negotiation and returns a new `StaticDirOptions`.
+    #[inline]
+    pub fn with_language_negotiation(mut self, language_negotiation: bool) -> Self {
+        self.language_negotiation = language_negotiation;
+        self
+    }
+
+    /// Sets default_language and returns a new `StaticDirOptions`.
+    #[inline]
+    pub fn with_default_language(mut self, default_language: impl Into<String>) -> Self {
+        self.default_language = Some(default_language.into());
+        self
+    }
+
+    /// Sets zip_download and returns a new `StaticDirOptions`.
+    #[inline]
+    pub fn with_zip_download(mut self, zip_download: bool) -> Self {
+        self.zip_download = zip_download;
+        self
+    }
+
+    /// Sets empty_listing and returns a new `StaticDirOptions`.
+    #[inline]
+    pub fn with_empty_listing(mut self, empty_listing: impl Into<String>) -> Self {
+        self.empty_listing = Some(empty_listing.into());
+        self
+    }
+
+    /// Sets max_listing_entries and returns a new `StaticDirOptions`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use salvo_serve_static::{ListingSort, StaticDir};
+    ///
+    /// // List the 1000 most recently modified entries.
+    /// let static_dir = StaticDir::new("static")
+    ///     .with_listing(true)
+    ///     .with_max_listing_entries(1000)
+    ///     .with_listing_sort(ListingSort::Modified);
+    /// ```
+    #[inline]
+    pub fn with_max_listing_entries(mut self, max: impl Into<Option<usize>>) -> Self {
+        self.max_listing_entries = max.into();
+        self
+    }
+
+    /// Sets listing_hide_metadata and returns a new `StaticDirOptions`.
+    #[inline]
+    pub fn with_listing_hide_metadata(mut self, listing_hide_metadata: bool) -> Self {
+        self.listing_hide_metadata = listing_hide_metadata;
+        self
+    }
+
+    /// Sets listing_etag and returns a new `StaticDirOptions`.
+    #[inline]
+    pub fn with_listing_etag(mut self, listing_etag: bool) -> Self {
+        self.listing_etag = listing_etag;
+        self
+    }
+
+    /// Sets listing_format_query and returns a new `StaticDirOptions`, `None` disables the override.
+    #[inline]
+    pub fn with_listing_format_query(mut self, listing_format_query: Option<&str>) -> Self {
+        self.listing_format_query = listing_format_query.map(ToOwned::to_owned);
+        self
+    }
+
+    /// Sets streaming_json_listing and returns a new `StaticDirOptions`.
+    #[inline]
+    pub fn with_streaming_json_listing(mut self, streaming_json_listing: bool) -> Self {
+        self.streaming_json_listing = streaming_json_listing;
+        self
+    }
+
+    /// Sets listing_sort and returns a new `StaticDirOptions`.
+    #[inline]
+    pub fn with_listing_sort(mut self, listing_sort: ListingSort) -> Self {
+        self.listing_sort = listing_sort;
+        self
+    }
+
+    /// Sets fall_through and returns a new `StaticDirOptions`.
+    #[inline]
+    pub fn with_fall_through(mut self, fall_through: bool) -> Self {
+        self.fall_through = fall_through;
+        self
+    }
+
+    /// Sets negative_cache_ttl and returns a new `StaticDirOptions`.
+    #[inline]
+    pub fn with_negative_cache_ttl(mut self, ttl: impl Into<Option<Duration>>) -> Self {
+        self.negative_cache_ttl = ttl.into();
+        self
+    }
+
+    /// Get the modification times of the parent directories of `rel_path` in the roots, they change
+    /// when a file is created in or removed from the directories.
+    fn parent_mtimes(&self, rel_path: &str) -> Vec<Option<SystemTime>> {
+        self.roots
+            .iter()
+            .map(|root| {
+                let path = root.join(rel_path);
+                let metadata = path.parent().and_then(|dir| std::fs::metadata(dir).ok());
+                metadata.and_then(|metadata| metadata.modified().ok())
+            })
+            .collect()
+    }
+
+    /// Get the key of `rel_path` in the negative cache, whether it is found depends on the accepted
+    /// languages with `language_negotiation`.
+    fn negative_cache_key(&self, rel_path: &str, req: &Request) -> String {
+        if self.language_negotiation {
+            format!("{rel_path}\0{}", self.candidate_languages(req).join(","))
+        } else {
+            rel_path.to_owned()
+        }
+    }
+
+    /// Check whether `key` of `rel_path` is a cached miss which is neither expired nor invalidated,
+    /// only the parent directories are looked up then.
+    fn is_cached_miss(&self, key: &str, rel_path: &str, ttl: Duration) -> bool {
+        let cached = self.negative_cache.lock().unwrap().get(key).cloned();
+        match cached {
+            Some((cached_at, mtimes)) if cached_at.elapsed() < ttl && mtimes == self.parent_mtimes(rel_path) => true,
+            Some(_) => {
+                self.negative_cache.lock().unwrap().remove(key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Remember `key` of `rel_path` as a miss, expired misses are dropped when the cache is full.
+    fn cache_miss(&self, key: String, rel_path: &str, ttl: Duration) {
+        let mtimes = self.parent_mtimes(rel_path);
+        let mut cache = self.negative_cache.lock().unwrap();
+        if cache.len() >= NEGATIVE_CACHE_CAPACITY {
+            cache.retain(|_, (cached_at, _)| cached_at.elapsed() < ttl);
+            if cache.len() >= NEGATIVE_CACHE_CAPACITY {
+                cache.clear();
+            }
         }
+        cache.insert(key, (Instant::now(), mtimes));
+    }
+
+    /// Sets follow_symlinks and returns a new `StaticDirOptions`.
+    #[inline]
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Check whether `path` resolves to a path under one of the roots, it is always `true` if
+    /// `follow_symlinks` is `true`.
+    ///
+    /// The canonicalized roots are the boundaries, so a root which is itself a symlink, like
+    /// `/var/www/current` pointing to a release directory, serves the files under its target.
+    fn is_contained(&self, path: &Path) -> bool {
+        if self.follow_symlinks {
+            return true;
+        }
+        let path = match path.canonicalize() {
+            Ok(path) => path,
+            Err(_) => return false,
+        };
+        self.roots.iter().enumerate().any(|(i, root)| {
+            if let Some((original, Some(canonical))) = self.canonical_roots.get(i) {
+                if original == root && path.starts_with(canonical) {
+                    return true;
+                }
+            }
+            // The root is canonicalized again if it did not exist when `StaticDir` was created, or it
+            // was changed since, like a `current` symlink switched to a new release.
+            root.canonicalize().map(|root| path.starts_with(root)).unwrap_or(false)
+        })
+    }
+
+    /// Sets etag_mode and returns a new `StaticDirOptions`.
+    #[inline]
+    pub fn with_etag_mode(mut self, etag_mode: EtagMode) -> Self {
+        self.etag_mode = etag_mode;
+        self
+    }
+
+    /// Sets cache_control and returns a new `StaticDirOptions`.
+    #[inline]
+    pub fn with_cache_control(mut self, cache_control: CacheControl) -> Self {
+        self.cache_control = Some(cache_control);
+        self
+    }
+
+    /// Sets `Cache-Control` of files with `extension` and returns a new `StaticDirOptions`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use salvo_serve_static::{CacheControl, StaticDir};
+    ///
+    /// let static_dir = StaticDir::new("static")
+    ///     .with_cache_control(CacheControl::new().with_max_age(Duration::from_secs(3600)))
+    ///     .with_extension_cache_control("html", CacheControl::new().with_max_age(Duration::from_secs(60)))
+    ///     .with_extension_cache_control(".js", CacheControl::new().with_max_age(Duration::from_secs(31536000)));
+    /// ```
+    #[inline]
+    pub fn with_extension_cache_control(mut self, extension: impl AsRef<str>, cache_control: CacheControl) -> Self {
+        let extension = normalize_extension(extension.as_ref());
+        self.extension_cache_controls.insert(extension, cache_control);
+        self
+    }
+
+    /// Sets `Content-Disposition` of files with `extension` and returns a new `StaticDirOptions`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use salvo_serve_static::{Disposition, StaticDir};
+    ///
+    /// let static_dir = StaticDir::new("static")
+    ///     .with_extension_disposition("pdf", Disposition::Inline)
+    ///     .with_extension_disposition("csv", Disposition::Attachment { filename: None });
+    /// ```
+    #[inline]
+    pub fn with_extension_disposition(mut self, extension: impl AsRef<str>, disposition: Disposition) -> Self {
+        let extension = normalize_extension(extension.as_ref());
+        self.extension_dispositions.insert(extension, disposition);
+        self
+    }
+
+    /// Get `Content-Disposition` of the file at `path`.
+    fn file_disposition(&self, path: &Path) -> Option<&Disposition> {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| self.extension_dispositions.get(&extension.to_lowercase()))
+    }
+
+    /// Get `Cache-Control` of the file at `path`.
+    fn file_cache_control(&self, path: &Path) -> Option<&CacheControl> {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| self.extension_cache_controls.get(&extension.to_lowercase()))
+            .or(self.cache_control.as_ref())
+    }
+
+    /// Sets unknown_mime and returns a new `StaticDirOptions`.
+    #[inline]
+    pub fn with_unknown_mime(mut self, unknown_mime: UnknownMimePolicy) -> Self {
+        self.unknown_mime = unknown_mime;
+        self
+    }
+
+    /// Sets the content type of files with `extension` and returns a new `StaticDirOptions`.
+    ///
+    /// The extension is matched case-insensitively, the leading dot is optional.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use salvo_serve_static::StaticDir;
+    ///
+    /// let static_dir = StaticDir::new("static")
+    ///     .with_mime_override("wasm", "application/wasm".parse().unwrap())
+    ///     .with_mime_override(".webmanifest", "application/manifest+json".parse().unwrap());
+    /// ```
+    #[inline]
+    pub fn with_mime_override(mut self, extension: impl AsRef<str>, mime: Mime) -> Self {
+        self.mime_overrides
+            .insert(normalize_extension(extension.as_ref()), mime);
+        self
+    }
+
+    /// Sets content types of files by extension and returns a new `StaticDirOptions`, see
+    /// [`with_mime_override`](Self::with_mime_override).
+    #[inline]
+    pub fn with_mime_overrides<K>(mut self, overrides: impl IntoIterator<Item = (K, Mime)>) -> Self
+    where
+        K: AsRef<str>,
+    {
+        for (extension, mime) in overrides {
+            self.mime_overrides
+                .insert(normalize_extension(extension.as_ref()), mime);
+        }
+        self
+    }
+
+    /// Register a renderer of directory listings for `mime` and returns a new `StaticDirOptions`.
+    ///
+    /// The renderer is used if `mime` is the first type in `Accept` of the request, it returns the body
+    /// and its content type. Renderers registered for a type are used before the renderer set with
+    /// [`StaticDir::with_listing_renderer`], so renderers registered for `text/plain`, `text/html`,
+    /// `application/json` or `text/xml` replace the built-in listings.
+    #[inline]
+    pub fn register_listing_renderer<F>(mut self, mime: Mime, renderer: F) -> Self
+    where
+        F: Fn(&CurrentInfo) -> (Bytes, Mime) + Send + Sync + 'static,
+    {
+        self.listing_renderers
+            .retain(|(registered, _)| registered.essence_str() != mime.essence_str());
+        self.listing_renderers.push((mime, Arc::new(renderer)));
+        self
+    }
+
+    /// Sets the renderer of directory listings and returns a new `StaticDirOptions`.
+    ///
+    /// It renders all listings which are not rendered by a renderer registered with
+    /// [`StaticDir::register_listing_renderer`], [`DefaultDirListRenderer`] is used by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use salvo_core::http::Mime;
+    /// use salvo_serve_static::dir::{CurrentInfo, DirListRenderer};
+    /// use salvo_serve_static::StaticDir;
+    ///
+    /// struct Names;
+    /// impl DirListRenderer for Names {
+    ///     fn render(&self, current: &CurrentInfo, _format: &Mime) -> (bytes::Bytes, Mime) {
+    ///         let names = current.entries().into_iter().map(|entry| entry.name).collect::<Vec<_>>();
+    ///         (names.join("\n").into(), mime::TEXT_PLAIN_UTF_8)
+    ///     }
+    /// }
+    /// let static_dir = StaticDir::new("static").with_listing(true).with_listing_renderer(Box::new(Names));
+    /// ```
+    #[inline]
+    pub fn with_listing_renderer(mut self, renderer: Box<dyn DirListRenderer>) -> Self {
+        self.listing_renderer = renderer.into();
+        self
+    }
+
+    /// Sets media_duration and returns a new `StaticDirOptions`.
+    #[inline]
+    pub fn with_media_duration(mut self, media_duration: bool) -> Self {
+        self.media_duration = media_duration;
+        self
+    }
+
+    /// Sets precompressed and returns a new `StaticDirOptions`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use salvo_serve_static::{PrecompressedEncoding, StaticDir};
+    ///
+    /// // `app.js.br` is served before `app.js.gz` if the client accepts both.
+    /// let static_dir = StaticDir::new("static").with_precompressed([PrecompressedEncoding::Brotli, PrecompressedEncoding::Gzip]);
+    /// ```
+    #[inline]
+    pub fn with_precompressed(mut self, precompressed: impl Into<Vec<PrecompressedEncoding>>) -> Self {
+        self.precompressed = precompressed.into();
+        self
+    }
+
+    /// Sets content_security_policy and returns a new `StaticDirOptions`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use salvo_serve_static::StaticDir;
+    ///
+    /// // `<script nonce="{nonce}">` in HTML files is rewritten with the nonce of the response.
+    /// let static_dir = StaticDir::new("static")
+    ///     .with_content_security_policy("script-src 'nonce-{nonce}'")
+    ///     .with_csp_nonce(true);
+    /// ```
+    #[inline]
+    pub fn with_content_security_policy(mut self, policy: impl Into<String>) -> Self {
+        self.content_security_policy = Some(policy.into());
+        self
+    }
+
+    /// Sets csp_nonce and returns a new `StaticDirOptions`.
+    #[inline]
+    pub fn with_csp_nonce(mut self, csp_nonce: bool) -> Self {
+        self.csp_nonce = csp_nonce;
+        self
+    }
+
+    /// Sets preload_links and returns a new `StaticDirOptions`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a link is not a `Link` header value, like `<uri>; rel=preload`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use salvo_serve_static::StaticDir;
+    ///
+    /// let static_dir = StaticDir::new("static").with_defaults("index.html").with_preload_links([
+    ///     "</css/app.css>; rel=preload; as=style",
+    ///     "</js/app.js>; rel=preload; as=script",
+    /// ]);
+    /// ```
+    #[inline]
+    pub fn with_preload_links(mut self, links: impl IntoVecString) -> Self {
+        self.preload_links = links
+            .into_vec_string()
+            .into_iter()
+            .map(|link| match HeaderValue::from_str(&link) {
+                Ok(value) if link.starts_with('<') && link.contains('>') => value,
+                _ => panic!("preload link `{link}` is not a valid `Link` header value"),
+            })
+            .collect();
+        self
+    }
+
+    /// Sets the value of the `{{name}}` placeholder and returns a new `StaticDirOptions`, see
+    /// [`substitutions`](Self::substitutions).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use salvo_serve_static::StaticDir;
+    ///
+    /// let static_dir = StaticDir::new("static")
+    ///     .with_substitution("API_BASE_URL", "https://api.example.com")
+    ///     .with_substitution("VERSION", env!("CARGO_PKG_VERSION"));
+    /// ```
+    #[inline]
+    pub fn with_substitution(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.substitutions.insert(name.into(), value.into());
+        self.substitution_cache = Arc::new(FileCache::new(SUBSTITUTION_CACHE_CAPACITY));
+        self
     }
 
-    /// Sets dot_files and returns a new `StaticDirOptions`.
+    /// Sets substitutions and returns a new `StaticDirOptions`.
     #[inline]
-    pub fn with_dot_files(mut self, dot_files: bool) -> Self {
-        self.dot_files = dot_files;
+    pub fn with_substitutions(mut self, substitutions: HashMap<String, String>) -> Self {
+        self.substitutions = substitutions;
+        self.substitution_cache = Arc::new(FileCache::new(SUBSTITUTION_CACHE_CAPACITY));
         self
     }
 
-    /// Sets listing and returns a new `StaticDirOptions`.
+    /// Sets substitution_types and returns a new `StaticDirOptions`.
     #[inline]
-    pub fn with_listing(mut self, listing: bool) -> Self {
-        self.listing = listing;
+    pub fn with_substitution_types(mut self, types: impl IntoVecString) -> Self {
+        self.substitution_types = types.into_vec_string();
         self
     }
 
-    /// Sets defaults and returns a new `StaticDirOptions`.
+    /// Sets max_substitution_size and returns a new `StaticDirOptions`.
     #[inline]
-    pub fn with_defaults(mut self, defaults: impl IntoVecString) -> Self {
-        self.defaults = defaults.into_vec_string();
+    pub fn with_max_substitution_size(mut self, size: u64) -> Self {
+        self.max_substitution_size = size;
         self
     }
 
-    /// Sets fallback and returns a new `StaticDirOptions`.
-    pub fn with_fallback(mut self, fallback: impl Into<String>) -> Self {
-        self.fallback = Some(fallback.into());
+    /// Sets untrusted_prefixes and returns a new `StaticDirOptions`, see
+    /// [`untrusted_prefixes`](Self::untrusted_prefixes).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use salvo_serve_static::StaticDir;
+    ///
+    /// let static_dir = StaticDir::new("static").with_untrusted_prefixes("uploads");
+    /// ```
+    #[inline]
+    pub fn with_untrusted_prefixes(mut self, prefixes: impl IntoVecString) -> Self {
+        self.untrusted_prefixes = prefixes.into_vec_string();
+        self
+    }
+
+    /// Sets strict_decode and returns a new `StaticDirOptions`.
+    #[inline]
+    pub fn with_strict_decode(mut self, strict_decode: bool) -> Self {
+        self.strict_decode = strict_decode;
         self
     }
 
+    /// Send the HTML file at `path` with a new nonce substituted in it and in `policy`.
+    async fn send_html_with_nonce(&self, path: &Path, policy: &str, res: &mut Response) -> bool {
+        match tokio::fs::metadata(path).await {
+            Ok(metadata) if metadata.len() <= self.max_substitution_size => {}
+            _ => return false,
+        }
+        let content = match tokio::fs::read(path).await {
+            Ok(content) => content,
+            Err(e) => {
+                tracing::error!(error = ?e, path = ?path, "read html file failed");
+                res.set_status_error(StatusError::internal_server_error().with_summary("read file failed"));
+                return true;
+            }
+        };
+        let nonce = general_purpose::STANDARD.encode(rand::thread_rng().gen::<[u8; 16]>());
+        let mut body = String::from_utf8_lossy(&content).into_owned();
+        if self.substitutes(&mime::TEXT_HTML) {
+            body = substitute(&body, &self.substitutions);
+        }
+        let body = body.replace(CSP_NONCE_PLACEHOLDER, &nonce);
+        res.set_status_code(StatusCode::OK);
+        let headers = res.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html; charset=utf-8"));
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        if let Ok(policy) = HeaderValue::from_str(&policy.replace(CSP_NONCE_PLACEHOLDER, &nonce)) {
+            headers.insert(CONTENT_SECURITY_POLICY, policy);
+        }
+        res.write_body(body).ok();
+        true
+    }
+
+    /// Whether files of the content type `mime` are rewritten with `substitutions`.
+    fn substitutes(&self, mime: &Mime) -> bool {
+        !self.substitutions.is_empty()
+            && self
+                .substitution_types
+                .iter()
+                .any(|ty| ty.eq_ignore_ascii_case(mime.essence_str()))
+    }
+
+    /// Whether the file at `path` is under one of `untrusted_prefixes` of a root.
+    fn is_untrusted(&self, path: &Path) -> bool {
+        if self.untrusted_prefixes.is_empty() {
+            return false;
+        }
+        self.roots
+            .iter()
+            .filter_map(|root| path.strip_prefix(root).ok())
+            .any(|rel_path| {
+                let rel_path = rel_path
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                self.untrusted_prefixes
+                    .iter()
+                    .any(|prefix| matches_prefix(prefix, &rel_path))
+            })
+    }
+
+    /// Read the file at `path` with `substitutions` replaced, `None` if it is sent as is.
+    async fn substituted_content(&self, path: &Path, mime: &Mime) -> Option<(Bytes, String)> {
+        if !self.substitutes(mime) {
+            return None;
+        }
+        let metadata = match tokio::fs::metadata(path).await {
+            Ok(metadata) if metadata.len() <= self.max_substitution_size => metadata,
+            _ => return None,
+        };
+        // The content and its ETag are kept until the size or modification time of the file changes.
+        if let Some(substituted) = self.substitution_cache.get(path, &metadata) {
+            return substituted;
+        }
+        let substituted = match tokio::fs::read(path).await {
+            Ok(content) => String::from_utf8(content).ok().map(|content| {
+                let data = Bytes::from(substitute(&content, &self.substitutions));
+                let etag = format!("\"{}\"", hex::encode(Sha256::digest(&data)));
+                (data, etag)
+            }),
+            Err(e) => {
+                tracing::warn!(error = ?e, path = ?path, "read file for substitutions failed");
+                return None;
+            }
+        };
+        self.substitution_cache.insert(path, &metadata, substituted.clone());
+        substituted
+    }
+
+    /// Find the first pre-compressed sidecar of the file at `path` accepted by `req`.
+    fn precompressed_variant(&self, path: &Path, req: &Request) -> Option<(PathBuf, PrecompressedEncoding)> {
+        if req.headers().contains_key(RANGE) {
+            return None;
+        }
+        let accept = req.headers().get(ACCEPT_ENCODING).and_then(|v| v.to_str().ok())?;
+        self.precompressed
+            .iter()
+            .filter(|algo| accepts_encoding(accept, algo.encoding()))
+            .find_map(|algo| {
+                let mut sidecar = path.as_os_str().to_owned();
+                sidecar.push(".");
+                sidecar.push(algo.extension());
+                let sidecar = PathBuf::from(sidecar);
+                if sidecar.is_file() && self.is_contained(&sidecar) {
+                    Some((sidecar, *algo))
+                } else {
+                    None
+                }
+            })
+    }
+
     /// During the file chunk read, the maximum read size at one time will affect the
     /// access experience and the demand for server memory.
     ///
@@ -139,24 +1378,151 @@ impl StaticDir {
         self.chunk_size = Some(size);
         self
     }
+
+    /// Get the content type of the file at `path` whose type can not be guessed from the extension.
+    async fn unknown_content_type(&self, path: &Path) -> mime::Mime {
+        match self.unknown_mime {
+            UnknownMimePolicy::OctetStream => mime::APPLICATION_OCTET_STREAM,
+            UnknownMimePolicy::TextPlain => mime::TEXT_PLAIN,
+            UnknownMimePolicy::Sniff => {
+                let mut head = Vec::with_capacity(SNIFF_SIZE);
+                let read = match tokio::fs::File::open(path).await {
+                    Ok(file) => file.take(SNIFF_SIZE as u64).read_to_end(&mut head).await,
+                    Err(e) => Err(e),
+                };
+                match read {
+                    Ok(_) if is_text(&head) => mime::TEXT_PLAIN,
+                    Ok(_) => mime::APPLICATION_OCTET_STREAM,
+                    Err(e) => {
+                        tracing::warn!(error = ?e, path = ?path, "sniff file content failed");
+                        mime::APPLICATION_OCTET_STREAM
+                    }
+                }
+            }
+        }
+    }
+
+    /// Get the media duration of the file at `path`, the cached value is used if size and
+    /// modification time are not changed.
+    async fn media_duration(&self, path: &Path) -> IoResult<Option<f64>> {
+        let metadata = tokio::fs::metadata(path).await?;
+        if let Some(duration) = self.duration_cache.get(path, &metadata) {
+            return Ok(duration);
+        }
+        let duration = read_duration(path).await?;
+        self.duration_cache.insert(path, &metadata, duration);
+        Ok(duration)
+    }
 }
-#[derive(Serialize, Deserialize, Debug)]
-struct CurrentInfo {
-    path: String,
-    files: Vec<FileInfo>,
-    dirs: Vec<DirInfo>,
+/// Directory listing, the JSON and plain text listings are its serialized form.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CurrentInfo {
+    /// Request path of the listed directory.
+    pub path: String,
+    /// Files in the directory.
+    pub files: Vec<FileInfo>,
+    /// Sub directories in the directory.
+    pub dirs: Vec<DirInfo>,
+    /// Number of files and sub directories.
+    #[serde(default)]
+    pub count: usize,
+    /// Total size of files in bytes, sub directories are not counted.
+    #[serde(default)]
+    pub total_size: u64,
+    /// Whether entries are left out by [`StaticDir::max_listing_entries`], `count` and `total_size`
+    /// still include them.
+    #[serde(default)]
+    pub truncated: bool,
+    /// Whether sizes and modification times are hidden by [`StaticDir::listing_hide_metadata`], they
+    /// are zeroed and left out of the built-in listings.
+    #[serde(skip)]
+    pub metadata_hidden: bool,
 }
 impl CurrentInfo {
     #[inline]
     fn new(path: String, files: Vec<FileInfo>, dirs: Vec<DirInfo>) -> CurrentInfo {
-        CurrentInfo { path, files, dirs }
+        let count = files.len() + dirs.len();
+        let total_size = files.iter().map(|file| file.size).sum();
+        CurrentInfo {
+            path,
+            files,
+            dirs,
+            count,
+            total_size,
+            truncated: false,
+            metadata_hidden: false,
+        }
+    }
+
+    /// Zero sizes and modification times, so custom renderers can't leak them either.
+    fn hide_metadata(mut self) -> Self {
+        for file in &mut self.files {
+            file.size = 0;
+            file.modified = OffsetDateTime::UNIX_EPOCH;
+        }
+        for dir in &mut self.dirs {
+            dir.modified = OffsetDateTime::UNIX_EPOCH;
+        }
+        self.total_size = 0;
+        self.metadata_hidden = true;
+        self
+    }
+
+    /// Keep at most `max` entries, directories come first.
+    fn truncate(mut self, max: usize) -> Self {
+        if self.count > max {
+            self.dirs.truncate(max);
+            self.files.truncate(max - self.dirs.len());
+            self.truncated = true;
+        }
+        self
+    }
+
+    /// Weak ETag of the listing rendered in `format`.
+    fn etag(&self, format: &Mime) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(format.essence_str());
+        hasher.update([0]);
+        hasher.update(&self.path);
+        hasher.update([0]);
+        hasher.update(self.count.to_le_bytes());
+        hasher.update(self.total_size.to_le_bytes());
+        hasher.update([self.truncated as u8]);
+        for entry in self.entries() {
+            hasher.update(&entry.name);
+            hasher.update([0, entry.is_dir as u8]);
+            hasher.update(entry.size.to_le_bytes());
+            hasher.update(entry.modified.unix_timestamp_nanos().to_le_bytes());
+        }
+        format!("W/\"{}\"", hex::encode(&hasher.finalize()[..16]))
+    }
+
+    /// Get the entries of the listing, directories come first.
+    pub fn entries(&self) -> Vec<DirListEntry> {
+        let dirs = self.dirs.iter().map(|dir| DirListEntry {
+            name: dir.name.clone(),
+            is_dir: true,
+            size: 0,
+            modified: dir.modified,
+        });
+        let files = self.files.iter().map(|file| DirListEntry {
+            name: file.name.clone(),
+            is_dir: false,
+            size: file.size,
+            modified: file.modified,
+        });
+        dirs.chain(files).collect()
     }
 }
-#[derive(Serialize, Deserialize, Debug)]
-struct FileInfo {
-    name: String,
-    size: u64,
-    modified: OffsetDateTime,
+/// File entry of [`CurrentInfo`].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FileInfo {
+    /// File name.
+    pub name: String,
+    /// File size in bytes.
+    pub size: u64,
+    /// Last modified time.
+    pub modified: OffsetDateTime,
 }
 impl FileInfo {
     #[inline]
@@ -168,10 +1534,13 @@ impl FileInfo {
         }
     }
 }
-#[derive(Serialize, Deserialize, Debug)]
-struct DirInfo {
-    name: String,
-    modified: OffsetDateTime,
+/// Directory entry of [`CurrentInfo`].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DirInfo {
+    /// Directory name.
+    pub name: String,
+    /// Last modified time.
+    pub modified: OffsetDateTime,
 }
 impl DirInfo {
     #[inline]
@@ -183,9 +1552,157 @@ impl DirInfo {
     }
 }
 
+/// Entry of a directory listing, see [`CurrentInfo::entries`].
+#[derive(Clone, Debug)]
+pub struct DirListEntry {
+    /// File or directory name.
+    pub name: String,
+    /// Whether the entry is a directory.
+    pub is_dir: bool,
+    /// File size in bytes, it is `0` for directories.
+    pub size: u64,
+    /// Last modified time.
+    pub modified: OffsetDateTime,
+}
+
+/// Renderer of directory listings set with [`StaticDir::with_listing_renderer`] or registered for a type
+/// with [`StaticDir::register_listing_renderer`].
+///
+/// It is implemented for closures which take the listing and return the body and its content type.
+pub trait DirListRenderer: Send + Sync {
+    /// Render `current`, the listing of a directory, its entries are sorted by [`StaticDir::listing_sort`]
+    /// and may be truncated by [`StaticDir::max_listing_entries`]. `format` is the first type in `Accept`
+    /// of the request, `text/html` if there is none.
+    ///
+    /// Returns the body and its content type.
+    fn render(&self, current: &CurrentInfo, format: &Mime) -> (Bytes, Mime);
+}
+impl<F> DirListRenderer for F
+where
+    F: Fn(&CurrentInfo) -> (Bytes, Mime) + Send + Sync,
+{
+    #[inline]
+    fn render(&self, current: &CurrentInfo, _format: &Mime) -> (Bytes, Mime) {
+        self(current)
+    }
+}
+
+/// Built-in [`DirListRenderer`], it renders plain text, JSON, XML or HTML by the subtype of `format`,
+/// HTML is used for other formats.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultDirListRenderer;
+impl DirListRenderer for DefaultDirListRenderer {
+    #[inline]
+    fn render(&self, current: &CurrentInfo, format: &Mime) -> (Bytes, Mime) {
+        render_listing(current, format)
+    }
+}
+
+/// Render `current` by the subtype of `format` as [`DefaultDirListRenderer`].
+fn render_listing(current: &CurrentInfo, format: &Mime) -> (Bytes, Mime) {
+    match format.subtype().as_ref() {
+        "plain" => (list_text(current).into(), mime::TEXT_PLAIN_UTF_8),
+        "json" => (list_json(current).into(), utf8_mime("application/json")),
+        "xml" => (list_xml(current).into(), utf8_mime("application/xml")),
+        _ => (list_html(current).into(), mime::TEXT_HTML_UTF_8),
+    }
+}
+
+/// Replace `{{NAME}}` placeholders in `content` with their values in `substitutions`, replaced values
+/// are not scanned again.
+fn substitute(content: &str, substitutions: &HashMap<String, String>) -> String {
+    let mut output = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let tail = &rest[start + 2..];
+        let value = tail
+            .find("}}")
+            .and_then(|end| substitutions.get(&tail[..end]).map(|value| (end, value)));
+        match value {
+            Some((end, value)) => {
+                output.push_str(value);
+                rest = &tail[end + 2..];
+            }
+            None => {
+                output.push_str("{{");
+                rest = tail;
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Format of the listing requested by the `query` parameter, or by `Accept` if the parameter is absent
+/// or unknown.
+fn listing_format(req: &Request, query: Option<&str>) -> Mime {
+    let format = match query.and_then(|query| req.query::<String>(query)).as_deref() {
+        Some("html") => Some(mime::TEXT_HTML),
+        Some("json") => Some(mime::APPLICATION_JSON),
+        Some("xml") => Some(mime::TEXT_XML),
+        Some("text") => Some(mime::TEXT_PLAIN),
+        _ => None,
+    };
+    format.or_else(|| req.first_accept()).unwrap_or(mime::TEXT_HTML)
+}
+
+/// Whether `name` is usable as a default file name, it must be a single path segment.
+#[inline]
+fn is_default_name(name: &str) -> bool {
+    !(name.is_empty() || name.contains('/') || name.contains('\\') || name == "..")
+}
+
+fn canonicalize_roots(roots: &[PathBuf]) -> Vec<(PathBuf, Option<PathBuf>)> {
+    roots
+        .iter()
+        .map(|root| (root.clone(), root.canonicalize().ok()))
+        .collect()
+}
+
+#[inline]
+fn utf8_mime(essence: &str) -> Mime {
+    format!("{essence}; charset=utf-8").parse().expect("mime is valid")
+}
+
 #[async_trait]
 impl Handler for StaticDir {
-    async fn handle(&self, req: &mut Request, _depot: &mut Depot, res: &mut Response, _ctrl: &mut FlowCtrl) {
+    async fn handle(&self, req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+        if !check_method_allowed(req, res) {
+            ctrl.skip_rest();
+            return;
+        }
+        if self.serve(req, res).await {
+            self.metrics.record(res);
+            if self.fall_through {
+                ctrl.skip_rest();
+            }
+        } else {
+            ctrl.call_next(req, depot, res).await;
+        }
+    }
+}
+
+impl StaticDir {
+    /// Write the `404 Not Found` response with `not_found_handler`.
+    async fn not_found(&self, req: &Request, res: &mut Response) {
+        let file = match &self.not_found_handler {
+            Some(NotFoundHandler::File(path)) => path.to_str().and_then(|path| {
+                self.roots
+                    .iter()
+                    .filter_map(|root| safe_join(root, path))
+                    .find(|path| path.is_file() && self.is_contained(path))
+            }),
+            _ => None,
+        };
+        render_not_found(self.not_found_handler.as_ref(), file.as_deref(), req, res).await;
+    }
+
+    /// Serve the request, returns `false` if nothing is written and the request falls through.
+    async fn serve(&self, req: &mut Request, res: &mut Response) -> bool {
+        if self.strict_decode && reject_malformed_path(req, res) {
+            return true;
+        }
         let param = req.params().iter().find(|(key, _)| key.starts_with('*'));
         let req_path = req.uri().path();
         let rel_path = if let Some((_, value)) = param {
@@ -193,7 +1710,20 @@ impl Handler for StaticDir {
         } else {
             decode_url_path_safely(req_path)
         };
+        if has_control_chars(&rel_path) {
+            tracing::warn!(path = ?rel_path, "request path contains control characters");
+            self.not_found(req, res).await;
+            return true;
+        }
         let rel_path = format_url_path_safely(&rel_path);
+        let negative_cache_key = self.negative_cache_ttl.map(|_| self.negative_cache_key(&rel_path, req));
+        if let (Some(ttl), Some(key)) = (self.negative_cache_ttl, &negative_cache_key) {
+            if self.is_cached_miss(key, &rel_path, ttl) {
+                self.metrics.negative_cache_hits.fetch_add(1, Ordering::Relaxed);
+                self.not_found(req, res).await;
+                return true;
+            }
+        }
         let mut files: HashMap<String, Metadata> = HashMap::new();
         let mut dirs: HashMap<String, Metadata> = HashMap::new();
         let is_dot_file = Path::new(&rel_path)
@@ -202,31 +1732,67 @@ impl Handler for StaticDir {
             .map(|s| s.starts_with('.'))
             .unwrap_or(false);
         let mut abs_path = None;
-        if self.dot_files || !is_dot_file {
+        let mut is_index = false;
+        let mut language = None;
+        let mut unlisted_dir = false;
+        if (self.dot_files || !is_dot_file) && !self.excludes.is_match_with_ancestors(&rel_path) {
+            #[cfg(test)]
+            self.metrics.root_lookups.fetch_add(1, Ordering::Relaxed);
+            let directory_policy = self.effective_directory_policy();
             for root in &self.roots {
                 let path = root.join(&rel_path);
                 if path.is_dir() {
+                    if directory_policy == DirectoryPolicy::Forbidden {
+                        res.set_status_error(StatusError::forbidden());
+                        return true;
+                    }
                     if !req_path.ends_with('/') && !req_path.is_empty() {
-                        redirect_to_dir_url(req.uri(), res);
-                        return;
+                        if let Some(status_code) = self.redirect_policy.status_code() {
+                            redirect_to_dir_url(req.uri(), status_code, res);
+                            return true;
+                        }
+                    }
+                    if self.zip_download && req.query::<String>("download").as_deref() == Some("zip") {
+                        if !self.is_contained(&path) {
+                            self.not_found(req, res).await;
+                            return true;
+                        }
+                        self.send_zip(&path, &rel_path, res).await;
+                        return true;
                     }
 
-                    for ifile in &self.defaults {
+                    for ifile in self.defaults_of(&rel_path).iter() {
                         let ipath = path.join(ifile);
                         if ipath.is_file() {
                             abs_path = Some(ipath);
+                            is_index = true;
                             break;
                         }
                     }
 
-                    if self.listing && abs_path.is_none() {
+                    if directory_policy == DirectoryPolicy::Listing && abs_path.is_none() {
                         abs_path = Some(path);
                     }
+                    unlisted_dir = abs_path.is_none();
                     if abs_path.is_some() {
                         break;
                     }
                 } else if path.is_file() {
+                    if self.canonical_case_redirect {
+                        if let Some(canonical) = canonical_case(root, &rel_path) {
+                            if canonical != rel_path && redirect_to_canonical(req.uri(), &rel_path, &canonical, res) {
+                                return true;
+                            }
+                        }
+                    }
                     abs_path = Some(path);
+                    break;
+                } else if self.language_negotiation && abs_path.is_none() {
+                    if let Some((path, lang)) = self.negotiate_language(&path, req) {
+                        abs_path = Some(path);
+                        language = Some(lang);
+                        break;
+                    }
                 }
             }
         }
@@ -241,73 +1807,473 @@ impl Handler for StaticDir {
             }
         }
 
+        // Existence is checked before evaluating preconditions, so conditional requests for
+        // missing files get `404 Not Found` rather than `304 Not Modified`.
         let abs_path = match abs_path {
+            // Symlinks are resolved after the request path is sanitized, a file or directory out of
+            // the roots is not found.
+            Some(path) if !self.is_contained(&path) => {
+                tracing::warn!(path = ?path, "path resolves out of static roots");
+                self.not_found(req, res).await;
+                return true;
+            }
             Some(path) => path,
+            None if unlisted_dir && self.fall_through => return false,
             None => {
-                res.set_status_error(StatusError::not_found());
-                return;
+                if let (Some(ttl), Some(key)) = (self.negative_cache_ttl, negative_cache_key) {
+                    self.cache_miss(key, &rel_path, ttl);
+                }
+                self.not_found(req, res).await;
+                return true;
             }
         };
 
         if abs_path.is_file() {
-            let builder = {
-                let mut builder = NamedFile::builder(abs_path);
+            // `NamedFile` serves byte ranges of any file.
+            if req.method() == Method::OPTIONS {
+                render_file_options(res);
+                return true;
+            }
+            let overridden_mime = mime_override(&self.mime_overrides, &abs_path);
+            let is_html = match overridden_mime {
+                Some(mime) => mime.essence_str() == mime::TEXT_HTML.essence_str(),
+                None => NamedFile::guess_content_type(&abs_path).essence_str() == mime::TEXT_HTML.essence_str(),
+            };
+            let untrusted = self.is_untrusted(&abs_path);
+            let policy = self.content_security_policy.as_deref().filter(|_| is_html);
+            if is_html && is_index {
+                for link in &self.preload_links {
+                    res.headers_mut().append(LINK, link.clone());
+                }
+            }
+            let nonce_sent = match policy.filter(|_| self.csp_nonce && !untrusted) {
+                Some(policy) => self.send_html_with_nonce(&abs_path, policy, res).await,
+                None => false,
+            };
+            let mime = overridden_mime
+                .cloned()
+                .unwrap_or_else(|| NamedFile::guess_content_type(&abs_path));
+            let cache_control = self.file_cache_control(&abs_path).cloned();
+            let forced_attachment = untrusted && !is_inline_safe(&mime);
+            let substituted = if untrusted || nonce_sent {
+                None
+            } else {
+                self.substituted_content(&abs_path, &mime).await
+            };
+            if nonce_sent {
+                // The file is sent with its nonce, the headers below still apply.
+            } else if let Some((data, etag)) = substituted {
+                render_bytes(data, &etag, req, res, mime);
+            } else {
+                // The sidecar has its own metadata and content, so its ETag differs from the requested file.
+                let (file_path, compression) = match self.precompressed_variant(&abs_path, req) {
+                    Some((sidecar, algo)) => (sidecar, Some(algo)),
+                    None => (abs_path.clone(), None),
+                };
+                let mut builder = NamedFile::builder(&file_path);
                 if let Some(size) = self.chunk_size {
                     builder = builder.buffer_size(size);
                 }
-                builder
-            };
-            if let Ok(named_file) = builder.build().await {
-                let headers = req.headers();
-                named_file.send(headers, res).await;
+                builder = apply_etag_mode(builder, self.etag_mode, &self.etag_cache, &file_path).await;
+                if let Some(algo) = compression {
+                    builder = builder.content_encoding(algo.encoding());
+                }
+                // `304 Not Modified` is sent from the file metadata, the file is neither sniffed nor read.
+                if !builder.send_not_modified(req.headers(), res).await {
+                    let duration = if self.media_duration {
+                        match self.media_duration(&abs_path).await {
+                            Ok(duration) => duration,
+                            Err(e) => {
+                                tracing::warn!(error = ?e, path = ?abs_path, "read media duration failed");
+                                None
+                            }
+                        }
+                    } else {
+                        None
+                    };
+                    if let Some(mime) = overridden_mime {
+                        builder = builder.content_type(mime.clone());
+                    } else if mime_guess::from_path(&abs_path).first().is_none() {
+                        // Sniffing untrusted content is what `untrusted_prefixes` protects against.
+                        builder = builder.content_type(if untrusted {
+                            mime::APPLICATION_OCTET_STREAM
+                        } else {
+                            self.unknown_content_type(&abs_path).await
+                        });
+                    } else if compression.is_some() {
+                        builder = builder.content_type(NamedFile::guess_content_type(&abs_path));
+                    }
+                    if let Ok(named_file) = builder.build().await {
+                        named_file.send(req.headers(), res).await;
+                        if compression.is_some() {
+                            rename_attachment(&abs_path, res);
+                        }
+                        if let Some(duration) = duration {
+                            if res.status_code().map(|code| code.is_success()).unwrap_or(false) {
+                                if let Ok(duration) = HeaderValue::from_str(&format_duration(duration)) {
+                                    res.headers_mut().insert(X_CONTENT_DURATION, duration);
+                                }
+                            }
+                        }
+                    } else {
+                        res.set_status_error(StatusError::internal_server_error().with_summary("read file failed"));
+                        return true;
+                    }
+                }
+            }
+            if let Some(lang) = language {
+                if let Ok(lang) = HeaderValue::from_str(&lang) {
+                    res.headers_mut().insert(CONTENT_LANGUAGE, lang);
+                }
+                res.headers_mut()
+                    .append(VARY, HeaderValue::from_static("accept-language"));
+            }
+            if !self.precompressed.is_empty() {
+                res.headers_mut()
+                    .append(VARY, HeaderValue::from_static("accept-encoding"));
+            }
+            if untrusted {
+                res.headers_mut()
+                    .insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+            }
+            if let Some(policy) = policy.filter(|_| !nonce_sent) {
+                if res.status_code().map(|code| code.is_success()).unwrap_or(false) {
+                    if let Ok(policy) = HeaderValue::from_str(policy) {
+                        res.headers_mut().insert(CONTENT_SECURITY_POLICY, policy);
+                    }
+                }
+            }
+            let attachment = Disposition::Attachment { filename: None };
+            let disposition = if forced_attachment {
+                Some(&attachment)
             } else {
-                res.set_status_error(StatusError::internal_server_error().with_summary("read file failed"));
+                self.file_disposition(&abs_path)
+            };
+            if let Some(disposition) = disposition {
+                if matches!(res.status_code(), Some(StatusCode::OK | StatusCode::PARTIAL_CONTENT)) {
+                    if let Some(value) = disposition.header_value(&abs_path) {
+                        res.headers_mut().insert(CONTENT_DISPOSITION, value);
+                    }
+                }
+            }
+            // Responses with a nonce must not be reused, they keep `Cache-Control: no-store`.
+            if let Some(cache_control) = cache_control.filter(|_| !nonce_sent) {
+                cache_control.apply(res);
             }
         } else if abs_path.is_dir() {
-            // list the dir
-            if let Ok(mut entries) = tokio::fs::read_dir(&abs_path).await {
+            // list the dir, merged with the same dir in later roots
+            let dir_paths = std::iter::once(abs_path.clone())
+                .chain(
+                    self.roots
+                        .iter()
+                        .map(|root| root.join(&rel_path))
+                        .filter(|path| *path != abs_path && path.is_dir() && self.is_contained(path)),
+                )
+                .collect::<Vec<_>>();
+            let format = listing_format(req, self.listing_format_query.as_deref());
+            if self.streaming_json_listing && format.subtype() == mime::JSON {
+                let stream = json_listing(
+                    dir_paths,
+                    decode_url_path_safely(req_path),
+                    rel_path,
+                    self.dot_files,
+                    self.excludes.clone(),
+                    self.max_listing_entries,
+                    self.listing_hide_metadata,
+                );
+                res.set_status_code(StatusCode::OK);
+                res.headers_mut().insert(
+                    CONTENT_TYPE,
+                    HeaderValue::from_static("application/json; charset=utf-8"),
+                );
+                res.streaming(stream).ok();
+                return true;
+            }
+            for dir_path in dir_paths {
+                let mut entries = match tokio::fs::read_dir(&dir_path).await {
+                    Ok(entries) => entries,
+                    Err(_) => continue,
+                };
                 while let Ok(Some(entry)) = entries.next_entry().await {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    if files.contains_key(&name) || dirs.contains_key(&name) {
+                        continue;
+                    }
+                    let entry_path = format!("{}/{}", rel_path, name);
+                    if self.excludes.is_match(&entry_path) {
+                        continue;
+                    }
                     if let Ok(metadata) = entry.metadata().await {
                         if metadata.is_dir() {
-                            dirs.entry(entry.file_name().to_string_lossy().to_string())
-                                .or_insert(metadata);
+                            dirs.insert(name, metadata);
                         } else {
-                            let file_name = entry.file_name().to_string_lossy().to_string();
-                            if !self.dot_files && file_name.starts_with('.') {
+                            if !self.dot_files && name.starts_with('.') {
                                 continue;
                             }
-                            files.entry(file_name).or_insert(metadata);
+                            files.insert(name, metadata);
                         }
                     }
                 }
             }
 
-            let format = req.first_accept().unwrap_or(mime::TEXT_HTML);
+            if files.is_empty() && dirs.is_empty() {
+                if let Some(empty_listing) = &self.empty_listing {
+                    let path = encode_text(&decode_url_path_safely(req_path));
+                    res.set_status_code(StatusCode::OK);
+                    res.render(Text::Html(empty_listing.replace("{path}", &path)));
+                    return true;
+                }
+            }
+
             let mut files: Vec<FileInfo> = files
                 .into_iter()
                 .map(|(name, metadata)| FileInfo::new(name, metadata))
                 .collect();
-            files.sort_by(|a, b| a.name.cmp(&b.name));
             let mut dirs: Vec<DirInfo> = dirs
                 .into_iter()
                 .map(|(name, metadata)| DirInfo::new(name, metadata))
                 .collect();
-            dirs.sort_by(|a, b| a.name.cmp(&b.name));
-            let root = CurrentInfo::new(decode_url_path_safely(req_path), files, dirs);
+            let listing_sort = match self.listing_sort {
+                ListingSort::Size | ListingSort::Modified if self.listing_hide_metadata => ListingSort::NameAsc,
+                listing_sort => listing_sort,
+            };
+            listing_sort.sort(&mut files, &mut dirs);
+            let mut root = CurrentInfo::new(decode_url_path_safely(req_path), files, dirs);
+            if let Some(max) = self.max_listing_entries {
+                root = root.truncate(max);
+            }
+            if self.listing_hide_metadata {
+                root = root.hide_metadata();
+            }
+            if self.listing_etag {
+                let etag = root.etag(&format);
+                if let Ok(value) = HeaderValue::from_str(&etag) {
+                    res.headers_mut().insert(ETAG, value);
+                }
+                if let Some(if_none_match) = req.headers().get(IF_NONE_MATCH) {
+                    if etag::if_none_match(if_none_match.to_str().unwrap_or_default(), &etag) {
+                        res.set_status_code(StatusCode::NOT_MODIFIED);
+                        return true;
+                    }
+                }
+            }
             res.set_status_code(StatusCode::OK);
-            match format.subtype().as_ref() {
-                "plain" => res.render(Text::Plain(list_text(&root))),
-                "json" => res.render(Text::Json(list_json(&root))),
-                "xml" => res.render(Text::Xml(list_xml(&root))),
-                _ => res.render(Text::Html(list_html(&root))),
+            let (body, content_type) = match self
+                .listing_renderers
+                .iter()
+                .find(|(mime, _)| mime.essence_str() == format.essence_str())
+            {
+                Some((_, renderer)) => renderer.render(&root, &format),
+                None => self.listing_renderer.render(&root, &format),
             };
+            if let Ok(content_type) = HeaderValue::from_str(content_type.as_ref()) {
+                res.headers_mut().insert(CONTENT_TYPE, content_type);
+            }
+            res.write_body(body).ok();
         }
+        true
+    }
+}
+
+impl StaticDir {
+    /// Stream the directory `path` as zip archive.
+    async fn send_zip(&self, path: &Path, rel_path: &str, res: &mut Response) {
+        let stream = match zip_dir(path, rel_path, self.dot_files, &self.excludes, &|path| {
+            self.is_contained(path)
+        })
+        .await
+        {
+            Ok(stream) => stream,
+            Err(e) => {
+                tracing::error!(error = ?e, path = ?path, "create zip archive failed");
+                res.set_status_error(StatusError::internal_server_error().with_summary("create zip archive failed"));
+                return;
+            }
+        };
+        let name = Path::new(rel_path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("archive")
+            .replace('"', "");
+        match HeaderValue::from_str(&format!("attachment; filename=\"{name}.zip\"")) {
+            Ok(value) => {
+                res.headers_mut().insert(CONTENT_DISPOSITION, value);
+            }
+            Err(_) => {
+                res.headers_mut().insert(
+                    CONTENT_DISPOSITION,
+                    HeaderValue::from_static("attachment; filename=\"archive.zip\""),
+                );
+            }
+        }
+        res.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/zip"));
+        res.set_status_code(StatusCode::OK);
+        res.streaming(stream).ok();
+    }
+
+    /// Find `<path>.<lang>.html` by `Accept-Language`, falls back to the default language.
+    fn negotiate_language(&self, path: &Path, req: &Request) -> Option<(PathBuf, String)> {
+        let file_name = path.file_name()?.to_str()?;
+        self.candidate_languages(req).into_iter().find_map(|lang| {
+            let variant = path.with_file_name(format!("{file_name}.{lang}.html"));
+            if variant.is_file() {
+                Some((variant, lang))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Get the languages of the variants to look up for the request in order.
+    fn candidate_languages(&self, req: &Request) -> Vec<String> {
+        let accept = req
+            .headers()
+            .get(ACCEPT_LANGUAGE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        let mut langs = Vec::new();
+        for lang in parse_accept_language(accept) {
+            // `fr-CA` can also be served by `fr`.
+            let primary = lang.split('-').next().unwrap_or_default().to_owned();
+            langs.push(lang);
+            langs.push(primary);
+        }
+        if let Some(default_language) = &self.default_language {
+            langs.push(default_language.clone());
+        }
+        langs.retain(|lang| !lang.is_empty());
+        langs
+    }
+}
+
+/// Parse `Accept-Language` into languages ordered by quality, `*`, `q=0` and tags which are not
+/// made of ASCII letters, digits and `-` are skipped, so they are safe to use in file names.
+fn parse_accept_language(header: &str) -> Vec<String> {
+    let mut langs = header
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.trim().split(';');
+            let lang = parts.next()?.trim();
+            let q = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            let is_tag = lang.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-');
+            if lang.is_empty() || !is_tag || q <= 0.0 {
+                None
+            } else {
+                Some((lang.to_lowercase(), q))
+            }
+        })
+        .collect::<Vec<_>>();
+    langs.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+    langs.into_iter().map(|(lang, _)| lang).collect()
+}
+
+/// Returns true if `encoding` is accepted with a non zero quality by `Accept-Encoding`, either
+/// explicitly or by `*`.
+fn accepts_encoding(header: &str, encoding: &str) -> bool {
+    let mut wildcard = None;
+    for item in header.split(',') {
+        let mut parts = item.trim().split(';');
+        let name = parts.next().unwrap_or_default().trim();
+        let q = parts
+            .find_map(|p| p.trim().strip_prefix("q="))
+            .and_then(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if name.eq_ignore_ascii_case(encoding) {
+            return q > 0.0;
+        } else if name == "*" {
+            wildcard = Some(q > 0.0);
+        }
+    }
+    wildcard.unwrap_or(false)
+}
+
+/// Whether content of type `mime` can't run scripts when it is rendered by browsers.
+fn is_inline_safe(mime: &Mime) -> bool {
+    match mime.type_() {
+        mime::AUDIO | mime::VIDEO => true,
+        mime::IMAGE => mime.subtype() != mime::SVG,
+        mime::TEXT => mime.subtype() == mime::PLAIN,
+        _ => false,
+    }
+}
+
+/// Name attachments of pre-compressed sidecars after the requested file at `path`.
+fn rename_attachment(path: &Path, res: &mut Response) {
+    let is_attachment = res
+        .headers()
+        .get(CONTENT_DISPOSITION)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.starts_with("attachment"))
+        .unwrap_or(false);
+    if is_attachment {
+        if let Some(value) = (Disposition::Attachment { filename: None }).header_value(path) {
+            res.headers_mut().insert(CONTENT_DISPOSITION, value);
+        }
+    }
+}
+
+/// Get the on-disk casing of `rel_path` under `root`.
+fn canonical_case(root: &Path, rel_path: &str) -> Option<String> {
+    let mut current = root.to_path_buf();
+    let mut parts = Vec::new();
+    for part in rel_path.split('/').filter(|part| !part.is_empty()) {
+        let lower_part = part.to_lowercase();
+        let mut found = None;
+        for entry in std::fs::read_dir(&current).ok()?.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name == part {
+                found = Some(name);
+                break;
+            } else if found.is_none() && name.to_lowercase() == lower_part {
+                found = Some(name);
+            }
+        }
+        let name = found?;
+        current.push(&name);
+        parts.push(name);
+    }
+    Some(parts.join("/"))
+}
+
+fn redirect_to_canonical(req_uri: &Uri, rel_path: &str, canonical: &str, res: &mut Response) -> bool {
+    let req_path = decode_url_path_safely(req_uri.path());
+    let prefix = match req_path.strip_suffix(rel_path) {
+        Some(prefix) => prefix,
+        None => return false,
+    };
+    let mut location = encode_url_path(&format!("{prefix}{canonical}"));
+    if let Some(query) = req_uri.query() {
+        location = format!("{location}?{query}");
+    }
+    match Redirect::with_status_code(StatusCode::MOVED_PERMANENTLY, location) {
+        Ok(redirect) => {
+            res.render(redirect);
+            true
+        }
+        Err(_) => false,
     }
 }
 
-#[inline]
 fn list_json(current: &CurrentInfo) -> String {
-    json!(current).to_string()
+    let mut value = json!(current);
+    if current.metadata_hidden {
+        if let Some(value) = value.as_object_mut() {
+            value.remove("total_size");
+            for entries in ["files", "dirs"] {
+                if let Some(entries) = value.get_mut(entries).and_then(|entries| entries.as_array_mut()) {
+                    for entry in entries.iter_mut().filter_map(|entry| entry.as_object_mut()) {
+                        entry.remove("size");
+                        entry.remove("modified");
+                    }
+                }
+            }
+        }
+    }
+    value.to_string()
 }
 fn list_xml(current: &CurrentInfo) -> String {
     let mut ftxt = "<list>".to_owned();
@@ -316,6 +2282,16 @@ fn list_xml(current: &CurrentInfo) -> String {
     } else {
         let format = format_description::parse("%Y-%m-%d %H:%M:%S").unwrap();
         for dir in &current.dirs {
+            if current.metadata_hidden {
+                write!(
+                    ftxt,
+                    "<dir><name>{}</name><link>{}</link></dir>",
+                    dir.name,
+                    encode_url_path(&dir.name),
+                )
+                .ok();
+                continue;
+            }
             write!(
                 ftxt,
                 "<dir><name>{}</name><modified>{}</modified><link>{}</link></dir>",
@@ -326,6 +2302,16 @@ fn list_xml(current: &CurrentInfo) -> String {
             .ok();
         }
         for file in &current.files {
+            if current.metadata_hidden {
+                write!(
+                    ftxt,
+                    "<file><name>{}</name><link>{}</link></file>",
+                    file.name,
+                    encode_url_path(&file.name),
+                )
+                .ok();
+                continue;
+            }
             write!(
                 ftxt,
                 "<file><name>{}</name><modified>{}</modified><size>{}</size><link>{}</link></file>",
@@ -337,9 +2323,31 @@ fn list_xml(current: &CurrentInfo) -> String {
             .ok();
         }
     }
+    if current.truncated {
+        ftxt.push_str("<truncated>true</truncated>");
+    }
     ftxt.push_str("</list>");
     ftxt
 }
+
+/// Check whether `head` of a file looks like UTF-8 text, a character may be cut at the end.
+#[inline]
+fn is_text(head: &[u8]) -> bool {
+    if head.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+fn encode_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
 fn list_html(current: &CurrentInfo) -> String {
     fn header_links(path: &str) -> String {
         let segments = path.trim_start_matches('/').trim_end_matches('/').split('/');
@@ -356,12 +2364,17 @@ fn list_html(current: &CurrentInfo) -> String {
                 .join("")
         )
     }
+    // Relative links of entries are resolved against the directory, even if it is served without a
+    // trailing slash by `RedirectPolicy::ServeIndex`.
+    let base = encode_text(&encode_url_path(&format!("{}/", current.path.trim_end_matches('/'))));
     let mut ftxt = format!(
         r#"<!DOCTYPE html><html><head>
         <meta charset="utf-8">
         <meta name="viewport" content="width=device-width">
+        <base href="{}">
         <title>{}</title>
         <style>{}</style></head><body><header><h3>Index of: {}</h3></header><hr/>"#,
+        base,
         current.path,
         HTML_STYLE,
         header_links(&current.path)
@@ -373,32 +2386,66 @@ fn list_html(current: &CurrentInfo) -> String {
         if !(current.path.is_empty() || current.path == "/") {
             write!(ftxt, "<a href=\"../\">[..]</a>").ok();
         }
-        write!(ftxt, "</th><th>Name</th><th>Last modified</th><th>Size</th></tr>").ok();
+        if current.metadata_hidden {
+            write!(ftxt, "</th><th>Name</th></tr>").ok();
+        } else {
+            write!(ftxt, "</th><th>Name</th><th>Last modified</th><th>Size</th></tr>").ok();
+        }
         let format = format_description::parse("%Y-%m-%d %H:%M:%S").unwrap();
         for dir in &current.dirs {
             write!(
                 ftxt,
-                r#"<tr><td>{}</td><td><a href="./{}/">{}</a></td><td>{}</td><td></td></tr>"#,
+                r#"<tr><td>{}</td><td><a href="./{}/">{}</a></td>"#,
                 DIR_ICON,
                 encode_url_path(&dir.name),
                 dir.name,
-                dir.modified.format(&format).unwrap(),
             )
             .ok();
+            if !current.metadata_hidden {
+                write!(ftxt, "<td>{}</td><td></td>", dir.modified.format(&format).unwrap()).ok();
+            }
+            write!(ftxt, "</tr>").ok();
         }
         for file in &current.files {
             write!(
                 ftxt,
-                r#"<tr><td>{}</td><td><a href="./{}">{}</a></td><td>{}</td><td>{}</td></tr>"#,
+                r#"<tr><td>{}</td><td><a href="./{}">{}</a></td>"#,
                 FILE_ICON,
                 encode_url_path(&file.name),
                 file.name,
-                file.modified.format(&format).unwrap(),
-                file.size
             )
             .ok();
+            if !current.metadata_hidden {
+                write!(
+                    ftxt,
+                    "<td>{}</td><td>{}</td>",
+                    file.modified.format(&format).unwrap(),
+                    file.size
+                )
+                .ok();
+            }
+            write!(ftxt, "</tr>").ok();
         }
         write!(ftxt, "</table>").ok();
+        if current.truncated {
+            write!(
+                ftxt,
+                "<p>Only the first {} of {} entries are listed</p>",
+                current.dirs.len() + current.files.len(),
+                current.count
+            )
+            .ok();
+        }
+        if current.metadata_hidden {
+            write!(ftxt, "<p>{} entries</p>", current.count).ok();
+        } else {
+            write!(
+                ftxt,
+                "<p>{} entries, {} bytes in total</p>",
+                current.count, current.total_size
+            )
+            .ok();
+        }
     }
     write!(
         ftxt,
@@ -409,7 +2456,7 @@ fn list_html(current: &CurrentInfo) -> String {
 }
 #[inline]
 fn list_text(current: &CurrentInfo) -> String {
-    json!(current).to_string()
+    list_json(current)
 }
 
 const HTML_STYLE: &str = r#"
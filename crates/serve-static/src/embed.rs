@@ -1,12 +1,19 @@
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::time::{Duration, UNIX_EPOCH};
 
+use bytes::Bytes;
 use rust_embed::{EmbeddedFile, Metadata, RustEmbed};
-use salvo_core::http::header::{CONTENT_TYPE, ETAG, IF_NONE_MATCH};
-use salvo_core::http::{Mime, Request, Response, StatusCode};
+use salvo_core::http::headers::{HeaderMapExt, LastModified};
+use salvo_core::http::{Method, Mime, Request, Response, StatusCode};
 use salvo_core::{async_trait, Depot, FlowCtrl, Handler, IntoVecString};
 
-use super::{decode_url_path_safely, format_url_path_safely, redirect_to_dir_url};
+use super::vfs::render_bytes;
+use super::{
+    check_method_allowed, decode_url_path_safely, format_url_path_safely, longest_prefix_match, redirect_to_dir_url,
+    reject_malformed_path, render_file_options,
+};
 
 macro_rules! join_path {
     ($($part:expr),+) => {
@@ -28,6 +35,11 @@ pub struct StaticEmbed<T> {
     pub defaults: Vec<String>,
     /// Fallback file name. This is used when the requested file is not found.
     pub fallback: Option<String>,
+    /// Fallback file names by path prefix. They are used before `fallback` when the requested file is not found.
+    pub fallbacks: HashMap<String, String>,
+    /// Reject request paths with malformed percent-encoding or invalid UTF-8 with `400 Bad Request`,
+    /// instead of decoding them lossily. Default is `false`.
+    pub strict_decode: bool,
 }
 
 /// Create a new `StaticEmbed` middleware.
@@ -37,10 +49,18 @@ pub fn static_embed<T: RustEmbed>() -> StaticEmbed<T> {
         _assets: PhantomData,
         defaults: vec![],
         fallback: None,
+        fallbacks: HashMap::new(),
+        strict_decode: false,
     }
 }
 
 /// Render [`EmbeddedFile`] to [`Response`].
+///
+/// The file is rendered as files of [`StaticVfs`](crate::StaticVfs): the strong `ETag` is the SHA-256
+/// hash computed by `rust_embed`, `If-Match` and `If-None-Match` are answered with `412 Precondition
+/// Failed` and `304 Not Modified`, and byte ranges are served with `206 Partial Content` unless
+/// `If-Range` doesn't match. `Last-Modified` is set if `rust_embed` recorded it, `If-Modified-Since`
+/// is not checked.
 #[inline]
 pub fn render_embedded_file(file: EmbeddedFile, req: &Request, res: &mut Response, mime: Option<Mime>) {
     let EmbeddedFile { data, metadata, .. } = file;
@@ -55,31 +75,18 @@ fn render_embedded_data(
     res: &mut Response,
     mime: Option<Mime>,
 ) {
-    let hash = hex::encode(metadata.sha256_hash());
-    // if etag is matched, return 304
-    if req
-        .headers()
-        .get(IF_NONE_MATCH)
-        .map(|etag| etag.to_str().unwrap_or("000000").eq(&hash))
-        .unwrap_or(false)
-    {
-        res.set_status_code(StatusCode::NOT_MODIFIED);
-        return;
+    let hash = format!("\"{}\"", hex::encode(metadata.sha256_hash()));
+    // Set before rendering, so `If-Range` with a date is compared to it.
+    if let Some(last_modified) = metadata.last_modified() {
+        res.headers_mut()
+            .typed_insert(LastModified::from(UNIX_EPOCH + Duration::from_secs(last_modified)));
     }
-
-    // otherwise, return 200 with etag hash
-    res.headers_mut().insert(ETAG, hash.parse().unwrap());
-
     let mime = mime.unwrap_or_else(|| mime_guess::from_path(req.uri().path()).first_or_octet_stream());
-    res.headers_mut().insert(CONTENT_TYPE, mime.as_ref().parse().unwrap());
-    match data {
-        Cow::Borrowed(data) => {
-            res.write_body(data).ok();
-        }
-        Cow::Owned(data) => {
-            res.write_body(data).ok();
-        }
-    }
+    let data = match data {
+        Cow::Borrowed(data) => Bytes::from_static(data),
+        Cow::Owned(data) => Bytes::from(data),
+    };
+    render_bytes(data, &hash, req, res, mime);
 }
 
 impl<T> StaticEmbed<T>
@@ -93,6 +100,8 @@ where
             _assets: PhantomData,
             defaults: vec![],
             fallback: None,
+            fallbacks: HashMap::new(),
+            strict_decode: false,
         }
     }
 
@@ -109,6 +118,43 @@ where
         self.fallback = Some(fallback.into());
         self
     }
+
+    /// Create a new `StaticEmbed` with fallbacks by path prefix.
+    ///
+    /// The key is a path prefix and the value is the fallback file name used when a file under this
+    /// prefix is not found. The longest matching prefix is used, then the fallback set by
+    /// [`with_fallback`](Self::with_fallback).
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use std::collections::HashMap;
+    ///
+    /// let fallbacks = HashMap::from([
+    ///     ("app1/".to_owned(), "app1/index.html".to_owned()),
+    ///     ("app2/".to_owned(), "app2/index.html".to_owned()),
+    /// ]);
+    /// let handler = static_embed::<Assets>().with_fallbacks(fallbacks);
+    /// ```
+    #[inline]
+    pub fn with_fallbacks(mut self, fallbacks: HashMap<String, String>) -> Self {
+        self.fallbacks = fallbacks;
+        self
+    }
+
+    /// Sets whether malformed request paths are rejected and returns `Self`.
+    #[inline]
+    pub fn with_strict_decode(mut self, strict_decode: bool) -> Self {
+        self.strict_decode = strict_decode;
+        self
+    }
+
+    /// Find the fallback file name of `req_path`, the longest matching prefix wins.
+    fn find_fallback(&self, req_path: &str) -> Option<&str> {
+        longest_prefix_match(&self.fallbacks, req_path)
+            .map(|fallback| fallback.as_str())
+            .or(self.fallback.as_deref())
+    }
 }
 #[async_trait]
 impl<T> Handler for StaticEmbed<T>
@@ -116,7 +162,14 @@ where
     T: RustEmbed + Send + Sync + 'static,
 {
     #[inline]
-    async fn handle(&self, req: &mut Request, _depot: &mut Depot, res: &mut Response, _ctrl: &mut FlowCtrl) {
+    async fn handle(&self, req: &mut Request, _depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+        if !check_method_allowed(req, res) {
+            ctrl.skip_rest();
+            return;
+        }
+        if self.strict_decode && reject_malformed_path(req, res) {
+            return;
+        }
         let param = req.params().iter().find(|(key, _)| key.starts_with('*'));
         let req_path = if let Some((_, value)) = param {
             value.clone()
@@ -136,12 +189,12 @@ where
                 }
             }
             if embedded_file.is_some() && !req_path.ends_with('/') && !req_path.is_empty() {
-                redirect_to_dir_url(req.uri(), res);
+                redirect_to_dir_url(req.uri(), StatusCode::FOUND, res);
                 return;
             }
         }
         if embedded_file.is_none() {
-            let fallback = self.fallback.as_deref().unwrap_or_default();
+            let fallback = self.find_fallback(&req_path).unwrap_or_default();
             if !fallback.is_empty() {
                 if let Some(file) = T::get(fallback) {
                     embedded_file = Some(file);
@@ -151,6 +204,7 @@ where
         }
 
         match embedded_file {
+            Some(_) if req.method() == Method::OPTIONS => render_file_options(res),
             Some(file) => {
                 let mime = mime_guess::from_path(&*key_path).first_or_octet_stream();
                 render_embedded_file(file, req, res, Some(mime));
@@ -175,7 +229,8 @@ impl Handler for EmbeddedFileHandler {
 
 /// Extension trait for [`EmbeddedFile`].
 pub trait EmbeddedFileExt {
-    /// Render the embedded file.
+    /// Render the embedded file, conditional and range requests are supported, see
+    /// [`render_embedded_file`].
     fn render(self, req: &Request, res: &mut Response);
     /// Create a handler for the embedded file.
     fn into_handler(self) -> EmbeddedFileHandler;
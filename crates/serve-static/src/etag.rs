@@ -0,0 +1,198 @@
+//! ETags of served files.
+//!
+//! Entity tags are compared as [RFC 7232](https://datatracker.ietf.org/doc/html/rfc7232#section-2.3.2)
+//! requires, `If-Match` uses the strong comparison function, and `If-None-Match` uses the weak one.
+
+use std::io::{ErrorKind, Result as IoResult};
+use std::path::Path;
+use std::sync::Arc;
+
+use salvo_core::fs::NamedFileBuilder;
+use salvo_core::http::headers::ETag;
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncReadExt;
+
+use super::file_cache::{FileCache, FILE_CACHE_CAPACITY};
+use super::EtagMode;
+
+/// Content hash ETags by path, with the size and modification time of the file they are computed for.
+pub(crate) type EtagCache = Arc<FileCache<String>>;
+
+/// Create a new empty [`EtagCache`].
+#[inline]
+pub(crate) fn new_etag_cache() -> EtagCache {
+    Arc::new(FileCache::new(FILE_CACHE_CAPACITY))
+}
+
+/// Sets the ETag of the file at `path` on `builder` as `mode` requires, the ETag computed from
+/// the file metadata by the builder is kept if the content can't be hashed.
+pub(crate) async fn apply_etag_mode(
+    builder: NamedFileBuilder,
+    mode: EtagMode,
+    cache: &EtagCache,
+    path: &Path,
+) -> NamedFileBuilder {
+    match mode {
+        EtagMode::Metadata => builder,
+        EtagMode::ContentHash => match content_hash_etag(cache, path).await {
+            Ok(etag) => match etag.parse::<ETag>() {
+                Ok(etag) => builder.etag(etag),
+                Err(_) => builder,
+            },
+            // Missing files are answered by the caller.
+            Err(e) if e.kind() == ErrorKind::NotFound => builder,
+            Err(e) => {
+                tracing::warn!(error = ?e, path = ?path, "hash file content failed");
+                builder
+            }
+        },
+        EtagMode::Disabled => builder.use_etag(false),
+    }
+}
+
+/// Get the content hash ETag of the file at `path`, the cached value is used if size and
+/// modification time are not changed.
+pub(crate) async fn content_hash_etag(cache: &EtagCache, path: &Path) -> IoResult<String> {
+    let metadata = tokio::fs::metadata(path).await?;
+    if let Some(etag) = cache.get(path, &metadata) {
+        return Ok(etag);
+    }
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let len = file.read(&mut buf).await?;
+        if len == 0 {
+            break;
+        }
+        hasher.update(&buf[..len]);
+    }
+    let etag = format!("\"{}\"", hex::encode(hasher.finalize()));
+    cache.insert(path, &metadata, etag.clone());
+    Ok(etag)
+}
+
+/// Split an entity tag into a weakness flag and its opaque value (without quotes).
+///
+/// Returns `None` when `tag` is not a valid entity tag.
+#[inline]
+pub(crate) fn parse_etag(tag: &str) -> Option<(bool, &str)> {
+    let tag = tag.trim();
+    let (weak, rest) = match tag.strip_prefix("W/") {
+        Some(rest) => (true, rest),
+        None => (false, tag),
+    };
+    if rest.len() >= 2 && rest.starts_with('"') && rest.ends_with('"') {
+        let opaque = &rest[1..rest.len() - 1];
+        if opaque.contains('"') {
+            None
+        } else {
+            Some((weak, opaque))
+        }
+    } else {
+        None
+    }
+}
+
+/// Strong comparison: both entity tags must not be weak and their opaque values must be identical.
+#[inline]
+pub(crate) fn strong_eq(a: &str, b: &str) -> bool {
+    match (parse_etag(a), parse_etag(b)) {
+        (Some((false, a)), Some((false, b))) => a == b,
+        _ => false,
+    }
+}
+
+/// Weak comparison: opaque values must be identical, regardless of either being weak.
+#[inline]
+pub(crate) fn weak_eq(a: &str, b: &str) -> bool {
+    match (parse_etag(a), parse_etag(b)) {
+        (Some((_, a)), Some((_, b))) => a == b,
+        _ => false,
+    }
+}
+
+/// Check `etag` against a header value which is `*` or a comma-separated list of entity tags.
+///
+/// When `weak` is `true` the weak comparison function is used, otherwise the strong one.
+pub(crate) fn etag_list_matches(header: &str, etag: &str, weak: bool) -> bool {
+    if header.trim() == "*" {
+        return true;
+    }
+    split_list(header)
+        .into_iter()
+        .any(|tag| if weak { weak_eq(tag, etag) } else { strong_eq(tag, etag) })
+}
+
+/// Check `etag` against an `If-Match` header value, uses strong comparison.
+#[inline]
+pub(crate) fn if_match(header: &str, etag: &str) -> bool {
+    etag_list_matches(header, etag, false)
+}
+
+/// Check `etag` against an `If-None-Match` header value, uses weak comparison.
+///
+/// Returns `true` if any entity tag of the header matches, which means the condition is **false**
+/// and a `304 Not Modified` (or `412 Precondition Failed`) should be returned.
+#[inline]
+pub(crate) fn if_none_match(header: &str, etag: &str) -> bool {
+    etag_list_matches(header, etag, true)
+}
+
+// Entity tags can contain commas, so the list can't simply be split on them.
+fn split_list(header: &str) -> Vec<&str> {
+    let mut tags = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, c) in header.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                tags.push(&header[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    tags.push(&header[start..]);
+    tags.retain(|tag| !tag.trim().is_empty());
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weak_and_strong() {
+        assert!(strong_eq(r#""abc""#, r#""abc""#));
+        assert!(!strong_eq(r#"W/"abc""#, r#""abc""#));
+        assert!(!strong_eq(r#"W/"abc""#, r#"W/"abc""#));
+        assert!(!strong_eq(r#""abc""#, r#""abd""#));
+        assert!(weak_eq(r#"W/"abc""#, r#""abc""#));
+        assert!(weak_eq(r#"W/"abc""#, r#"W/"abc""#));
+        assert!(!weak_eq(r#"W/"abc""#, r#"W/"abd""#));
+        assert!(!weak_eq("abc", "abc"));
+
+        assert!(if_none_match(r#"W/"abc""#, r#""abc""#));
+        assert!(!if_match(r#"W/"abc""#, r#""abc""#));
+    }
+
+    #[test]
+    fn test_wildcard() {
+        assert!(if_match("*", r#""abc""#));
+        assert!(if_none_match(" * ", r#"W/"abc""#));
+    }
+
+    #[test]
+    fn test_list() {
+        let header = r#""xyz", W/"abc", "a,b""#;
+        assert!(if_none_match(header, r#""abc""#));
+        assert!(!if_match(header, r#""abc""#));
+        assert!(if_match(header, r#""xyz""#));
+        assert!(if_match(header, r#""a,b""#));
+        assert!(!if_match(header, r#""a""#));
+        assert!(!if_none_match(header, r#""nope""#));
+        assert!(!if_none_match("", r#""abc""#));
+    }
+}
@@ -1,18 +1,107 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
 use salvo_core::fs::{NamedFile, NamedFileBuilder};
-use salvo_core::http::{Request, Response, StatusError};
+use salvo_core::http::header::{HeaderValue, CONTENT_DISPOSITION};
+use salvo_core::http::{Method, Mime, Request, Response, StatusCode};
 use salvo_core::{async_trait, Depot, FlowCtrl, Handler, Writer};
 
+use super::etag::{apply_etag_mode, new_etag_cache, EtagCache};
+use super::not_found::render_not_found;
+use super::{
+    check_method_allowed, mime_override, normalize_extension, render_file_options, strip_head_body, CacheControl,
+    EtagMode, NotFoundHandler,
+};
+
+/// Characters which are kept as is in `filename*` parameter, `attr-char` of RFC 5987.
+const ATTR_CHAR: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'!')
+    .remove(b'#')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'+')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'^')
+    .remove(b'_')
+    .remove(b'`')
+    .remove(b'|')
+    .remove(b'~');
+
+/// `Content-Disposition` of files served by [`StaticFile`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Disposition {
+    /// Display the file in the browser.
+    Inline,
+    /// Download the file, the file name of the served path is used if `filename` is `None`.
+    Attachment {
+        /// File name suggested to the browser.
+        filename: Option<String>,
+    },
+}
+
+impl Disposition {
+    /// Build the header value, non-ASCII file names are encoded in `filename*` parameter as
+    /// RFC 5987, with an ASCII fallback in `filename` parameter for old clients.
+    pub(crate) fn header_value(&self, path: &Path) -> Option<HeaderValue> {
+        let filename = match self {
+            Disposition::Inline => return Some(HeaderValue::from_static("inline")),
+            Disposition::Attachment {
+                filename: Some(filename),
+            } => filename.as_str(),
+            Disposition::Attachment { filename: None } => {
+                path.file_name().and_then(|name| name.to_str()).unwrap_or("file")
+            }
+        };
+        let fallback = filename
+            .chars()
+            .map(|c| match c {
+                '"' | '\\' => '_',
+                c if c.is_ascii() && !c.is_ascii_control() => c,
+                _ => '_',
+            })
+            .collect::<String>();
+        let value = if fallback == filename {
+            format!("attachment; filename=\"{filename}\"")
+        } else {
+            format!(
+                "attachment; filename=\"{fallback}\"; filename*=UTF-8''{}",
+                utf8_percent_encode(filename, ATTR_CHAR)
+            )
+        };
+        HeaderValue::from_str(&value).ok()
+    }
+}
+
 /// StaticFile
 #[derive(Clone)]
-pub struct StaticFile(NamedFileBuilder);
+pub struct StaticFile {
+    path: PathBuf,
+    builder: NamedFileBuilder,
+    cache_control: Option<CacheControl>,
+    disposition: Option<Disposition>,
+    mime_overrides: HashMap<String, Mime>,
+    not_found_handler: Option<NotFoundHandler>,
+    etag_mode: EtagMode,
+    etag_cache: EtagCache,
+}
 
 impl StaticFile {
     /// Create a new `StaticFile`.
     #[inline]
     pub fn new(path: impl Into<PathBuf>) -> Self {
-        StaticFile(NamedFile::builder(path))
+        let path = path.into();
+        StaticFile {
+            builder: NamedFile::builder(path.clone()),
+            path,
+            cache_control: None,
+            disposition: None,
+            mime_overrides: HashMap::new(),
+            not_found_handler: None,
+            etag_mode: EtagMode::default(),
+            etag_cache: new_etag_cache(),
+        }
     }
 
     /// During the file chunk read, the maximum read size at one time will affect the
@@ -22,8 +111,81 @@ impl StaticFile {
     ///
     /// The default is 1M.
     #[inline]
-    pub fn chunk_size(self, size: u64) -> Self {
-        Self(self.0.buffer_size(size))
+    pub fn chunk_size(mut self, size: u64) -> Self {
+        self.builder = self.builder.buffer_size(size);
+        self
+    }
+
+    /// Sets `Cache-Control` of successful responses and returns `Self`.
+    #[inline]
+    pub fn with_cache_control(mut self, cache_control: CacheControl) -> Self {
+        self.cache_control = Some(cache_control);
+        self
+    }
+
+    /// Sets `Content-Disposition` of successful responses and returns `Self`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use salvo_serve_static::{Disposition, StaticFile};
+    ///
+    /// let file = StaticFile::new("uploads/report.pdf").with_content_disposition(Disposition::Attachment {
+    ///     filename: Some("年度报告.pdf".into()),
+    /// });
+    /// ```
+    #[inline]
+    pub fn with_content_disposition(mut self, disposition: Disposition) -> Self {
+        self.disposition = Some(disposition);
+        self
+    }
+
+    /// Sets the content type of the file if it has `extension` and returns `Self`.
+    ///
+    /// The extension is matched case-insensitively, the leading dot is optional. The override takes
+    /// precedence over the content type guessed from the extension.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use salvo_serve_static::StaticFile;
+    ///
+    /// let file = StaticFile::new("static/app.wasm").with_mime_override("wasm", "application/wasm".parse().unwrap());
+    /// ```
+    #[inline]
+    pub fn with_mime_override(mut self, extension: impl AsRef<str>, mime: Mime) -> Self {
+        self.mime_overrides
+            .insert(normalize_extension(extension.as_ref()), mime);
+        self
+    }
+
+    /// Sets content types by extension and returns `Self`, see
+    /// [`with_mime_override`](Self::with_mime_override).
+    #[inline]
+    pub fn with_mime_overrides<K>(mut self, overrides: impl IntoIterator<Item = (K, Mime)>) -> Self
+    where
+        K: AsRef<str>,
+    {
+        for (extension, mime) in overrides {
+            self.mime_overrides
+                .insert(normalize_extension(extension.as_ref()), mime);
+        }
+        self
+    }
+    /// Sets the response when the file is not found and returns `Self`.
+    ///
+    /// A file path is used as is, the file is served with `404 Not Found`.
+    #[inline]
+    pub fn with_not_found_handler(mut self, handler: impl Into<NotFoundHandler>) -> Self {
+        self.not_found_handler = Some(handler.into());
+        self
+    }
+
+    /// Sets how the ETag of the file is computed and returns `Self`, see [`EtagMode`].
+    #[inline]
+    pub fn with_etag_mode(mut self, etag_mode: EtagMode) -> Self {
+        self.etag_mode = etag_mode;
+        self
     }
 }
 
@@ -31,10 +193,41 @@ impl StaticFile {
 impl Handler for StaticFile {
     #[inline]
     async fn handle(&self, req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
-        match self.0.clone().build().await {
-            Ok(file) => file.write(req, depot, res).await,
+        if !check_method_allowed(req, res) {
+            ctrl.skip_rest();
+            return;
+        }
+        let mut builder = apply_etag_mode(self.builder.clone(), self.etag_mode, &self.etag_cache, &self.path).await;
+        // `304 Not Modified` is sent from the file metadata, the file is only read to hash its content.
+        if req.method() != Method::OPTIONS && builder.send_not_modified(req.headers(), res).await {
+            ctrl.skip_rest();
+            return;
+        }
+        if let Some(mime) = mime_override(&self.mime_overrides, &self.path) {
+            builder = builder.content_type(mime.clone());
+        }
+        match builder.build().await {
+            Ok(_) if req.method() == Method::OPTIONS => render_file_options(res),
+            Ok(file) => {
+                file.write(req, depot, res).await;
+                if let Some(cache_control) = &self.cache_control {
+                    cache_control.apply(res);
+                }
+                if let Some(disposition) = &self.disposition {
+                    if matches!(res.status_code(), Some(StatusCode::OK | StatusCode::PARTIAL_CONTENT)) {
+                        if let Some(value) = disposition.header_value(&self.path) {
+                            res.headers_mut().insert(CONTENT_DISPOSITION, value);
+                        }
+                    }
+                }
+                strip_head_body(req, res);
+            }
             Err(_) => {
-                res.set_status_error(StatusError::not_found());
+                let file = match &self.not_found_handler {
+                    Some(NotFoundHandler::File(path)) if path.is_file() => Some(path.as_path()),
+                    _ => None,
+                };
+                render_not_found(self.not_found_handler.as_ref(), file, req, res).await;
             }
         }
         ctrl.skip_rest();
@@ -0,0 +1,89 @@
+//! Caches of values computed from file contents.
+
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Default number of files a [`FileCache`] keeps.
+pub(crate) const FILE_CACHE_CAPACITY: usize = 4096;
+
+/// Values computed from files by path, a value is valid as long as the size and modification time of
+/// the file are not changed.
+///
+/// At most `capacity` files are kept, an entry is evicted to insert a new file when it is full, so
+/// serving many distinct files doesn't grow the memory without bound.
+#[derive(Debug)]
+pub(crate) struct FileCache<V> {
+    entries: Mutex<HashMap<PathBuf, (u64, SystemTime, V)>>,
+    capacity: usize,
+}
+
+impl<V: Clone> FileCache<V> {
+    /// Create a new empty `FileCache` which keeps at most `capacity` files.
+    pub(crate) fn new(capacity: usize) -> Self {
+        FileCache {
+            entries: Mutex::new(HashMap::new()),
+            capacity,
+        }
+    }
+
+    /// Get the value of the file at `path` if it was computed for the file described by `metadata`.
+    pub(crate) fn get(&self, path: &Path, metadata: &Metadata) -> Option<V> {
+        let modified = metadata.modified().ok()?;
+        let entries = self.entries.lock().unwrap();
+        match entries.get(path) {
+            Some((size, mtime, value)) if *size == metadata.len() && *mtime == modified => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    /// Insert the value computed for the file at `path` described by `metadata`.
+    pub(crate) fn insert(&self, path: &Path, metadata: &Metadata, value: V) {
+        let modified = match metadata.modified() {
+            Ok(modified) => modified,
+            Err(_) => return,
+        };
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity && !entries.contains_key(path) {
+            // Any entry is evicted, the cache only saves work for files which are served again.
+            if let Some(evicted) = entries.keys().next().cloned() {
+                entries.remove(&evicted);
+            }
+        }
+        if self.capacity > 0 {
+            entries.insert(path.to_owned(), (metadata.len(), modified, value));
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_cache_capacity() {
+        let dir = std::env::temp_dir().join(format!("salvo_serve_static_file_cache_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cache = FileCache::new(2);
+        for i in 0..5 {
+            let path = dir.join(format!("{i}.txt"));
+            std::fs::write(&path, i.to_string()).unwrap();
+            let metadata = std::fs::metadata(&path).unwrap();
+            cache.insert(&path, &metadata, i);
+            assert_eq!(cache.get(&path, &metadata), Some(i));
+            assert!(cache.len() <= 2);
+        }
+
+        let path = dir.join("4.txt");
+        std::fs::write(&path, "changed").unwrap();
+        assert_eq!(cache.get(&path, &std::fs::metadata(&path).unwrap()), None);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,219 @@
+//! Glob matching used by static options.
+
+/// A set of glob patterns, matches a path if any of the patterns matches.
+///
+/// Paths are relative to the static root, separated by `/` and without leading `/`, for example
+/// `dir/file.txt`. Patterns support:
+///
+/// - `*` matches any sequence of characters except `/`.
+/// - `?` matches any single character except `/`.
+/// - `**` as a whole path segment matches zero or more segments, for example `**/*.log` or `secret/**`.
+///
+/// A pattern without `/` is matched against the last segment (the file name) only, so `*.log` matches
+/// both `a.log` and `dir/a.log`. Matching is case sensitive by default.
+#[derive(Clone, Debug)]
+pub struct GlobSet {
+    patterns: Vec<String>,
+    case_sensitive: bool,
+}
+
+impl Default for GlobSet {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GlobSet {
+    /// Create a new empty `GlobSet`.
+    #[inline]
+    pub fn new() -> Self {
+        GlobSet {
+            patterns: vec![],
+            case_sensitive: true,
+        }
+    }
+
+    /// Add a pattern and returns `Self`.
+    #[inline]
+    pub fn add(mut self, pattern: impl Into<String>) -> Self {
+        self.patterns.push(pattern.into().trim_start_matches('/').to_owned());
+        self
+    }
+
+    /// Sets whether matching is case sensitive and returns `Self`.
+    #[inline]
+    pub fn with_case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+
+    /// Check whether there are no patterns.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Check that all patterns are well formed, returns the first invalid pattern if any.
+    ///
+    /// A pattern is invalid if it is empty, or `**` is used with other characters in a segment,
+    /// for example `a**.txt`, which would only match in a single segment.
+    pub fn check(&self) -> Result<(), String> {
+        for pattern in &self.patterns {
+            if pattern.is_empty() || pattern.split('/').any(|part| part != "**" && part.contains("**")) {
+                return Err(pattern.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Check whether `path` matches any of the patterns.
+    pub fn is_match(&self, path: &str) -> bool {
+        if self.patterns.is_empty() {
+            return false;
+        }
+        let path = path.trim_start_matches('/');
+        let path = if self.case_sensitive {
+            path.to_owned()
+        } else {
+            path.to_lowercase()
+        };
+        let segments = path.split('/').filter(|s| !s.is_empty()).collect::<Vec<_>>();
+        self.patterns.iter().any(|pattern| {
+            let pattern = if self.case_sensitive {
+                pattern.clone()
+            } else {
+                pattern.to_lowercase()
+            };
+            if pattern.contains('/') {
+                let parts = pattern.split('/').filter(|s| !s.is_empty()).collect::<Vec<_>>();
+                match_segments(&parts, &segments)
+            } else {
+                segments
+                    .last()
+                    .map(|name| pattern == "**" || match_segment(&pattern, name))
+                    .unwrap_or(false)
+            }
+        })
+    }
+
+    /// Check whether `path` or any of its parent directories matches any of the patterns, so files
+    /// under an excluded directory are excluded too.
+    pub fn is_match_with_ancestors(&self, path: &str) -> bool {
+        if self.patterns.is_empty() {
+            return false;
+        }
+        let path = path.trim_matches('/');
+        path.match_indices('/')
+            .map(|(index, _)| &path[..index])
+            .chain(std::iter::once(path))
+            .any(|path| self.is_match(path))
+    }
+}
+
+impl<T: Into<String>> FromIterator<T> for GlobSet {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        iter.into_iter().fold(GlobSet::new(), |set, pattern| set.add(pattern))
+    }
+}
+
+fn match_segments(parts: &[&str], segments: &[&str]) -> bool {
+    match parts.split_first() {
+        None => segments.is_empty(),
+        Some((&"**", rest)) => (0..=segments.len()).any(|skip| match_segments(rest, &segments[skip..])),
+        Some((part, rest)) => match segments.split_first() {
+            Some((segment, others)) => match_segment(part, segment) && match_segments(rest, others),
+            None => false,
+        },
+    }
+}
+
+fn match_segment(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    let mut star = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_star() {
+        let set = GlobSet::new().add("*.log");
+        assert!(set.is_match("a.log"));
+        assert!(set.is_match("dir/sub/a.log"));
+        assert!(!set.is_match("a.log.txt"));
+
+        let set = GlobSet::new().add("dir/*.txt");
+        assert!(set.is_match("dir/a.txt"));
+        assert!(set.is_match("/dir/a.txt"));
+        assert!(!set.is_match("dir/sub/a.txt"));
+        assert!(!set.is_match("other/dir/a.txt"));
+    }
+
+    #[test]
+    fn test_glob_double_star() {
+        let set = GlobSet::new().add("**/secret/*");
+        assert!(set.is_match("secret/a.txt"));
+        assert!(set.is_match("a/b/secret/a.txt"));
+        assert!(!set.is_match("a/secret"));
+
+        let set = GlobSet::new().add("private/**");
+        assert!(set.is_match("private"));
+        assert!(set.is_match("private/a/b.txt"));
+        assert!(!set.is_match("public/private.txt"));
+    }
+
+    #[test]
+    fn test_glob_question_mark() {
+        let set = GlobSet::new().add("file?.txt");
+        assert!(set.is_match("file1.txt"));
+        assert!(!set.is_match("file.txt"));
+        assert!(!set.is_match("file12.txt"));
+    }
+
+    #[test]
+    fn test_glob_check() {
+        assert!(GlobSet::new().add("**/*.txt").add("a/**").check().is_ok());
+        assert_eq!(GlobSet::new().add("a**.txt").check(), Err("a**.txt".to_owned()));
+        assert_eq!(GlobSet::new().add("/").check(), Err("".to_owned()));
+    }
+
+    #[test]
+    fn test_glob_ancestors() {
+        let set = GlobSet::new().add("private");
+        assert!(!set.is_match("private/a.txt"));
+        assert!(set.is_match_with_ancestors("private/a.txt"));
+        assert!(set.is_match_with_ancestors("/dir/private/sub/a.txt"));
+        assert!(!set.is_match_with_ancestors("public/a.txt"));
+    }
+
+    #[test]
+    fn test_glob_case_sensitive() {
+        let set = ["*.TXT"].into_iter().collect::<GlobSet>();
+        assert!(set.is_match("a.TXT"));
+        assert!(!set.is_match("a.txt"));
+        let set = set.with_case_sensitive(false);
+        assert!(set.is_match("a.txt"));
+        assert!(set.is_match("DIR/A.Txt"));
+    }
+}
@@ -0,0 +1,229 @@
+//! Stream directory listings as JSON.
+//!
+//! Directories are read lazily and entries are written in chunks as they are read, so memory stays
+//! bounded however large the directory is. Nothing is remembered to merge several directories, an
+//! entry of a later directory is looked up in the earlier ones instead.
+
+use std::fs::Metadata;
+use std::io::Result as IoResult;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use futures_util::stream::{self, Stream};
+use serde::Serialize;
+use time::OffsetDateTime;
+use tokio::fs::ReadDir;
+
+use crate::GlobSet;
+
+const CHUNK_SIZE: usize = 16 * 1024;
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    modified: Option<OffsetDateTime>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Start,
+    Dirs,
+    Files,
+    Done,
+}
+
+struct ListingStream {
+    dir_paths: Vec<PathBuf>,
+    /// Index of the directory in `dir_paths` which is read.
+    dir_index: usize,
+    read_dir: Option<ReadDir>,
+    path: String,
+    rel_path: String,
+    dot_files: bool,
+    excludes: GlobSet,
+    max_entries: Option<usize>,
+    hide_metadata: bool,
+    phase: Phase,
+    /// Entries written to the array of the current phase.
+    written: usize,
+    /// Entries written to both arrays.
+    listed: usize,
+    count: usize,
+    total_size: u64,
+}
+
+/// Create a stream which yields the entries of the directories `dir_paths` as JSON with the fields
+/// of [`CurrentInfo`](crate::dir::CurrentInfo), the entry of the earliest directory wins if a name
+/// exists in several of them.
+///
+/// The directories are read twice, once for `dirs` and once for `files`, which is written after
+/// `dirs`, so directories are kept first when entries are left out. `path` is the request path of the
+/// directories and `rel_path` their path relative to the static roots, it is used to match `excludes`.
+/// Dot files are skipped unless `dot_files` is `true`. At most `max_entries` entries are written,
+/// `count` and `total_size` still include the others and `truncated` is set then, as by the listing
+/// which is not streamed.
+pub(crate) fn json_listing(
+    dir_paths: Vec<PathBuf>,
+    path: String,
+    rel_path: String,
+    dot_files: bool,
+    excludes: GlobSet,
+    max_entries: Option<usize>,
+    hide_metadata: bool,
+) -> impl Stream<Item = IoResult<Vec<u8>>> + Send + 'static {
+    let state = ListingStream {
+        dir_paths,
+        dir_index: 0,
+        read_dir: None,
+        path,
+        rel_path,
+        dot_files,
+        excludes,
+        max_entries,
+        hide_metadata,
+        phase: Phase::Start,
+        written: 0,
+        listed: 0,
+        count: 0,
+        total_size: 0,
+    };
+    stream::unfold(Some(state), |state| async move {
+        let mut state = state?;
+        match state.next_chunk().await {
+            Ok(Some(chunk)) => Some((Ok(chunk), Some(state))),
+            Ok(None) => None,
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+}
+
+impl ListingStream {
+    async fn next_chunk(&mut self) -> IoResult<Option<Vec<u8>>> {
+        let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+        match self.phase {
+            Phase::Done => return Ok(None),
+            // The start of the object is sent before any directory is read, so the client gets the
+            // first bytes at once.
+            Phase::Start => {
+                chunk.extend_from_slice(b"{\"path\":");
+                serde_json::to_writer(&mut chunk, &self.path)?;
+                chunk.extend_from_slice(b",\"dirs\":[");
+                self.phase = Phase::Dirs;
+                return Ok(Some(chunk));
+            }
+            Phase::Dirs | Phase::Files => {}
+        }
+        while chunk.len() < CHUNK_SIZE {
+            let (name, metadata) = match self.next_entry().await {
+                Some(entry) => entry,
+                None if self.phase == Phase::Dirs => {
+                    chunk.extend_from_slice(b"],\"files\":[");
+                    self.phase = Phase::Files;
+                    self.dir_index = 0;
+                    self.written = 0;
+                    continue;
+                }
+                None => {
+                    chunk.push(b']');
+                    self.write_summary(&mut chunk);
+                    self.phase = Phase::Done;
+                    break;
+                }
+            };
+            self.count += 1;
+            let is_dir = metadata.is_dir();
+            if !is_dir {
+                self.total_size += metadata.len();
+            }
+            // Entries over the limit are only counted.
+            if self.max_entries.map(|max| self.listed >= max).unwrap_or(false) {
+                continue;
+            }
+            if self.written > 0 {
+                chunk.push(b',');
+            }
+            let modified = metadata.modified().unwrap_or_else(|_| SystemTime::now()).into();
+            let entry = Entry {
+                name: &name,
+                size: (!is_dir && !self.hide_metadata).then(|| metadata.len()),
+                modified: (!self.hide_metadata).then_some(modified),
+            };
+            serde_json::to_writer(&mut chunk, &entry)?;
+            self.written += 1;
+            self.listed += 1;
+        }
+        Ok(Some(chunk))
+    }
+
+    /// Write the fields following the entries and close the object.
+    fn write_summary(&self, chunk: &mut Vec<u8>) {
+        chunk.extend_from_slice(format!(",\"count\":{}", self.count).as_bytes());
+        if !self.hide_metadata {
+            chunk.extend_from_slice(format!(",\"total_size\":{}", self.total_size).as_bytes());
+        }
+        let truncated = self.max_entries.map(|max| self.count > max).unwrap_or(false);
+        chunk.extend_from_slice(format!(",\"truncated\":{truncated}}}").as_bytes());
+    }
+
+    /// Read the next entry of the current phase to list, directories which can't be read are skipped
+    /// as by the listing which is not streamed.
+    async fn next_entry(&mut self) -> Option<(String, Metadata)> {
+        let dirs = self.phase == Phase::Dirs;
+        loop {
+            let read_dir = match &mut self.read_dir {
+                Some(read_dir) => read_dir,
+                None => {
+                    let dir_path = self.dir_paths.get(self.dir_index)?.clone();
+                    self.read_dir = tokio::fs::read_dir(&dir_path).await.ok();
+                    if self.read_dir.is_none() {
+                        self.dir_index += 1;
+                    }
+                    continue;
+                }
+            };
+            let entry = match read_dir.next_entry().await {
+                Ok(Some(entry)) => entry,
+                _ => {
+                    self.read_dir = None;
+                    self.dir_index += 1;
+                    continue;
+                }
+            };
+            let name = entry.file_name().to_string_lossy().to_string();
+            if self.excludes.is_match(&format!("{}/{}", self.rel_path, name)) {
+                continue;
+            }
+            let metadata = match entry.metadata().await {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            if metadata.is_dir() != dirs || !self.is_listed(&name, &metadata) {
+                continue;
+            }
+            if self.listed_earlier(&name).await {
+                continue;
+            }
+            return Some((name, metadata));
+        }
+    }
+
+    /// Whether an entry named `name` is listed if it is in the directories.
+    fn is_listed(&self, name: &str, metadata: &Metadata) -> bool {
+        metadata.is_dir() || self.dot_files || !name.starts_with('.')
+    }
+
+    /// Whether an entry named `name` is listed from a directory before the current one.
+    async fn listed_earlier(&self, name: &str) -> bool {
+        for dir_path in &self.dir_paths[..self.dir_index] {
+            if let Ok(metadata) = tokio::fs::symlink_metadata(dir_path.join(name)).await {
+                if self.is_listed(name, &metadata) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
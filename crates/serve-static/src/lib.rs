@@ -7,16 +7,42 @@
 #![warn(missing_docs)]
 #![warn(clippy::future_not_send)]
 
+mod async_source;
+mod cache_control;
 pub mod dir;
+mod etag;
 mod file;
+mod file_cache;
+mod glob;
+mod json_listing;
+mod media;
+mod multipart;
+mod not_found;
+mod sources;
+mod vfs;
+mod zip;
 
-use percent_encoding::{utf8_percent_encode, CONTROLS};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use salvo_core::http::header::{HeaderValue, ACCEPT_RANGES, ALLOW, CONTENT_LENGTH};
 use salvo_core::http::uri::{Parts as UriParts, Uri};
+use salvo_core::http::{Method, Mime, StatusCode, StatusError};
 use salvo_core::writer::Redirect;
-use salvo_core::Response;
+use salvo_core::{Request, Response};
 
-pub use dir::StaticDir;
-pub use file::StaticFile;
+pub use async_source::{AsyncFileSource, AsyncStaticDir, FsSource, SourceMetadata};
+pub use cache_control::{CacheControl, CacheVisibility};
+pub use dir::{
+    DirectoryPolicy, EtagMode, ListingSort, PrecompressedEncoding, RedirectPolicy, StaticDir, UnknownMimePolicy,
+};
+pub use file::{Disposition, StaticFile};
+pub use glob::GlobSet;
+pub use multipart::MultipartFilesExt;
+pub use not_found::NotFoundHandler;
+pub use sources::StaticSources;
+pub use vfs::StaticVfs;
 
 #[macro_use]
 mod cfg;
@@ -27,10 +53,76 @@ cfg_feature! {
     pub use embed::{render_embedded_file, static_embed, EmbeddedFileExt, StaticEmbed};
 }
 
+/// Methods supported by static handlers.
+pub(crate) const ALLOWED_METHODS: &str = "GET, HEAD, OPTIONS";
+
+/// Check the request method is supported by static handlers, otherwise `405 Method Not Allowed`
+/// is written to `res` and `false` is returned.
+#[inline]
+pub(crate) fn check_method_allowed(req: &Request, res: &mut Response) -> bool {
+    if [Method::GET, Method::HEAD, Method::OPTIONS].contains(req.method()) {
+        true
+    } else {
+        res.headers_mut()
+            .insert(ALLOW, HeaderValue::from_static(ALLOWED_METHODS));
+        res.set_status_error(StatusError::method_not_allowed());
+        false
+    }
+}
+
+/// Answer an `OPTIONS` request to a file with `204 No Content`, the allowed methods and
+/// `Accept-Ranges: bytes`.
+#[inline]
+pub(crate) fn render_file_options(res: &mut Response) {
+    res.headers_mut()
+        .insert(ALLOW, HeaderValue::from_static(ALLOWED_METHODS));
+    res.headers_mut()
+        .insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    res.set_status_code(StatusCode::NO_CONTENT);
+}
+
+/// Drop the body of the response to a `HEAD` request, so it has the status and headers of the
+/// response to `GET` only, `Content-Length` is set from the dropped body if it is not set yet.
+///
+/// Responses without body, like `304 Not Modified`, are kept as is.
+#[inline]
+pub(crate) fn strip_head_body(req: &Request, res: &mut Response) {
+    if req.method() != Method::HEAD || res.body.is_none() {
+        return;
+    }
+    let body = res.take_body();
+    if !res.headers().contains_key(CONTENT_LENGTH) {
+        if let Some(size) = body.size() {
+            res.headers_mut().insert(CONTENT_LENGTH, size.into());
+        }
+    }
+}
+
+/// Characters which are percent-encoded in a path segment, they are either not allowed in URLs or
+/// end the path, like `?` and `#`, or are `%` itself, so encoded segments decode to the original.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'\'')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b'\\')
+    .add(b']')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+/// Percent-encode each segment of `path`, `/` is kept as the separator.
 #[inline]
 pub(crate) fn encode_url_path(path: &str) -> String {
     path.split('/')
-        .map(|s| utf8_percent_encode(s, CONTROLS).to_string())
+        .map(|s| utf8_percent_encode(s, PATH_SEGMENT).to_string())
         .collect::<Vec<_>>()
         .join("/")
 }
@@ -42,23 +134,154 @@ pub(crate) fn decode_url_path_safely(path: &str) -> String {
         .to_string()
 }
 
+/// Decode a url path strictly, `None` is returned if a `%` is not followed by two hex digits or the
+/// decoded path is not valid UTF-8, so different malformed paths are never decoded to the same path.
+#[inline]
+pub(crate) fn decode_url_path_strictly(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let valid_escapes = bytes.iter().enumerate().filter(|(_, b)| **b == b'%').all(|(i, _)| {
+        bytes
+            .get(i + 1..i + 3)
+            .map(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .unwrap_or(false)
+    });
+    if !valid_escapes {
+        return None;
+    }
+    percent_encoding::percent_decode_str(path)
+        .decode_utf8()
+        .ok()
+        .map(|path| path.into_owned())
+}
+
+/// Reject the request with `400 Bad Request` if its path can't be decoded strictly, returns `true`
+/// if it is rejected.
+pub(crate) fn reject_malformed_path(req: &Request, res: &mut Response) -> bool {
+    if decode_url_path_strictly(req.uri().path()).is_some() {
+        return false;
+    }
+    tracing::warn!(path = req.uri().path(), "request path has malformed percent-encoding");
+    res.set_status_error(StatusError::bad_request());
+    true
+}
+
+/// Check whether a decoded path contains control characters, like `NUL` decoded from `%00`, which
+/// may truncate paths or be used for smuggling on some platforms.
+#[inline]
+pub(crate) fn has_control_chars(path: &str) -> bool {
+    path.chars().any(char::is_control)
+}
+
 #[inline]
 pub(crate) fn format_url_path_safely(path: &str) -> String {
+    // Windows strips trailing dots and spaces of file names, so `secret.txt.` opens `secret.txt`.
+    // Normalize them here, otherwise such requests can bypass checks based on file name.
+    format_path_parts(path, cfg!(windows))
+}
+
+/// Join the request path `url_path` to `root` safely, it is the building block of custom static
+/// file handlers.
+///
+/// `url_path` is percent-decoded, both `/` and `\\` are treated as separators, empty and `.`
+/// segments are skipped and `..` removes the previous segment. `None` is returned if the path
+/// contains control characters, a segment is absolute (like the `C:` drive prefix on Windows) or `..`
+/// goes above `root`. The path is resolved lexically, the file system is not accessed, so symlinks
+/// in `root` are not checked.
+///
+/// # Example
+///
+/// ```
+/// use std::path::Path;
+/// use salvo_serve_static::safe_join;
+///
+/// let root = Path::new("static");
+/// assert_eq!(safe_join(root, "/css/../js/app.js"), Some(root.join("js").join("app.js")));
+/// assert_eq!(safe_join(root, "/%2e%2e/secret.txt"), None);
+/// ```
+pub fn safe_join(root: &Path, url_path: &str) -> Option<PathBuf> {
+    let path = decode_url_path_safely(url_path);
+    if has_control_chars(&path) {
+        return None;
+    }
+    let parts = sanitize_path_parts(&path, cfg!(windows), true)?;
+    let mut joined = root.to_path_buf();
+    joined.extend(parts);
+    Some(joined)
+}
+
+/// Get the value of the longest prefix of `path` in `map`, prefixes match whole path segments and
+/// leading or trailing `/` of them are ignored.
+pub(crate) fn longest_prefix_match<'a, V>(map: &'a HashMap<String, V>, path: &str) -> Option<&'a V> {
+    map.iter()
+        .filter(|(prefix, _)| matches_prefix(prefix, path))
+        .map(|(prefix, value)| (prefix.trim_matches('/').len(), value))
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, value)| value)
+}
+
+/// Whether `prefix` is a prefix of `path` matching whole path segments, leading or trailing `/` of
+/// `prefix` are ignored.
+pub(crate) fn matches_prefix(prefix: &str, path: &str) -> bool {
+    let prefix = prefix.trim_matches('/');
+    prefix.is_empty() || path == prefix || (path.starts_with(prefix) && path[prefix.len()..].starts_with('/'))
+}
+
+/// Normalize a file extension as a key of extension maps, it is lower case without leading dot.
+#[inline]
+pub(crate) fn normalize_extension(extension: &str) -> String {
+    extension.trim_start_matches('.').to_lowercase()
+}
+
+/// Get the overridden content type of the file at `path` by its extension, the extension is
+/// matched case-insensitively.
+pub(crate) fn mime_override<'a>(overrides: &'a HashMap<String, Mime>, path: &Path) -> Option<&'a Mime> {
+    if overrides.is_empty() {
+        return None;
+    }
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .and_then(|extension| overrides.get(&extension.to_lowercase()))
+}
+
+#[inline]
+fn format_path_parts(path: &str, trim_trailing_dots: bool) -> String {
+    sanitize_path_parts(path, trim_trailing_dots, false)
+        .unwrap_or_default()
+        .join("/")
+}
+
+/// Split a decoded path into the segments which are kept, `..` above the root is ignored unless
+/// `strict` is set, then `None` is returned for it and for absolute segments.
+pub(crate) fn sanitize_path_parts(path: &str, trim_trailing_dots: bool, strict: bool) -> Option<Vec<&str>> {
     let mut used_parts = Vec::with_capacity(8);
     for part in path.split(['/', '\\']) {
         if part.is_empty() || part == "." {
             continue;
         } else if part == ".." {
-            used_parts.pop();
+            if used_parts.pop().is_none() && strict {
+                return None;
+            }
+            continue;
+        }
+        let part = if trim_trailing_dots {
+            part.trim_end_matches(['.', ' '])
         } else {
-            used_parts.push(part);
+            part
+        };
+        if part.is_empty() {
+            continue;
         }
+        if strict && !matches!(Path::new(part).components().next(), Some(Component::Normal(_))) {
+            return None;
+        }
+        used_parts.push(part);
     }
-    used_parts.join("/")
+    Some(used_parts)
 }
 
+/// Redirect to the request URI with a trailing slash appended to the path, the query is kept.
 #[inline]
-pub(crate) fn redirect_to_dir_url(req_uri: &Uri, res: &mut Response) {
+pub(crate) fn redirect_to_dir_url(req_uri: &Uri, status_code: StatusCode, res: &mut Response) {
     let UriParts {
         scheme,
         authority,
@@ -80,7 +303,7 @@ pub(crate) fn redirect_to_dir_url(req_uri: &Uri, res: &mut Response) {
         }
     }
     let redirect_uri = builder.build().unwrap();
-    res.render(Redirect::found(redirect_uri));
+    res.render(Redirect::with_status_code(status_code, redirect_uri).expect("status code is a redirection"));
 }
 
 #[cfg(test)]
@@ -88,9 +311,184 @@ mod tests {
     use rust_embed::RustEmbed;
     use salvo_core::prelude::*;
     use salvo_core::test::{ResponseExt, TestClient};
+    use sha2::Digest;
 
+    use crate::dir::CurrentInfo;
     use crate::*;
 
+    /// Create an empty directory under the system temporary directory, unique to this process and
+    /// call, so tests running in parallel, or in several processes, don't share their files.
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        static NEXT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let index = NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("{name}_{}_{index}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_format_path_trailing_dots() {
+        assert_eq!(format_path_parts("dir/secret.txt.", true), "dir/secret.txt");
+        assert_eq!(format_path_parts("dir/secret.txt ", true), "dir/secret.txt");
+        assert_eq!(format_path_parts("dir. /secret.txt. .", true), "dir/secret.txt");
+        assert_eq!(format_path_parts("dir/.../secret.txt", true), "dir/secret.txt");
+        assert_eq!(format_path_parts("dir/secret.txt.", false), "dir/secret.txt.");
+    }
+
+    #[test]
+    fn test_has_control_chars() {
+        assert!(has_control_chars(&decode_url_path_safely("test1.txt%00.png")));
+        assert!(has_control_chars(&decode_url_path_safely("dir1/%0a/test1.txt")));
+        assert!(has_control_chars("dir1/\u{7f}"));
+        assert!(!has_control_chars(&decode_url_path_safely("dir1/%2e%2e/test%201.txt")));
+    }
+
+    #[test]
+    fn test_safe_join() {
+        use std::path::Path;
+
+        let root = Path::new("static");
+        assert_eq!(
+            safe_join(root, "/dir1/test1.txt"),
+            Some(root.join("dir1").join("test1.txt"))
+        );
+        assert_eq!(safe_join(root, "./dir1/../test1.txt"), Some(root.join("test1.txt")));
+        assert_eq!(safe_join(root, "/test%201.txt"), Some(root.join("test 1.txt")));
+        assert_eq!(safe_join(root, ""), Some(root.to_path_buf()));
+
+        // Traversal.
+        assert_eq!(safe_join(root, "../secret.txt"), None);
+        assert_eq!(safe_join(root, "/dir1/../../secret.txt"), None);
+        // Backslash.
+        assert_eq!(safe_join(root, "..\\secret.txt"), None);
+        assert_eq!(safe_join(root, "dir1\\..\\..\\secret.txt"), None);
+        assert_eq!(
+            safe_join(root, "dir1\\test1.txt"),
+            Some(root.join("dir1").join("test1.txt"))
+        );
+        // Percent-encoded.
+        assert_eq!(safe_join(root, "/%2e%2e/secret.txt"), None);
+        assert_eq!(safe_join(root, "/dir1/%2E%2E%2f%2e%2e%2fsecret.txt"), None);
+        assert_eq!(safe_join(root, "/dir1%5c..%5c..%5csecret.txt"), None);
+        assert_eq!(safe_join(root, "/test1.txt%00.png"), None);
+    }
+
+    #[test]
+    fn test_decode_url_path_strictly() {
+        assert_eq!(
+            decode_url_path_strictly("/dir1/test%201.txt").unwrap(),
+            "/dir1/test 1.txt"
+        );
+        assert_eq!(decode_url_path_strictly("/%25zz").unwrap(), "/%zz");
+        assert!(decode_url_path_strictly("/dir1/%zz.txt").is_none());
+        assert!(decode_url_path_strictly("/dir1/%2").is_none());
+        assert!(decode_url_path_strictly("/dir1/%%41").is_none());
+        assert!(decode_url_path_strictly("/dir1/%ff.txt").is_none());
+    }
+
+    #[test]
+    fn test_encode_url_path() {
+        assert_eq!(encode_url_path("dir1/test1.txt"), "dir1/test1.txt");
+        assert_eq!(encode_url_path("a#b/c?d.txt"), "a%23b/c%3Fd.txt");
+        assert_eq!(encode_url_path("with space/100%.txt"), "with%20space/100%25.txt");
+        assert_eq!(encode_url_path("报告.txt"), "%E6%8A%A5%E5%91%8A.txt");
+        for name in [
+            "a#b?c.txt",
+            "with space.txt",
+            "100%25.txt",
+            "报告 \"final\".txt",
+            "-_.~!$&()*+,;=:@",
+        ] {
+            assert_eq!(decode_url_path_strictly(&encode_url_path(name)).unwrap(), name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_listing_links() {
+        let root = temp_dir("salvo_serve_static_listing_links");
+        std::fs::create_dir_all(root.join("sub dir#1")).unwrap();
+        let names = ["a#b.txt", "with space.txt", "100%.txt", "报告.txt"];
+        for name in names {
+            std::fs::write(root.join(name), name).unwrap();
+        }
+        let router = Router::with_path("<**path>").get(StaticDir::new(vec![root.clone()]).with_listing(true));
+        let service = Service::new(router);
+
+        let content = TestClient::get("http://127.0.0.1:5801/")
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        let links = content
+            .split("href=\"./")
+            .skip(1)
+            .map(|s| decode_url_path_strictly(&s[..s.find('"').unwrap()]).unwrap())
+            .collect::<Vec<_>>();
+        assert!(links.contains(&"sub dir#1/".to_owned()), "{links:?}");
+        for name in names {
+            assert!(links.contains(&name.to_owned()), "{name} {links:?}");
+            let mut response = TestClient::get(format!("http://127.0.0.1:5801/{}", encode_url_path(name)))
+                .send(&service)
+                .await;
+            assert_eq!(response.take_string().await.unwrap(), name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_strict_decode() {
+        let router = Router::new()
+            .push(Router::with_path("strict/<**path>").get(StaticDir::new(["test/static"]).with_strict_decode(true)))
+            .push(Router::with_path("lossy/<**path>").get(StaticDir::new(["test/static"])))
+            .push(Router::with_path("vfs/<**path>").get(StaticVfs::new().with_strict_decode(true)));
+        let service = Service::new(router);
+
+        for url in ["strict/dir1/%zz.txt", "strict/dir1/%ff.txt", "vfs/%zz.txt"] {
+            let response = TestClient::get(format!("http://127.0.0.1:5801/{url}"))
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::BAD_REQUEST, "{url}");
+        }
+        let response = TestClient::get("http://127.0.0.1:5801/lossy/dir1/%zz.txt")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+        let mut response = TestClient::get("http://127.0.0.1:5801/strict/test1.txt")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.take_string().await.unwrap(), "copy1");
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_control_chars() {
+        let router = Router::with_path("<**path>").get(StaticDir::new(vec!["test/static"]).with_listing(true));
+        let service = Service::new(router);
+
+        // `..` which goes above the roots is dropped instead of rejected, so the last two look up
+        // `Cargo.toml` in the roots, where it doesn't exist.
+        for url in [
+            "http://127.0.0.1:5801/test1.txt%00",
+            "http://127.0.0.1:5801/test1.txt%00.png",
+            "http://127.0.0.1:5801/dir1%00/",
+            "http://127.0.0.1:5801/%0d%0atest1.txt",
+            "http://127.0.0.1:5801/%2e%2e/%2e%2e/Cargo.toml",
+            "http://127.0.0.1:5801/%2e%2e%2f%2e%2e%2fCargo.toml",
+        ] {
+            let response = TestClient::get(url).send(&service).await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND, "{url}");
+        }
+
+        // The client resolves `.` and `..` segments, `%2e%2e` included, before the request is sent, so
+        // this requests `/test1.txt`.
+        let mut response = TestClient::get("http://127.0.0.1:5801/dir1/%2e%2e/./test1.txt")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert!(response.take_string().await.unwrap().starts_with("copy1"));
+    }
+
     #[tokio::test]
     async fn test_serve_static_dir() {
         let router = Router::with_path("<**path>").get(
@@ -126,6 +524,18 @@ mod tests {
 
         let content = access(&service, "application/json", "http://127.0.0.1:5801/dir1/").await;
         assert!(content.starts_with('{') && content.contains("test3.txt") && content.contains("dir2"));
+        let listing: CurrentInfo = serde_json::from_str(&content).unwrap();
+        assert_eq!(listing.path, "/dir1/");
+        assert_eq!(listing.count, 2);
+        assert_eq!(
+            listing.total_size,
+            std::fs::metadata("test/static/dir1/test3.txt").unwrap().len()
+        );
+        assert!(listing
+            .files
+            .iter()
+            .any(|file| file.name == "test3.txt" && file.size > 0));
+        assert!(listing.dirs.iter().any(|dir| dir.name == "dir2"));
 
         let content = access(&service, "text/plain", "http://127.0.0.1:5801/test1.txt").await;
         assert!(content.contains("copy1"));
@@ -154,80 +564,2953 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_serve_static_file() {
+    async fn test_serve_static_dir_defaults_order() {
         let router = Router::new()
-            .push(Router::with_path("test1.txt").get(StaticFile::new("test/static/test1.txt").chunk_size(1024)))
-            .push(Router::with_path("notexist.txt").get(StaticFile::new("test/static/notexist.txt")));
+            .push(
+                Router::with_path("htm/<**path>")
+                    .get(StaticDir::new(vec!["test/static"]).with_defaults(["index.htm", "index.html"])),
+            )
+            .push(
+                Router::with_path("html/<**path>")
+                    .get(StaticDir::new(vec!["test/static"]).with_defaults(["index.html", "index.htm"])),
+            );
+        let service = Service::new(router);
+
+        let content = TestClient::get("http://127.0.0.1:5801/htm/defaults/")
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert!(content.contains("Index htm page"));
+
+        let content = TestClient::get("http://127.0.0.1:5801/html/defaults/")
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert!(content.contains("Index html page"));
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_metrics() {
+        let static_dir = StaticDir::new(vec!["test/static"]);
+        let metrics = static_dir.metrics();
+        let service = Service::new(Router::with_path("<**path>").get(static_dir));
+
+        for url in [
+            "http://127.0.0.1:5801/test1.txt",
+            "http://127.0.0.1:5801/test2.txt",
+            "http://127.0.0.1:5801/notexist.txt",
+        ] {
+            TestClient::get(url).send(&service).await.take_string().await.unwrap();
+        }
+        assert_eq!(metrics.hits(), 2);
+        assert_eq!(metrics.misses(), 1);
+        assert_eq!(metrics.bytes_served(), 10);
+        assert_eq!(metrics.cache_hit_ratio(), 0.0);
+    }
+
+    #[test]
+    fn test_static_dir_validate() {
+        assert!(StaticDir::new(vec!["test/static"]).validate().is_ok());
+
+        let err = StaticDir::new(vec!["test/static", "test/not_exist"])
+            .validate()
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        assert!(err
+            .to_string()
+            .contains("static root `test/not_exist` is not accessible"));
+
+        let err = StaticDir::new(vec!["test/static/test1.txt"]).validate().unwrap_err();
+        assert!(err.to_string().contains("is not a directory"));
+
+        let err = StaticDir::new(vec!["test/static"])
+            .with_defaults("sub/index.html")
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("default file name `sub/index.html`"));
+
+        let err = StaticDir::new(vec!["test/static"])
+            .with_excludes(GlobSet::new().add("a**.txt"))
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("exclude pattern `a**.txt` is invalid"));
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_excludes() {
+        let router = Router::with_path("<**path>").get(
+            StaticDir::new(vec!["test/static"])
+                .with_listing(true)
+                .with_zip_download(true)
+                .with_excludes(GlobSet::new().add("dir1/**/TEST?.txt").with_case_sensitive(false)),
+        );
         let service = Service::new(router);
 
+        let response = TestClient::get("http://127.0.0.1:5801/dir1/dir2/test3.txt")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
         let mut response = TestClient::get("http://127.0.0.1:5801/test1.txt").send(&service).await;
-        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
         assert_eq!(response.take_string().await.unwrap(), "copy1");
 
-        let response = TestClient::get("http://127.0.0.1:5801/notexist.txt")
+        let content = TestClient::get("http://127.0.0.1:5801/dir1/")
+            .add_header("accept", "text/plain", true)
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert!(content.contains("dir2") && !content.contains("test3.txt"));
+
+        let archive = TestClient::get("http://127.0.0.1:5801/dir1/?download=zip")
+            .send(&service)
+            .await
+            .take_bytes()
+            .await
+            .unwrap();
+        assert!(!archive.windows(9).any(|w| w == b"test3.txt"));
+
+        // Files under an excluded directory are excluded too.
+        let router = Router::with_path("<**path>").get(
+            StaticDir::new(vec!["test/static"])
+                .with_zip_download(true)
+                .with_excludes(GlobSet::new().add("dir1/dir2")),
+        );
+        let service = Service::new(router);
+        for url in [
+            "http://127.0.0.1:5801/dir1/dir2/test3.txt",
+            "http://127.0.0.1:5801/dir1/dir2/?download=zip",
+        ] {
+            let response = TestClient::get(url).send(&service).await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND, "{url}");
+        }
+        let response = TestClient::get("http://127.0.0.1:5801/dir1/test3.txt")
             .send(&service)
             .await;
-        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        let archive = TestClient::get("http://127.0.0.1:5801/dir1/?download=zip")
+            .send(&service)
+            .await
+            .take_bytes()
+            .await
+            .unwrap();
+        assert!(archive.windows(9).any(|w| w == b"test3.txt"));
+        assert!(!archive.windows(5).any(|w| w == b"dir2/"));
     }
 
-    #[cfg(feature = "embed")]
     #[tokio::test]
-    async fn test_serve_embed_files() {
-        #[derive(RustEmbed)]
-        #[folder = "test/static"]
-        struct Assets;
-
+    async fn test_serve_static_dir_directory_policy() {
         let router = Router::new()
-            .push(Router::with_path("test1.txt").get(Assets::get("test1.txt").unwrap().into_handler()))
-            .push(Router::with_path("files/<**path>").get(serve_file))
             .push(
-                Router::with_path("dir/<**path>").get(
-                    static_embed::<Assets>()
+                Router::with_path("listing/<**path>").get(
+                    StaticDir::new(vec!["test/static"])
                         .with_defaults("index.html")
-                        .with_fallback("fallback.html"),
+                        .with_directory_policy(DirectoryPolicy::Listing),
                 ),
             )
-            .push(Router::with_path("dir2/<**path>").get(static_embed::<Assets>()))
-            .push(Router::with_path("dir3/<**path>").get(static_embed::<Assets>().with_fallback("notexist.html")));
+            .push(
+                Router::with_path("index/<**path>").get(
+                    StaticDir::new(vec!["test/static"])
+                        .with_defaults("index.html")
+                        .with_directory_policy(DirectoryPolicy::IndexOnly),
+                ),
+            )
+            .push(
+                Router::with_path("forbidden/<**path>").get(
+                    StaticDir::new(vec!["test/static"])
+                        .with_defaults("index.html")
+                        .with_directory_policy(DirectoryPolicy::Forbidden),
+                ),
+            );
+        let service = Service::new(router);
+
+        async fn access(service: &Service, url: &str) -> (StatusCode, String) {
+            let mut response = TestClient::get(format!("http://127.0.0.1:5801/{url}"))
+                .send(service)
+                .await;
+            (response.status_code().unwrap(), response.take_string().await.unwrap())
+        }
+
+        // `defaults/` has an index file, `dir1/` has none.
+        let (status, content) = access(&service, "listing/defaults/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(content.contains("Index html page"), "{content}");
+        let (status, content) = access(&service, "listing/dir1/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(content.contains("test3.txt"), "{content}");
+
+        let (status, content) = access(&service, "index/defaults/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(content.contains("Index html page"), "{content}");
+        let (status, _) = access(&service, "index/dir1/").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        for url in ["forbidden/defaults/", "forbidden/dir1/", "forbidden/dir1", "forbidden/"] {
+            let (status, _) = access(&service, url).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{url}");
+        }
+        let (status, content) = access(&service, "forbidden/dir1/test3.txt").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content, "copy3");
+
+        // The deprecated `listing` field still lists directories.
+        let mut static_dir = StaticDir::new(vec!["test/static"]);
+        #[allow(deprecated)]
+        {
+            static_dir.listing = true;
+        }
+        let service = Service::new(Router::with_path("<**path>").get(static_dir));
+        let (status, content) = access(&service, "dir1/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(content.contains("test3.txt"), "{content}");
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_extension_dispositions() {
+        let root = temp_dir("salvo_serve_static_extension_dispositions");
+        std::fs::create_dir_all(&root).unwrap();
+        for name in ["report.csv", "logo.png", "manual.PDF", "notes.txt"] {
+            std::fs::write(root.join(name), name).unwrap();
+        }
+        let router = Router::with_path("<**path>").get(
+            StaticDir::new(vec![root.clone()])
+                .with_extension_disposition("csv", Disposition::Attachment { filename: None })
+                .with_extension_disposition(".png", Disposition::Inline)
+                .with_extension_disposition("pdf", Disposition::Inline),
+        );
         let service = Service::new(router);
 
+        for (name, expected) in [
+            ("report.csv", "attachment; filename=\"report.csv\""),
+            ("logo.png", "inline"),
+            ("manual.PDF", "inline"),
+            // Not configured, the disposition guessed from the content type is kept.
+            ("notes.txt", "inline"),
+        ] {
+            let mut response = TestClient::get(format!("http://127.0.0.1:5801/{name}"))
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK, "{name}");
+            assert_eq!(
+                response.headers().get("content-disposition").unwrap(),
+                expected,
+                "{name}"
+            );
+            assert_eq!(response.take_string().await.unwrap(), name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_fall_through() {
         #[handler]
-        async fn serve_file(req: &mut Request, res: &mut Response) {
-            let path = req.param::<String>("**path").unwrap();
-            if let Some(file) = Assets::get(&path) {
-                file.render(req, res);
-            }
+        async fn search(req: &mut Request) -> String {
+            format!("search {}", req.uri().path())
         }
+        let router = Router::with_path("<**path>")
+            .hoop(StaticDir::new(vec!["test/static"]).with_fall_through(true))
+            .get(search);
+        let service = Service::new(router);
 
-        let mut response = TestClient::get("http://127.0.0.1:5801/files/test1.txt")
-            .send(&service)
-            .await;
+        let mut response = TestClient::get("http://127.0.0.1:5801/dir1/").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.take_string().await.unwrap(), "search /dir1/");
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/test1.txt").send(&service).await;
         assert_eq!(response.status_code().unwrap(), StatusCode::OK);
         assert_eq!(response.take_string().await.unwrap(), "copy1");
 
-        let mut response = TestClient::get("http://127.0.0.1:5801/dir/test1.txt")
+        let response = TestClient::get("http://127.0.0.1:5801/notexist.txt")
             .send(&service)
             .await;
-        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
-        assert_eq!(response.take_string().await.unwrap(), "copy1");
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+    }
 
-        let mut response = TestClient::get("http://127.0.0.1:5801/dir/test1111.txt")
+    #[tokio::test]
+    async fn test_serve_static_dir_listing_renderer() {
+        let router = Router::with_path("<**path>").get(
+            StaticDir::new(vec!["test/static"])
+                .with_listing(true)
+                .register_listing_renderer("text/csv".parse().unwrap(), |listing| {
+                    let mut body = String::from("name,size\n");
+                    for file in &listing.files {
+                        body.push_str(&format!("{},{}\n", file.name, file.size));
+                    }
+                    (body.into(), "text/csv; charset=utf-8".parse().unwrap())
+                }),
+        );
+        let service = Service::new(router);
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/dir1/")
+            .add_header("accept", "text/csv, text/html;q=0.9", true)
             .send(&service)
             .await;
         assert_eq!(response.status_code().unwrap(), StatusCode::OK);
-        assert!(response.take_string().await.unwrap().contains("Fallback page"));
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            "text/csv; charset=utf-8"
+        );
+        let size = std::fs::metadata("test/static/dir1/test3.txt").unwrap().len();
+        assert_eq!(
+            response.take_string().await.unwrap(),
+            format!("name,size\ntest3.txt,{size}\n")
+        );
 
-        let response = TestClient::get("http://127.0.0.1:5801/dir").send(&service).await;
-        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        let content = TestClient::get("http://127.0.0.1:5801/dir1/")
+            .add_header("accept", "text/html", true)
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert!(content.contains("<html>"));
+    }
 
-        let mut response = TestClient::get("http://127.0.0.1:5801/dir/").send(&service).await;
+    #[tokio::test]
+    async fn test_serve_static_dir_content_security_policy() {
+        let router = Router::new()
+            .push(
+                Router::with_path("nonce/<**path>").get(
+                    StaticDir::new(vec!["test/csp"])
+                        .with_content_security_policy("script-src 'nonce-{nonce}'")
+                        .with_csp_nonce(true)
+                        .with_cache_control(CacheControl::new().with_max_age(std::time::Duration::from_secs(60)))
+                        .with_extension_disposition("html", Disposition::Inline),
+                ),
+            )
+            .push(
+                Router::with_path("large/<**path>").get(
+                    StaticDir::new(vec!["test/csp"])
+                        .with_content_security_policy("script-src 'nonce-{nonce}'")
+                        .with_csp_nonce(true)
+                        .with_max_substitution_size(8),
+                ),
+            )
+            .push(
+                Router::with_path("static/<**path>")
+                    .get(StaticDir::new(vec!["test/csp"]).with_content_security_policy("default-src 'self'")),
+            );
+        let service = Service::new(router);
+
+        let mut nonces = Vec::new();
+        for _ in 0..2 {
+            let mut response = TestClient::get("http://127.0.0.1:5801/nonce/index.html")
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+            assert_eq!(
+                response.headers().get("content-type").unwrap(),
+                "text/html; charset=utf-8"
+            );
+            assert_eq!(response.headers().get("cache-control").unwrap(), "no-store");
+            assert_eq!(response.headers().get("content-disposition").unwrap(), "inline");
+            let policy = response
+                .headers()
+                .get("content-security-policy")
+                .unwrap()
+                .to_str()
+                .unwrap();
+            let nonce = policy
+                .strip_prefix("script-src 'nonce-")
+                .and_then(|policy| policy.strip_suffix('\''))
+                .unwrap()
+                .to_owned();
+            assert_eq!(nonce.len(), 24);
+            assert_eq!(
+                response.take_string().await.unwrap(),
+                format!(
+                    "<html><body><script nonce=\"{nonce}\">main()</script><script nonce=\"{nonce}\" src=\"app.js\"></script></body></html>\n"
+                )
+            );
+            nonces.push(nonce);
+        }
+        assert_ne!(nonces[0], nonces[1]);
+
+        // Files larger than `max_substitution_size` are sent as is, their scripts stay blocked.
+        let mut response = TestClient::get("http://127.0.0.1:5801/large/index.html")
+            .send(&service)
+            .await;
         assert_eq!(response.status_code().unwrap(), StatusCode::OK);
-        assert!(response.take_string().await.unwrap().contains("Index page"));
+        assert!(response.headers().get("etag").is_some());
+        assert_eq!(
+            response.headers().get("content-security-policy").unwrap(),
+            "script-src 'nonce-{nonce}'"
+        );
+        assert!(response.take_string().await.unwrap().contains("nonce=\"{nonce}\""));
 
-        let response = TestClient::get("http://127.0.0.1:5801/dir2/").send(&service).await;
-        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+        let mut response = TestClient::get("http://127.0.0.1:5801/static/index.html")
+            .send(&service)
+            .await;
+        assert_eq!(
+            response.headers().get("content-security-policy").unwrap(),
+            "default-src 'self'"
+        );
+        assert!(response.take_string().await.unwrap().contains("nonce=\"{nonce}\""));
 
-        let response = TestClient::get("http://127.0.0.1:5801/dir3/abc.txt")
+        // Only HTML files have the policy.
+        for url in [
+            "http://127.0.0.1:5801/nonce/note.txt",
+            "http://127.0.0.1:5801/static/note.txt",
+        ] {
+            let mut response = TestClient::get(url).send(&service).await;
+            assert!(response.headers().get("content-security-policy").is_none());
+            assert_eq!(response.take_string().await.unwrap(), "plain");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_format_query() {
+        let router = Router::with_path("<**path>").get(StaticDir::new(vec!["test/static"]).with_listing(true));
+        let service = Service::new(router);
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/dir1/?format=json")
+            .add_header("accept", "text/html", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            "application/json; charset=utf-8"
+        );
+        let listing: serde_json::Value = serde_json::from_str(&response.take_string().await.unwrap()).unwrap();
+        assert_eq!(listing["path"], "/dir1/");
+        assert_eq!(listing["files"][0]["name"], "test3.txt");
+        assert_eq!(listing["dirs"][0]["name"], "dir2");
+
+        let content = TestClient::get("http://127.0.0.1:5801/dir1/?format=html")
+            .add_header("accept", "application/json", true)
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert!(content.contains("<html>"));
+
+        // Unknown formats are negotiated by `Accept`, and files are served as is.
+        let response = TestClient::get("http://127.0.0.1:5801/dir1/?format=csv")
+            .add_header("accept", "application/json", true)
+            .send(&service)
+            .await;
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            "application/json; charset=utf-8"
+        );
+        let mut response = TestClient::get("http://127.0.0.1:5801/test1.txt?format=json")
+            .send(&service)
+            .await;
+        assert_eq!(response.take_string().await.unwrap(), "copy1");
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_negative_cache() {
+        use std::sync::atomic::Ordering;
+        use std::time::Duration;
+
+        let root = temp_dir("salvo_serve_static_negative_cache");
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        let static_dir = StaticDir::new(vec![root.clone()]).with_negative_cache_ttl(Duration::from_secs(60));
+        let metrics = static_dir.metrics();
+        let service = Service::new(Router::with_path("<**path>").get(static_dir));
+
+        for _ in 0..3 {
+            let response = TestClient::get("http://127.0.0.1:5801/sub/new.txt")
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+        }
+        assert_eq!(metrics.misses(), 3);
+        assert_eq!(metrics.negative_cache_hits(), 2);
+        // Only the first request looked up the roots.
+        assert_eq!(metrics.root_lookups.load(Ordering::Relaxed), 1);
+
+        // A file created within the ttl is served at once, as its directory is modified. The
+        // modification time of the directory has a coarse granularity on some file systems.
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        std::fs::write(root.join("sub/new.txt"), "new").unwrap();
+        let mut response = TestClient::get("http://127.0.0.1:5801/sub/new.txt")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.take_string().await.unwrap(), "new");
+        assert_eq!(metrics.negative_cache_hits(), 2);
+        assert_eq!(metrics.root_lookups.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_negative_cache_languages() {
+        use std::time::Duration;
+
+        let root = temp_dir("salvo_serve_static_negative_cache_languages");
+        std::fs::write(root.join("page.de.html"), "de").unwrap();
+        let static_dir = StaticDir::new(vec![root.clone()])
+            .with_language_negotiation(true)
+            .with_negative_cache_ttl(Duration::from_secs(60));
+        let metrics = static_dir.metrics();
+        let service = Service::new(Router::with_path("<**path>").get(static_dir));
+
+        for _ in 0..2 {
+            let response = TestClient::get("http://127.0.0.1:5801/page")
+                .add_header("accept-language", "fr", true)
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+        }
+        assert_eq!(metrics.negative_cache_hits(), 1);
+
+        // The miss for `fr` doesn't hide the variant of another language.
+        let mut response = TestClient::get("http://127.0.0.1:5801/page")
+            .add_header("accept-language", "de", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.take_string().await.unwrap(), "de");
+        assert_eq!(metrics.negative_cache_hits(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_prefix_defaults() {
+        let router = Router::with_path("<**path>").get(
+            StaticDir::new(vec!["test/defaults_by_prefix"])
+                .with_defaults("index.html")
+                .with_prefix_defaults(HashMap::from([
+                    ("docs".to_owned(), vec!["readme.html".to_owned()]),
+                    (
+                        "/blog/".to_owned(),
+                        vec!["home.html".to_owned(), "index.html".to_owned()],
+                    ),
+                ])),
+        );
+        let service = Service::new(router);
+
+        for (url, content) in [
+            ("http://127.0.0.1:5801/", "root index"),
+            ("http://127.0.0.1:5801/docs/", "docs readme"),
+            ("http://127.0.0.1:5801/docs/guide/", "guide readme"),
+            ("http://127.0.0.1:5801/blog/", "blog home"),
+            ("http://127.0.0.1:5801/docs/index.html", "docs index"),
+        ] {
+            let mut response = TestClient::get(url).send(&service).await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK, "{url}");
+            assert_eq!(response.take_string().await.unwrap(), content, "{url}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_defaults_resolver() {
+        let router = Router::with_path("<**path>").get(
+            StaticDir::new(vec!["test/defaults_by_prefix"])
+                .with_defaults("index.html")
+                .with_defaults_resolver(|dir| {
+                    if dir.as_os_str().is_empty() {
+                        vec!["home.html".to_owned()]
+                    } else if dir.ends_with("guide") {
+                        vec!["../index.html".to_owned(), "readme.html".to_owned()]
+                    } else {
+                        vec![]
+                    }
+                }),
+        );
+        let service = Service::new(router);
+
+        for (url, content) in [
+            ("http://127.0.0.1:5801/", "root home"),
+            ("http://127.0.0.1:5801/docs/", "docs index"),
+            ("http://127.0.0.1:5801/docs/guide/", "guide readme"),
+            ("http://127.0.0.1:5801/blog/", "blog index"),
+        ] {
+            let mut response = TestClient::get(url).send(&service).await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK, "{url}");
+            assert_eq!(response.take_string().await.unwrap(), content, "{url}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_multiple_roots() {
+        let router = Router::with_path("<**path>")
+            .get(StaticDir::new(vec!["test/roots/first", "test/roots/second"]).with_listing(true));
+        let service = Service::new(router);
+
+        for (url, content) in [
+            ("http://127.0.0.1:5801/shared/common.txt", "first common"),
+            ("http://127.0.0.1:5801/shared/first.txt", "first only"),
+            ("http://127.0.0.1:5801/shared/second.txt", "second only"),
+            ("http://127.0.0.1:5801/shared/nested/inner.txt", "nested"),
+        ] {
+            let mut response = TestClient::get(url).send(&service).await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK, "{url}");
+            assert_eq!(response.take_string().await.unwrap(), content, "{url}");
+        }
+
+        let content = TestClient::get("http://127.0.0.1:5801/shared/?format=json")
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        let listing: CurrentInfo = serde_json::from_str(&content).unwrap();
+        let mut names = listing.files.iter().map(|file| file.name.as_str()).collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(names, ["common.txt", "first.txt", "second.txt"]);
+        let common = listing.files.iter().find(|file| file.name == "common.txt").unwrap();
+        assert_eq!(common.size, "first common".len() as u64);
+        assert_eq!(listing.dirs.len(), 1);
+        assert_eq!(listing.dirs[0].name, "nested");
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_preload_links() {
+        let router = Router::with_path("<**path>").get(
+            StaticDir::new(vec!["test/static"])
+                .with_defaults("index.html")
+                .with_preload_links(["</app.css>; rel=preload; as=style", "</app.js>; rel=preload; as=script"]),
+        );
+        let service = Service::new(router);
+
+        let response = TestClient::get("http://127.0.0.1:5801/").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        let links = response.headers().get_all("link").iter().collect::<Vec<_>>();
+        assert_eq!(
+            links,
+            ["</app.css>; rel=preload; as=style", "</app.js>; rel=preload; as=script"]
+        );
+
+        // Only default files of directories get the links, not other HTML files or the index file
+        // requested by its name.
+        for url in [
+            "http://127.0.0.1:5801/test1.txt",
+            "http://127.0.0.1:5801/fallback.html",
+            "http://127.0.0.1:5801/index.html",
+        ] {
+            let response = TestClient::get(url).send(&service).await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+            assert!(response.headers().get("link").is_none(), "{url}");
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a valid `Link` header value")]
+    fn test_serve_static_dir_preload_links_invalid() {
+        let _ = StaticDir::new(vec!["test/static"]).with_preload_links(["app.css; rel=preload"]);
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_preload_links_h2() {
+        use http_body_util::Empty;
+        use salvo_core::conn::{Acceptor, Listener, TcpListener};
+        use salvo_core::hyper::client::conn::http2;
+        use salvo_core::runtimes::TokioExecutor;
+
+        let acceptor = TcpListener::new("127.0.0.1:0").bind().await;
+        let addr = acceptor.holdings()[0].local_addr.clone().into_std().unwrap();
+        let router = Router::with_path("<**path>").get(
+            StaticDir::new(vec!["test/static"])
+                .with_defaults("index.html")
+                .with_preload_links(["</app.css>; rel=preload; as=style"]),
+        );
+        tokio::spawn(async move {
+            Server::new(acceptor).h2c(true).serve(router).await;
+        });
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let (mut sender, conn) = http2::handshake(TokioExecutor, stream).await.unwrap();
+        tokio::spawn(async move {
+            let _ = conn.await;
+        });
+        let req = salvo_core::hyper::Request::builder()
+            .uri(format!("http://{addr}/"))
+            .body(Empty::<bytes::Bytes>::new())
+            .unwrap();
+        let res = sender.send_request(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.version(), salvo_core::http::Version::HTTP_2);
+        assert_eq!(res.headers()["link"], "</app.css>; rel=preload; as=style");
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_redirect_policy() {
+        for (policy, status_code) in [
+            (RedirectPolicy::Found, StatusCode::FOUND),
+            (RedirectPolicy::MovedPermanently, StatusCode::MOVED_PERMANENTLY),
+            (RedirectPolicy::PermanentRedirect, StatusCode::PERMANENT_REDIRECT),
+        ] {
+            let router = Router::with_path("<**path>").get(
+                StaticDir::new(vec!["test/static"])
+                    .with_listing(true)
+                    .with_redirect_policy(policy),
+            );
+            let service = Service::new(router);
+            for (url, location) in [
+                ("http://127.0.0.1:5801/dir1", "/dir1/"),
+                ("http://127.0.0.1:5801/dir1?a=1&b=2", "/dir1/?a=1&b=2"),
+            ] {
+                let response = TestClient::get(url).send(&service).await;
+                assert_eq!(response.status_code().unwrap(), status_code, "{policy:?} {url}");
+                assert_eq!(
+                    response.headers().get("location").unwrap(),
+                    location,
+                    "{policy:?} {url}"
+                );
+            }
+        }
+
+        let router = Router::with_path("<**path>").get(
+            StaticDir::new(vec!["test/static"])
+                .with_listing(true)
+                .with_redirect_policy(RedirectPolicy::ServeIndex),
+        );
+        let service = Service::new(router);
+        let mut response = TestClient::get("http://127.0.0.1:5801/dir1").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert!(response.headers().get("location").is_none());
+        let content = response.take_string().await.unwrap();
+        assert!(content.contains("test3.txt"));
+        // `./test3.txt` is resolved against `/dir1/`, not `/`.
+        assert!(content.contains(r#"<base href="/dir1/">"#), "{content}");
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_listing_etag() {
+        let root = temp_dir("salvo_serve_static_listing_etag");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("a.txt"), "a").unwrap();
+        let router = Router::with_path("<**path>").get(
+            StaticDir::new(vec![root.clone()])
+                .with_listing(true)
+                .with_listing_etag(true),
+        );
+        let service = Service::new(router);
+        async fn access(service: &Service, url: &str, etag: &str) -> Response {
+            TestClient::get(url)
+                .add_header("if-none-match", etag, true)
+                .send(service)
+                .await
+        }
+
+        let response = TestClient::get("http://127.0.0.1:5801/?format=json")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        let etag = response.headers().get("etag").unwrap().to_str().unwrap().to_owned();
+        assert!(etag.starts_with("W/\""));
+
+        let response = access(&service, "http://127.0.0.1:5801/?format=json", &etag).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers().get("etag").unwrap(), etag.as_str());
+
+        // The format is part of the ETag.
+        let response = access(&service, "http://127.0.0.1:5801/?format=html", &etag).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_ne!(response.headers().get("etag").unwrap(), etag.as_str());
+
+        std::fs::write(root.join("b.txt"), "b").unwrap();
+        let mut response = access(&service, "http://127.0.0.1:5801/?format=json", &etag).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_ne!(response.headers().get("etag").unwrap(), etag.as_str());
+        let listing: CurrentInfo = serde_json::from_str(&response.take_string().await.unwrap()).unwrap();
+        assert_eq!(listing.count, 2);
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_listing_format_query() {
+        let router = Router::new()
+            .push(
+                Router::with_path("fmt/<**path>").get(
+                    StaticDir::new(vec!["test/static"])
+                        .with_listing(true)
+                        .with_listing_format_query(Some("fmt")),
+                ),
+            )
+            .push(
+                Router::with_path("accept/<**path>").get(
+                    StaticDir::new(vec!["test/static"])
+                        .with_listing(true)
+                        .with_listing_format_query(None),
+                ),
+            );
+        let service = Service::new(router);
+        async fn access(service: &Service, url: &str) -> (String, String) {
+            let mut response = TestClient::get(url)
+                .add_header("accept", "application/json", true)
+                .send(service)
+                .await;
+            let content_type = response
+                .headers()
+                .get("content-type")
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_owned();
+            (content_type, response.take_string().await.unwrap())
+        }
+
+        let (content_type, content) = access(&service, "http://127.0.0.1:5801/fmt/dir1/?fmt=json").await;
+        assert!(content_type.starts_with("application/json"));
+        let listing: CurrentInfo = serde_json::from_str(&content).unwrap();
+        assert_eq!(listing.count, 2);
+
+        let (content_type, content) = access(&service, "http://127.0.0.1:5801/fmt/dir1/?fmt=xml").await;
+        assert!(content_type.starts_with("application/xml"));
+        assert!(content.starts_with("<list>") && content.contains("test3.txt"));
+
+        let (content_type, content) = access(&service, "http://127.0.0.1:5801/fmt/dir1/?fmt=html").await;
+        assert!(content_type.starts_with("text/html"));
+        assert!(content.contains("<html") && content.contains("test3.txt"));
+
+        let (content_type, content) = access(&service, "http://127.0.0.1:5801/fmt/dir1/?fmt=text").await;
+        assert!(content_type.starts_with("text/plain"));
+        assert!(content.contains("test3.txt"));
+
+        // Unknown values and other parameters fall back to `Accept`.
+        for url in [
+            "http://127.0.0.1:5801/fmt/dir1/?fmt=yaml",
+            "http://127.0.0.1:5801/fmt/dir1/?format=html",
+            "http://127.0.0.1:5801/accept/dir1/?format=html",
+        ] {
+            let (content_type, content) = access(&service, url).await;
+            assert!(content_type.starts_with("application/json"));
+            assert!(serde_json::from_str::<CurrentInfo>(&content).is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_substitutions() {
+        let router = Router::with_path("<**path>").get(
+            StaticDir::new(vec!["test/substitutions"])
+                .with_substitution("API_BASE_URL", "https://api.example.com")
+                .with_substitution("VERSION", "{{API_BASE_URL}}"),
+        );
+        let service = Service::new(router);
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/index.html").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        let etag = response.headers().get("etag").unwrap().to_str().unwrap().to_owned();
+        assert_eq!(
+            response.take_string().await.unwrap(),
+            "<html><body data-api=\"https://api.example.com\">{{API_BASE_URL}} {{UNKNOWN}}</body></html>\n"
+        );
+
+        let response = TestClient::get("http://127.0.0.1:5801/index.html")
+            .add_header("if-none-match", &etag, true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_MODIFIED);
+
+        // Other content types are sent as is.
+        let mut response = TestClient::get("http://127.0.0.1:5801/notes.txt").send(&service).await;
+        assert_eq!(response.take_string().await.unwrap(), "Version {{VERSION}}\n");
+
+        // The substituted content is cached until the file is changed.
+        let root = temp_dir("salvo_serve_static_substitution_cache");
+        std::fs::write(root.join("page.html"), "{{NAME}}").unwrap();
+        let service = Service::new(
+            Router::with_path("<**path>").get(StaticDir::new(vec![root.clone()]).with_substitution("NAME", "value")),
+        );
+        for _ in 0..2 {
+            let mut response = TestClient::get("http://127.0.0.1:5801/page.html").send(&service).await;
+            assert_eq!(response.take_string().await.unwrap(), "value");
+        }
+        std::fs::write(root.join("page.html"), "<b>{{NAME}}</b>").unwrap();
+        let mut response = TestClient::get("http://127.0.0.1:5801/page.html").send(&service).await;
+        assert_eq!(response.take_string().await.unwrap(), "<b>value</b>");
+
+        // Substituted files get the headers of files which are sent as is.
+        let static_dir = StaticDir::new(vec!["test/substitutions"])
+            .with_substitution("API_BASE_URL", "https://api.example.com")
+            .with_content_security_policy("default-src 'self'")
+            .with_cache_control(CacheControl::new().with_max_age(std::time::Duration::from_secs(60)));
+        let metrics = static_dir.metrics();
+        let service = Service::new(Router::with_path("<**path>").get(static_dir));
+        let mut response = TestClient::get("http://127.0.0.1:5801/index.html").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("content-security-policy").unwrap(),
+            "default-src 'self'"
+        );
+        assert_eq!(response.headers().get("cache-control").unwrap(), "max-age=60");
+        assert!(response
+            .take_string()
+            .await
+            .unwrap()
+            .contains("data-api=\"https://api.example.com\""));
+        assert_eq!(metrics.hits(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_untrusted_prefixes() {
+        let router = Router::with_path("<**path>").get(
+            StaticDir::new(vec!["test/untrusted"])
+                .with_untrusted_prefixes("uploads")
+                .with_substitution("NAME", "value")
+                .with_unknown_mime(UnknownMimePolicy::TextPlain),
+        );
+        let service = Service::new(router);
+
+        let response = TestClient::get("http://127.0.0.1:5801/uploads/page.html")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.headers().get("x-content-type-options").unwrap(), "nosniff");
+        assert_eq!(
+            response.headers().get("content-disposition").unwrap(),
+            "attachment; filename=\"page.html\""
+        );
+
+        let response = TestClient::get("http://127.0.0.1:5801/uploads/data.unknownext")
+            .send(&service)
+            .await;
+        assert_eq!(response.headers().get("x-content-type-options").unwrap(), "nosniff");
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            "application/octet-stream"
+        );
+        assert!(response
+            .headers()
+            .get("content-disposition")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("attachment"));
+
+        let response = TestClient::get("http://127.0.0.1:5801/uploads/notes.txt")
+            .send(&service)
+            .await;
+        assert_eq!(response.headers().get("x-content-type-options").unwrap(), "nosniff");
+        assert_eq!(response.headers().get("content-disposition").unwrap(), "inline");
+
+        // Files out of the untrusted prefixes are served as usual.
+        let response = TestClient::get("http://127.0.0.1:5801/index.html").send(&service).await;
+        assert!(response.headers().get("x-content-type-options").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_max_listing_entries() {
+        let root = temp_dir("salvo_serve_static_max_listing_entries");
+        for dir in ["sub1", "sub2"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for i in 0..10 {
+            std::fs::write(root.join(format!("file{i}.txt")), "x".repeat(i * 10)).unwrap();
+        }
+        let router = Router::new()
+            .push(
+                Router::with_path("name/<**path>").get(
+                    StaticDir::new(vec![root.clone()])
+                        .with_listing(true)
+                        .with_max_listing_entries(5)
+                        .with_listing_sort(ListingSort::NameDesc),
+                ),
+            )
+            .push(
+                Router::with_path("size/<**path>").get(
+                    StaticDir::new(vec![root.clone()])
+                        .with_listing(true)
+                        .with_max_listing_entries(20)
+                        .with_listing_sort(ListingSort::Size),
+                ),
+            );
+        let service = Service::new(router);
+
+        let content = TestClient::get("http://127.0.0.1:5801/name/?format=json")
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        let listing: CurrentInfo = serde_json::from_str(&content).unwrap();
+        assert!(listing.truncated);
+        assert_eq!(listing.count, 12);
+        assert_eq!(listing.total_size, 450);
+        let names = listing.dirs.iter().map(|dir| dir.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["sub2", "sub1"]);
+        let names = listing.files.iter().map(|file| file.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["file9.txt", "file8.txt", "file7.txt"]);
+
+        let content = TestClient::get("http://127.0.0.1:5801/name/?format=html")
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert!(content.contains("<p>Only the first 5 of 12 entries are listed</p>"));
+        assert!(!content.contains("file6.txt"));
+        let content = TestClient::get("http://127.0.0.1:5801/name/?format=xml")
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert!(content.ends_with("<truncated>true</truncated></list>"));
+
+        let content = TestClient::get("http://127.0.0.1:5801/size/?format=json")
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        let listing: CurrentInfo = serde_json::from_str(&content).unwrap();
+        assert!(!listing.truncated);
+        assert_eq!(listing.dirs.len() + listing.files.len(), 12);
+        assert_eq!(listing.files[0].name, "file9.txt");
+        assert_eq!(listing.files[9].name, "file0.txt");
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_listing_hide_metadata() {
+        let router = Router::with_path("<**path>").get(
+            StaticDir::new(vec!["test/static"])
+                .with_listing(true)
+                .with_listing_hide_metadata(true),
+        );
+        let service = Service::new(router);
+
+        let content = TestClient::get("http://127.0.0.1:5801/dir1/?format=json")
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        let listing: serde_json::Value = serde_json::from_str(&content).unwrap();
+        assert!(listing.get("total_size").is_none());
+        assert_eq!(listing["files"][0]["name"], "test3.txt");
+        assert!(listing["files"][0].get("size").is_none());
+        assert!(listing["files"][0].get("modified").is_none());
+        assert_eq!(listing["dirs"][0]["name"], "dir2");
+        assert!(listing["dirs"][0].get("modified").is_none());
+
+        let content = TestClient::get("http://127.0.0.1:5801/dir1/?format=xml")
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert!(content.contains("<file><name>test3.txt</name><link>test3.txt</link></file>"));
+        assert!(!content.contains("<size>"));
+        let content = TestClient::get("http://127.0.0.1:5801/dir1/?format=html")
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert!(content.contains("test3.txt"));
+        assert!(!content.contains("<th>Size</th>"));
+        assert!(!content.contains("bytes in total"));
+
+        // Sorting by size would expose the hidden sizes, so entries are sorted by name.
+        let root = temp_dir("salvo_serve_static_listing_hide_metadata");
+        std::fs::write(root.join("a.txt"), "a").unwrap();
+        std::fs::write(root.join("b.txt"), "b".repeat(100)).unwrap();
+        let router = Router::with_path("<**path>").get(
+            StaticDir::new(vec![root.clone()])
+                .with_listing(true)
+                .with_listing_sort(ListingSort::Size)
+                .with_listing_hide_metadata(true),
+        );
+        let content = TestClient::get("http://127.0.0.1:5801/?format=json")
+            .send(&Service::new(router))
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        let listing: serde_json::Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(listing["files"][0]["name"], "a.txt");
+        assert_eq!(listing["files"][1]["name"], "b.txt");
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_with_listing_renderer() {
+        use crate::dir::{CurrentInfo, DirListRenderer};
+
+        struct Csv;
+        impl DirListRenderer for Csv {
+            fn render(&self, current: &CurrentInfo, _format: &mime::Mime) -> (bytes::Bytes, mime::Mime) {
+                let mut body = format!("{},{}\nname,is_dir,size\n", current.path, current.truncated);
+                for entry in current.entries() {
+                    body.push_str(&format!("{},{},{}\n", entry.name, entry.is_dir, entry.size));
+                }
+                (body.into(), "text/csv; charset=utf-8".parse().unwrap())
+            }
+        }
+        let router = Router::with_path("<**path>").get(
+            StaticDir::new(vec!["test/static"])
+                .with_listing(true)
+                .with_listing_renderer(Box::new(Csv)),
+        );
+        let service = Service::new(router);
+
+        for accept in ["text/html", "application/json", "text/csv"] {
+            let mut response = TestClient::get("http://127.0.0.1:5801/dir1/")
+                .add_header("accept", accept, true)
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+            assert_eq!(
+                response.headers().get("content-type").unwrap(),
+                "text/csv; charset=utf-8"
+            );
+            let size = std::fs::metadata("test/static/dir1/test3.txt").unwrap().len();
+            assert_eq!(
+                response.take_string().await.unwrap(),
+                format!("/dir1/,false\nname,is_dir,size\ndir2,true,0\ntest3.txt,false,{size}\n")
+            );
+        }
+
+        let router = Router::with_path("<**path>").get(
+            StaticDir::new(vec!["test/static"])
+                .with_listing(true)
+                .with_max_listing_entries(1)
+                .with_listing_renderer(Box::new(Csv)),
+        );
+        let content = TestClient::get("http://127.0.0.1:5801/dir1/")
+            .send(router)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_eq!(content, "/dir1/,true\nname,is_dir,size\ndir2,true,0\n");
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_empty_listing() {
+        let root = temp_dir("salvo_serve_static_empty_listing");
+        std::fs::create_dir_all(root.join("empty")).unwrap();
+        let router = Router::with_path("<**path>").get(
+            StaticDir::new(vec![root])
+                .with_listing(true)
+                .with_empty_listing("<p>{path} has no files yet</p>"),
+        );
+        let service = Service::new(router);
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/empty/").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.take_string().await.unwrap(), "<p>/empty/ has no files yet</p>");
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert!(response.take_string().await.unwrap().contains("empty"));
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_zip_download() {
+        let router = Router::with_path("<**path>").get(StaticDir::new(vec!["test/static"]).with_zip_download(true));
+        let service = Service::new(router);
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/dir1/?download=zip")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.headers().get("content-type").unwrap(), "application/zip");
+        assert_eq!(
+            response.headers().get("content-disposition").unwrap(),
+            r#"attachment; filename="dir1.zip""#
+        );
+        let archive = response.take_bytes().await.unwrap();
+
+        // Read entry names from the central directory.
+        let u16_at = |pos: usize| u16::from_le_bytes([archive[pos], archive[pos + 1]]) as usize;
+        let u32_at = |pos: usize| u32::from_le_bytes(archive[pos..pos + 4].try_into().unwrap()) as usize;
+        let end = archive.len() - 22;
+        assert_eq!(u32_at(end), 0x06054b50);
+        let mut pos = u32_at(end + 16);
+        let mut entries = vec![];
+        for _ in 0..u16_at(end + 10) {
+            assert_eq!(u32_at(pos), 0x02014b50);
+            let name_len = u16_at(pos + 28);
+            let name = std::str::from_utf8(&archive[pos + 46..pos + 46 + name_len]).unwrap();
+            let size = u32_at(pos + 24);
+            let offset = u32_at(pos + 42);
+            let data_start = offset + 30 + u16_at(offset + 26);
+            entries.push((name.to_owned(), archive[data_start..data_start + size].to_vec()));
+            pos += 46 + name_len;
+        }
+        assert_eq!(
+            entries,
+            vec![
+                ("dir2/".to_owned(), vec![]),
+                ("test3.txt".to_owned(), b"copy3".to_vec()),
+                ("dir2/test3.txt".to_owned(), b"dir2 test3".to_vec()),
+            ]
+        );
+
+        let response = TestClient::get("http://127.0.0.1:5801/dir1/?download=tar")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    #[tokio::test]
+    async fn test_serve_static_dir_canonical_case_redirect() {
+        let router =
+            Router::with_path("<**path>").get(StaticDir::new(vec!["test/static"]).with_canonical_case_redirect(true));
+        let service = Service::new(router);
+
+        let response = TestClient::get("http://127.0.0.1:5801/dir1/Test3.txt?a=1")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(response.headers().get("location").unwrap(), "/dir1/test3.txt?a=1");
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/dir1/test3.txt")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.take_string().await.unwrap(), "copy3");
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_language_negotiation() {
+        let router = Router::with_path("<**path>").get(
+            StaticDir::new(vec!["test/static"])
+                .with_language_negotiation(true)
+                .with_default_language("en"),
+        );
+        let service = Service::new(router);
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/i18n/page")
+            .add_header("accept-language", "fr-CH, fr;q=0.9, en;q=0.8", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.headers().get("content-language").unwrap(), "fr");
+        assert_eq!(response.headers().get("vary").unwrap(), "accept-language");
+        assert!(response.take_string().await.unwrap().contains("French page"));
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/i18n/page")
+            .add_header("accept-language", "de", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.headers().get("content-language").unwrap(), "en");
+        assert!(response.take_string().await.unwrap().contains("English page"));
+
+        // Tags are joined into file names, they must not carry path separators or dots.
+        for accept in ["../i18n/page.fr", "fr/..", "fr.html, fr;q=0"] {
+            let mut response = TestClient::get("http://127.0.0.1:5801/i18n/page")
+                .add_header("accept-language", accept, true)
+                .send(&service)
+                .await;
+            assert_eq!(response.headers().get("content-language").unwrap(), "en");
+            assert!(response.take_string().await.unwrap().contains("English page"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_not_found_handler() {
+        let router = Router::new()
+            .push(
+                Router::with_path("file/<**path>")
+                    .get(StaticDir::new(vec!["test/not_found"]).with_not_found_handler("404.html")),
+            )
+            .push(
+                Router::with_path("escape/<**path>")
+                    .get(StaticDir::new(vec!["test/not_found"]).with_not_found_handler("../static/index.html")),
+            )
+            .push(Router::with_path("json/<**path>").get(
+                StaticDir::new(vec!["test/not_found"]).with_not_found_handler(NotFoundHandler::writer(|_req, res| {
+                    res.render(Text::Json(r#"{"error":"not_found"}"#));
+                })),
+            ))
+            .push(
+                Router::with_path("single.txt").get(
+                    StaticFile::new("test/not_found/missing.txt").with_not_found_handler("test/not_found/404.html"),
+                ),
+            );
+        let service = Service::new(router);
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/file/page.txt")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.take_string().await.unwrap(), "page");
+
+        for url in [
+            "http://127.0.0.1:5801/file/missing.txt",
+            "http://127.0.0.1:5801/single.txt",
+        ] {
+            let mut response = TestClient::get(url).send(&service).await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND, "{url}");
+            assert_eq!(response.headers().get("content-type").unwrap(), "text/html", "{url}");
+            assert!(
+                response.take_string().await.unwrap().contains("custom not found"),
+                "{url}"
+            );
+        }
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/escape/missing.txt")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+        assert!(!response.take_string().await.unwrap().contains("Index page"));
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/json/missing.txt")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+        assert_eq!(response.take_string().await.unwrap(), r#"{"error":"not_found"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_file() {
+        let router = Router::new()
+            .push(Router::with_path("test1.txt").get(StaticFile::new("test/static/test1.txt").chunk_size(1024)))
+            .push(Router::with_path("notexist.txt").get(StaticFile::new("test/static/notexist.txt")));
+        let service = Service::new(router);
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/test1.txt").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.take_string().await.unwrap(), "copy1");
+
+        let response = TestClient::get("http://127.0.0.1:5801/notexist.txt")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_file_head() {
+        let router = Router::new()
+            .push(Router::with_path("test1.txt").handle(StaticFile::new("test/static/test1.txt")))
+            .push(Router::with_path("notexist.txt").handle(StaticFile::new("test/static/notexist.txt")));
+        let service = Service::new(router);
+
+        let mut get = TestClient::get("http://127.0.0.1:5801/test1.txt").send(&service).await;
+        let mut head = TestClient::head("http://127.0.0.1:5801/test1.txt").send(&service).await;
+        assert_eq!(head.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(head.headers().get("content-length").unwrap(), "5");
+        for name in [
+            "content-length",
+            "content-type",
+            "etag",
+            "accept-ranges",
+            "last-modified",
+        ] {
+            assert_eq!(head.headers().get(name), get.headers().get(name), "{name}");
+        }
+        assert_eq!(get.take_string().await.unwrap(), "copy1");
+        assert_eq!(head.take_string().await.unwrap(), "");
+
+        let mut response = TestClient::head("http://127.0.0.1:5801/notexist.txt")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+        assert_eq!(response.take_string().await.unwrap(), "");
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_file_streaming() {
+        use futures_util::StreamExt;
+
+        const CHUNK_SIZE: usize = 64 * 1024;
+        let dir = temp_dir("salvo_serve_static_file_streaming");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("large.bin");
+        let content = (0..8 * 1024 * 1024).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        std::fs::write(&path, &content).unwrap();
+
+        let router = Router::with_path("large.bin").get(StaticFile::new(path.clone()).chunk_size(CHUNK_SIZE as u64));
+        let service = Service::new(router);
+
+        // The body is streamed in chunks of at most `CHUNK_SIZE`, the file is never read as a whole,
+        // and a range request reads only the requested window.
+        for (range, expected) in [
+            (None, &content[..]),
+            (Some("bytes=1000000-2999999"), &content[1000000..3000000]),
+        ] {
+            let mut request = TestClient::get("http://127.0.0.1:5801/large.bin");
+            if let Some(range) = range {
+                request = request.add_header("range", range, true);
+            }
+            let mut response = request.send(&service).await;
+            let mut body = response.take_body();
+            assert!(body.is_stream(), "{range:?}");
+            let mut received = Vec::with_capacity(expected.len());
+            let mut max_chunk = 0;
+            while let Some(chunk) = body.next().await {
+                let chunk = chunk.unwrap();
+                max_chunk = max_chunk.max(chunk.len());
+                received.extend_from_slice(&chunk);
+            }
+            assert!(max_chunk <= CHUNK_SIZE, "{range:?}");
+            assert!(received == expected, "{range:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_streaming_json_listing() {
+        use futures_util::StreamExt;
+
+        let root = temp_dir("salvo_serve_static_streaming_json_listing");
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        for i in 0..5000 {
+            std::fs::write(root.join(format!("file_with_a_long_name_{i:05}.txt")), "x").unwrap();
+        }
+        let router = Router::with_path("<**path>").get(
+            StaticDir::new(vec![root.clone()])
+                .with_listing(true)
+                .with_streaming_json_listing(true),
+        );
+        let service = Service::new(router);
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/?format=json")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            "application/json; charset=utf-8"
+        );
+        let mut body = response.take_body();
+        assert!(body.is_stream());
+        // The first bytes are sent before the directory is read.
+        let first = body.next().await.unwrap().unwrap();
+        assert_eq!(&first[..], br#"{"path":"/","dirs":["#);
+        let mut content = first.to_vec();
+        let mut chunks = 1;
+        while let Some(chunk) = body.next().await {
+            content.extend_from_slice(&chunk.unwrap());
+            chunks += 1;
+        }
+        assert!(chunks > 3, "{chunks}");
+        // It is the same listing as the one which is not streamed.
+        let listing: CurrentInfo = serde_json::from_slice(&content).unwrap();
+        assert_eq!(listing.path, "/");
+        assert_eq!(listing.dirs.len(), 1);
+        assert_eq!(listing.dirs[0].name, "sub");
+        assert_eq!(listing.files.len(), 5000);
+        assert_eq!(listing.count, 5001);
+        assert_eq!(listing.total_size, 5000);
+        assert!(!listing.truncated);
+        let file = listing
+            .files
+            .iter()
+            .find(|file| file.name == "file_with_a_long_name_00042.txt")
+            .unwrap();
+        assert_eq!(file.size, 1);
+
+        // Truncated listings keep directories first and are marked, entries of later roots with the
+        // same name as an entry of an earlier root are left out.
+        let other_root = temp_dir("salvo_serve_static_streaming_json_listing_other");
+        std::fs::create_dir_all(other_root.join("file_with_a_long_name_00000.txt")).unwrap();
+        std::fs::write(other_root.join("other.txt"), "other").unwrap();
+        let router = Router::with_path("<**path>").get(
+            StaticDir::new(vec![root.clone(), other_root.clone()])
+                .with_listing(true)
+                .with_streaming_json_listing(true)
+                .with_max_listing_entries(10),
+        );
+        let content = TestClient::get("http://127.0.0.1:5801/?format=json")
+            .send(&Service::new(router))
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        let listing: CurrentInfo = serde_json::from_str(&content).unwrap();
+        assert_eq!(listing.dirs.len(), 1);
+        assert_eq!(listing.files.len(), 9);
+        assert_eq!(listing.count, 5002);
+        assert_eq!(listing.total_size, 5005);
+        assert!(listing.truncated);
+
+        // Other formats are not streamed.
+        let response = TestClient::get("http://127.0.0.1:5801/?format=html")
+            .send(&service)
+            .await;
+        assert!(!response.body().is_stream());
+    }
+
+    #[tokio::test]
+    async fn test_stream_multipart_files() {
+        #[handler]
+        async fn export(req: &mut Request, res: &mut Response) {
+            let paths = req.query::<String>("paths").unwrap_or_default();
+            let paths = paths.split(',').collect::<Vec<_>>();
+            if res.stream_multipart_files("test/static", &paths).await.is_err() {
+                res.render(StatusError::not_found());
+            }
+        }
+        let service = Service::new(Router::with_path("export").get(export));
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/export?paths=test1.txt,dir1/test3.txt,index.html")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        let content_type = response.headers()["content-type"].to_str().unwrap().to_owned();
+        let boundary = content_type.strip_prefix("multipart/mixed; boundary=").unwrap();
+        let body = response.take_string().await.unwrap();
+        assert!(body.ends_with(&format!("--{boundary}--\r\n")));
+        let parts = body
+            .split(&format!("--{boundary}"))
+            .filter(|part| part.starts_with("\r\n"))
+            .map(|part| part.trim_start_matches("\r\n").split_once("\r\n\r\n").unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(parts.len(), 3);
+        let expected = [
+            ("test1.txt", "text/plain", "test/static/test1.txt"),
+            ("test3.txt", "text/plain", "test/static/dir1/test3.txt"),
+            ("index.html", "text/html", "test/static/index.html"),
+        ];
+        for ((headers, content), (name, content_type, path)) in parts.into_iter().zip(expected) {
+            let expected = std::fs::read_to_string(path).unwrap();
+            assert!(
+                headers.contains(&format!("Content-Type: {content_type}\r\n")),
+                "{headers}"
+            );
+            assert!(headers.contains(&format!("filename=\"{name}\"")), "{headers}");
+            assert!(
+                headers.contains(&format!("Content-Length: {}", expected.len())),
+                "{headers}"
+            );
+            assert_eq!(content, format!("{expected}\r\n"));
+        }
+
+        // Nothing is streamed if any of the files can't be resolved.
+        for paths in [
+            "test1.txt,../../Cargo.toml",
+            "test1.txt,notexist.txt",
+            "test1.txt,dir1",
+            "test1.txt,dir1/.secret",
+        ] {
+            let response = TestClient::get(format!("http://127.0.0.1:5801/export?paths={paths}"))
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND, "{paths}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stream_multipart_files_symlink() {
+        let base = temp_dir("salvo_serve_static_multipart_symlink");
+        let root = base.join("root");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("real.txt"), "real").unwrap();
+        std::fs::write(base.join("secret.txt"), "secret").unwrap();
+        std::os::unix::fs::symlink(root.join("real.txt"), root.join("alias.txt")).unwrap();
+        std::os::unix::fs::symlink(base.join("secret.txt"), root.join("escape.txt")).unwrap();
+
+        let mut res = Response::new();
+        assert!(res.stream_multipart_files(&root, &["alias.txt"]).await.is_ok());
+        let mut res = Response::new();
+        let err = res
+            .stream_multipart_files(&root, &["real.txt", "escape.txt"])
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        assert!(res.headers().get("content-type").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_serve_mime_override() {
+        let wasm: Mime = "application/wasm".parse().unwrap();
+        let router = Router::new()
+            .push(
+                Router::with_path("file/app.wasm")
+                    .get(StaticFile::new("test/mime/app.wasm").with_mime_override(".WASM", wasm.clone())),
+            )
+            .push(
+                Router::with_path("dir/<**path>").get(
+                    StaticDir::new("test/mime")
+                        .with_mime_override("WASM", wasm)
+                        .with_mime_overrides([("avif", "image/avif".parse::<Mime>().unwrap())]),
+                ),
+            );
+        let service = Service::new(router);
+
+        for (path, content_type) in [
+            ("file/app.wasm", "application/wasm"),
+            ("dir/app.wasm", "application/wasm"),
+            ("dir/logo.AVIF", "image/avif"),
+        ] {
+            let response = TestClient::get(format!("http://127.0.0.1:5801/{path}"))
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK, "{path}");
+            assert_eq!(response.headers().get("content-type").unwrap(), content_type, "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_head_not_modified() {
+        let mut vfs = StaticVfs::new();
+        vfs.insert("app.js", "console.log('hello');");
+        let router = Router::new()
+            .push(Router::with_path("test1.txt").handle(StaticFile::new("test/static/test1.txt")))
+            .push(Router::with_path("<**path>").handle(vfs));
+        let service = Service::new(router);
+
+        for url in ["http://127.0.0.1:5801/test1.txt", "http://127.0.0.1:5801/app.js"] {
+            let response = TestClient::get(url).send(&service).await;
+            let etag = response.headers().get("etag").unwrap().to_str().unwrap().to_owned();
+
+            let mut response = TestClient::head(url)
+                .add_header("if-none-match", &etag, true)
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::NOT_MODIFIED, "{url}");
+            assert_eq!(response.headers().get("etag").unwrap(), &etag);
+            assert!(response.headers().get("content-length").is_none());
+            assert_eq!(response.take_string().await.unwrap(), "");
+
+            let response = TestClient::head(url)
+                .add_header("if-none-match", "\"other\"", true)
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK, "{url}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_file_ranges() {
+        let router = Router::with_path("test1.txt").get(StaticFile::new("test/static/test1.txt"));
+        let service = Service::new(router);
+
+        async fn access(service: &Service, range: &str) -> Response {
+            TestClient::get("http://127.0.0.1:5801/test1.txt")
+                .add_header("range", range, true)
+                .send(service)
+                .await
+        }
+        let mut response = access(&service, "bytes=2-").await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers().get("content-range").unwrap(), "bytes 2-4/5");
+        assert_eq!(response.headers().get("content-length").unwrap(), "3");
+        assert_eq!(response.headers().get("accept-ranges").unwrap(), "bytes");
+        assert_eq!(response.take_string().await.unwrap(), "py1");
+
+        let mut response = access(&service, "bytes=1-2").await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers().get("content-range").unwrap(), "bytes 1-2/5");
+        assert_eq!(response.headers().get("content-length").unwrap(), "2");
+        assert_eq!(response.take_string().await.unwrap(), "op");
+
+        let response = access(&service, "bytes=5-").await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers().get("content-range").unwrap(), "bytes */5");
+        assert_eq!(response.headers().get("accept-ranges").unwrap(), "bytes");
+
+        let mut response = access(&service, "bytes=0-1,3-4").await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert!(response.headers().get("content-range").is_none());
+        assert_eq!(response.take_string().await.unwrap(), "copy1");
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/test1.txt").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.headers().get("accept-ranges").unwrap(), "bytes");
+        assert_eq!(response.take_string().await.unwrap(), "copy1");
+    }
+
+    #[tokio::test]
+    async fn test_serve_if_range() {
+        #[derive(RustEmbed)]
+        #[folder = "test/static"]
+        struct Assets;
+
+        let router = Router::new()
+            .push(Router::with_path("file.txt").get(StaticFile::new("test/static/test1.txt")))
+            .push(Router::with_path("embed.txt").get(Assets::get("test1.txt").unwrap().into_handler()));
+        let service = Service::new(router);
+
+        for url in ["http://127.0.0.1:5801/file.txt", "http://127.0.0.1:5801/embed.txt"] {
+            let response = TestClient::get(url).send(&service).await;
+            let etag = response.headers().get("etag").unwrap().to_str().unwrap().to_owned();
+
+            let mut response = TestClient::get(url)
+                .add_header("range", "bytes=2-", true)
+                .add_header("if-range", &etag, true)
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::PARTIAL_CONTENT, "{url}");
+            assert_eq!(response.headers().get("content-range").unwrap(), "bytes 2-4/5", "{url}");
+            assert_eq!(response.take_string().await.unwrap(), "py1", "{url}");
+
+            // A stale validator, whether an ETag or a date, makes the whole file sent.
+            for stale in ["\"stale\"", "Thu, 01 Jan 1970 00:00:00 GMT"] {
+                let mut response = TestClient::get(url)
+                    .add_header("range", "bytes=2-", true)
+                    .add_header("if-range", stale, true)
+                    .send(&service)
+                    .await;
+                assert_eq!(response.status_code().unwrap(), StatusCode::OK, "{url} {stale}");
+                assert!(response.headers().get("content-range").is_none(), "{url} {stale}");
+                assert_eq!(response.take_string().await.unwrap(), "copy1", "{url} {stale}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_file_content_disposition() {
+        let dir = temp_dir("salvo_serve_static_content_disposition");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("年报 2023.txt");
+        std::fs::write(&path, "annual report").unwrap();
+        let named = StaticFile::new(&path).with_content_disposition(Disposition::Attachment {
+            filename: Some("报告 \"final\".txt".into()),
+        });
+        let derived = StaticFile::new(&path).with_content_disposition(Disposition::Attachment { filename: None });
+        let ascii = StaticFile::new(&path).with_content_disposition(Disposition::Attachment {
+            filename: Some("report.txt".into()),
+        });
+        let inline = StaticFile::new(&path).with_content_disposition(Disposition::Inline);
+        let router = Router::new()
+            .push(Router::with_path("named").get(named))
+            .push(Router::with_path("derived").get(derived))
+            .push(Router::with_path("ascii").get(ascii))
+            .push(Router::with_path("inline").get(inline));
+        let service = Service::new(router);
+
+        let response = TestClient::get("http://127.0.0.1:5801/named").send(&service).await;
+        assert_eq!(
+            response.headers().get("content-disposition").unwrap(),
+            "attachment; filename=\"__ _final_.txt\"; filename*=UTF-8''%E6%8A%A5%E5%91%8A%20%22final%22.txt"
+        );
+
+        // Byte ranges are served as before.
+        let mut response = TestClient::get("http://127.0.0.1:5801/derived")
+            .add_header("range", "bytes=0-5", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            response.headers().get("content-disposition").unwrap(),
+            "attachment; filename=\"__ 2023.txt\"; filename*=UTF-8''%E5%B9%B4%E6%8A%A5%202023.txt"
+        );
+        assert_eq!(response.take_string().await.unwrap(), "annual");
+
+        let response = TestClient::get("http://127.0.0.1:5801/ascii").send(&service).await;
+        assert_eq!(
+            response.headers().get("content-disposition").unwrap(),
+            "attachment; filename=\"report.txt\""
+        );
+        let response = TestClient::get("http://127.0.0.1:5801/inline").send(&service).await;
+        assert_eq!(response.headers().get("content-disposition").unwrap(), "inline");
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_conditional_missing_file() {
+        let router = Router::new()
+            .push(Router::with_path("file/notexist.txt").get(StaticFile::new("test/static/notexist.txt")))
+            .push(Router::with_path("dir/<**path>").get(StaticDir::new(vec!["test/static"])));
+        let service = Service::new(router);
+
+        for url in [
+            "http://127.0.0.1:5801/file/notexist.txt",
+            "http://127.0.0.1:5801/dir/notexist.txt",
+        ] {
+            let response = TestClient::get(url)
+                .add_header("if-none-match", r#""anything""#, true)
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+            let response = TestClient::get(url)
+                .add_header("if-modified-since", "Wed, 21 Oct 2015 07:28:00 GMT", true)
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_content_hash_etag() {
+        // Same content in another root, it has different inode and modification time.
+        let root = temp_dir("salvo_serve_static_content_hash_etag");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("test1.txt"), "copy1").unwrap();
+
+        let router = Router::new()
+            .push(
+                Router::with_path("a/<**path>")
+                    .get(StaticDir::new(vec!["test/static"]).with_etag_mode(EtagMode::ContentHash)),
+            )
+            .push(
+                Router::with_path("b/<**path>").get(StaticDir::new(vec![root]).with_etag_mode(EtagMode::ContentHash)),
+            );
+        let service = Service::new(router);
+
+        let mut etags = vec![];
+        for url in [
+            "http://127.0.0.1:5801/a/test1.txt",
+            "http://127.0.0.1:5801/a/test1.txt",
+            "http://127.0.0.1:5801/b/test1.txt",
+        ] {
+            let response = TestClient::get(url).send(&service).await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+            etags.push(response.headers().get("etag").unwrap().to_str().unwrap().to_owned());
+        }
+        let expected = format!("\"{}\"", hex::encode(sha2::Sha256::digest(b"copy1")));
+        assert!(etags.iter().all(|etag| *etag == expected));
+
+        let response = TestClient::get("http://127.0.0.1:5801/b/test1.txt")
+            .add_header("if-none-match", &expected, true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_file_etag_mode() {
+        let root = temp_dir("salvo_serve_static_file_etag_mode");
+        std::fs::create_dir_all(&root).unwrap();
+        let path = root.join("app.js");
+        std::fs::write(&path, "v1").unwrap();
+
+        let router = Router::new()
+            .push(Router::with_path("hash").get(StaticFile::new(&path).with_etag_mode(EtagMode::ContentHash)))
+            .push(Router::with_path("none").get(StaticFile::new(&path).with_etag_mode(EtagMode::Disabled)));
+        let service = Service::new(router);
+        async fn etag(service: &Service) -> String {
+            let response = TestClient::get("http://127.0.0.1:5801/hash").send(service).await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+            response.headers().get("etag").unwrap().to_str().unwrap().to_owned()
+        }
+
+        let first = etag(&service).await;
+        assert_eq!(first, format!("\"{}\"", hex::encode(sha2::Sha256::digest(b"v1"))));
+        assert_eq!(etag(&service).await, first);
+        let response = TestClient::get("http://127.0.0.1:5801/hash")
+            .add_header("if-none-match", &first, true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_MODIFIED);
+
+        std::fs::write(&path, "v2 changed").unwrap();
+        let second = etag(&service).await;
+        assert_eq!(
+            second,
+            format!("\"{}\"", hex::encode(sha2::Sha256::digest(b"v2 changed")))
+        );
+        let response = TestClient::get("http://127.0.0.1:5801/hash")
+            .add_header("if-none-match", &first, true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/none").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert!(response.headers().get("etag").is_none());
+        assert!(response.headers().get("last-modified").is_some());
+        assert_eq!(response.take_string().await.unwrap(), "v2 changed");
+
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_conditional_not_modified() {
+        let router = Router::new()
+            .push(Router::with_path("file/test1.txt").get(StaticFile::new("test/static/test1.txt")))
+            .push(Router::with_path("dir/<**path>").get(StaticDir::new(vec!["test/static"])));
+        let service = Service::new(router);
+
+        for url in [
+            "http://127.0.0.1:5801/file/test1.txt",
+            "http://127.0.0.1:5801/dir/test1.txt",
+        ] {
+            let response = TestClient::get(url).send(&service).await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+            let etag = response.headers().get("etag").unwrap().to_str().unwrap().to_owned();
+            let last_modified = response
+                .headers()
+                .get("last-modified")
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_owned();
+
+            // Both the file served by `StaticFile` and `StaticDir` share the negotiation of `NamedFile::send`.
+            for (name, value) in [
+                ("if-none-match", etag.clone()),
+                ("if-none-match", format!("W/{etag}")),
+                ("if-none-match", format!(r#""other", {etag}"#)),
+                ("if-none-match", "*".to_owned()),
+                ("if-modified-since", last_modified.clone()),
+            ] {
+                let mut response = TestClient::get(url).add_header(name, &value, true).send(&service).await;
+                assert_eq!(
+                    response.status_code().unwrap(),
+                    StatusCode::NOT_MODIFIED,
+                    "{name}: {value}"
+                );
+                assert!(response.take_bytes().await.unwrap().is_empty());
+            }
+            let mut response = TestClient::get(url)
+                .add_header("if-none-match", r#""other""#, true)
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+            assert_eq!(response.take_string().await.unwrap(), "copy1");
+            let mut response = TestClient::get(url)
+                .add_header("if-modified-since", "Wed, 21 Oct 2015 07:28:00 GMT", true)
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+            assert_eq!(response.take_string().await.unwrap(), "copy1");
+
+            let response = TestClient::get(url)
+                .add_header("if-none-match", &etag, true)
+                .add_header("cache-control", "no-cache", true)
+                .add_header("pragma", "no-cache", true)
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::NOT_MODIFIED);
+
+            let mut response = TestClient::get(url)
+                .add_header("cache-control", "no-store", true)
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+            assert_eq!(response.take_string().await.unwrap(), "copy1");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_cache_control() {
+        use std::time::Duration;
+
+        let year = CacheControl::new()
+            .with_visibility(CacheVisibility::Public)
+            .with_max_age(Duration::from_secs(31536000))
+            .with_immutable(true);
+        let minute = CacheControl::new()
+            .with_visibility(CacheVisibility::Private)
+            .with_max_age(Duration::from_millis(60500));
+        assert_eq!(year.to_string(), "public, max-age=31536000, immutable");
+        assert_eq!(minute.to_string(), "private, max-age=60");
+        assert_eq!(CacheControl::new().to_string(), "");
+
+        let router = Router::new()
+            .push(
+                Router::with_path("file/test1.txt")
+                    .get(StaticFile::new("test/static/test1.txt").with_cache_control(year.clone())),
+            )
+            .push(
+                Router::with_path("dir/<**path>").get(
+                    StaticDir::new(vec!["test/static"])
+                        .with_listing(true)
+                        .with_cache_control(year)
+                        .with_extension_cache_control(".HTML", minute),
+                ),
+            );
+        let service = Service::new(router);
+
+        async fn cache_control(service: &Service, url: &str, status_code: StatusCode) -> Option<String> {
+            let response = TestClient::get(url).send(service).await;
+            assert_eq!(response.status_code().unwrap(), status_code, "{url}");
+            response
+                .headers()
+                .get("cache-control")
+                .map(|value| value.to_str().unwrap().to_owned())
+        }
+        for url in [
+            "http://127.0.0.1:5801/file/test1.txt",
+            "http://127.0.0.1:5801/dir/test1.txt",
+        ] {
+            assert_eq!(
+                cache_control(&service, url, StatusCode::OK).await.as_deref(),
+                Some("public, max-age=31536000, immutable")
+            );
+        }
+        assert_eq!(
+            cache_control(&service, "http://127.0.0.1:5801/dir/index.html", StatusCode::OK)
+                .await
+                .as_deref(),
+            Some("private, max-age=60")
+        );
+        assert_eq!(
+            cache_control(&service, "http://127.0.0.1:5801/dir/dir1/", StatusCode::OK).await,
+            None
+        );
+        assert_eq!(
+            cache_control(
+                &service,
+                "http://127.0.0.1:5801/dir/notexist.txt",
+                StatusCode::NOT_FOUND
+            )
+            .await,
+            None
+        );
+
+        let response = TestClient::get("http://127.0.0.1:5801/dir/test1.txt")
+            .add_header("range", "bytes=0-1", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            response.headers().get("cache-control").unwrap(),
+            "public, max-age=31536000, immutable"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_not_modified_without_read() {
+        let root = temp_dir("salvo_serve_static_not_modified_without_read");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("README"), "plain text").unwrap();
+
+        let router = Router::new()
+            .push(Router::with_path("file/README").get(StaticFile::new(root.join("README"))))
+            .push(
+                Router::with_path("dir/<**path>")
+                    .get(StaticDir::new(vec![root.clone()]).with_unknown_mime(UnknownMimePolicy::Sniff)),
+            );
+        let service = Service::new(router);
+
+        for url in ["http://127.0.0.1:5801/file/README", "http://127.0.0.1:5801/dir/README"] {
+            let response = TestClient::get(url).send(&service).await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+            assert!(response.headers().contains_key("content-type"));
+            let etag = response.headers().get("etag").unwrap().to_str().unwrap().to_owned();
+            let last_modified = response
+                .headers()
+                .get("last-modified")
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_owned();
+
+            for (name, value) in [("if-none-match", etag.clone()), ("if-modified-since", last_modified)] {
+                let mut response = TestClient::get(url).add_header(name, &value, true).send(&service).await;
+                assert_eq!(
+                    response.status_code().unwrap(),
+                    StatusCode::NOT_MODIFIED,
+                    "{name}: {value}"
+                );
+                assert_eq!(response.headers().get("etag").unwrap(), etag.as_str());
+                // The content type is sniffed from the file or set when it is opened, so it is
+                // missing if the file is not read.
+                assert!(!response.headers().contains_key("content-type"), "{url}");
+                assert!(response.take_bytes().await.unwrap().is_empty());
+            }
+
+            // `If-Match` is evaluated by `NamedFile::send` on the opened file.
+            let response = TestClient::get(url)
+                .add_header("if-match", &etag, true)
+                .add_header("if-none-match", &etag, true)
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::NOT_MODIFIED);
+            assert!(response.headers().contains_key("content-type"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_empty_file() {
+        let router = Router::new()
+            .push(Router::with_path("file/empty.txt").get(StaticFile::new("test/empty/empty.txt")))
+            .push(Router::with_path("dir/<**path>").get(StaticDir::new(vec!["test/empty"])));
+        let service = Service::new(router);
+
+        for url in [
+            "http://127.0.0.1:5801/file/empty.txt",
+            "http://127.0.0.1:5801/dir/empty.txt",
+        ] {
+            let mut response = TestClient::get(url).send(&service).await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+            assert_eq!(response.headers().get("content-length").unwrap(), "0");
+            assert!(response
+                .headers()
+                .get("content-type")
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .starts_with("text/plain"));
+            assert!(response.take_bytes().await.unwrap().is_empty());
+
+            let response = TestClient::get(url)
+                .add_header("range", "bytes=0-", true)
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+            assert_eq!(response.headers().get("content-length").unwrap(), "0");
+
+            for range in ["bytes=0-0", "bytes=-5", "bytes=5-"] {
+                let response = TestClient::get(url)
+                    .add_header("range", range, true)
+                    .send(&service)
+                    .await;
+                assert_eq!(response.status_code().unwrap(), StatusCode::RANGE_NOT_SATISFIABLE);
+                assert_eq!(response.headers().get("content-range").unwrap(), "bytes */0");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_method_not_allowed() {
+        #[handler]
+        async fn fallback(res: &mut Response) {
+            res.set_status_code(StatusCode::OK);
+            res.render("fallback");
+        }
+
+        let router = Router::new()
+            .push(Router::with_path("test1.txt").handle(StaticFile::new("test/static/test1.txt")))
+            .push(Router::with_path("dir/<**path>").handle(StaticDir::new(vec!["test/static"])))
+            .push(
+                Router::with_path("hoop/<**path>")
+                    .hoop(StaticDir::new(vec!["test/static"]))
+                    .handle(fallback),
+            );
+        let service = Service::new(router);
+
+        for url in [
+            "http://127.0.0.1:5801/test1.txt",
+            "http://127.0.0.1:5801/dir/test1.txt",
+            "http://127.0.0.1:5801/hoop/test1.txt",
+        ] {
+            let mut response = TestClient::post(url).send(&service).await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::METHOD_NOT_ALLOWED);
+            assert_eq!(response.headers().get("allow").unwrap(), "GET, HEAD, OPTIONS");
+            assert!(!response.take_string().await.unwrap().contains("fallback"));
+        }
+        let response = TestClient::get("http://127.0.0.1:5801/dir/test1.txt")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_media_duration() {
+        let root = temp_dir("salvo_serve_static_media_duration");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("tone.wav"), crate::media::tests::wav(1000, 1500)).unwrap();
+        std::fs::write(root.join("notes.txt"), "notes").unwrap();
+        let router = Router::with_path("<**path>").get(StaticDir::new(vec![root]).with_media_duration(true));
+        let service = Service::new(router);
+
+        let response = TestClient::get("http://127.0.0.1:5801/tone.wav").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.headers().get("x-content-duration").unwrap(), "1.5");
+        let response = TestClient::get("http://127.0.0.1:5801/tone.wav")
+            .add_header("range", "bytes=100-199", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers().get("x-content-duration").unwrap(), "1.5");
+
+        let response = TestClient::get("http://127.0.0.1:5801/notes.txt").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert!(response.headers().get("x-content-duration").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_unknown_mime() {
+        let root = temp_dir("salvo_serve_static_unknown_mime");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("README"), "plain text, ünicode").unwrap();
+        std::fs::write(root.join("blob"), [0x7f, b'E', b'L', b'F', 0, 0]).unwrap();
+
+        for (policy, readme, blob) in [
+            (
+                UnknownMimePolicy::OctetStream,
+                "application/octet-stream",
+                "application/octet-stream",
+            ),
+            (UnknownMimePolicy::TextPlain, "text/plain", "text/plain"),
+            (UnknownMimePolicy::Sniff, "text/plain", "application/octet-stream"),
+        ] {
+            let router =
+                Router::with_path("<**path>").get(StaticDir::new(vec![root.clone()]).with_unknown_mime(policy));
+            let service = Service::new(router);
+            for (url, content_type) in [
+                ("http://127.0.0.1:5801/README", readme),
+                ("http://127.0.0.1:5801/blob", blob),
+            ] {
+                let response = TestClient::get(url).send(&service).await;
+                assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+                assert_eq!(
+                    response.headers().get("content-type").unwrap(),
+                    content_type,
+                    "{policy:?} {url}"
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_precompressed() {
+        let root = temp_dir("salvo_serve_static_precompressed");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("app.js"), "console.log('raw');").unwrap();
+        std::fs::write(root.join("app.js.br"), "brotli").unwrap();
+        std::fs::write(root.join("app.js.gz"), "gzip").unwrap();
+        std::fs::write(root.join("style.css"), "body {}").unwrap();
+        std::fs::write(root.join("my report.pdf"), "pdf").unwrap();
+        std::fs::write(root.join("my report.pdf.gz"), "gzip pdf").unwrap();
+
+        let router = Router::with_path("<**path>").get(
+            StaticDir::new(vec![root.clone()])
+                .with_precompressed([PrecompressedEncoding::Brotli, PrecompressedEncoding::Gzip]),
+        );
+        let service = Service::new(router);
+
+        let mut etags = Vec::new();
+        for (accept, encoding, body) in [
+            (Some("br, gzip"), Some("br"), "brotli"),
+            (Some("gzip, br"), Some("br"), "brotli"),
+            (Some("*"), Some("br"), "brotli"),
+            (Some("gzip"), Some("gzip"), "gzip"),
+            (Some("br;q=0, gzip"), Some("gzip"), "gzip"),
+            (Some("br;q=0, gzip;q=0"), None, "console.log('raw');"),
+            (Some("*;q=0"), None, "console.log('raw');"),
+            (Some("deflate"), None, "console.log('raw');"),
+            (None, None, "console.log('raw');"),
+        ] {
+            let mut request = TestClient::get("http://127.0.0.1:5801/app.js");
+            if let Some(accept) = accept {
+                request = request.add_header("accept-encoding", accept, true);
+            }
+            let mut response = request.send(&service).await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+            assert_eq!(
+                response.headers().get("content-encoding").map(|v| v.to_str().unwrap()),
+                encoding,
+                "{accept:?}"
+            );
+            assert!(response
+                .headers()
+                .get("content-type")
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .contains("javascript"));
+            assert!(response
+                .headers()
+                .get_all("vary")
+                .iter()
+                .any(|v| v == "accept-encoding"));
+            let etag = response.headers().get("etag").unwrap().to_str().unwrap().to_owned();
+            if !etags.contains(&(encoding, etag.clone())) {
+                etags.push((encoding, etag));
+            }
+            assert_eq!(response.take_string().await.unwrap(), body);
+        }
+        // One ETag for each representation.
+        assert_eq!(etags.len(), 3);
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/style.css")
+            .add_header("accept-encoding", "br, gzip", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert!(response.headers().get("content-encoding").is_none());
+        assert_eq!(response.take_string().await.unwrap(), "body {}");
+
+        // The attachment is named after the requested file, not the sidecar.
+        let mut response = TestClient::get("http://127.0.0.1:5801/my%20report.pdf")
+            .add_header("accept-encoding", "gzip", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.headers().get("content-encoding").unwrap(), "gzip");
+        assert_eq!(response.headers().get("content-type").unwrap(), "application/pdf");
+        assert_eq!(
+            response.headers().get("content-disposition").unwrap(),
+            "attachment; filename=\"my report.pdf\""
+        );
+        assert_eq!(response.take_string().await.unwrap(), "gzip pdf");
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_dir_precompressed_range() {
+        let root = temp_dir("salvo_serve_static_precompressed_range");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("app.js"), "console.log('raw');").unwrap();
+        std::fs::write(root.join("app.js.gz"), "gzip").unwrap();
+
+        let router = Router::with_path("<**path>")
+            .get(StaticDir::new(vec![root.clone()]).with_precompressed([PrecompressedEncoding::Gzip]));
+        let service = Service::new(router);
+
+        let etag = |accept: &'static str| {
+            let service = &service;
+            async move {
+                let response = TestClient::get("http://127.0.0.1:5801/app.js")
+                    .add_header("accept-encoding", accept, true)
+                    .send(service)
+                    .await;
+                response.headers()["etag"].to_str().unwrap().to_owned()
+            }
+        };
+        let identity_etag = etag("identity").await;
+        let gzip_etag = etag("gzip").await;
+        assert_ne!(identity_etag, gzip_etag);
+
+        // Ranges are served from the identity representation, with or without a matching `If-Range`.
+        for if_range in [None, Some(&identity_etag)] {
+            let mut request = TestClient::get("http://127.0.0.1:5801/app.js")
+                .add_header("accept-encoding", "gzip", true)
+                .add_header("range", "bytes=0-6", true);
+            if let Some(if_range) = if_range {
+                request = request.add_header("if-range", if_range, true);
+            }
+            let mut response = request.send(&service).await;
+            assert_eq!(
+                response.status_code().unwrap(),
+                StatusCode::PARTIAL_CONTENT,
+                "{if_range:?}"
+            );
+            assert!(response.headers().get("content-encoding").is_none());
+            assert_eq!(response.headers()["content-range"], "bytes 0-6/19");
+            assert_eq!(response.headers()["etag"], identity_etag.as_str());
+            assert_eq!(response.take_string().await.unwrap(), "console");
+        }
+
+        // The ETag of the compressed representation doesn't match, the whole file is served.
+        let mut response = TestClient::get("http://127.0.0.1:5801/app.js")
+            .add_header("accept-encoding", "gzip", true)
+            .add_header("range", "bytes=0-6", true)
+            .add_header("if-range", &gzip_etag, true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert!(response.headers().get("content-encoding").is_none());
+        assert!(response.headers().get("content-range").is_none());
+        assert_eq!(response.take_string().await.unwrap(), "console.log('raw');");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_serve_static_dir_follow_symlinks() {
+        let base = temp_dir("salvo_serve_static_follow_symlinks");
+        let root = base.join("root");
+        let outside = base.join("outside");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(root.join("real.txt"), "real").unwrap();
+        std::fs::write(outside.join("secret.txt"), "secret").unwrap();
+        for (target, link) in [
+            (root.join("real.txt"), root.join("alias.txt")),
+            (outside.join("secret.txt"), root.join("escape.txt")),
+            (outside.clone(), root.join("escape_dir")),
+        ] {
+            std::fs::remove_file(&link).ok();
+            std::os::unix::fs::symlink(target, link).unwrap();
+        }
+
+        for follow_symlinks in [false, true] {
+            let router = Router::with_path("<**path>").get(
+                StaticDir::new(vec![root.clone()])
+                    .with_listing(true)
+                    .with_zip_download(true)
+                    .with_follow_symlinks(follow_symlinks),
+            );
+            let service = Service::new(router);
+
+            let archive = TestClient::get("http://127.0.0.1:5801/?download=zip")
+                .send(&service)
+                .await
+                .take_bytes()
+                .await
+                .unwrap();
+            let contains = |needle: &[u8]| archive.windows(needle.len()).any(|window| window == needle);
+            assert!(contains(b"alias.txt"));
+            assert_eq!(contains(b"escape.txt"), follow_symlinks);
+            // Linked directories are never archived, they may form loops.
+            assert!(!contains(b"escape_dir"));
+
+            let mut response = TestClient::get("http://127.0.0.1:5801/alias.txt").send(&service).await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+            assert_eq!(response.take_string().await.unwrap(), "real");
+
+            for url in [
+                "http://127.0.0.1:5801/escape.txt",
+                "http://127.0.0.1:5801/escape_dir/secret.txt",
+                "http://127.0.0.1:5801/escape_dir/",
+            ] {
+                let mut response = TestClient::get(url).send(&service).await;
+                if follow_symlinks {
+                    assert_eq!(response.status_code().unwrap(), StatusCode::OK, "{url}");
+                    assert!(response.take_string().await.unwrap().contains("secret"), "{url}");
+                } else {
+                    assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND, "{url}");
+                }
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_serve_static_dir_symlinked_root() {
+        let base = temp_dir("salvo_serve_static_symlinked_root");
+        let release = base.join("release-1");
+        let outside = base.join("outside");
+        let current = base.join("current");
+        std::fs::create_dir_all(release.join("assets")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(release.join("index.txt"), "release").unwrap();
+        std::fs::write(release.join("assets/app.js"), "app").unwrap();
+        std::fs::write(outside.join("secret.txt"), "secret").unwrap();
+        for (target, link) in [
+            (release.clone(), current.clone()),
+            (outside.join("secret.txt"), release.join("escape.txt")),
+        ] {
+            std::fs::remove_file(&link).ok();
+            std::os::unix::fs::symlink(target, link).unwrap();
+        }
+
+        let router = Router::with_path("<**path>").get(StaticDir::new(vec![current.clone()]).with_listing(true));
+        let service = Service::new(router);
+
+        for (url, content) in [
+            ("http://127.0.0.1:5801/index.txt", "release"),
+            ("http://127.0.0.1:5801/assets/app.js", "app"),
+        ] {
+            let mut response = TestClient::get(url).send(&service).await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK, "{url}");
+            assert_eq!(response.take_string().await.unwrap(), content, "{url}");
+        }
+        let response = TestClient::get("http://127.0.0.1:5801/").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        let response = TestClient::get("http://127.0.0.1:5801/escape.txt").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+
+        // The root is switched to a new release after `StaticDir` is created.
+        let next = base.join("release-2");
+        std::fs::create_dir_all(&next).unwrap();
+        std::fs::write(next.join("index.txt"), "next").unwrap();
+        std::fs::remove_file(&current).unwrap();
+        std::os::unix::fs::symlink(&next, &current).unwrap();
+        let mut response = TestClient::get("http://127.0.0.1:5801/index.txt").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.take_string().await.unwrap(), "next");
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_file_options() {
+        let router = Router::new()
+            .push(Router::with_path("test1.txt").handle(StaticFile::new("test/static/test1.txt")))
+            .push(Router::with_path("dir/<**path>").handle(StaticDir::new(vec!["test/static"]).with_listing(true)));
+        let service = Service::new(router);
+
+        for url in ["http://127.0.0.1:5801/test1.txt", "http://127.0.0.1:5801/dir/test1.txt"] {
+            let mut response = TestClient::options(url).send(&service).await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::NO_CONTENT);
+            assert_eq!(response.headers().get("allow").unwrap(), "GET, HEAD, OPTIONS");
+            assert_eq!(response.headers().get("accept-ranges").unwrap(), "bytes");
+            assert!(response.headers().get("etag").is_none());
+            assert!(response.take_string().await.unwrap().is_empty());
+        }
+
+        // Directories are not file targets.
+        let response = TestClient::options("http://127.0.0.1:5801/dir/dir1/")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        let response = TestClient::options("http://127.0.0.1:5801/dir/notexist.txt")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_serve_async_static_dir() {
+        use std::collections::HashMap;
+        use std::io::Result as IoResult;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        /// Files in an object store, every access takes a round trip.
+        #[derive(Default)]
+        struct MockSource {
+            files: HashMap<String, bytes::Bytes>,
+            etag: &'static str,
+            reads: Arc<AtomicUsize>,
+        }
+        #[async_trait]
+        impl AsyncFileSource for MockSource {
+            async fn metadata(&self, path: &str) -> IoResult<Option<SourceMetadata>> {
+                tokio::task::yield_now().await;
+                Ok(self
+                    .files
+                    .get(path)
+                    .map(|data| SourceMetadata::new(data.len() as u64).with_etag(self.etag)))
+            }
+            async fn read(&self, path: &str) -> IoResult<bytes::Bytes> {
+                tokio::task::yield_now().await;
+                self.reads.fetch_add(1, Ordering::Relaxed);
+                self.files
+                    .get(path)
+                    .cloned()
+                    .ok_or_else(|| std::io::ErrorKind::NotFound.into())
+            }
+        }
+
+        let mut source = MockSource {
+            etag: "\"v1\"",
+            ..Default::default()
+        };
+        let reads = source.reads.clone();
+        source.files.insert("docs/index.html".into(), "<h1>Docs</h1>".into());
+        source
+            .files
+            .insert("assets/app.js".into(), "console.log('hello');".into());
+        let router = Router::with_path("<**path>").handle(AsyncStaticDir::new(source).with_defaults("index.html"));
+        let service = Service::new(router);
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/assets/app.js")
+            .add_header("range", "bytes=8-12", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers().get("content-range").unwrap(), "bytes 8-12/21");
+        assert_eq!(response.headers().get("etag").unwrap(), "\"v1\"");
+        assert!(response
+            .headers()
+            .get("content-type")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .contains("javascript"));
+        assert_eq!(response.take_string().await.unwrap(), "log('");
+        assert_eq!(reads.load(Ordering::Relaxed), 1);
+
+        // Neither unmodified files nor `HEAD` requests are read from the source.
+        let response = TestClient::get("http://127.0.0.1:5801/assets/app.js")
+            .add_header("if-none-match", "\"v1\"", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_MODIFIED);
+        let response = TestClient::head("http://127.0.0.1:5801/assets/app.js")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.headers().get("content-length").unwrap(), "21");
+        assert_eq!(reads.load(Ordering::Relaxed), 1);
+
+        let response = TestClient::get("http://127.0.0.1:5801/docs").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::FOUND);
+        let mut response = TestClient::get("http://127.0.0.1:5801/docs/").send(&service).await;
+        assert_eq!(response.take_string().await.unwrap(), "<h1>Docs</h1>");
+
+        let response = TestClient::get("http://127.0.0.1:5801/notexist.js")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+
+        // An etag which is not a valid header value is encoded instead of sent as is.
+        let mut source = MockSource {
+            etag: "v1\nbroken",
+            ..Default::default()
+        };
+        source.files.insert("app.js".into(), "console.log('hello');".into());
+        let service = Service::new(Router::with_path("<**path>").get(AsyncStaticDir::new(source)));
+        let mut response = TestClient::get("http://127.0.0.1:5801/app.js").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        let hash = hex::encode(sha2::Sha256::digest(b"v1\nbroken"));
+        assert_eq!(
+            response.headers().get("etag").unwrap().to_str().unwrap(),
+            format!("\"{hash}\"")
+        );
+        assert_eq!(response.take_string().await.unwrap(), "console.log('hello');");
+
+        // A source which returns less content than its size is an error, not a panic.
+        struct ShortSource;
+        #[async_trait]
+        impl AsyncFileSource for ShortSource {
+            async fn metadata(&self, _path: &str) -> IoResult<Option<SourceMetadata>> {
+                Ok(Some(SourceMetadata::new(100)))
+            }
+            async fn read(&self, _path: &str) -> IoResult<bytes::Bytes> {
+                Ok(bytes::Bytes::from_static(b"short"))
+            }
+        }
+        let service = Service::new(Router::with_path("<**path>").get(AsyncStaticDir::new(ShortSource)));
+        let response = TestClient::get("http://127.0.0.1:5801/app.js")
+            .add_header("range", "bytes=50-60", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn test_serve_async_static_dir_fs() {
+        let router = Router::with_path("<**path>").get(AsyncStaticDir::new(FsSource::new("test/static")));
+        let service = Service::new(router);
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/test1.txt")
+            .add_header("range", "bytes=1-", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::PARTIAL_CONTENT);
+        assert!(response.headers().contains_key("last-modified"));
+        assert_eq!(response.take_string().await.unwrap(), "opy1");
+        let response = TestClient::get("http://127.0.0.1:5801/dir1").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+
+        // The path is decoded once by the handler, `%2541` is the file named `%41`, not `A`.
+        let root = temp_dir("salvo_serve_static_async_fs");
+        std::fs::write(root.join("%41.txt"), "percent").unwrap();
+        std::fs::write(root.join("A.txt"), "letter").unwrap();
+        let service = Service::new(Router::with_path("<**path>").get(AsyncStaticDir::new(FsSource::new(&root))));
+        let mut response = TestClient::get("http://127.0.0.1:5801/%2541.txt").send(&service).await;
+        assert_eq!(response.take_string().await.unwrap(), "percent");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_serve_async_static_dir_fs_defaults() {
+        let base = temp_dir("salvo_serve_static_async_fs_defaults");
+        let root = base.join("root");
+        let outside = base.join("outside");
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(root.join(".env"), "SECRET=1").unwrap();
+        std::fs::write(root.join(".git/config"), "[core]").unwrap();
+        std::fs::write(outside.join("secret.txt"), "secret").unwrap();
+        std::os::unix::fs::symlink(outside.join("secret.txt"), root.join("escape.txt")).unwrap();
+
+        for (source, served) in [
+            (FsSource::new(&root), false),
+            (
+                FsSource::new(&root).with_dot_files(true).with_follow_symlinks(true),
+                true,
+            ),
+        ] {
+            let service = Service::new(Router::with_path("<**path>").get(AsyncStaticDir::new(source)));
+            for path in [".env", ".git/config", "escape.txt"] {
+                let response = TestClient::get(format!("http://127.0.0.1:5801/{path}"))
+                    .send(&service)
+                    .await;
+                let expected = if served { StatusCode::OK } else { StatusCode::NOT_FOUND };
+                assert_eq!(response.status_code().unwrap(), expected, "{path}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serve_static_vfs() {
+        let mut vfs = StaticVfs::new().with_defaults("index.html");
+        vfs.insert("index.html", "Index page");
+        vfs.insert("/assets/app.js", "console.log('hello');");
+        vfs.insert("data.bin", vec![b'a'; 100]);
+        let router = Router::with_path("<**path>").get(vfs);
+        let service = Service::new(router);
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/assets/app.js")
+            .add_header("range", "bytes=0-6", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers().get("content-range").unwrap(), "bytes 0-6/21");
+        assert!(response
+            .headers()
+            .get("content-type")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .contains("javascript"));
+        assert_eq!(response.take_string().await.unwrap(), "console");
+        let etag = response.headers().get("etag").unwrap().to_str().unwrap().to_owned();
+        let hash = hex::encode(sha2::Sha256::digest(b"console.log('hello');"));
+        assert_eq!(etag, format!("\"{hash}\""));
+
+        let response = TestClient::get("http://127.0.0.1:5801/assets/app.js")
+            .add_header("if-none-match", &etag, true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_MODIFIED);
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.take_string().await.unwrap(), "Index page");
+
+        // Overlapping ranges are merged, and too many ranges get the whole content.
+        let ranges = format!("bytes={}", vec!["0-"; 1000].join(","));
+        let mut response = TestClient::get("http://127.0.0.1:5801/assets/app.js")
+            .add_header("range", ranges, true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers().get("content-range").unwrap(), "bytes 0-20/21");
+        assert_eq!(response.take_string().await.unwrap(), "console.log('hello');");
+        let ranges = (0..20).map(|i| format!("{i}-{i}")).collect::<Vec<_>>().join(",");
+        let mut response = TestClient::get("http://127.0.0.1:5801/assets/app.js")
+            .add_header("range", format!("bytes={ranges}"), true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers().get("content-range").unwrap(), "bytes 0-19/21");
+        assert_eq!(response.take_string().await.unwrap(), "console.log('hello')");
+        for (count, status) in [(16, StatusCode::PARTIAL_CONTENT), (17, StatusCode::OK)] {
+            let ranges = (0..count).map(|i| format!("{0}-{0}", i * 2)).collect::<Vec<_>>();
+            let mut response = TestClient::get("http://127.0.0.1:5801/data.bin")
+                .add_header("range", format!("bytes={}", ranges.join(",")), true)
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), status, "{count}");
+            if status == StatusCode::OK {
+                assert_eq!(response.take_bytes().await.unwrap().len(), 100);
+            }
+        }
+
+        let response = TestClient::get("http://127.0.0.1:5801/assets").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+        let response = TestClient::get("http://127.0.0.1:5801/notexist.js")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+    }
+
+    #[cfg(feature = "embed")]
+    #[tokio::test]
+    async fn test_serve_embed_head() {
+        #[derive(RustEmbed)]
+        #[folder = "test/static"]
+        struct Assets;
+
+        let router = Router::with_path("<**path>").handle(static_embed::<Assets>());
+        let service = Service::new(router);
+
+        let mut get = TestClient::get("http://127.0.0.1:5801/test1.txt").send(&service).await;
+        let mut head = TestClient::head("http://127.0.0.1:5801/test1.txt").send(&service).await;
+        assert_eq!(head.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(head.headers().get("content-length").unwrap(), "5");
+        for name in ["content-type", "etag", "accept-ranges"] {
+            assert_eq!(head.headers().get(name), get.headers().get(name), "{name}");
+        }
+        assert_eq!(get.take_string().await.unwrap(), "copy1");
+        assert_eq!(head.take_string().await.unwrap(), "");
+
+        let mut response = TestClient::head("http://127.0.0.1:5801/notexist.txt")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+        assert_eq!(response.take_string().await.unwrap(), "");
+
+        // Other methods are answered as by `StaticVfs`.
+        let response = TestClient::post("http://127.0.0.1:5801/test1.txt").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers().get("allow").unwrap(), "GET, HEAD, OPTIONS");
+        let response = TestClient::options("http://127.0.0.1:5801/test1.txt")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers().get("accept-ranges").unwrap(), "bytes");
+    }
+
+    #[cfg(feature = "embed")]
+    #[tokio::test]
+    async fn test_serve_embed_files() {
+        #[derive(RustEmbed)]
+        #[folder = "test/static"]
+        struct Assets;
+
+        let router = Router::new()
+            .push(Router::with_path("test1.txt").get(Assets::get("test1.txt").unwrap().into_handler()))
+            .push(Router::with_path("files/<**path>").get(serve_file))
+            .push(
+                Router::with_path("dir/<**path>").get(
+                    static_embed::<Assets>()
+                        .with_defaults("index.html")
+                        .with_fallback("fallback.html"),
+                ),
+            )
+            .push(Router::with_path("dir2/<**path>").get(static_embed::<Assets>()))
+            .push(Router::with_path("dir3/<**path>").get(static_embed::<Assets>().with_fallback("notexist.html")));
+        let service = Service::new(router);
+
+        #[handler]
+        async fn serve_file(req: &mut Request, res: &mut Response) {
+            let path = req.param::<String>("**path").unwrap();
+            if let Some(file) = Assets::get(&path) {
+                file.render(req, res);
+            }
+        }
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/files/test1.txt")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.take_string().await.unwrap(), "copy1");
+        let etag = response.headers().get("etag").unwrap().to_str().unwrap().to_owned();
+
+        let response = TestClient::get("http://127.0.0.1:5801/files/test1.txt")
+            .add_header("if-none-match", format!(r#""other", W/{etag}"#), true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_MODIFIED);
+        let response = TestClient::get("http://127.0.0.1:5801/files/test1.txt")
+            .add_header("if-match", format!("W/{etag}"), true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::PRECONDITION_FAILED);
+        let response = TestClient::get("http://127.0.0.1:5801/files/test1.txt")
+            .add_header("if-match", "*", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/files/test1.txt")
+            .add_header("range", "bytes=1-2", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers().get("content-range").unwrap(), "bytes 1-2/5");
+        assert_eq!(response.take_string().await.unwrap(), "op");
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/files/test1.txt")
+            .add_header("range", "bytes=0-1,-2", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::PARTIAL_CONTENT);
+        let content_type = response.headers().get("content-type").unwrap().to_str().unwrap();
+        let boundary = content_type
+            .strip_prefix("multipart/byteranges; boundary=")
+            .unwrap()
+            .to_owned();
+        let body = response.take_string().await.unwrap();
+        let parts = body
+            .strip_suffix(&format!("--{boundary}--\r\n"))
+            .unwrap()
+            .split(&format!("--{boundary}\r\n"))
+            .filter(|part| !part.is_empty())
+            .map(|part| part.split_once("\r\n\r\n").unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(parts.len(), 2);
+        assert!(parts[0].0.contains("content-range: bytes 0-1/5"));
+        assert_eq!(parts[0].1, "co\r\n");
+        assert!(parts[1].0.contains("content-range: bytes 3-4/5"));
+        assert_eq!(parts[1].1, "y1\r\n");
+
+        let response = TestClient::get("http://127.0.0.1:5801/files/test1.txt")
+            .add_header("range", "bytes=10-20", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::RANGE_NOT_SATISFIABLE);
+
+        // `If-Range` is compared to the recorded modification time as by `StaticVfs` and `StaticDir`.
+        let last_modified = response
+            .headers()
+            .get("last-modified")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_owned();
+        for (if_range, status) in [
+            (last_modified.as_str(), StatusCode::PARTIAL_CONTENT),
+            ("Thu, 01 Jan 1970 00:00:00 GMT", StatusCode::OK),
+        ] {
+            let response = TestClient::get("http://127.0.0.1:5801/files/test1.txt")
+                .add_header("range", "bytes=1-2", true)
+                .add_header("if-range", if_range, true)
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code().unwrap(), status, "{if_range}");
+        }
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/dir/test1.txt")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.take_string().await.unwrap(), "copy1");
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/dir/test1111.txt")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert!(response.take_string().await.unwrap().contains("Fallback page"));
+
+        let response = TestClient::get("http://127.0.0.1:5801/dir").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/dir/").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert!(response.take_string().await.unwrap().contains("Index page"));
+
+        let response = TestClient::get("http://127.0.0.1:5801/dir2/").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+
+        let response = TestClient::get("http://127.0.0.1:5801/dir3/abc.txt")
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
+    }
+
+    #[cfg(feature = "embed")]
+    #[tokio::test]
+    async fn test_serve_embed_fallbacks() {
+        #[derive(RustEmbed)]
+        #[folder = "test/spa"]
+        struct Assets;
+
+        let fallbacks = std::collections::HashMap::from([
+            ("app1/".to_owned(), "app1/index.html".to_owned()),
+            ("app2".to_owned(), "app2/index.html".to_owned()),
+            ("app2/admin/".to_owned(), "notexist.html".to_owned()),
+        ]);
+        let router = Router::new()
+            .push(
+                Router::with_path("global/<**path>").get(
+                    static_embed::<Assets>()
+                        .with_fallbacks(fallbacks.clone())
+                        .with_fallback("index.html"),
+                ),
+            )
+            .push(Router::with_path("<**path>").get(static_embed::<Assets>().with_fallbacks(fallbacks)));
+        let service = Service::new(router);
+
+        for (url, content) in [
+            ("http://127.0.0.1:5801/app1/users/42", "app1 index"),
+            ("http://127.0.0.1:5801/app1/deep/nested/route", "app1 index"),
+            ("http://127.0.0.1:5801/app2/settings", "app2 index"),
+            ("http://127.0.0.1:5801/global/app2/settings", "app2 index"),
+            ("http://127.0.0.1:5801/global/app3/settings", "home"),
+        ] {
+            let mut response = TestClient::get(url).send(&service).await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::OK, "{url}");
+            assert_eq!(response.headers().get("content-type").unwrap(), "text/html", "{url}");
+            assert_eq!(response.take_string().await.unwrap(), content, "{url}");
+        }
+
+        // The longest prefix wins even if its fallback does not exist, and prefixes match whole segments.
+        for url in [
+            "http://127.0.0.1:5801/app2/admin/users",
+            "http://127.0.0.1:5801/app10/users",
+            "http://127.0.0.1:5801/app3/users",
+        ] {
+            let response = TestClient::get(url).send(&service).await;
+            assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND, "{url}");
+        }
+    }
+
+    #[cfg(feature = "embed")]
+    #[tokio::test]
+    async fn test_serve_static_sources() {
+        #[derive(RustEmbed)]
+        #[folder = "test/static"]
+        struct Assets;
+
+        // A response with a body but without status is not a miss.
+        #[handler]
+        async fn unset_status(req: &mut Request, res: &mut Response) {
+            if req.uri().path() == "/unset.txt" {
+                res.write_body("unset").ok();
+            }
+        }
+
+        let router = Router::with_path("<**path>").get(
+            StaticSources::new()
+                .push(unset_status)
+                .push(StaticDir::new("test/override"))
+                .push(static_embed::<Assets>()),
+        );
+        let service = Service::new(router);
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/test1.txt").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.take_string().await.unwrap(), "override1");
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/test2.txt").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.take_string().await.unwrap(), "copy2");
+
+        let mut response = TestClient::get("http://127.0.0.1:5801/unset.txt").send(&service).await;
+        assert_eq!(response.status_code().unwrap(), StatusCode::OK);
+        assert_eq!(response.take_string().await.unwrap(), "unset");
+
+        let response = TestClient::get("http://127.0.0.1:5801/notexist.txt")
             .send(&service)
             .await;
         assert_eq!(response.status_code().unwrap(), StatusCode::NOT_FOUND);
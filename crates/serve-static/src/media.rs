@@ -0,0 +1,120 @@
+//! Media metadata of served files.
+
+use std::io::Result as IoResult;
+use std::path::Path;
+
+use tokio::io::AsyncReadExt;
+
+/// Size of the file head read to find media metadata.
+const HEAD_SIZE: u64 = 64 * 1024;
+
+/// Read the duration in seconds of the media file at `path`, returns `None` if the format is not
+/// known or the metadata is not found in the head of the file.
+pub(crate) async fn read_duration(path: &Path) -> IoResult<Option<f64>> {
+    let file = tokio::fs::File::open(path).await?;
+    let mut head = Vec::new();
+    file.take(HEAD_SIZE).read_to_end(&mut head).await?;
+    Ok(parse_duration(&head))
+}
+
+/// Parse duration from the head of a WAV or FLAC file.
+pub(crate) fn parse_duration(head: &[u8]) -> Option<f64> {
+    if head.len() >= 12 && &head[0..4] == b"RIFF" && &head[8..12] == b"WAVE" {
+        parse_wav_duration(&head[12..])
+    } else if head.starts_with(b"fLaC") {
+        parse_flac_duration(&head[4..])
+    } else {
+        None
+    }
+}
+
+fn parse_wav_duration(mut chunks: &[u8]) -> Option<f64> {
+    let mut byte_rate = None;
+    while chunks.len() >= 8 {
+        let id = &chunks[0..4];
+        let size = u32::from_le_bytes(chunks[4..8].try_into().ok()?) as usize;
+        let data = &chunks[8..];
+        if id == b"fmt " && data.len() >= 12 {
+            byte_rate = Some(u32::from_le_bytes(data[8..12].try_into().ok()?));
+        } else if id == b"data" {
+            // `data` chunk is usually after `fmt ` chunk, only its size is needed.
+            return match byte_rate {
+                Some(byte_rate) if byte_rate > 0 => Some(size as f64 / byte_rate as f64),
+                _ => None,
+            };
+        }
+        // Chunks are padded to even size, the size is read from the file, so it may overflow on 32-bit.
+        let next = size.checked_add(8 + (size & 1))?;
+        chunks = chunks.get(next..)?;
+    }
+    None
+}
+
+fn parse_flac_duration(blocks: &[u8]) -> Option<f64> {
+    // The first metadata block must be `STREAMINFO`, sample rate (20 bits) and total samples (36 bits)
+    // are kept in its bytes 10 to 18.
+    if blocks.len() < 4 + 18 || blocks[0] & 0x7f != 0 {
+        return None;
+    }
+    let info = &blocks[4..];
+    let bits = u64::from_be_bytes(info[10..18].try_into().ok()?);
+    let sample_rate = bits >> 44;
+    let total_samples = bits & 0xf_ffff_ffff;
+    if sample_rate == 0 || total_samples == 0 {
+        return None;
+    }
+    Some(total_samples as f64 / sample_rate as f64)
+}
+
+/// Format a duration for the `X-Content-Duration` header, with at most millisecond precision.
+#[inline]
+pub(crate) fn format_duration(duration: f64) -> String {
+    format!("{}", (duration * 1000.0).round() / 1000.0)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Build a mono 8-bit PCM WAV file with `samples` samples at `sample_rate`.
+    pub(crate) fn wav(sample_rate: u32, samples: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&(36 + samples).to_le_bytes());
+        data.extend_from_slice(b"WAVEfmt ");
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&sample_rate.to_le_bytes());
+        data.extend_from_slice(&sample_rate.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&8u16.to_le_bytes());
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&samples.to_le_bytes());
+        data.resize(data.len() + samples as usize, 0x80);
+        data
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration(&wav(1000, 1500)), Some(1.5));
+        assert_eq!(parse_duration(&wav(1000, 1500)[..40]), None);
+        let mut huge_chunk = wav(1000, 1500);
+        huge_chunk[36..40].copy_from_slice(b"LIST");
+        huge_chunk[40..44].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(parse_duration(&huge_chunk), None);
+
+        let mut flac = b"fLaC".to_vec();
+        flac.extend_from_slice(&[0x80, 0, 0, 34]);
+        flac.extend_from_slice(&[0; 10]);
+        // 44100 Hz, 2 channels, 16 bits, 88200 samples.
+        let bits = (44100u64 << 44) | (1 << 41) | (15 << 36) | 88200;
+        flac.extend_from_slice(&bits.to_be_bytes());
+        flac.extend_from_slice(&[0; 16]);
+        assert_eq!(parse_duration(&flac), Some(2.0));
+
+        assert_eq!(parse_duration(b"ID3\x04\x00"), None);
+        assert_eq!(format_duration(1.0 / 3.0), "0.333");
+        assert_eq!(format_duration(2.0), "2");
+    }
+}
@@ -0,0 +1,169 @@
+//! stream files as a multipart response
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::path::{Component, Path, PathBuf};
+
+use bytes::{Bytes, BytesMut};
+use futures_util::stream::{self, Stream, StreamExt, TryStreamExt};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use salvo_core::async_trait;
+use salvo_core::http::header::{HeaderValue, CONTENT_TYPE};
+use salvo_core::http::Response;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use super::file::Disposition;
+use super::safe_join;
+
+/// Size of the chunks files are read in.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Extension of [`Response`] to stream several files as one `multipart/mixed` body.
+#[async_trait]
+pub trait MultipartFilesExt {
+    /// Stream the files at `paths` in `root` as the parts of a `multipart/mixed` body, in order.
+    ///
+    /// Paths are resolved with [`safe_join`], so they can't escape `root`, symlinks
+    /// which point out of `root` and dot files, like `.env`, are not found. Each part has its own
+    /// `Content-Type` guessed from the file name, `Content-Disposition` and `Content-Length`. Files
+    /// are read in chunks while the body is sent, they are never buffered as a whole.
+    ///
+    /// All files are checked before anything is written, an error of `ErrorKind::NotFound` is
+    /// returned and the response is left untouched if any of them is missing or not a file. If a file
+    /// shrinks while it is sent, the body fails with `ErrorKind::UnexpectedEof`, as the length of its
+    /// part is already sent.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use salvo_core::prelude::*;
+    /// use salvo_serve_static::MultipartFilesExt;
+    ///
+    /// #[handler]
+    /// async fn export(res: &mut Response) {
+    ///     if res
+    ///         .stream_multipart_files("exports", &["report.csv", "summary.pdf"])
+    ///         .await
+    ///         .is_err()
+    ///     {
+    ///         res.render(StatusError::not_found());
+    ///     }
+    /// }
+    /// ```
+    async fn stream_multipart_files<P, S>(&mut self, root: P, paths: &[S]) -> IoResult<()>
+    where
+        P: AsRef<Path> + Send,
+        S: AsRef<str> + Sync;
+}
+
+#[async_trait]
+impl MultipartFilesExt for Response {
+    async fn stream_multipart_files<P, S>(&mut self, root: P, paths: &[S]) -> IoResult<()>
+    where
+        P: AsRef<Path> + Send,
+        S: AsRef<str> + Sync,
+    {
+        let boundary = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .map(char::from)
+            .collect::<String>();
+        let root = root.as_ref();
+        let canonical_root = tokio::fs::canonicalize(root).await?;
+        let mut parts = Vec::with_capacity(paths.len());
+        for url_path in paths {
+            let url_path = url_path.as_ref();
+            let path = safe_join(root, url_path)
+                .filter(|path| !is_dot_path(path.strip_prefix(root).unwrap_or(path)))
+                .ok_or_else(|| IoError::new(ErrorKind::NotFound, format!("invalid path `{url_path}`")))?;
+            if !tokio::fs::canonicalize(&path).await?.starts_with(&canonical_root) {
+                return Err(IoError::new(
+                    ErrorKind::NotFound,
+                    format!("`{url_path}` is out of root"),
+                ));
+            }
+            let metadata = tokio::fs::metadata(&path).await?;
+            if !metadata.is_file() {
+                return Err(IoError::new(ErrorKind::NotFound, format!("`{url_path}` is not a file")));
+            }
+            let mut head = format!(
+                "--{boundary}\r\nContent-Type: {}\r\n",
+                mime_guess::from_path(&path).first_or_octet_stream()
+            );
+            if let Some(disposition) = (Disposition::Attachment { filename: None }).header_value(&path) {
+                if let Ok(disposition) = disposition.to_str() {
+                    head.push_str(&format!("Content-Disposition: {disposition}\r\n"));
+                }
+            }
+            head.push_str(&format!("Content-Length: {}\r\n\r\n", metadata.len()));
+            parts.push((head, path, metadata.len()));
+        }
+
+        let content_type = HeaderValue::from_str(&format!("multipart/mixed; boundary={boundary}"))
+            .map_err(|e| IoError::new(ErrorKind::Other, e))?;
+        let body = stream::iter(parts)
+            .flat_map(|(head, path, len)| {
+                stream::once(async move { Ok(Bytes::from(head)) })
+                    .chain(read_file(path, len))
+                    .chain(stream::once(async { Ok(Bytes::from_static(b"\r\n")) }))
+            })
+            .chain(stream::once(
+                async move { Ok(Bytes::from(format!("--{boundary}--\r\n"))) },
+            ));
+        self.headers_mut().insert(CONTENT_TYPE, content_type);
+        self.streaming(body)
+            .map_err(|e| IoError::new(ErrorKind::Other, e.to_string()))
+    }
+}
+
+/// Whether any segment of `path` is a dot file name.
+fn is_dot_path(path: &Path) -> bool {
+    path.components().any(|component| match component {
+        Component::Normal(name) => name.to_string_lossy().starts_with('.'),
+        _ => false,
+    })
+}
+
+/// Read the first `len` bytes of the file at `path` in chunks, the length is fixed when the part
+/// header is written, so the file is cut if it grows meanwhile, and the stream fails if it shrinks.
+fn read_file(path: PathBuf, len: u64) -> impl Stream<Item = IoResult<Bytes>> + Send {
+    stream::once(tokio::fs::File::open(path))
+        .map_ok(move |file| read_chunks(file.take(len), len))
+        .try_flatten()
+}
+
+fn read_chunks<R>(reader: R, len: u64) -> impl Stream<Item = IoResult<Bytes>> + Send
+where
+    R: AsyncRead + Unpin + Send,
+{
+    stream::try_unfold((reader, len), |(mut reader, remaining)| async move {
+        if remaining == 0 {
+            return Ok(None);
+        }
+        let mut buf = BytesMut::with_capacity(CHUNK_SIZE);
+        match reader.read_buf(&mut buf).await? {
+            0 => Err(IoError::new(
+                ErrorKind::UnexpectedEof,
+                format!("file is {remaining} bytes shorter than its part"),
+            )),
+            read => Ok(Some((buf.freeze(), (reader, remaining - read as u64)))),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_file_short() {
+        let path = std::env::temp_dir().join(format!("salvo_serve_static_multipart_short_{}", std::process::id()));
+        std::fs::write(&path, "short").unwrap();
+        let chunks = read_file(path.clone(), 8).collect::<Vec<_>>().await;
+        assert_eq!(chunks[0].as_ref().unwrap(), &Bytes::from_static(b"short"));
+        assert_eq!(chunks[1].as_ref().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+
+        let chunks = read_file(path.clone(), 5).collect::<Vec<_>>().await;
+        assert_eq!(chunks.len(), 1);
+        std::fs::remove_file(&path).ok();
+    }
+}
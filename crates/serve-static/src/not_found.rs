@@ -0,0 +1,108 @@
+//! Responses of files which are not found.
+
+use std::fmt::{self, Debug, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use salvo_core::http::header::{HeaderValue, CONTENT_TYPE};
+use salvo_core::http::{Request, Response, StatusError};
+
+type NotFoundWriter = Arc<dyn Fn(&Request, &mut Response) + Send + Sync>;
+
+/// Response of [`StaticDir`](crate::StaticDir) and [`StaticFile`](crate::StaticFile) when the
+/// requested file is not found, it is always sent with `404 Not Found`.
+///
+/// It is converted from the path of a file to serve, or created from a closure with
+/// [`NotFoundHandler::writer`]:
+///
+/// ```
+/// use salvo_core::prelude::*;
+/// use salvo_serve_static::{NotFoundHandler, StaticDir, StaticFile};
+///
+/// // `404.html` is looked up in the roots.
+/// let dir = StaticDir::new("static").with_not_found_handler("404.html");
+/// let file = StaticFile::new("static/app.js").with_not_found_handler(NotFoundHandler::writer(|_req, res| {
+///     res.render(Text::Json(r#"{"error":"not_found"}"#));
+/// }));
+/// ```
+#[derive(Clone)]
+pub enum NotFoundHandler {
+    /// Serve the file at the path. The path of [`StaticDir`](crate::StaticDir) is relative to its
+    /// roots and sanitized like request paths, the path of [`StaticFile`](crate::StaticFile) is used
+    /// as is.
+    File(PathBuf),
+    /// Write the response, the status is set before it is called.
+    Writer(NotFoundWriter),
+}
+
+impl NotFoundHandler {
+    /// Create a new `NotFoundHandler` writing the response with `writer`.
+    #[inline]
+    pub fn writer<F>(writer: F) -> Self
+    where
+        F: Fn(&Request, &mut Response) + Send + Sync + 'static,
+    {
+        NotFoundHandler::Writer(Arc::new(writer))
+    }
+}
+
+impl Debug for NotFoundHandler {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            NotFoundHandler::File(path) => f.debug_tuple("File").field(path).finish(),
+            NotFoundHandler::Writer(_) => f.write_str("Writer"),
+        }
+    }
+}
+
+impl From<&str> for NotFoundHandler {
+    #[inline]
+    fn from(path: &str) -> Self {
+        NotFoundHandler::File(path.into())
+    }
+}
+impl From<String> for NotFoundHandler {
+    #[inline]
+    fn from(path: String) -> Self {
+        NotFoundHandler::File(path.into())
+    }
+}
+impl From<&Path> for NotFoundHandler {
+    #[inline]
+    fn from(path: &Path) -> Self {
+        NotFoundHandler::File(path.into())
+    }
+}
+impl From<PathBuf> for NotFoundHandler {
+    #[inline]
+    fn from(path: PathBuf) -> Self {
+        NotFoundHandler::File(path)
+    }
+}
+
+/// Write the not found response, `file` is the resolved path of [`NotFoundHandler::File`], the
+/// default response is written if it is `None` or can't be read.
+pub(crate) async fn render_not_found(
+    handler: Option<&NotFoundHandler>,
+    file: Option<&Path>,
+    req: &Request,
+    res: &mut Response,
+) {
+    res.set_status_error(StatusError::not_found());
+    match (handler, file) {
+        (Some(NotFoundHandler::File(_)), Some(path)) => match tokio::fs::read(path).await {
+            Ok(content) => {
+                let mime = mime_guess::from_path(path).first_or_octet_stream();
+                if let Ok(content_type) = HeaderValue::from_str(mime.as_ref()) {
+                    res.headers_mut().insert(CONTENT_TYPE, content_type);
+                }
+                res.write_body(content).ok();
+            }
+            Err(e) => {
+                tracing::error!(error = ?e, path = ?path, "read not found file failed");
+            }
+        },
+        (Some(NotFoundHandler::Writer(writer)), _) => writer(req, res),
+        _ => {}
+    }
+}
@@ -0,0 +1,68 @@
+//! serve static assets from multiple sources
+
+use std::mem;
+
+use salvo_core::http::{Request, Response, StatusCode, StatusError};
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler};
+
+/// Serve static assets from multiple sources, such as [`StaticDir`] and [`StaticEmbed`],
+/// the first source which finds the requested asset wins.
+///
+/// Sources are tried in the order they are pushed. For example, push a [`StaticDir`] before a
+/// [`StaticEmbed`] to allow files on disk to override the assets shipped in the binary.
+///
+/// [`StaticDir`]: crate::StaticDir
+/// [`StaticEmbed`]: crate::StaticEmbed
+#[derive(Default)]
+pub struct StaticSources {
+    sources: Vec<Box<dyn Handler>>,
+}
+
+impl StaticSources {
+    /// Create a new `StaticSources`.
+    #[inline]
+    pub fn new() -> Self {
+        StaticSources { sources: vec![] }
+    }
+
+    /// Push a new source with lower priority than all sources pushed before, and returns `Self`.
+    #[inline]
+    pub fn push<H: Handler>(mut self, source: H) -> Self {
+        self.sources.push(Box::new(source));
+        self
+    }
+
+    /// Get the number of sources.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Returns `true` if there is no source.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+}
+
+#[async_trait]
+impl Handler for StaticSources {
+    async fn handle(&self, req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+        for source in &self.sources {
+            let mut source_res = Response::new();
+            source.handle(req, depot, &mut source_res, ctrl).await;
+            // A response without status is only a miss if it has no body either.
+            let status = match source_res.status_code() {
+                Some(StatusCode::NOT_FOUND) => continue,
+                None if source_res.body().is_none() => continue,
+                Some(status) => status,
+                None => StatusCode::OK,
+            };
+            res.headers_mut().extend(mem::take(source_res.headers_mut()));
+            res.set_body(source_res.take_body());
+            res.set_status_code(status);
+            return;
+        }
+        res.set_status_error(StatusError::not_found());
+    }
+}
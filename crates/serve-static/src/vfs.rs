@@ -0,0 +1,347 @@
+//! serve static assets from an in-memory virtual filesystem
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use bytes::Bytes;
+use path_slash::PathExt;
+use salvo_core::http::header::{
+    HeaderValue, ACCEPT_RANGES, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_MATCH, IF_NONE_MATCH, RANGE,
+};
+use salvo_core::http::headers::{ETag, HeaderMapExt, LastModified};
+use salvo_core::http::{HttpRange, Method, Mime, Request, Response, StatusCode, StatusError};
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler, IntoVecString};
+use sha2::{Digest, Sha256};
+
+use super::etag;
+use super::{
+    check_method_allowed, decode_url_path_safely, format_url_path_safely, redirect_to_dir_url, reject_malformed_path,
+    render_file_options, strip_head_body,
+};
+
+#[derive(Clone, Debug)]
+struct VfsFile {
+    data: Bytes,
+    etag: String,
+}
+
+/// Serve static assets from an in-memory virtual filesystem.
+///
+/// Files are served with the same contract as [`StaticDir`](crate::StaticDir): the MIME type is
+/// guessed from the path, a strong ETag is computed from the content when the file is inserted, and
+/// conditional and range requests are supported. It is useful in tests and for asset bundles generated
+/// at runtime.
+///
+/// ```
+/// use salvo_core::prelude::*;
+/// use salvo_serve_static::StaticVfs;
+///
+/// let vfs = StaticVfs::new()
+///     .with_file("index.html", "<h1>Hello</h1>")
+///     .with_file("js/app.js", "console.log('hello');")
+///     .with_defaults("index.html");
+/// let router = Router::with_path("<**path>").get(vfs);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct StaticVfs {
+    files: HashMap<PathBuf, VfsFile>,
+    /// Default file names list.
+    pub defaults: Vec<String>,
+    /// Fallback file name. This is used when the requested file is not found.
+    pub fallback: Option<String>,
+    /// Reject request paths with malformed percent-encoding or invalid UTF-8 with `400 Bad Request`,
+    /// instead of decoding them lossily. Default is `false`.
+    pub strict_decode: bool,
+}
+
+impl StaticVfs {
+    /// Create a new empty `StaticVfs`.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert a file, returns the content of the file previously at `path` if any.
+    ///
+    /// The path is relative to the root of the virtual filesystem, separated by `/`, for example
+    /// `js/app.js`.
+    pub fn insert(&mut self, path: impl AsRef<Path>, data: impl Into<Bytes>) -> Option<Bytes> {
+        let data = data.into();
+        let etag = format!("\"{}\"", hex::encode(Sha256::digest(&data)));
+        self.files
+            .insert(normalize_path(path.as_ref()), VfsFile { data, etag })
+            .map(|file| file.data)
+    }
+
+    /// Remove a file, returns its content if it exists.
+    #[inline]
+    pub fn remove(&mut self, path: impl AsRef<Path>) -> Option<Bytes> {
+        self.files.remove(&normalize_path(path.as_ref())).map(|file| file.data)
+    }
+
+    /// Get the content of a file.
+    #[inline]
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<&Bytes> {
+        self.files.get(&normalize_path(path.as_ref())).map(|file| &file.data)
+    }
+
+    /// Insert a file and returns `Self`.
+    #[inline]
+    pub fn with_file(mut self, path: impl AsRef<Path>, data: impl Into<Bytes>) -> Self {
+        self.insert(path, data);
+        self
+    }
+
+    /// Sets default file names and returns `Self`.
+    #[inline]
+    pub fn with_defaults(mut self, defaults: impl IntoVecString) -> Self {
+        self.defaults = defaults.into_vec_string();
+        self
+    }
+
+    /// Sets fallback file name and returns `Self`.
+    #[inline]
+    pub fn with_fallback(mut self, fallback: impl Into<String>) -> Self {
+        self.fallback = Some(fallback.into());
+        self
+    }
+
+    /// Sets whether malformed request paths are rejected and returns `Self`.
+    #[inline]
+    pub fn with_strict_decode(mut self, strict_decode: bool) -> Self {
+        self.strict_decode = strict_decode;
+        self
+    }
+
+    fn find(&self, path: &str) -> Option<(&VfsFile, PathBuf)> {
+        let path = normalize_path(Path::new(path));
+        self.files.get(&path).map(|file| (file, path))
+    }
+}
+
+impl<P, D> FromIterator<(P, D)> for StaticVfs
+where
+    P: AsRef<Path>,
+    D: Into<Bytes>,
+{
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = (P, D)>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(StaticVfs::new(), |vfs, (path, data)| vfs.with_file(path, data))
+    }
+}
+
+#[async_trait]
+impl Handler for StaticVfs {
+    async fn handle(&self, req: &mut Request, _depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+        if !check_method_allowed(req, res) {
+            ctrl.skip_rest();
+            return;
+        }
+        if self.strict_decode && reject_malformed_path(req, res) {
+            return;
+        }
+        let param = req.params().iter().find(|(key, _)| key.starts_with('*'));
+        let req_path = if let Some((_, value)) = param {
+            value.clone()
+        } else {
+            decode_url_path_safely(req.uri().path())
+        };
+        let req_path = format_url_path_safely(&req_path);
+        let mut found = self.find(&req_path);
+        if found.is_none() {
+            found = self
+                .defaults
+                .iter()
+                .find_map(|default| self.find(&format!("{req_path}/{default}")));
+            if found.is_some() && !req.uri().path().ends_with('/') && !req_path.is_empty() {
+                redirect_to_dir_url(req.uri(), StatusCode::FOUND, res);
+                return;
+            }
+        }
+        if found.is_none() {
+            found = self.fallback.as_deref().and_then(|fallback| self.find(fallback));
+        }
+
+        match found {
+            Some(_) if req.method() == Method::OPTIONS => render_file_options(res),
+            Some((file, path)) => {
+                let mime = mime_guess::from_path(path).first_or_octet_stream();
+                render_bytes(file.data.clone(), &file.etag, req, res, mime);
+            }
+            None => {
+                res.set_status_error(StatusError::not_found());
+            }
+        }
+    }
+}
+
+#[inline]
+fn normalize_path(path: &Path) -> PathBuf {
+    PathBuf::from(format_url_path_safely(&path.to_slash_lossy()))
+}
+
+/// Maximum number of ranges sent as `multipart/byteranges` after overlapping ranges are merged,
+/// requests with more ranges get the whole content.
+const MAX_RANGES: usize = 16;
+
+/// Render in-memory `data` with its strong `etag`, conditional and range requests are handled, the
+/// response to `HEAD` has no body.
+pub(crate) fn render_bytes(data: Bytes, etag: &str, req: &Request, res: &mut Response, mime: Mime) {
+    let size = data.len() as u64;
+    match prepare_bytes(size, etag, req, res, &mime) {
+        BytesBody::Empty => return,
+        BytesBody::Whole => {
+            res.write_body(data).ok();
+        }
+        BytesBody::Ranges(ranges) => {
+            let parts = ranges
+                .iter()
+                .map(|range| data.slice(range.start as usize..(range.start + range.length) as usize))
+                .collect();
+            render_ranges(parts, &ranges, size, etag, &mime, res);
+        }
+    }
+    strip_head_body(req, res);
+}
+
+/// Body to send for a file after [`prepare_bytes`] handled the request.
+pub(crate) enum BytesBody {
+    /// The response is complete without a body, like `304 Not Modified`.
+    Empty,
+    /// The whole file is sent.
+    Whole,
+    /// The ranges of the file are sent with [`render_ranges`].
+    Ranges(Vec<HttpRange>),
+}
+
+/// Handle the conditional and range headers of the request for a file of `size` bytes with the strong
+/// `etag`, the status and headers are set on `res` and the body to send is returned.
+pub(crate) fn prepare_bytes(size: u64, etag: &str, req: &Request, res: &mut Response, mime: &Mime) -> BytesBody {
+    // `If-Match` uses strong comparison, if no etag is matched, return 412
+    if let Some(if_match) = req.headers().get(IF_MATCH) {
+        if !etag::if_match(if_match.to_str().unwrap_or_default(), etag) {
+            res.set_status_code(StatusCode::PRECONDITION_FAILED);
+            return BytesBody::Empty;
+        }
+    }
+    if let Ok(value) = HeaderValue::from_str(etag) {
+        res.headers_mut().insert(ETAG, value);
+    }
+    // `If-None-Match` uses weak comparison, if etag is matched, return 304, request cache directives
+    // such as `Cache-Control: no-cache` are for caches and don't bypass it.
+    if let Some(if_none_match) = req.headers().get(IF_NONE_MATCH) {
+        if etag::if_none_match(if_none_match.to_str().unwrap_or_default(), etag) {
+            res.set_status_code(StatusCode::NOT_MODIFIED);
+            return BytesBody::Empty;
+        }
+    }
+
+    res.headers_mut().insert(CONTENT_TYPE, mime.as_ref().parse().unwrap());
+    res.headers_mut()
+        .insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+
+    // check for range header, multiple ranges are sent as `multipart/byteranges`, an empty file has
+    // no satisfiable range, but an open-ended range from 0 like `bytes=0-` asks for the whole file, so
+    // it is served as is. A range with `If-Range` is ignored if the content is not the representation
+    // the client has.
+    let range = req
+        .headers()
+        .get(RANGE)
+        .filter(|range| size > 0 || !range.to_str().map(HttpRange::is_whole).unwrap_or(false))
+        .filter(|_| {
+            HttpRange::if_range_matched(
+                req.headers(),
+                etag.parse::<ETag>().ok().as_ref(),
+                res.headers().typed_get::<LastModified>().as_ref(),
+            )
+        });
+    if let Some(range) = range {
+        let range = match range.to_str() {
+            Ok(range) => range,
+            Err(_) => {
+                res.set_status_code(StatusCode::BAD_REQUEST);
+                return BytesBody::Empty;
+            }
+        };
+        match HttpRange::parse(range, size) {
+            Ok(ranges) if ranges.is_empty() => {}
+            Ok(ranges) if ranges.iter().all(|range| range.length > 0) => {
+                // Too many ranges are served as the whole content, so a request can't make the
+                // content copied once per range.
+                let ranges = coalesce_ranges(ranges);
+                if ranges.len() <= MAX_RANGES {
+                    return BytesBody::Ranges(ranges);
+                }
+            }
+            _ => {
+                res.headers_mut()
+                    .insert(CONTENT_RANGE, format!("bytes */{size}").parse().unwrap());
+                res.set_status_code(StatusCode::RANGE_NOT_SATISFIABLE);
+                return BytesBody::Empty;
+            }
+        }
+    }
+
+    // otherwise, return 200 with etag hash
+    res.set_status_code(StatusCode::OK);
+    BytesBody::Whole
+}
+
+/// Merge overlapping and adjacent `ranges`, they are ordered by their start then, see
+/// [RFC 7233](https://datatracker.ietf.org/doc/html/rfc7233#section-6.1).
+fn coalesce_ranges(mut ranges: Vec<HttpRange>) -> Vec<HttpRange> {
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<HttpRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.start + last.length => {
+                last.length = last.length.max(range.start + range.length - last.start);
+            }
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Send `parts`, the content of `ranges` of a file of `size` bytes, as `206 Partial Content`.
+pub(crate) fn render_ranges(
+    parts: Vec<Bytes>,
+    ranges: &[HttpRange],
+    size: u64,
+    etag: &str,
+    mime: &Mime,
+    res: &mut Response,
+) {
+    let content_range = |range: &HttpRange| format!("bytes {}-{}/{size}", range.start, range.start + range.length - 1);
+    res.set_status_code(StatusCode::PARTIAL_CONTENT);
+    if ranges.len() == 1 {
+        res.headers_mut()
+            .insert(CONTENT_RANGE, content_range(&ranges[0]).parse().unwrap());
+        res.write_body(parts.into_iter().next().unwrap_or_default()).ok();
+        return;
+    }
+
+    // The etag hash is unlikely to be a part of the content, so it is used as boundary.
+    let boundary = etag.trim_matches('"');
+    let mut body = Vec::new();
+    for (range, part) in ranges.iter().zip(parts) {
+        body.extend_from_slice(
+            format!(
+                "--{boundary}\r\n{}: {mime}\r\n{}: {}\r\n\r\n",
+                CONTENT_TYPE,
+                CONTENT_RANGE,
+                content_range(range)
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(&part);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+    res.headers_mut().insert(
+        CONTENT_TYPE,
+        format!("multipart/byteranges; boundary={boundary}").parse().unwrap(),
+    );
+    res.write_body(body).ok();
+}
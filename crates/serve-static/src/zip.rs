@@ -0,0 +1,335 @@
+//! Stream a directory as zip archive.
+//!
+//! Files are stored without compression, and entries are written with data descriptors, so the
+//! archive can be produced on the fly without buffering file contents. Zip64 is not supported, an
+//! error is yielded if any file is larger than 4GiB or the archive has more than 65535 entries.
+
+use std::collections::VecDeque;
+use std::io::{Error as IoError, ErrorKind, Result as IoResult};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use futures_util::stream::{self, Stream};
+use time::OffsetDateTime;
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+
+use crate::GlobSet;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+// Data descriptor is used and file names are encoded with UTF-8.
+const FLAGS: u16 = 0x0808;
+const VERSION: u16 = 20;
+
+struct Entry {
+    path: PathBuf,
+    name: String,
+    is_dir: bool,
+    modified: SystemTime,
+}
+
+struct Writing {
+    file: File,
+    name: String,
+    dos_time: (u16, u16),
+    header_offset: u64,
+    crc: u32,
+    size: u64,
+}
+
+struct ZipStream {
+    entries: VecDeque<Entry>,
+    current: Option<Writing>,
+    offset: u64,
+    central: Vec<u8>,
+    count: u16,
+    finished: bool,
+}
+
+/// Collect entries of `root`, dot files and dot directories are skipped unless `dot_files` is `true`.
+///
+/// `rel_root` is the path of `root` relative to the static root, it is used to match `excludes`.
+/// Symbolic links are only followed to files for which `is_contained` is `true`.
+async fn collect_entries(
+    root: &Path,
+    rel_root: &str,
+    dot_files: bool,
+    excludes: &GlobSet,
+    is_contained: &(dyn Fn(&Path) -> bool + Sync),
+) -> IoResult<VecDeque<Entry>> {
+    let mut entries = VecDeque::new();
+    let mut dirs = vec![(root.to_owned(), String::new())];
+    while let Some((dir, prefix)) = dirs.pop() {
+        let mut read_dir = tokio::fs::read_dir(&dir).await?;
+        let mut children = Vec::new();
+        while let Some(child) = read_dir.next_entry().await? {
+            let file_name = child.file_name().to_string_lossy().to_string();
+            if !dot_files && file_name.starts_with('.') {
+                continue;
+            }
+            if excludes.is_match_with_ancestors(&format!("{rel_root}/{prefix}{file_name}")) {
+                continue;
+            }
+            let path = child.path();
+            let mut metadata = tokio::fs::symlink_metadata(&path).await?;
+            if metadata.file_type().is_symlink() {
+                if !is_contained(&path) {
+                    continue;
+                }
+                // Symbolic links to directories are skipped to avoid loops, broken links are skipped too.
+                metadata = match tokio::fs::metadata(&path).await {
+                    Ok(metadata) if metadata.is_file() => metadata,
+                    _ => continue,
+                };
+            }
+            children.push((file_name, path, metadata));
+        }
+        children.sort_by(|a, b| a.0.cmp(&b.0));
+        for (file_name, path, metadata) in children {
+            let modified = metadata.modified().unwrap_or_else(|_| SystemTime::now());
+            if metadata.is_dir() {
+                let name = format!("{prefix}{file_name}/");
+                entries.push_back(Entry {
+                    path: path.clone(),
+                    name: name.clone(),
+                    is_dir: true,
+                    modified,
+                });
+                dirs.push((path, name));
+            } else if metadata.is_file() {
+                if metadata.len() > u32::MAX as u64 {
+                    return Err(IoError::new(ErrorKind::Other, "file is too large for zip archive"));
+                }
+                entries.push_back(Entry {
+                    path,
+                    name: format!("{prefix}{file_name}"),
+                    is_dir: false,
+                    modified,
+                });
+            }
+        }
+    }
+    if entries.len() > u16::MAX as usize {
+        return Err(IoError::new(ErrorKind::Other, "too many entries for zip archive"));
+    }
+    Ok(entries)
+}
+
+/// Create a stream which yields a zip archive of the directory `root`.
+pub(crate) async fn zip_dir(
+    root: &Path,
+    rel_root: &str,
+    dot_files: bool,
+    excludes: &GlobSet,
+    is_contained: &(dyn Fn(&Path) -> bool + Sync),
+) -> IoResult<impl Stream<Item = IoResult<Vec<u8>>> + Send + 'static> {
+    let state = ZipStream {
+        entries: collect_entries(root, rel_root, dot_files, excludes, is_contained).await?,
+        current: None,
+        offset: 0,
+        central: Vec::new(),
+        count: 0,
+        finished: false,
+    };
+    Ok(stream::unfold(Some(state), |state| async move {
+        let mut state = state?;
+        match state.next_chunk().await {
+            Ok(Some(chunk)) => Some((Ok(chunk), Some(state))),
+            Ok(None) => None,
+            Err(e) => Some((Err(e), None)),
+        }
+    }))
+}
+
+impl ZipStream {
+    async fn next_chunk(&mut self) -> IoResult<Option<Vec<u8>>> {
+        if let Some(writing) = &mut self.current {
+            let mut buf = vec![0; CHUNK_SIZE];
+            let len = writing.file.read(&mut buf).await?;
+            if len > 0 {
+                buf.truncate(len);
+                writing.crc = crc32_update(writing.crc, &buf);
+                writing.size += len as u64;
+                if writing.size > u32::MAX as u64 {
+                    return Err(IoError::new(ErrorKind::Other, "file is too large for zip archive"));
+                }
+                self.offset += len as u64;
+                return Ok(Some(buf));
+            }
+            let Writing {
+                name,
+                dos_time,
+                header_offset,
+                crc,
+                size,
+                ..
+            } = self.current.take().expect("current entry should exist");
+            let crc = !crc;
+            let mut descriptor = Vec::with_capacity(16);
+            put_u32(&mut descriptor, 0x08074b50);
+            put_u32(&mut descriptor, crc);
+            put_u32(&mut descriptor, size as u32);
+            put_u32(&mut descriptor, size as u32);
+            self.add_central(&name, dos_time, crc, size as u32, header_offset, false)?;
+            return self.advance(descriptor);
+        }
+        if let Some(entry) = self.entries.pop_front() {
+            let dos_time = dos_date_time(entry.modified);
+            let header_offset = self.offset;
+            let header = local_header(&entry.name, dos_time);
+            if entry.is_dir {
+                self.add_central(&entry.name, dos_time, 0, 0, header_offset, true)?;
+            } else {
+                self.current = Some(Writing {
+                    file: File::open(&entry.path).await?,
+                    name: entry.name,
+                    dos_time,
+                    header_offset,
+                    crc: !0,
+                    size: 0,
+                });
+            }
+            return self.advance(header);
+        }
+        if !self.finished {
+            self.finished = true;
+            let central = std::mem::take(&mut self.central);
+            let mut end = Vec::with_capacity(central.len() + 22);
+            end.extend_from_slice(&central);
+            put_u32(&mut end, 0x06054b50);
+            put_u16(&mut end, 0);
+            put_u16(&mut end, 0);
+            put_u16(&mut end, self.count);
+            put_u16(&mut end, self.count);
+            put_u32(&mut end, central.len() as u32);
+            put_u32(&mut end, checked_u32(self.offset)?);
+            put_u16(&mut end, 0);
+            return self.advance(end);
+        }
+        Ok(None)
+    }
+
+    fn advance(&mut self, chunk: Vec<u8>) -> IoResult<Option<Vec<u8>>> {
+        self.offset += chunk.len() as u64;
+        checked_u32(self.offset)?;
+        Ok(Some(chunk))
+    }
+
+    fn add_central(
+        &mut self,
+        name: &str,
+        dos_time: (u16, u16),
+        crc: u32,
+        size: u32,
+        header_offset: u64,
+        is_dir: bool,
+    ) -> IoResult<()> {
+        let central = &mut self.central;
+        put_u32(central, 0x02014b50);
+        put_u16(central, VERSION);
+        put_u16(central, VERSION);
+        put_u16(central, FLAGS);
+        put_u16(central, 0);
+        put_u16(central, dos_time.0);
+        put_u16(central, dos_time.1);
+        put_u32(central, crc);
+        put_u32(central, size);
+        put_u32(central, size);
+        put_u16(central, name.len() as u16);
+        put_u16(central, 0);
+        put_u16(central, 0);
+        put_u16(central, 0);
+        put_u16(central, 0);
+        // MS-DOS directory attribute.
+        put_u32(central, if is_dir { 0x10 } else { 0 });
+        put_u32(central, checked_u32(header_offset)?);
+        central.extend_from_slice(name.as_bytes());
+        self.count += 1;
+        Ok(())
+    }
+}
+
+fn local_header(name: &str, dos_time: (u16, u16)) -> Vec<u8> {
+    let mut header = Vec::with_capacity(30 + name.len());
+    put_u32(&mut header, 0x04034b50);
+    put_u16(&mut header, VERSION);
+    put_u16(&mut header, FLAGS);
+    // Stored, no compression.
+    put_u16(&mut header, 0);
+    put_u16(&mut header, dos_time.0);
+    put_u16(&mut header, dos_time.1);
+    // CRC-32 and sizes are written in data descriptor.
+    put_u32(&mut header, 0);
+    put_u32(&mut header, 0);
+    put_u32(&mut header, 0);
+    put_u16(&mut header, name.len() as u16);
+    put_u16(&mut header, 0);
+    header.extend_from_slice(name.as_bytes());
+    header
+}
+
+#[inline]
+fn checked_u32(value: u64) -> IoResult<u32> {
+    u32::try_from(value).map_err(|_| IoError::new(ErrorKind::Other, "zip archive is too large"))
+}
+
+#[inline]
+fn put_u16(buf: &mut Vec<u8>, value: u16) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+#[inline]
+fn put_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Convert to MS-DOS time and date, times before 1980 are clamped.
+fn dos_date_time(time: SystemTime) -> (u16, u16) {
+    let time = OffsetDateTime::from(time);
+    if time.year() < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let dos_time = ((time.hour() as u16) << 11) | ((time.minute() as u16) << 5) | (time.second() as u16 / 2);
+    let dos_date = (((time.year() - 1980) as u16) << 9) | ((time.month() as u16) << 5) | time.day() as u16;
+    (dos_time, dos_date)
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Update a CRC-32 which is not finalized, start with `!0` and finalize with `!crc`.
+#[inline]
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for byte in data {
+        crc = CRC32_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(!crc32_update(!0, b"123456789"), 0xCBF43926);
+        assert_eq!(!crc32_update(!0, b""), 0);
+    }
+}